@@ -1,6 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use quartz::{component::Component, World};
 
+#[allow(dead_code)]
 struct Position {
     x: f32,
     y: f32,
@@ -0,0 +1,643 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, GenericArgument, GenericParam,
+    Ident, Index, Path, PathArguments, Type,
+};
+
+/// Implements `MapEntities` by mapping every field of type `Entity`, `Option<Entity>` or
+/// `Vec<Entity>`. Fields of other types implementing `MapEntities` can be included with the
+/// `#[entities]` attribute.
+#[proc_macro_derive(MapEntities, attributes(entities))]
+pub fn derive_map_entities(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (_, mapped) = map_fields(&data.fields, |member| quote!(self.#member));
+            quote!(#(#mapped)*)
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let (pattern, mapped) = map_fields(&variant.fields, |member| {
+                    let binding = binding(member);
+                    quote!(*#binding)
+                });
+                quote!(Self::#ident #pattern => { #(#mapped)* })
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return syn::Error::new(
+                data.union_token.span(),
+                "MapEntities can't be derived for unions",
+            )
+            .into_compile_error()
+            .into();
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::quartz::map_entities::MapEntities for #name #ty_generics #where_clause {
+            fn map_entities<M: ::quartz::map_entities::EntityMapper>(&mut self, mapper: &mut M) {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `Component`, configured through the `#[component(..)]` attribute:
+///
+/// - `stable_rows` sets `STABLE_ROWS`
+/// - `change_detection = PerTable` sets `CHANGE_DETECTION` to the given `ChangeDetection` variant
+/// - `storage = SparseSet` sets `STORAGE_TYPE` to the given `StorageType` variant
+/// - `on_add = path`, `on_insert = path`, `on_replace = path` and `on_remove = path` register the
+///   function at `path` as the lifecycle hook
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut stable_rows = None;
+    let mut change_detection = None;
+    let mut storage_type = None;
+    let mut hooks = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("component"))
+    {
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("stable_rows") {
+                stable_rows = Some(quote!(
+                    const STABLE_ROWS: bool = true;
+                ));
+            } else if meta.path.is_ident("change_detection") {
+                let variant: Ident = meta.value()?.parse()?;
+                change_detection = Some(quote! {
+                    const CHANGE_DETECTION: ::quartz::component::ChangeDetection =
+                        ::quartz::component::ChangeDetection::#variant;
+                });
+            } else if meta.path.is_ident("storage") {
+                let variant: Ident = meta.value()?.parse()?;
+                storage_type = Some(quote! {
+                    const STORAGE_TYPE: ::quartz::component::StorageType =
+                        ::quartz::component::StorageType::#variant;
+                });
+            } else if let Some(hook) = ["on_add", "on_insert", "on_replace", "on_remove"]
+                .into_iter()
+                .find(|hook| meta.path.is_ident(hook))
+            {
+                let hook = Ident::new(hook, meta.path.span());
+                let path: Path = meta.value()?.parse()?;
+                hooks.push(quote!(hooks.#hook(#path);));
+            } else {
+                return Err(meta.error("unsupported component attribute"));
+            }
+            Ok(())
+        });
+        if let Err(err) = parsed {
+            return err.into_compile_error().into();
+        }
+    }
+
+    let register_hooks = (!hooks.is_empty()).then(|| {
+        quote! {
+            fn register_hooks(hooks: &mut ::quartz::component::ComponentHooks) {
+                #(#hooks)*
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::quartz::component::Component for #name #ty_generics #where_clause {
+            #stable_rows
+            #change_detection
+            #storage_type
+            #register_hooks
+        }
+    }
+    .into()
+}
+
+/// Implements `Resource`.
+#[proc_macro_derive(Resource)]
+pub fn derive_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::quartz::resource::Resource for #name #ty_generics #where_clause {}
+    }
+    .into()
+}
+
+/// Implements `Event`.
+#[proc_macro_derive(Event)]
+pub fn derive_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::quartz::event::Event for #name #ty_generics #where_clause {}
+    }
+    .into()
+}
+
+/// Implements `SystemSet`, the type also has to implement `Debug`, `Clone`, `Eq` and `Hash`.
+#[proc_macro_derive(SystemSet)]
+pub fn derive_system_set(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::quartz::schedule::SystemSet for #name #ty_generics #where_clause {}
+    }
+    .into()
+}
+
+/// Implements `States`, the type also has to implement `Debug`, `Clone`, `Eq` and `Hash`.
+#[proc_macro_derive(States)]
+pub fn derive_states(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::quartz::state::States for #name #ty_generics #where_clause {}
+    }
+    .into()
+}
+
+/// Implements `SystemParam` for a struct whose fields are all system parameters, fetching every
+/// field and registering the accesses of all of them. The struct may have at most one lifetime,
+/// the one of the world the fields borrow from.
+#[proc_macro_derive(SystemParam)]
+pub fn derive_system_param(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new(name.span(), "SystemParam can only be derived for structs")
+            .into_compile_error()
+            .into();
+    };
+    if input.generics.lifetimes().count() > 1 {
+        return syn::Error::new(
+            input.generics.span(),
+            "SystemParam can only be derived for structs with at most one lifetime",
+        )
+        .into_compile_error()
+        .into();
+    }
+
+    // The item borrows from the world for the lifetime of `get_param` instead of the struct's own
+    let item_generics = input.generics.params.iter().map(|param| match param {
+        GenericParam::Lifetime(_) => quote!('__w),
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            quote!(#ident)
+        }
+        GenericParam::Const(param) => {
+            let ident = &param.ident;
+            quote!(#ident)
+        }
+    });
+    let (pattern, _) = map_fields(&data.fields, |member| quote!(#member));
+    let states = (0..data.fields.len())
+        .map(|index| format_ident!("__state_{index}"))
+        .collect::<Vec<_>>();
+    let bindings = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => binding(&quote!(#ident)),
+            None => {
+                let index = Index::from(index);
+                binding(&quote!(#index))
+            }
+        })
+        .collect::<Vec<_>>();
+    let types = data
+        .fields
+        .iter()
+        .map(|field| &field.ty)
+        .collect::<Vec<_>>();
+
+    quote! {
+        // SAFETY: The access of every field is registered.
+        unsafe impl #impl_generics ::quartz::system::SystemParam for #name #ty_generics
+            #where_clause
+        {
+            type State = (#(<#types as ::quartz::system::SystemParam>::State,)*);
+            type Item<'__w> = #name<#(#item_generics),*>;
+
+            fn init_state(
+                world: &mut ::quartz::World,
+                access: &mut ::quartz::system::SystemAccess,
+            ) -> Self::State {
+                (#(<#types as ::quartz::system::SystemParam>::init_state(world, access),)*)
+            }
+
+            unsafe fn get_param<'__w>(
+                state: &'__w mut Self::State,
+                world: &'__w ::quartz::World,
+                since: ::quartz::component::Tick,
+            ) -> Self::Item<'__w> {
+                let (#(#states,)*) = state;
+                #(
+                    let #bindings =
+                        <#types as ::quartz::system::SystemParam>::get_param(#states, world, since);
+                )*
+                #name #pattern
+            }
+
+            fn apply(state: &mut Self::State, world: &mut ::quartz::World) {
+                let (#(#states,)*) = state;
+                #(<#types as ::quartz::system::SystemParam>::apply(#states, world);)*
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `Bundle` for a struct whose fields are all components or bundles themselves, which
+/// flattens nested bundles into a single set of components.
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new(name.span(), "Bundle can only be derived for structs")
+            .into_compile_error()
+            .into();
+    };
+    let (pattern, _) = map_fields(&data.fields, |member| quote!(#member));
+    let bindings = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => binding(&quote!(#ident)),
+            None => {
+                let index = Index::from(index);
+                binding(&quote!(#index))
+            }
+        })
+        .collect::<Vec<_>>();
+    let types = data.fields.iter().map(|field| &field.ty);
+
+    quote! {
+        impl #impl_generics ::quartz::component::Bundle for #name #ty_generics #where_clause {
+            fn get_components(
+                self,
+                func: &mut impl FnMut(::quartz::ptr::OwningPtr<'_>),
+            ) {
+                let Self #pattern = self;
+                #(::quartz::component::Bundle::get_components(#bindings, &mut *func);)*
+            }
+
+            fn get_component_ptrs(
+                &self,
+                func: &mut impl FnMut(::quartz::ptr::Ptr<'_>),
+            ) {
+                let Self #pattern = self;
+                #(::quartz::component::Bundle::get_component_ptrs(#bindings, &mut *func);)*
+            }
+
+            fn component_ids(
+                components: &mut ::quartz::component::Components,
+                func: &mut impl FnMut(::quartz::component::ComponentId),
+            ) {
+                #(<#types as ::quartz::component::Bundle>::component_ids(components, &mut *func);)*
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `Reflect`, `Struct`, `Typed` and `FromReflect` for a struct whose fields all
+/// implement `Reflect` and `FromReflect`. Fields of tuple structs are named by their position.
+#[proc_macro_derive(Reflect)]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new(name.span(), "Reflect can only be derived for structs")
+            .into_compile_error()
+            .into();
+    };
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(index);
+                quote!(#index)
+            }
+        })
+        .collect::<Vec<_>>();
+    let names = members
+        .iter()
+        .map(|member| member.to_string())
+        .collect::<Vec<_>>();
+    let indices = (0..members.len()).map(Index::from).collect::<Vec<_>>();
+    let len = members.len();
+    let types = data
+        .fields
+        .iter()
+        .map(|field| &field.ty)
+        .collect::<Vec<_>>();
+
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    for ty in &types {
+        where_clause.predicates.push(syn::parse_quote!(
+            #ty: ::quartz::reflect::Reflect + ::quartz::reflect::FromReflect
+        ));
+    }
+
+    quote! {
+        impl #impl_generics ::quartz::reflect::Reflect for #name #ty_generics #where_clause {
+            fn type_name(&self) -> &'static str {
+                ::std::any::type_name::<Self>()
+            }
+
+            fn as_any(&self) -> &dyn ::std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn ::std::any::Any {
+                self
+            }
+
+            fn reflect_ref(&self) -> ::quartz::reflect::ReflectRef<'_> {
+                ::quartz::reflect::ReflectRef::Struct(self)
+            }
+
+            fn reflect_mut(&mut self) -> ::quartz::reflect::ReflectMut<'_> {
+                ::quartz::reflect::ReflectMut::Struct(self)
+            }
+
+            fn clone_value(&self) -> ::std::boxed::Box<dyn ::quartz::reflect::Reflect> {
+                ::std::boxed::Box::new(::quartz::reflect::DynamicStruct::from_struct(self))
+            }
+        }
+
+        impl #impl_generics ::quartz::reflect::Struct for #name #ty_generics #where_clause {
+            fn field(&self, name: &str) -> ::std::option::Option<&dyn ::quartz::reflect::Reflect> {
+                match name {
+                    #(#names => ::std::option::Option::Some(&self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn field_mut(
+                &mut self,
+                name: &str,
+            ) -> ::std::option::Option<&mut dyn ::quartz::reflect::Reflect> {
+                match name {
+                    #(#names => ::std::option::Option::Some(&mut self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn field_at(
+                &self,
+                index: usize,
+            ) -> ::std::option::Option<&dyn ::quartz::reflect::Reflect> {
+                match index {
+                    #(#indices => ::std::option::Option::Some(&self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn field_at_mut(
+                &mut self,
+                index: usize,
+            ) -> ::std::option::Option<&mut dyn ::quartz::reflect::Reflect> {
+                match index {
+                    #(#indices => ::std::option::Option::Some(&mut self.#members),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn name_at(&self, index: usize) -> ::std::option::Option<&str> {
+                const NAMES: &[&str] = &[#(#names),*];
+                NAMES.get(index).copied()
+            }
+
+            fn field_len(&self) -> usize {
+                #len
+            }
+        }
+
+        impl #impl_generics ::quartz::reflect::Typed for #name #ty_generics #where_clause {
+            fn type_info() -> ::quartz::reflect::TypeInfo {
+                ::quartz::reflect::TypeInfo::Struct {
+                    type_id: ::std::any::TypeId::of::<Self>(),
+                    type_name: ::std::any::type_name::<Self>(),
+                    fields: ::std::vec![
+                        #(::quartz::reflect::NamedField::new::<#types>(#names),)*
+                    ],
+                }
+            }
+        }
+
+        impl #impl_generics ::quartz::reflect::FromReflect for #name #ty_generics #where_clause {
+            fn from_reflect(
+                value: &dyn ::quartz::reflect::Reflect,
+            ) -> ::std::option::Option<Self> {
+                let ::quartz::reflect::ReflectRef::Struct(value) = value.reflect_ref() else {
+                    return ::std::option::Option::None;
+                };
+                ::std::option::Option::Some(Self {
+                    #(#members: ::quartz::reflect::FromReflect::from_reflect(value.field(#names)?)?,)*
+                })
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `Component` and `EnumComponent` for an enum, which tags every entity with a marker
+/// of its current variant. Also generates a module named after the enum in snake case holding an
+/// alias of the marker of every variant, e.g. `state::Attacking` for `State::Attacking`.
+#[proc_macro_derive(EnumComponent)]
+pub fn derive_enum_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let vis = &input.vis;
+
+    let Data::Enum(data) = &input.data else {
+        return syn::Error::new(name.span(), "EnumComponent can only be derived for enums")
+            .into_compile_error()
+            .into();
+    };
+    if !input.generics.params.is_empty() {
+        return syn::Error::new(
+            input.generics.span(),
+            "EnumComponent can't be derived for generic enums",
+        )
+        .into_compile_error()
+        .into();
+    }
+
+    let variants = data.variants.iter().map(|variant| &variant.ident);
+    let indices = (0..data.variants.len()).collect::<Vec<_>>();
+    let arms = data.variants.iter().zip(&indices).map(|(variant, index)| {
+        let ident = &variant.ident;
+        let pattern = match &variant.fields {
+            Fields::Named(_) => quote!({ .. }),
+            Fields::Unnamed(_) => quote!((..)),
+            Fields::Unit => quote!(),
+        };
+        quote!(Self::#ident #pattern => #index,)
+    });
+    let module = format_ident!("{}", snake_case(&name.to_string()), span = name.span());
+
+    quote! {
+        impl ::quartz::component::Component for #name {
+            fn register_hooks(hooks: &mut ::quartz::component::ComponentHooks) {
+                ::quartz::variant::register_hooks::<Self>(hooks);
+            }
+        }
+
+        impl ::quartz::variant::EnumComponent for #name {
+            const VARIANTS: &'static [fn(
+                &mut ::quartz::component::Components,
+            ) -> ::quartz::component::ComponentId] = &[
+                #(::quartz::component::Components::register_component::<
+                    ::quartz::variant::Variant<Self, #indices>
+                >,)*
+            ];
+
+            fn variant_index(&self) -> usize {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+
+        /// The markers of the variants of the enum component, see `EnumComponent`.
+        #vis mod #module {
+            #(pub type #variants = ::quartz::variant::Variant<super::#name, #indices>;)*
+        }
+    }
+    .into()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if index > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Returns the pattern binding all fields by name and the mapping statements of the fields that
+/// hold entities, accessing each field through `access`.
+fn map_fields(
+    fields: &Fields,
+    access: impl Fn(&TokenStream2) -> TokenStream2,
+) -> (TokenStream2, Vec<TokenStream2>) {
+    let members = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(index);
+                quote!(#index)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mapped = fields
+        .iter()
+        .zip(&members)
+        .filter(|(field, _)| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("entities"))
+                || holds_entities(&field.ty)
+        })
+        .map(|(_, member)| {
+            let value = access(member);
+            quote!(::quartz::map_entities::MapEntities::map_entities(&mut #value, mapper);)
+        })
+        .collect();
+
+    let bindings = members.iter().map(binding);
+    let pattern = match fields {
+        Fields::Named(_) => quote!({ #(#members: #bindings),* }),
+        Fields::Unnamed(_) => quote!((#(#bindings),*)),
+        Fields::Unit => quote!(),
+    };
+
+    (pattern, mapped)
+}
+
+fn binding(member: &TokenStream2) -> Ident {
+    format_ident!("__field_{}", member.to_string(), span = Span::call_site())
+}
+
+/// Returns `true` for `Entity`, `Option<Entity>` and `Vec<Entity>`.
+fn holds_entities(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+
+    match segment.ident.to_string().as_str() {
+        "Entity" => segment.arguments.is_none(),
+        "Option" | "Vec" => match &segment.arguments {
+            PathArguments::AngleBracketed(args) => {
+                matches!(
+                    args.args.first(),
+                    Some(GenericArgument::Type(inner)) if args.args.len() == 1 && is_entity(inner)
+                )
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_entity(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| {
+        segment.ident == "Entity" && segment.arguments.is_none()
+    }))
+}
@@ -1,9 +1,15 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use crate::{
+    builder::WorldHasher,
     component::ComponentId,
     entity::EntityLocation,
-    storage::{TableId, TableRow},
+    query::Access,
+    small_vec::IdList,
+    storage::{RemovalMode, TableId, TableRow},
     Entity,
 };
 
@@ -33,10 +39,28 @@ pub struct Archetype {
     table: TableId,
     entities: Vec<EntityRecord>,
     components: HashSet<ComponentId>,
+    /// Incremented whenever an entity is added to or removed from the archetype.
+    version: u64,
+    edges: Edges,
+}
+
+/// The archetypes entities of an archetype move to when components are added or removed, cached
+/// so repeated transitions don't have to look up the target by its sorted components.
+#[derive(Debug)]
+struct Edges {
+    /// Keyed by the added components, in the order they are inserted.
+    insert: HashMap<IdList, ArchetypeId, WorldHasher>,
+    /// Keyed by the removed components, in the order they are removed.
+    remove: HashMap<IdList, ArchetypeId, WorldHasher>,
 }
 
 impl Archetype {
-    fn new(id: ArchetypeId, table: TableId, component_ids: &[ComponentId]) -> Self {
+    fn new(
+        id: ArchetypeId,
+        table: TableId,
+        component_ids: &[ComponentId],
+        hasher: WorldHasher,
+    ) -> Self {
         let mut components = HashSet::new();
 
         for comp_id in component_ids {
@@ -48,19 +72,97 @@ impl Archetype {
             entities: Vec::new(),
             components,
             table,
+            version: 0,
+            edges: Edges {
+                insert: HashMap::with_hasher(hasher.clone()),
+                remove: HashMap::with_hasher(hasher),
+            },
         }
     }
 
-    fn contains(&self, id: ComponentId) -> bool {
+    /// The archetype entities move to when the components are added, if the transition happened
+    /// before.
+    pub(crate) fn insert_edge(&self, added: &[ComponentId]) -> Option<ArchetypeId> {
+        self.edges.insert.get(added).copied()
+    }
+
+    pub(crate) fn set_insert_edge(&mut self, added: &[ComponentId], target: ArchetypeId) {
+        self.edges
+            .insert
+            .insert(added.iter().copied().collect(), target);
+    }
+
+    /// The archetype entities move to when the components are removed, if the transition
+    /// happened before.
+    pub(crate) fn remove_edge(&self, removed: &[ComponentId]) -> Option<ArchetypeId> {
+        self.edges.remove.get(removed).copied()
+    }
+
+    pub(crate) fn set_remove_edge(&mut self, removed: &[ComponentId], target: ArchetypeId) {
+        self.edges
+            .remove
+            .insert(removed.iter().copied().collect(), target);
+    }
+
+    pub(crate) fn contains(&self, id: ComponentId) -> bool {
         self.components.contains(&id)
     }
 
+    pub(crate) fn components(&self) -> impl Iterator<Item = ComponentId> + use<'_> {
+        self.components.iter().copied()
+    }
+
+    pub(crate) fn table_id(&self) -> TableId {
+        self.table
+    }
+
+    pub(crate) fn id(&self) -> ArchetypeId {
+        self.id
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns `true` if the archetype has all required components, at least one of every
+    /// any-of group and none of the excluded components of the access.
+    pub(crate) fn matches(&self, access: &Access) -> bool {
+        access.required().iter().all(|id| self.contains(*id))
+            && access
+                .any_of()
+                .all(|ids| ids.iter().any(|id| self.contains(*id)))
+            && !access.excluded().iter().any(|id| self.contains(*id))
+    }
+
+    /// Iterates over the entities stored in the archetype in row order.
+    pub(crate) fn entities(&self) -> impl Iterator<Item = Entity> + use<'_> {
+        self.entities
+            .iter()
+            .map(|record| record.entity)
+            .filter(|entity| *entity != Entity::PLACEHOLDER)
+    }
+
+    /// The number of entities stored in the archetype.
+    pub(crate) fn entity_count(&self) -> usize {
+        self.entities
+            .iter()
+            .filter(|record| record.entity != Entity::PLACEHOLDER)
+            .count()
+    }
+
     pub(crate) fn allocate(&mut self, entity: Entity, table_row: TableRow) -> EntityLocation {
-        debug_assert!(self.entities.len() == table_row.index());
-        self.entities.push(EntityRecord {
+        self.version += 1;
+        let record = EntityRecord {
             entity,
             row: table_row,
-        });
+        };
+        if let Some(tombstone) = self.entities.get_mut(table_row.index()) {
+            debug_assert_eq!(tombstone.entity, Entity::PLACEHOLDER);
+            *tombstone = record;
+        } else {
+            debug_assert!(self.entities.len() == table_row.index());
+            self.entities.push(record);
+        }
 
         EntityLocation {
             archetype_id: self.id,
@@ -69,16 +171,59 @@ impl Archetype {
         }
     }
 
-    pub(crate) fn swap_remove(&mut self, row: TableRow) -> Option<Entity> {
-        let is_last = self.entities.len() - 1 == row.index();
-        let _ = self.entities.swap_remove(row.index());
+    /// Replaces the entity stored in a live row, e.g. after it was given a new id.
+    pub(crate) fn set_entity(&mut self, row: TableRow, entity: Entity) {
+        self.version += 1;
+        self.entities[row.index()].entity = entity;
+    }
 
-        if !is_last {
-            // Return the now moved entity
-            Some(self.entities[row.index()].entity)
-        } else {
-            None
-        }
+    /// Removes all entities, e.g. after their table was cleared.
+    fn clear(&mut self) {
+        self.version += 1;
+        self.entities.clear();
+    }
+
+    /// Reserves room for at least `additional` more entities.
+    pub(crate) fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), std::collections::TryReserveError> {
+        self.entities.try_reserve(additional)
+    }
+
+    /// Removes the entity at `row` according to `mode`, which has to match how its table removed
+    /// the row.
+    ///
+    /// Returns every entity that was moved to a different row together with its new row.
+    pub(crate) fn remove(
+        &mut self,
+        row: TableRow,
+        mode: RemovalMode,
+    ) -> impl Iterator<Item = (Entity, TableRow)> + use<'_> {
+        self.version += 1;
+        let index = row.index();
+        let moved = match mode {
+            RemovalMode::Swap => {
+                self.entities.swap_remove(index);
+                index..(index + 1).min(self.entities.len())
+            }
+            RemovalMode::Shift => {
+                self.entities.remove(index);
+                index..self.entities.len()
+            }
+            RemovalMode::Tombstone => {
+                self.entities[index].entity = Entity::PLACEHOLDER;
+                index..index
+            }
+        };
+
+        self.entities[moved.clone()]
+            .iter_mut()
+            .zip(moved)
+            .map(|(record, index)| {
+                record.row = TableRow(index);
+                (record.entity, record.row)
+            })
     }
 
     fn is_superset_of(&self, sub: &HashSet<ComponentId>) -> bool {
@@ -86,35 +231,87 @@ impl Archetype {
     }
 }
 
+/// Notification that a new [`Archetype`] has been created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchetypeCreated<'a> {
+    pub id: ArchetypeId,
+    pub table_id: TableId,
+    /// The sorted ids of the components stored in the archetype.
+    pub components: &'a [ComponentId],
+}
+
+type ArchetypeObserver = Box<dyn FnMut(&ArchetypeCreated) + Send + Sync>;
+
+#[derive(Default)]
+struct ArchetypeObservers(Vec<ArchetypeObserver>);
+
+impl fmt::Debug for ArchetypeObservers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchetypeObservers")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Archetypes {
     archetypes: Vec<Archetype>,
-    archetype_index: HashMap<ArchetypeComponents, ArchetypeId>,
-    component_index: HashMap<ComponentId, HashSet<ArchetypeId>>,
+    archetype_index: HashMap<ArchetypeComponents, ArchetypeId, WorldHasher>,
+    component_index: HashMap<ComponentId, HashSet<ArchetypeId>, WorldHasher>,
+    observers: ArchetypeObservers,
+    /// The number of entities every new archetype reserves.
+    capacity_hint: usize,
 }
 
 impl Archetypes {
+    pub(crate) fn with_hasher(hasher: WorldHasher, capacity_hint: usize) -> Self {
+        Self {
+            archetypes: Vec::new(),
+            archetype_index: HashMap::with_hasher(hasher.clone()),
+            component_index: HashMap::with_hasher(hasher),
+            observers: ArchetypeObservers::default(),
+            capacity_hint,
+        }
+    }
+
     pub fn get_id_or_insert(&mut self, table_id: TableId, ids: &[ComponentId]) -> ArchetypeId {
         let identifier = ArchetypeComponents {
             components: ids.into(),
         };
 
+        let hasher = self.component_index.hasher().clone();
         *self.archetype_index.entry(identifier).or_insert_with(|| {
             let id = ArchetypeId(self.archetypes.len());
 
             for comp_id in ids {
-                self.component_index
-                    .entry(*comp_id)
-                    .or_insert_with(HashSet::new)
-                    .insert(id);
+                self.component_index.entry(*comp_id).or_default().insert(id);
+            }
+
+            let mut archetype = Archetype::new(id, table_id, ids, hasher);
+            archetype.entities.reserve(self.capacity_hint);
+            self.archetypes.push(archetype);
+
+            let event = ArchetypeCreated {
+                id,
+                table_id,
+                components: ids,
+            };
+            for observer in self.observers.0.iter_mut() {
+                observer(&event);
             }
 
-            self.archetypes
-                .push(Archetype::new(id, table_id, ids.into()));
             id
         })
     }
 
+    /// Registers a callback that is invoked for every [`Archetype`] created from now on.
+    pub(crate) fn observe(
+        &mut self,
+        observer: impl FnMut(&ArchetypeCreated) + Send + Sync + 'static,
+    ) {
+        self.observers.0.push(Box::new(observer));
+    }
+
     /// Receives the [`Archetype`] for the given [`ArchetypeId`].
     ///
     /// Panics: If the archetype does not exist in this world.
@@ -133,26 +330,48 @@ impl Archetypes {
         self.archetypes.len()
     }
 
-    pub(crate) fn get_query_archetypes(
-        &self,
-        components: &[ComponentId],
-    ) -> (Vec<ArchetypeId>, Vec<TableId>) {
-        let initial = if let Some(initial) = self.component_index.get(&components[0]) {
-            initial
-        } else {
-            return (vec![], vec![]);
-        };
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Archetype> {
+        self.archetypes.iter()
+    }
 
-        let mut comps = HashSet::new();
-        for comp in components {
-            comps.insert(*comp);
+    /// Removes the entities of all archetypes, keeping the archetypes themselves.
+    pub(crate) fn clear_entities(&mut self) {
+        for archetype in self.archetypes.iter_mut() {
+            archetype.clear();
         }
+    }
 
-        let mut archetype_ids = initial
-            .iter()
-            .filter(|id| self.archetypes[id.index()].is_superset_of(&comps))
-            .copied()
-            .collect::<Vec<_>>();
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.is_empty()
+    }
+
+    pub(crate) fn get_query_archetypes(&self, access: &Access) -> (Vec<ArchetypeId>, Vec<TableId>) {
+        let components = access.required();
+        let mut archetype_ids = if let Some(first) = components.first() {
+            let initial = if let Some(initial) = self.component_index.get(first) {
+                initial
+            } else {
+                return (vec![], vec![]);
+            };
+
+            let mut comps = HashSet::new();
+            for comp in components {
+                comps.insert(*comp);
+            }
+
+            initial
+                .iter()
+                .filter(|id| self.archetypes[id.index()].is_superset_of(&comps))
+                .copied()
+                .collect::<Vec<_>>()
+        } else {
+            // Queries without any required components match every archetype
+            self.archetypes
+                .iter()
+                .map(|archetype| archetype.id)
+                .collect()
+        };
+        archetype_ids.retain(|id| self.archetypes[id.index()].matches(access));
         let mut table_ids = archetype_ids
             .iter()
             .map(|id| self.archetypes[id.index()].table)
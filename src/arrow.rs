@@ -0,0 +1,792 @@
+use std::{
+    borrow::Cow,
+    ffi::{c_char, c_void, CStr, CString},
+    fmt,
+    marker::PhantomData,
+    mem::{align_of, size_of},
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    component::{Component, ComponentId, Disabled},
+    storage::{Table, TableId, TableRow},
+    World,
+};
+
+/// The primitive Arrow types components can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl DataType {
+    /// The size of a single value in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Int64 | Self::UInt64 | Self::Float64 => 8,
+        }
+    }
+
+    fn align(self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => align_of::<u16>(),
+            Self::Int32 | Self::UInt32 => align_of::<u32>(),
+            Self::Float32 => align_of::<f32>(),
+            Self::Int64 | Self::UInt64 => align_of::<u64>(),
+            Self::Float64 => align_of::<f64>(),
+        }
+    }
+
+    /// The format string of the type in the Arrow C data interface.
+    fn format(self) -> &'static CStr {
+        match self {
+            Self::Int8 => c"c",
+            Self::Int16 => c"s",
+            Self::Int32 => c"i",
+            Self::Int64 => c"l",
+            Self::UInt8 => c"C",
+            Self::UInt16 => c"S",
+            Self::UInt32 => c"I",
+            Self::UInt64 => c"L",
+            Self::Float32 => c"f",
+            Self::Float64 => c"g",
+        }
+    }
+}
+
+/// A Rust type with the same layout as one of the Arrow [`DataType`]s.
+pub trait ArrowPrimitive: Copy + 'static {
+    const DATA_TYPE: DataType;
+}
+
+macro_rules! impl_arrow_primitive {
+    ($($ty:ty => $data_type:ident),*) => {
+        $(
+            impl ArrowPrimitive for $ty {
+                const DATA_TYPE: DataType = DataType::$data_type;
+            }
+        )*
+    };
+}
+
+impl_arrow_primitive!(
+    i8 => Int8, i16 => Int16, i32 => Int32, i64 => Int64,
+    u8 => UInt8, u16 => UInt16, u32 => UInt32, u64 => UInt64,
+    f32 => Float32, f64 => Float64
+);
+
+/// A primitive value stored inside a component, which is exported as its own Arrow column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    name: Cow<'static, str>,
+    data_type: DataType,
+    offset: usize,
+}
+
+impl Field {
+    /// A field of type `P` stored `offset` bytes into the component, e.g. as returned by
+    /// [`std::mem::offset_of`].
+    pub fn new<P: ArrowPrimitive>(name: impl Into<Cow<'static, str>>, offset: usize) -> Self {
+        Self {
+            name: name.into(),
+            data_type: P::DATA_TYPE,
+            offset,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+}
+
+/// A component that can be exported to Arrow with [`World::export_arrow`].
+///
+/// Components consisting of a single field at offset 0, e.g. `#[repr(transparent)]` wrappers of
+/// a primitive, are exported without copying, all others are copied field by field.
+///
+/// # Safety
+/// Every field must lie within the component and only cover initialized bytes of the declared
+/// type, so no padding.
+pub unsafe trait ArrowComponent: Component {
+    /// The fields exported for this component, whose names have to be unique among all exported
+    /// components.
+    fn fields() -> Vec<Field>;
+}
+
+/// Selects the components to export with [`World::export_arrow`].
+pub struct ArrowExport<'w> {
+    world: &'w World,
+    components: Vec<(Option<ComponentId>, usize, Vec<Field>)>,
+}
+
+impl<'w> ArrowExport<'w> {
+    /// Adds the fields of `T` to the export. Only entities with every added component are
+    /// exported.
+    ///
+    /// Panics: If a field is out of bounds for `T`.
+    pub fn component<T: ArrowComponent>(mut self) -> Self {
+        let fields = T::fields();
+        for field in fields.iter() {
+            assert!(
+                field.offset + field.data_type.size() <= size_of::<T>(),
+                "Field {} is out of bounds for {}",
+                field.name,
+                std::any::type_name::<T>()
+            );
+        }
+        self.components
+            .push((self.world.component_id::<T>(), size_of::<T>(), fields));
+        self
+    }
+
+    /// Exports the selected components, one [`RecordBatch`] per table. Disabled entities are
+    /// left out, like in queries. Components stored in a sparse set are always copied.
+    ///
+    /// Every batch starts with the `entity_index` and `entity_generation` columns, followed by
+    /// the fields of the components in the order they were added.
+    pub fn export(self) -> Vec<RecordBatch<'w>> {
+        let Some(ids) = self
+            .components
+            .iter()
+            .map(|(id, ..)| *id)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Vec::new();
+        };
+        let sparse = ids
+            .iter()
+            .filter(|id| self.world.is_sparse(**id))
+            .copied()
+            .collect::<Vec<_>>();
+        let disabled = self.world.component_id::<Disabled>();
+
+        self.world
+            .archetypes
+            .iter()
+            .filter(|archetype| {
+                ids.iter()
+                    .all(|id| sparse.contains(id) || archetype.contains(*id))
+            })
+            .filter(|archetype| !disabled.is_some_and(|id| archetype.contains(id)))
+            .filter_map(|archetype| {
+                let table_id = archetype.table_id();
+                let table = self.world.tables.get(table_id)?;
+                let rows = (0..table.len())
+                    .map(TableRow)
+                    .filter(|row| table.is_live(*row))
+                    .filter(|row| {
+                        let entity = table.entity(*row);
+                        sparse.iter().all(|id| self.world.has_sparse(entity, *id))
+                    })
+                    .collect::<Vec<_>>();
+                if rows.is_empty() {
+                    return None;
+                }
+
+                let mut columns = vec![
+                    ArrowColumn::entities(table, &rows, "entity_index", |entity| entity.index()),
+                    ArrowColumn::entities(table, &rows, "entity_generation", |entity| {
+                        entity.generation()
+                    }),
+                ];
+                for ((_, size, fields), component_id) in self.components.iter().zip(&ids) {
+                    for field in fields {
+                        columns.push(ArrowColumn::component(
+                            self.world,
+                            table,
+                            *component_id,
+                            *size,
+                            field,
+                            &rows,
+                        ));
+                    }
+                }
+
+                Some(RecordBatch {
+                    table: table_id,
+                    len: rows.len(),
+                    columns,
+                })
+            })
+            .collect()
+    }
+}
+
+impl World {
+    /// Starts an export of components to [Apache Arrow](https://arrow.apache.org).
+    ///
+    /// ```
+    /// # use quartz::{arrow::{ArrowComponent, Field}, component::Component, World};
+    /// #[repr(transparent)]
+    /// struct Health(f32);
+    /// impl Component for Health {}
+    /// unsafe impl ArrowComponent for Health {
+    ///     fn fields() -> Vec<Field> {
+    ///         vec![Field::new::<f32>("health", 0)]
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.spawn(Health(10.0));
+    ///
+    /// let batches = world.export_arrow().component::<Health>().export();
+    /// assert_eq!(batches[0].column::<f32>("health"), Some(&[10.0][..]));
+    /// ```
+    pub fn export_arrow(&self) -> ArrowExport<'_> {
+        ArrowExport {
+            world: self,
+            components: Vec::new(),
+        }
+    }
+}
+
+/// The exported components of a single table, with one column per field.
+pub struct RecordBatch<'w> {
+    table: TableId,
+    len: usize,
+    columns: Vec<ArrowColumn<'w>>,
+}
+
+struct ArrowColumn<'w> {
+    name: CString,
+    data_type: DataType,
+    buffer: Buffer<'w>,
+}
+
+enum Buffer<'w> {
+    /// Points into a column of a table of the world.
+    Borrowed(NonNull<u8>, PhantomData<&'w World>),
+    /// Stored in `u64`s to align the values for every data type.
+    Owned(Box<[u64]>),
+}
+
+impl Buffer<'_> {
+    fn as_ptr(&self) -> *const u8 {
+        match self {
+            Self::Borrowed(ptr, _) => ptr.as_ptr(),
+            Self::Owned(data) => data.as_ptr().cast(),
+        }
+    }
+}
+
+impl<'w> ArrowColumn<'w> {
+    fn new(name: &str, data_type: DataType, buffer: Buffer<'w>) -> Self {
+        Self {
+            name: CString::new(name).expect("Arrow field names must not contain nul bytes"),
+            data_type,
+            buffer,
+        }
+    }
+
+    fn entities<P: ArrowPrimitive>(
+        table: &Table,
+        rows: &[TableRow],
+        name: &str,
+        value: impl Fn(crate::Entity) -> P,
+    ) -> Self {
+        let mut data = vec![0u64; (rows.len() * size_of::<P>()).div_ceil(8)].into_boxed_slice();
+        let dst = data.as_mut_ptr().cast::<P>();
+        for (index, row) in rows.iter().enumerate() {
+            // SAFETY: The buffer has room for a value of every row.
+            unsafe { dst.add(index).write(value(table.entity(*row))) };
+        }
+
+        Self::new(name, P::DATA_TYPE, Buffer::Owned(data))
+    }
+
+    /// Exports the field of the component of the rows, from their table or sparse set. Every
+    /// entity of the rows has to have the component.
+    fn component(
+        world: &'w World,
+        table: &'w Table,
+        id: ComponentId,
+        component_size: usize,
+        field: &Field,
+        rows: &[TableRow],
+    ) -> Self {
+        let data_type = field.data_type;
+        let sparse = world.sparse_sets.get(id).filter(|_| world.is_sparse(id));
+        // SAFETY: The rows are live rows of the table and their entities have the component.
+        let get = |row: TableRow| unsafe {
+            match sparse {
+                Some(set) => set.get(table.entity(row)),
+                None => table.get_component(id, row),
+            }
+            .unwrap()
+            .as_ptr()
+        };
+
+        let base = get(rows[0]);
+        let zero_copy = sparse.is_none()
+            && field.offset == 0
+            && data_type.size() == component_size
+            && base.align_offset(data_type.align()) == 0
+            && rows.len() == table.len();
+        if zero_copy {
+            let ptr = NonNull::new(base).unwrap();
+            return Self::new(&field.name, data_type, Buffer::Borrowed(ptr, PhantomData));
+        }
+
+        let size = data_type.size();
+        let mut data = vec![0u64; (rows.len() * size).div_ceil(8)].into_boxed_slice();
+        let dst = data.as_mut_ptr().cast::<u8>();
+        for (index, row) in rows.iter().enumerate() {
+            // SAFETY: `ArrowComponent` guarantees that the field lies within the component.
+            unsafe {
+                let src = get(*row);
+                ptr::copy_nonoverlapping(src.add(field.offset), dst.add(index * size), size);
+            }
+        }
+
+        Self::new(&field.name, data_type, Buffer::Owned(data))
+    }
+}
+
+impl<'w> RecordBatch<'w> {
+    /// The table the batch was exported from.
+    pub fn table(&self) -> TableId {
+        self.table
+    }
+
+    /// The number of rows in the batch.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The names and types of the columns.
+    pub fn schema(&self) -> impl Iterator<Item = (&str, DataType)> + use<'_, 'w> {
+        self.columns.iter().map(|column| {
+            (
+                column
+                    .name
+                    .to_str()
+                    .expect("names are created from strings"),
+                column.data_type,
+            )
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&ArrowColumn<'w>> {
+        self.columns
+            .iter()
+            .find(|column| column.name.as_bytes() == name.as_bytes())
+    }
+
+    /// Returns the values of a column, if it exists and has the type of `P`.
+    pub fn column<P: ArrowPrimitive>(&self, name: &str) -> Option<&[P]> {
+        let column = self.get(name).filter(|c| c.data_type == P::DATA_TYPE)?;
+        // SAFETY: The buffer holds `len` aligned and initialized values of the column's type.
+        Some(unsafe { std::slice::from_raw_parts(column.buffer.as_ptr().cast(), self.len) })
+    }
+
+    /// Whether the column points directly into the world instead of being copied.
+    pub fn is_borrowed(&self, name: &str) -> Option<bool> {
+        self.get(name)
+            .map(|column| matches!(column.buffer, Buffer::Borrowed(..)))
+    }
+
+    /// Copies all borrowed columns, which detaches the batch from the world.
+    pub fn into_owned(self) -> RecordBatch<'static> {
+        let columns = self
+            .columns
+            .into_iter()
+            .map(|column| {
+                let data = match column.buffer {
+                    Buffer::Owned(data) => data,
+                    Buffer::Borrowed(ptr, _) => {
+                        let bytes = self.len * column.data_type.size();
+                        let mut data = vec![0u64; bytes.div_ceil(8)].into_boxed_slice();
+                        // SAFETY: The column holds `len` initialized values.
+                        unsafe {
+                            ptr::copy_nonoverlapping(ptr.as_ptr(), data.as_mut_ptr().cast(), bytes)
+                        };
+                        data
+                    }
+                };
+                ArrowColumn {
+                    name: column.name,
+                    data_type: column.data_type,
+                    buffer: Buffer::Owned(data),
+                }
+            })
+            .collect();
+
+        RecordBatch {
+            table: self.table,
+            len: self.len,
+            columns,
+        }
+    }
+
+    /// Exports the batch as a struct array through the
+    /// [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html), which
+    /// arrow-rs, polars and pyarrow can import.
+    ///
+    /// # Safety
+    /// Borrowed columns point into the world, which must neither be changed nor dropped until
+    /// the array is released. Use [`RecordBatch::into_owned`] to export a batch independently
+    /// of the world.
+    pub unsafe fn into_ffi(self) -> (ArrowArray, ArrowSchema) {
+        let schema_children = self
+            .columns
+            .iter()
+            .map(|column| {
+                Box::into_raw(Box::new(ArrowSchema::new(
+                    column.data_type.format(),
+                    column.name.clone(),
+                    Vec::new(),
+                )))
+            })
+            .collect();
+        let schema = ArrowSchema::new(c"+s", CString::default(), schema_children);
+
+        let len = self.len as i64;
+        let array_children = self
+            .columns
+            .into_iter()
+            .map(|column| {
+                let (data, owned) = match column.buffer {
+                    Buffer::Owned(data) => {
+                        let data = NonNull::from(Box::leak(data));
+                        (data.as_ptr().cast(), Some(data))
+                    }
+                    Buffer::Borrowed(ptr, _) => (ptr.as_ptr().cast(), None),
+                };
+                Box::into_raw(Box::new(ArrowArray::new(
+                    len,
+                    vec![ptr::null(), data],
+                    Vec::new(),
+                    owned,
+                )))
+            })
+            .collect();
+        let array = ArrowArray::new(len, vec![ptr::null()], array_children, None);
+
+        (array, schema)
+    }
+}
+
+impl fmt::Debug for RecordBatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordBatch")
+            .field("table", &self.table)
+            .field("len", &self.len)
+            .field("schema", &self.schema().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// The `ArrowSchema` struct of the Arrow C data interface, released when dropped.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowSchema {
+    pub format: *const c_char,
+    pub name: *const c_char,
+    pub metadata: *const c_char,
+    pub flags: i64,
+    pub n_children: i64,
+    pub children: *mut *mut ArrowSchema,
+    pub dictionary: *mut ArrowSchema,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    pub private_data: *mut c_void,
+}
+
+struct SchemaPrivateData {
+    name: CString,
+    children: Box<[*mut ArrowSchema]>,
+}
+
+impl ArrowSchema {
+    fn new(format: &'static CStr, name: CString, children: Vec<*mut ArrowSchema>) -> Self {
+        let mut private_data = Box::new(SchemaPrivateData {
+            name,
+            children: children.into_boxed_slice(),
+        });
+
+        Self {
+            format: format.as_ptr(),
+            name: private_data.name.as_ptr(),
+            metadata: ptr::null(),
+            flags: 0,
+            n_children: private_data.children.len() as i64,
+            children: private_data.children.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_schema),
+            private_data: Box::into_raw(private_data).cast(),
+        }
+    }
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    let schema = &mut *schema;
+    let private_data = Box::from_raw(schema.private_data.cast::<SchemaPrivateData>());
+    for child in private_data.children.iter() {
+        // Dropping the child releases it
+        drop(Box::from_raw(*child));
+    }
+    schema.release = None;
+}
+
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+/// The `ArrowArray` struct of the Arrow C data interface, released when dropped.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowArray {
+    pub length: i64,
+    pub null_count: i64,
+    pub offset: i64,
+    pub n_buffers: i64,
+    pub n_children: i64,
+    pub buffers: *mut *const c_void,
+    pub children: *mut *mut ArrowArray,
+    pub dictionary: *mut ArrowArray,
+    pub release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    pub private_data: *mut c_void,
+}
+
+struct ArrayPrivateData {
+    buffers: Box<[*const c_void]>,
+    children: Box<[*mut ArrowArray]>,
+    /// An owned data buffer, leaked so the pointer in `buffers` stays valid when this is moved.
+    data: Option<NonNull<[u64]>>,
+}
+
+impl ArrowArray {
+    fn new(
+        length: i64,
+        buffers: Vec<*const c_void>,
+        children: Vec<*mut ArrowArray>,
+        data: Option<NonNull<[u64]>>,
+    ) -> Self {
+        let mut private_data = Box::new(ArrayPrivateData {
+            buffers: buffers.into_boxed_slice(),
+            children: children.into_boxed_slice(),
+            data,
+        });
+
+        Self {
+            length,
+            null_count: 0,
+            offset: 0,
+            n_buffers: private_data.buffers.len() as i64,
+            n_children: private_data.children.len() as i64,
+            buffers: private_data.buffers.as_mut_ptr(),
+            children: private_data.children.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_array),
+            private_data: Box::into_raw(private_data).cast(),
+        }
+    }
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    let array = &mut *array;
+    let private_data = Box::from_raw(array.private_data.cast::<ArrayPrivateData>());
+    for child in private_data.children.iter() {
+        // Dropping the child releases it
+        drop(Box::from_raw(*child));
+    }
+    if let Some(data) = private_data.data {
+        drop(Box::from_raw(data.as_ptr()));
+    }
+    array.release = None;
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CStr, mem::offset_of};
+
+    use crate::{
+        component::{Component, Disabled, StorageType},
+        World,
+    };
+
+    use super::{ArrowComponent, DataType, Field};
+
+    #[repr(transparent)]
+    struct Health(f32);
+    impl Component for Health {}
+    unsafe impl ArrowComponent for Health {
+        fn fields() -> Vec<Field> {
+            vec![Field::new::<f32>("health", 0)]
+        }
+    }
+
+    struct Position {
+        x: f64,
+        y: f64,
+        level: u8,
+    }
+    impl Component for Position {}
+    unsafe impl ArrowComponent for Position {
+        fn fields() -> Vec<Field> {
+            vec![
+                Field::new::<f64>("x", offset_of!(Position, x)),
+                Field::new::<f64>("y", offset_of!(Position, y)),
+                Field::new::<u8>("level", offset_of!(Position, level)),
+            ]
+        }
+    }
+
+    #[repr(transparent)]
+    struct Charge(u32);
+    impl Component for Charge {
+        const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+    }
+    unsafe impl ArrowComponent for Charge {
+        fn fields() -> Vec<Field> {
+            vec![Field::new::<u32>("charge", 0)]
+        }
+    }
+
+    fn position(i: u8) -> Position {
+        Position {
+            x: f64::from(i),
+            y: -f64::from(i),
+            level: i,
+        }
+    }
+
+    #[test]
+    fn export_tables() {
+        let mut world = World::new();
+        let entities = (0..4)
+            .map(|i| world.spawn((Health(f32::from(i)), position(i))))
+            .collect::<Vec<_>>();
+        world.spawn(Health(10.0));
+        world.insert(entities[1], Disabled);
+
+        let batches = world.export_arrow().component::<Health>().export();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 4);
+        assert!(batches
+            .iter()
+            .all(|b| b.is_borrowed("health") == Some(true)));
+
+        let batches = world
+            .export_arrow()
+            .component::<Health>()
+            .component::<Position>()
+            .export();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(
+            batch.schema().map(|(name, _)| name).collect::<Vec<_>>(),
+            [
+                "entity_index",
+                "entity_generation",
+                "health",
+                "x",
+                "y",
+                "level"
+            ]
+        );
+        assert_eq!(batch.is_borrowed("x"), Some(false));
+        assert_eq!(batch.column::<f64>("y"), Some(&[-0.0, -3.0, -2.0][..]));
+        assert_eq!(batch.column::<u8>("level"), Some(&[0, 3, 2][..]));
+        assert_eq!(batch.column::<u32>("level"), None);
+        assert_eq!(
+            batch.column::<u32>("entity_generation"),
+            Some(&[1, 1, 1][..])
+        );
+    }
+
+    #[test]
+    fn export_sparse() {
+        let mut world = World::new();
+        let entities = (0u8..4)
+            .map(|i| world.spawn(Health(f32::from(i))))
+            .collect::<Vec<_>>();
+        world.insert(entities[1], Charge(10));
+        world.insert(entities[3], Charge(30));
+        world.spawn(Charge(40));
+
+        let batches = world
+            .export_arrow()
+            .component::<Health>()
+            .component::<Charge>()
+            .export();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.is_borrowed("charge"), Some(false));
+        assert_eq!(batch.column::<f32>("health"), Some(&[1.0, 3.0][..]));
+        assert_eq!(batch.column::<u32>("charge"), Some(&[10, 30][..]));
+        assert_eq!(
+            batch.column("entity_index"),
+            Some(&[entities[1].index(), entities[3].index()][..])
+        );
+
+        let batches = world.export_arrow().component::<Charge>().export();
+        assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn export_ffi() {
+        let mut world = World::new();
+        for i in 0..3 {
+            world.spawn((Health(f32::from(i)), position(i)));
+        }
+
+        let batch = world
+            .export_arrow()
+            .component::<Health>()
+            .component::<Position>()
+            .export()
+            .remove(0)
+            .into_owned();
+        drop(world);
+
+        let (array, schema) = unsafe { batch.into_ffi() };
+        unsafe {
+            assert_eq!(CStr::from_ptr(schema.format), c"+s");
+            assert_eq!(schema.n_children, 6);
+            let x = &**schema.children.add(3);
+            assert_eq!(CStr::from_ptr(x.format), DataType::Float64.format());
+            assert_eq!(CStr::from_ptr(x.name), c"x");
+
+            assert_eq!(array.length, 3);
+            assert_eq!(array.n_children, 6);
+            let health = &**array.children.add(2);
+            assert_eq!(health.n_buffers, 2);
+            let values = std::slice::from_raw_parts(health.buffers.add(1).read().cast::<f32>(), 3);
+            assert_eq!(values, [0.0, 1.0, 2.0]);
+        }
+    }
+}
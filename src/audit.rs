@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+use crate::{
+    component::{ComponentId, Tick},
+    entity::Entity,
+};
+
+/// The kind of structural change an [`AuditRecord`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StructuralChange {
+    Spawn,
+    Despawn,
+    Insert,
+    Remove,
+}
+
+/// A single structural change of an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The world tick the change happened in.
+    pub tick: Tick,
+    pub change: StructuralChange,
+    pub entity: Entity,
+    /// The components that were spawned with, inserted into or removed from the entity. For
+    /// despawns these are all components the entity had.
+    pub components: Box<[ComponentId]>,
+}
+
+/// A ring buffer of the most recent structural changes of a world, for tracking down which code
+/// despawned an entity or removed a component.
+///
+/// Recording is opt-in, see [`World::enable_audit_log`](crate::World::enable_audit_log).
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    records: VecDeque<AuditRecord>,
+    capacity: usize,
+}
+
+impl AuditLog {
+    /// Creates a log holding up to `capacity` records, evicting the oldest record once full.
+    ///
+    /// Panics: If the capacity is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "The audit log must be able to hold a record");
+
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        tick: Tick,
+        change: StructuralChange,
+        entity: Entity,
+        components: &[ComponentId],
+    ) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(AuditRecord {
+            tick,
+            change,
+            entity,
+            components: components.into(),
+        });
+    }
+
+    /// Iterates over the records from oldest to newest.
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &AuditRecord> {
+        self.records.iter()
+    }
+
+    /// Iterates over the records of the entity from oldest to newest.
+    pub fn entity_records(&self, entity: Entity) -> impl DoubleEndedIterator<Item = &AuditRecord> {
+        self.records
+            .iter()
+            .filter(move |record| record.entity == entity)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{ComponentId, Tick},
+        entity::Entity,
+    };
+
+    use super::{AuditLog, StructuralChange};
+
+    #[test]
+    fn ring_buffer() {
+        let mut log = AuditLog::new(2);
+        let e0 = Entity::from(1, 0);
+        let e1 = Entity::from(1, 1);
+        let ids = [ComponentId::new(0)];
+
+        log.record(Tick::new(0), StructuralChange::Spawn, e0, &ids);
+        log.record(Tick::new(0), StructuralChange::Spawn, e1, &ids);
+        log.record(Tick::new(1), StructuralChange::Despawn, e0, &ids);
+        assert_eq!(log.len(), 2);
+
+        let changes = log
+            .records()
+            .map(|record| (record.entity, record.change))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (e1, StructuralChange::Spawn),
+                (e0, StructuralChange::Despawn)
+            ]
+        );
+        assert_eq!(log.entity_records(e0).count(), 1);
+    }
+}
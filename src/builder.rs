@@ -0,0 +1,219 @@
+use std::{
+    collections::hash_map::{DefaultHasher, RandomState},
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+};
+
+use crate::{
+    archetype::Archetypes,
+    component::{Components, SharedComponentIds},
+    entity::Entities,
+    storage::{RemovalMode, Tables},
+    World,
+};
+
+/// Configures a [`World`] before it is created, see [`World::builder`].
+///
+/// Pre-sizing the world avoids reallocations and rehashing once it is under load.
+#[derive(Debug, Default)]
+pub struct WorldBuilder {
+    entities_capacity: usize,
+    table_capacity_hint: usize,
+    hasher: WorldHasher,
+    shared_component_ids: Option<SharedComponentIds>,
+    removal_mode: RemovalMode,
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves room for this many entities up front.
+    pub fn entities_capacity(mut self, capacity: usize) -> Self {
+        self.entities_capacity = capacity;
+        self
+    }
+
+    /// Reserves room for this many rows in every table and archetype when it is created.
+    pub fn table_capacity_hint(mut self, capacity: usize) -> Self {
+        self.table_capacity_hint = capacity;
+        self
+    }
+
+    /// Sets the hasher used by the maps that look up tables and archetypes by their components.
+    pub fn hasher(mut self, hasher: WorldHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Takes the [`ComponentId`](crate::component::ComponentId)s from `shared`, see
+    /// [`World::with_shared_component_ids`].
+    pub fn shared_component_ids(mut self, shared: SharedComponentIds) -> Self {
+        self.shared_component_ids = Some(shared);
+        self
+    }
+
+    /// Sets how entities are removed from tables, see [`World::set_removal_mode`].
+    pub fn removal_mode(mut self, mode: RemovalMode) -> Self {
+        self.removal_mode = mode;
+        self
+    }
+
+    pub fn build(self) -> World {
+        World {
+            entities: Entities::with_capacity(self.entities_capacity),
+            archetypes: Archetypes::with_hasher(self.hasher.clone(), self.table_capacity_hint),
+            components: self
+                .shared_component_ids
+                .map_or_else(Components::new, Components::with_shared_ids),
+            tables: Tables::with_hasher(self.hasher, self.table_capacity_hint),
+            removal_mode: self.removal_mode,
+            ..World::new()
+        }
+    }
+}
+
+/// The [`BuildHasher`] of the maps a [`World`] uses internally.
+///
+/// Defaults to the randomly seeded hasher of the standard library.
+#[derive(Clone)]
+pub struct WorldHasher(HasherKind);
+
+#[derive(Clone)]
+enum HasherKind {
+    Random(RandomState),
+    Custom(Arc<dyn Fn() -> Box<dyn Hasher> + Send + Sync>),
+}
+
+impl WorldHasher {
+    /// Uses a custom hasher, e.g. a faster non-cryptographic one or a deterministic one for
+    /// reproducible runs.
+    ///
+    /// Hashers of the custom builder are boxed, which costs an allocation per hashed key.
+    pub fn new<S>(build_hasher: S) -> Self
+    where
+        S: BuildHasher + Send + Sync + 'static,
+        S::Hasher: 'static,
+    {
+        Self(HasherKind::Custom(Arc::new(move || {
+            Box::new(build_hasher.build_hasher())
+        })))
+    }
+}
+
+impl Default for WorldHasher {
+    fn default() -> Self {
+        Self(HasherKind::Random(RandomState::new()))
+    }
+}
+
+impl fmt::Debug for WorldHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            HasherKind::Random(_) => write!(f, "WorldHasher(Random)"),
+            HasherKind::Custom(_) => write!(f, "WorldHasher(Custom)"),
+        }
+    }
+}
+
+impl BuildHasher for WorldHasher {
+    type Hasher = WorldHasherState;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match &self.0 {
+            HasherKind::Random(state) => WorldHasherState::Random(state.build_hasher()),
+            HasherKind::Custom(build) => WorldHasherState::Custom(build()),
+        }
+    }
+}
+
+/// The [`Hasher`] built by a [`WorldHasher`].
+pub enum WorldHasherState {
+    Random(DefaultHasher),
+    Custom(Box<dyn Hasher>),
+}
+
+impl Hasher for WorldHasherState {
+    fn finish(&self) -> u64 {
+        match self {
+            Self::Random(hasher) => hasher.finish(),
+            Self::Custom(hasher) => hasher.finish(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Random(hasher) => hasher.write(bytes),
+            Self::Custom(hasher) => hasher.write(bytes),
+        }
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        match self {
+            Self::Random(hasher) => hasher.write_usize(i),
+            Self::Custom(hasher) => hasher.write_usize(i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        hash::{BuildHasherDefault, DefaultHasher},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{component::Component, storage::RemovalMode, World};
+
+    use super::WorldHasher;
+
+    struct Marker(u32);
+    impl Component for Marker {}
+
+    struct Other;
+    impl Component for Other {}
+
+    #[test]
+    fn build_world() {
+        let mut world = World::builder()
+            .entities_capacity(64)
+            .table_capacity_hint(16)
+            .hasher(WorldHasher::new(
+                BuildHasherDefault::<DefaultHasher>::default(),
+            ))
+            .removal_mode(RemovalMode::Shift)
+            .build();
+        assert!(world.entities.capacity() >= 64);
+        assert_eq!(world.removal_mode(), RemovalMode::Shift);
+
+        let entity = world.spawn(Marker(1));
+        world.spawn((Marker(2), Other));
+        world.insert(entity, Other);
+
+        let table = world.tables.get(world.entity(entity).location().table_id);
+        assert!(table.unwrap().capacity() >= 16);
+        assert_eq!(world.query::<&Marker>().map(|m| m.0).sum::<u32>(), 3);
+    }
+
+    #[test]
+    fn custom_hasher() {
+        static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Default)]
+        struct Counting;
+        impl std::hash::BuildHasher for Counting {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                BUILT.fetch_add(1, Ordering::Relaxed);
+                DefaultHasher::new()
+            }
+        }
+
+        let mut world = World::builder().hasher(WorldHasher::new(Counting)).build();
+        world.spawn((Marker(1), Other));
+        assert!(BUILT.load(Ordering::Relaxed) > 0);
+    }
+}
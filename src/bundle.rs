@@ -0,0 +1,275 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use crate::{
+    archetype::{ArchetypeId, Archetypes},
+    component::{Bundle, ComponentId, ComponentTicks, Components, StorageType},
+    entity::{Entity, EntityLocation},
+    storage::{SparseSets, TableId, Tables},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BundleId(usize);
+
+impl BundleId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Metadata of a [`Bundle`] type, computed once when the bundle is first used.
+#[derive(Debug)]
+pub struct BundleInfo {
+    id: BundleId,
+    name: &'static str,
+    /// Component ids in the order [`Bundle::get_components`] yields the components.
+    component_ids: Arc<[ComponentId]>,
+    /// Only initialized once the bundle is spawned, so inserting a bundle doesn't create an
+    /// archetype no entity might ever live in.
+    spawn_target: Option<SpawnTarget>,
+}
+
+/// The table and archetype entities spawned with only this bundle are stored in.
+#[derive(Debug)]
+pub(crate) struct SpawnTarget {
+    pub(crate) table_id: TableId,
+    pub(crate) archetype_id: ArchetypeId,
+    /// Column index inside the table for each table component of the bundle, in bundle order.
+    pub(crate) columns: Box<[usize]>,
+    /// For each component of the bundle, in bundle order, its id if it is stored in a sparse set.
+    pub(crate) sparse: Box<[Option<ComponentId>]>,
+}
+
+impl SpawnTarget {
+    /// Writes the components of the bundle into a new row of the target table and the sparse
+    /// sets and adds the entity to the target archetype.
+    ///
+    /// The sparse sets of the bundle must have been created, see [`SparseSets::init`].
+    pub(crate) fn write<B: Bundle>(
+        &self,
+        tables: &mut Tables,
+        archetypes: &mut Archetypes,
+        sparse_sets: &mut SparseSets,
+        entity: Entity,
+        bundle: B,
+        ticks: ComponentTicks,
+    ) -> EntityLocation {
+        let table = tables.get_mut_unchecked(self.table_id);
+        let row = table.allocate(entity);
+        let mut columns = self.columns.iter();
+        let mut sparse = self.sparse.iter();
+        bundle.get_components(&mut |ptr| unsafe {
+            if let Some(id) = sparse.next().unwrap() {
+                sparse_sets
+                    .get_mut(*id)
+                    .expect("sparse sets must be created before spawning")
+                    .insert(entity, ptr, ticks.added);
+                return;
+            }
+            let column = *columns.next().unwrap();
+            table
+                .get_column_unchecked_mut(column)
+                .initialize_unchecked(row.index(), ptr, ticks);
+        });
+
+        archetypes
+            .get_mut_unchecked(self.archetype_id)
+            .allocate(entity, row)
+    }
+}
+
+impl BundleInfo {
+    pub fn id(&self) -> BundleId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The components of this bundle, in the order they are declared.
+    pub fn components(&self) -> &[ComponentId] {
+        &self.component_ids
+    }
+
+    pub(crate) fn component_ids(&self) -> &Arc<[ComponentId]> {
+        &self.component_ids
+    }
+
+    pub(crate) fn spawn_target(&self) -> Option<&SpawnTarget> {
+        self.spawn_target.as_ref()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Bundles {
+    bundles: Vec<BundleInfo>,
+    indices: HashMap<TypeId, BundleId>,
+}
+
+impl Bundles {
+    /// Registers the bundle type and its components.
+    ///
+    /// Panics: If the bundle contains the same component more than once.
+    pub fn register<B: Bundle>(&mut self, components: &mut Components) -> BundleId {
+        *self.indices.entry(TypeId::of::<B>()).or_insert_with(|| {
+            let mut component_ids = Vec::new();
+            B::component_ids(components, &mut |id| component_ids.push(id));
+
+            let mut sorted = component_ids.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(
+                sorted.len(),
+                component_ids.len(),
+                "Bundle {} contains duplicate components",
+                type_name::<B>()
+            );
+
+            let id = BundleId(self.bundles.len());
+            self.bundles.push(BundleInfo {
+                id,
+                name: type_name::<B>(),
+                component_ids: component_ids.into(),
+                spawn_target: None,
+            });
+            id
+        })
+    }
+
+    /// Registers the bundle type like [`Bundles::register`] and additionally creates the table and
+    /// archetype it spawns into if necessary. Components stored in sparse sets are not part of
+    /// either.
+    pub(crate) fn register_spawn<B: Bundle>(
+        &mut self,
+        components: &mut Components,
+        tables: &mut Tables,
+        archetypes: &mut Archetypes,
+    ) -> BundleId {
+        let id = self.register::<B>(components);
+        let info = &mut self.bundles[id.index()];
+        if info.spawn_target.is_none() {
+            let sparse = info
+                .component_ids
+                .iter()
+                .map(|id| {
+                    let info = components
+                        .get_info(id)
+                        .expect("bundle components are registered");
+                    (info.storage_type == StorageType::SparseSet).then_some(*id)
+                })
+                .collect::<Box<[_]>>();
+            let table_ids = info
+                .component_ids
+                .iter()
+                .zip(&sparse)
+                .filter(|(_, sparse)| sparse.is_none())
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            let mut sorted = table_ids.clone();
+            sorted.sort_unstable();
+
+            let table_id = tables.get_id_or_insert(&sorted, components);
+            let archetype_id = archetypes.get_id_or_insert(table_id, &sorted);
+            let table = tables.get(table_id).unwrap();
+            let columns = table_ids
+                .iter()
+                .map(|id| table.column_index(*id).unwrap())
+                .collect();
+
+            info.spawn_target = Some(SpawnTarget {
+                table_id,
+                archetype_id,
+                columns,
+                sparse,
+            });
+        }
+        id
+    }
+
+    pub fn get(&self, id: BundleId) -> Option<&BundleInfo> {
+        self.bundles.get(id.index())
+    }
+
+    pub(crate) fn get_unchecked(&self, id: BundleId) -> &BundleInfo {
+        &self.bundles[id.index()]
+    }
+
+    pub fn get_id(&self, type_id: TypeId) -> Option<BundleId> {
+        self.indices.get(&type_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::{
+        archetype::Archetypes,
+        component::{Component, Components},
+        storage::Tables,
+    };
+
+    use super::Bundles;
+
+    #[derive(Debug)]
+    struct A;
+    impl Component for A {}
+
+    #[derive(Debug)]
+    struct B;
+    impl Component for B {}
+
+    #[test]
+    fn register_bundle() {
+        let mut components = Components::new();
+        let mut tables = Tables::default();
+        let mut archetypes = Archetypes::default();
+        let mut bundles = Bundles::default();
+
+        let b = components.register_component::<B>();
+        let a = components.register_component::<A>();
+
+        let id = bundles.register::<(A, B)>(&mut components);
+        assert_eq!(bundles.get_id(TypeId::of::<(A, B)>()), Some(id));
+        assert_eq!(bundles.get(id).unwrap().components(), &[a, b]);
+        assert!(bundles.get(id).unwrap().spawn_target().is_none());
+        assert_eq!(archetypes.len(), 0);
+
+        assert_eq!(
+            id,
+            bundles.register_spawn::<(A, B)>(&mut components, &mut tables, &mut archetypes)
+        );
+        let target = bundles.get(id).unwrap().spawn_target().unwrap();
+        // Columns are sorted by component id, so `B` comes first
+        assert_eq!(&*target.columns, &[1, 0]);
+        let archetype_id = target.archetype_id;
+
+        let reversed =
+            bundles.register_spawn::<(B, A)>(&mut components, &mut tables, &mut archetypes);
+        assert_ne!(id, reversed);
+        let target = bundles.get(reversed).unwrap().spawn_target().unwrap();
+        assert_eq!(&*target.columns, &[0, 1]);
+        assert_eq!(target.archetype_id, archetype_id);
+        assert_eq!(bundles.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_components() {
+        let mut components = Components::new();
+        let mut bundles = Bundles::default();
+        bundles.register::<(A, A)>(&mut components);
+    }
+}
@@ -0,0 +1,221 @@
+use std::mem;
+
+use crate::{
+    archetype::ArchetypeId,
+    component::Tick,
+    entity::Entity,
+    query::{Query, QueryFilter, Queryable, ReadOnlyQueryable},
+    World, WorldId,
+};
+
+/// A query that caches the entities it matches between uses.
+///
+/// The cache is only rebuilt when an entity enters or leaves one of the matched archetypes or a
+/// new matching archetype is created, which makes iterating queries with expensive filters over
+/// mostly static worlds cheap. Changes to component values never invalidate the cache, row
+/// filters like [`Changed`](crate::query::Changed) are checked while iterating instead.
+///
+/// A cache can only be used with the world it was created from, using it with another world
+/// panics.
+///
+/// ```
+/// # use quartz::{cached_query::CachedQuery, component::Component, query::Without, World};
+/// struct Position(f32);
+/// impl Component for Position {}
+/// struct Frozen;
+/// impl Component for Frozen {}
+///
+/// let mut world = World::new();
+/// world.spawn(Position(0.0));
+/// world.spawn((Position(1.0), Frozen));
+///
+/// let mut query = CachedQuery::<&mut Position, Without<Frozen>>::new(&world);
+/// for position in query.iter_mut(&mut world) {
+///     position.0 += 1.0;
+/// }
+/// assert_eq!(query.entities(&world).len(), 1);
+/// ```
+pub struct CachedQuery<T: Queryable, F: QueryFilter = ()> {
+    /// The world the cache was created for, the only one it can be used with.
+    world_id: WorldId,
+    state: T::State,
+    filter_state: F::State,
+    /// The matched archetypes with the version they had when the cache was built.
+    archetypes: Vec<(ArchetypeId, u64)>,
+    /// The number of archetypes that have already been checked for a match.
+    seen_archetypes: usize,
+    entities: Vec<Entity>,
+    /// The world tick of the previous iteration, see [`QueryState`](crate::query::QueryState).
+    last_run: Tick,
+}
+
+impl<T: Queryable, F: QueryFilter> CachedQuery<T, F> {
+    pub fn new(world: &World) -> Self {
+        Self {
+            world_id: world.id(),
+            state: T::init_state(world),
+            filter_state: F::init_state(world),
+            archetypes: Vec::new(),
+            seen_archetypes: 0,
+            entities: Vec::new(),
+            last_run: Tick::default(),
+        }
+    }
+
+    /// Returns the entities of the matched archetypes, rebuilding the cache if it is outdated.
+    pub fn entities(&mut self, world: &World) -> &[Entity] {
+        self.refresh(world);
+        &self.entities
+    }
+
+    pub fn iter<'w>(&'w mut self, world: &'w World) -> impl Iterator<Item = T::Item<'w>> + 'w
+    where
+        T: ReadOnlyQueryable,
+    {
+        self.refresh(world);
+        let since = mem::replace(&mut self.last_run, world.tick());
+        // SAFETY: Read-only items can alias.
+        unsafe { self.fetch_all(world, since) }
+    }
+
+    pub fn iter_mut<'w>(
+        &'w mut self,
+        world: &'w mut World,
+    ) -> impl Iterator<Item = T::Item<'w>> + 'w {
+        let world = &*world;
+        self.refresh(world);
+        let since = mem::replace(&mut self.last_run, world.tick());
+        // SAFETY: The world is borrowed mutably and every cached entity is unique.
+        unsafe { self.fetch_all(world, since) }
+    }
+
+    /// Safety: If `T` accesses components mutably, no other reference to the components of the
+    /// cached entities may be alive while the items are.
+    unsafe fn fetch_all<'w>(
+        &'w self,
+        world: &'w World,
+        since: Tick,
+    ) -> impl Iterator<Item = T::Item<'w>> + 'w {
+        self.entities.iter().filter_map(move |entity| {
+            let location = world
+                .entities
+                .get(*entity)
+                .expect("cached entities must be alive");
+            let table = world.tables.get_unchecked_raw(location.table_id);
+            if !Query::<T, F>::live_row_matches(
+                world,
+                &self.state,
+                &self.filter_state,
+                table,
+                location.table_row,
+                since,
+            ) {
+                return None;
+            }
+            Some(T::fetch(
+                world,
+                &self.state,
+                table,
+                location.table_row,
+                since,
+            ))
+        })
+    }
+
+    fn refresh(&mut self, world: &World) {
+        assert_eq!(
+            self.world_id,
+            world.id(),
+            "Tried to use a CachedQuery with a world it was not created for"
+        );
+        let archetypes = &world.archetypes;
+        let mut outdated = false;
+
+        if archetypes.len() > self.seen_archetypes {
+            let (access, _) = Query::<T, F>::access(world, &self.state, &self.filter_state);
+            for archetype in archetypes.iter().skip(self.seen_archetypes) {
+                if archetype.matches(&access) {
+                    self.archetypes.push((archetype.id(), archetype.version()));
+                    outdated = true;
+                }
+            }
+            self.seen_archetypes = archetypes.len();
+        }
+
+        for (id, version) in self.archetypes.iter_mut() {
+            let current = archetypes.get_unchecked(*id).version();
+            if current != *version {
+                *version = current;
+                outdated = true;
+            }
+        }
+
+        if outdated {
+            self.entities.clear();
+            for (id, _) in &self.archetypes {
+                self.entities
+                    .extend(archetypes.get_unchecked(*id).entities());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{Component, Disabled},
+        query::Without,
+        World,
+    };
+
+    use super::CachedQuery;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct MyComponent(u32);
+    impl Component for MyComponent {}
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct Marker;
+    impl Component for Marker {}
+
+    #[test]
+    fn cached_query() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Marker));
+        let mut query = CachedQuery::<&MyComponent, Without<Marker>>::new(&world);
+        assert_eq!(query.entities(&world), &[e0]);
+
+        // Value changes keep the cache
+        for component in CachedQuery::<&mut MyComponent>::new(&world).iter_mut(&mut world) {
+            component.0 += 10;
+        }
+        assert_eq!(
+            query.iter(&world).collect::<Vec<_>>(),
+            vec![&MyComponent(10)]
+        );
+
+        // New matching archetypes and entities invalidate it
+        let e2 = world.spawn((MyComponent(2), Disabled));
+        let e3 = world.spawn(MyComponent(3));
+        assert_eq!(query.entities(&world), &[e0, e3]);
+
+        world.remove_by_ids(e1, &[world.component_id::<Marker>().unwrap()]);
+        world.despawn(e0);
+        world.enable(e2);
+        assert_eq!(query.entities(&world), &[e1, e3, e2]);
+        assert_eq!(
+            query.iter(&world).map(|c| c.0).collect::<Vec<_>>(),
+            vec![11, 3, 2]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "world it was not created for")]
+    fn cached_query_other_world() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        let mut query = CachedQuery::<&MyComponent>::new(&world);
+        query.entities(&World::new());
+    }
+}
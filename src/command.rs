@@ -0,0 +1,322 @@
+use std::{
+    fmt,
+    mem::{self, MaybeUninit},
+};
+
+use crate::{
+    component::{Bundle, Component},
+    entity::{Entities, Entity},
+    World,
+};
+
+/// A deferred mutation of the [`World`].
+pub trait Command: Send + 'static {
+    fn apply(self, world: &mut World);
+}
+
+impl<F: FnOnce(&mut World) + Send + 'static> Command for F {
+    fn apply(self, world: &mut World) {
+        self(world)
+    }
+}
+
+/// Header written in front of every command in the [`CommandQueue`] buffer.
+#[derive(Clone, Copy)]
+struct CommandMeta {
+    /// Reads the command located at the pointer and either applies it to the world or drops it.
+    /// Returns the size of the command in bytes.
+    ///
+    /// # Safety
+    /// The pointer must point to an initialized command of the type this header was written for,
+    /// which must not be read again afterwards.
+    consume: unsafe fn(*mut MaybeUninit<u8>, Option<&mut World>) -> usize,
+}
+
+/// A queue of [`Command`]s that are applied to the [`World`] in the order they were pushed.
+///
+/// Commands are stored inline in a single grow-only byte buffer, each preceded by a small header,
+/// so pushing a command does not allocate once the buffer has grown large enough.
+#[derive(Default)]
+pub struct CommandQueue {
+    bytes: Vec<MaybeUninit<u8>>,
+    len: usize,
+}
+
+impl CommandQueue {
+    pub fn push<C: Command>(&mut self, command: C) {
+        let meta = CommandMeta {
+            consume: |command, world| {
+                // SAFETY: guaranteed by the caller of `consume`.
+                let command = unsafe { command.cast::<C>().read_unaligned() };
+                match world {
+                    Some(world) => command.apply(world),
+                    None => drop(command),
+                }
+                mem::size_of::<C>()
+            },
+        };
+
+        let offset = self.bytes.len();
+        let size = mem::size_of::<CommandMeta>() + mem::size_of::<C>();
+        self.bytes.reserve(size);
+
+        // SAFETY: the buffer has room for `size` more bytes and both values are written unaligned.
+        unsafe {
+            let ptr = self.bytes.as_mut_ptr().add(offset);
+            ptr.cast::<CommandMeta>().write_unaligned(meta);
+            ptr.add(mem::size_of::<CommandMeta>())
+                .cast::<C>()
+                .write_unaligned(command);
+            self.bytes.set_len(offset + size);
+        }
+        self.len += 1;
+    }
+
+    /// Applies all queued commands to the world, leaving the queue empty.
+    pub fn apply(&mut self, world: &mut World) {
+        self.consume(Some(world));
+    }
+
+    /// Reads every command out of the buffer, applying them to the world if one is given and
+    /// dropping them otherwise. The allocation of the buffer is kept for reuse.
+    fn consume(&mut self, mut world: Option<&mut World>) {
+        let end = self.bytes.len();
+        let ptr = self.bytes.as_mut_ptr();
+        // Forget the commands up front, so a panicking command leaks the remaining ones instead
+        // of dropping them twice.
+        // SAFETY: `MaybeUninit<u8>` needs no drop and the length is only shrunk.
+        unsafe { self.bytes.set_len(0) };
+        self.len = 0;
+
+        let mut cursor = 0;
+        while cursor < end {
+            // SAFETY: every command in the buffer is preceded by its header, and each header and
+            // command is read exactly once.
+            unsafe {
+                let meta = ptr.add(cursor).cast::<CommandMeta>().read_unaligned();
+                cursor += mem::size_of::<CommandMeta>();
+                cursor += (meta.consume)(ptr.add(cursor), world.as_deref_mut());
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for CommandQueue {
+    fn drop(&mut self) {
+        self.consume(None);
+    }
+}
+
+impl fmt::Debug for CommandQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandQueue")
+            .field("len", &self.len)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// Records structural changes to apply them to the [`World`] later, e.g. while a query borrows
+/// it or in a [`System`](crate::system::System), which applies them after it ran.
+///
+/// Spawned entities are reserved right away, so their ids can be used in further commands or
+/// stored in components before the commands are applied.
+///
+/// ```
+/// # use quartz::{command::{CommandQueue, Commands}, component::Component, World};
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Dead;
+///
+/// let mut world = World::new();
+/// world.spawn(Health(0));
+/// world.spawn(Health(5));
+///
+/// let mut queue = CommandQueue::default();
+/// let mut commands = Commands::new(&mut queue, &world);
+/// for (entity, health) in world.query::<(Entity, &Health)>() {
+///     if health.0 == 0 {
+///         commands.insert(entity, Dead);
+///     }
+/// }
+/// let spawned = commands.spawn(Health(10));
+///
+/// queue.apply(&mut world);
+/// assert_eq!(world.query::<&Dead>().count(), 1);
+/// assert_eq!(world.get::<Health>(spawned).unwrap().0, 10);
+/// # use quartz::entity::Entity;
+/// ```
+pub struct Commands<'w> {
+    queue: &'w mut CommandQueue,
+    entities: &'w Entities,
+}
+
+impl<'w> Commands<'w> {
+    /// Records commands into the `queue`, which have to be applied to the same world, see
+    /// [`CommandQueue::apply`].
+    pub fn new(queue: &'w mut CommandQueue, world: &'w World) -> Self {
+        Self {
+            queue,
+            entities: &world.entities,
+        }
+    }
+
+    /// Reserves a new entity and spawns the bundle into it once the commands are applied.
+    pub fn spawn<B: Bundle + Send>(&mut self, bundle: B) -> Entity {
+        let entity = self.entities.reserve_entity();
+        self.queue.push(move |world: &mut World| {
+            world.spawn_in(Some(entity), bundle);
+        });
+        entity
+    }
+
+    /// Inserts the bundle into the entity, see [`World::insert`].
+    ///
+    /// Panics when applied, if the entity does not exist.
+    pub fn insert<B: Bundle + Send>(&mut self, entity: Entity, bundle: B) {
+        self.queue
+            .push(move |world: &mut World| world.insert(entity, bundle));
+    }
+
+    /// Removes the component from the entity and drops it, see [`World::remove`].
+    pub fn remove<T: Component>(&mut self, entity: Entity) {
+        self.queue.push(move |world: &mut World| {
+            world.remove::<T>(entity);
+        });
+    }
+
+    /// Despawns the entity, see [`World::despawn`].
+    pub fn despawn(&mut self, entity: Entity) {
+        self.queue
+            .push(move |world: &mut World| world.despawn(entity));
+    }
+
+    /// Queues a custom command.
+    pub fn queue(&mut self, command: impl Command) {
+        self.queue.push(command);
+    }
+}
+
+impl fmt::Debug for Commands<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Commands")
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::{component::Component, World};
+
+    use super::{CommandQueue, Commands};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct MyComponent(u32);
+    impl Component for MyComponent {}
+
+    #[derive(Debug, PartialEq, Eq, Component)]
+    struct Target(crate::entity::Entity);
+
+    #[test]
+    fn apply_in_order() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+
+        let mut queue = CommandQueue::default();
+        queue.push(move |world: &mut World| world.insert(entity, MyComponent(1)));
+        queue.push(move |world: &mut World| {
+            world.get_mut::<MyComponent>(entity).unwrap().0 *= 10;
+        });
+        assert_eq!(queue.len(), 2);
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(0)));
+
+        queue.apply(&mut world);
+        assert!(queue.is_empty());
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(10)));
+    }
+
+    #[test]
+    fn commands() {
+        let mut world = World::new();
+        let existing = world.spawn(MyComponent(0));
+        let removed = world.spawn((MyComponent(1), Target(existing)));
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let parent = commands.spawn(MyComponent(2));
+        let child = commands.spawn(Target(parent));
+        commands.insert(parent, Target(child));
+        commands.remove::<Target>(removed);
+        commands.despawn(existing);
+        assert!(world.get_entity(parent).is_none());
+
+        // Spawning directly does not hand out the reserved ids
+        let spawned = world.spawn(MyComponent(3));
+        assert!(![parent, child].contains(&spawned));
+
+        queue.apply(&mut world);
+        assert_eq!(world.get::<MyComponent>(parent), Some(&MyComponent(2)));
+        assert_eq!(world.get::<Target>(parent), Some(&Target(child)));
+        assert_eq!(world.get::<Target>(child), Some(&Target(parent)));
+        assert_eq!(world.get::<Target>(removed), None);
+        assert!(world.get_entity(existing).is_none());
+        assert_eq!(world.query::<&MyComponent>().count(), 3);
+    }
+
+    #[test]
+    fn reuse_buffer() {
+        let mut world = World::new();
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let mut queue = CommandQueue::default();
+        for _ in 0..100 {
+            let counter = counter.clone();
+            // Commands with an odd size are stored unaligned in the buffer
+            let padding = [0u8; 3];
+            queue.push(move |_: &mut World| {
+                let _ = padding;
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        let capacity = queue.bytes.capacity();
+        queue.apply(&mut world);
+        assert_eq!(counter.load(Ordering::Relaxed), 100);
+        assert_eq!(queue.bytes.capacity(), capacity);
+
+        queue.push(|_: &mut World| {});
+        assert_eq!(queue.bytes.capacity(), capacity);
+    }
+
+    #[test]
+    fn drop_unapplied() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let mut queue = CommandQueue::default();
+        queue.push({
+            let counter = counter.clone();
+            move |_: &mut World| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        assert_eq!(Arc::strong_count(&counter), 2);
+
+        drop(queue);
+        assert_eq!(Arc::strong_count(&counter), 1);
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+    }
+}
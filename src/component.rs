@@ -1,8 +1,71 @@
-use std::{alloc::Layout, any::TypeId, borrow::Cow, collections::HashMap, mem::needs_drop};
-
-use crate::ptr::OwningPtr;
+use std::{
+    alloc::Layout,
+    any::TypeId,
+    borrow::Cow,
+    collections::HashMap,
+    mem::needs_drop,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{
+    entity::Entity,
+    ptr::{OwningPtr, Ptr},
+    DeferredWorld,
+};
+
+pub use quartz_macros::{Bundle, Component};
+
+/// Data that can be attached to entities.
+///
+/// Usually derived, with the `#[component(..)]` attribute configuring the associated constants
+/// and registering [`ComponentHooks`]:
+///
+/// ```
+/// # use quartz::{
+/// #     component::{Component, ComponentId},
+/// #     entity::Entity,
+/// #     DeferredWorld,
+/// # };
+/// #[derive(Component)]
+/// #[component(change_detection = PerTable, on_add = Self::on_add)]
+/// struct Particle {
+///     lifetime: f32,
+/// }
+///
+/// impl Particle {
+///     fn on_add(world: DeferredWorld, entity: Entity, _: ComponentId) {}
+/// }
+///
+/// #[derive(Component)]
+/// #[component(stable_rows)]
+/// struct Collider<T: Send + Sync + 'static>(T);
+/// ```
+pub trait Component: Send + Sync + 'static {
+    /// How precisely changes to this component are tracked.
+    const CHANGE_DETECTION: ChangeDetection = ChangeDetection::PerEntity;
+
+    /// Keeps the rows of all entities in tables with this component stable, e.g. because
+    /// external systems cache them. Removed rows are tombstoned and reused by later entities
+    /// instead of moving another entity into them, which leaves holes that queries skip.
+    const STABLE_ROWS: bool = false;
+
+    /// Where the components are stored. Components stored in a [`StorageType::SparseSet`] are
+    /// not part of an entity's archetype, so adding or removing them never moves the entity to
+    /// another table.
+    const STORAGE_TYPE: StorageType = StorageType::Table;
+
+    /// Registers the lifecycle hooks the component always needs, called once when the component
+    /// is registered in a world.
+    fn register_hooks(_hooks: &mut ComponentHooks) {}
+}
 
-pub trait Component: Send + Sync + 'static {}
+/// Marks an entity as disabled.
+///
+/// Queries skip disabled entities unless they explicitly access or filter on [`Disabled`], which
+/// allows deactivating entities without despawning them and losing their components.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Disabled;
+impl Component for Disabled {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ComponentId(usize);
@@ -17,13 +80,182 @@ impl ComponentId {
     }
 }
 
+/// A point in time of a [`World`](crate::World), advanced with
+/// [`World::increment_tick`](crate::World::increment_tick).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Tick(u32);
+
+impl Tick {
+    pub const fn new(tick: u32) -> Self {
+        Self(tick)
+    }
+
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// The ticks at which a component was added to an entity and last changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: Tick,
+    pub changed: Tick,
+}
+
+impl ComponentTicks {
+    /// Ticks of a component that was added at `tick`, which also counts as a change.
+    pub const fn new(tick: Tick) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+}
+
+/// The granularity at which changes to a component are tracked, see
+/// [`Component::CHANGE_DETECTION`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeDetection {
+    /// Tracks the ticks of every entity's component separately, which costs one
+    /// [`ComponentTicks`] of memory per entity.
+    #[default]
+    PerEntity,
+    /// Tracks a single pair of ticks for all components stored in a table, which costs no memory
+    /// per entity. A change to one entity reports the component of every entity sharing its table
+    /// as changed.
+    PerTable,
+}
+
+/// Where the components of a type are stored, see [`Component::STORAGE_TYPE`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StorageType {
+    /// In a column of the table of every entity with the component, which makes iterating the
+    /// component fast, but adding or removing it moves all other components of the entity to
+    /// another table.
+    #[default]
+    Table,
+    /// In a sparse set indexed by the entities, which makes adding and removing the component
+    /// cheap, at the cost of slower iteration. Queries check these components for every entity
+    /// of the tables their table components match, so this suits markers that come and go
+    /// frequently, e.g. `Stunned`.
+    SparseSet,
+}
+
+/// A function invoked at a point of a component's lifecycle on an entity.
+pub type ComponentHook = for<'w> fn(DeferredWorld<'w>, Entity, ComponentId);
+
+/// The lifecycle hooks registered for a component.
+///
+/// Every hook can only be registered once per component, registering it a second time panics.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ComponentHooks {
+    pub(crate) on_add: Option<ComponentHook>,
+    pub(crate) on_insert: Option<ComponentHook>,
+    pub(crate) on_replace: Option<ComponentHook>,
+    pub(crate) on_remove: Option<ComponentHook>,
+}
+
+impl ComponentHooks {
+    /// Runs after the component has been added to an entity that did not have it before.
+    pub fn on_add(&mut self, hook: ComponentHook) -> &mut Self {
+        assert!(
+            self.on_add.is_none(),
+            "Component already has an on_add hook"
+        );
+        self.on_add = Some(hook);
+        self
+    }
+
+    /// Runs after a value of the component has been written to an entity, both when it was
+    /// newly added and when it overwrote a previous value.
+    pub fn on_insert(&mut self, hook: ComponentHook) -> &mut Self {
+        assert!(
+            self.on_insert.is_none(),
+            "Component already has an on_insert hook"
+        );
+        self.on_insert = Some(hook);
+        self
+    }
+
+    /// Runs before a value of the component is overwritten by
+    /// [`World::insert`](crate::World::insert), while the previous value can still be read.
+    pub fn on_replace(&mut self, hook: ComponentHook) -> &mut Self {
+        assert!(
+            self.on_replace.is_none(),
+            "Component already has an on_replace hook"
+        );
+        self.on_replace = Some(hook);
+        self
+    }
+
+    /// Runs before the component is removed from an entity, including when it is despawned.
+    pub fn on_remove(&mut self, hook: ComponentHook) -> &mut Self {
+        assert!(
+            self.on_remove.is_none(),
+            "Component already has an on_remove hook"
+        );
+        self.on_remove = Some(hook);
+        self
+    }
+}
+
+/// A function that drops the component the pointer points to in place.
+pub type DropFn = for<'a> unsafe fn(OwningPtr<'a>);
+
+/// Describes a component whose type is only known at runtime, e.g. one defined by a script.
+///
+/// Registered with [`Components::register_with_descriptor`], which assigns it a [`ComponentId`]
+/// that can be spawned and inserted through the untyped paths like
+/// [`World::spawn_by_ids`](crate::World::spawn_by_ids).
+#[derive(Debug, Clone)]
+pub struct ComponentDescriptor {
+    name: Cow<'static, str>,
+    layout: Layout,
+    drop: Option<DropFn>,
+    storage_type: StorageType,
+}
+
+impl ComponentDescriptor {
+    /// Describes a component with the given layout, which is dropped with `drop` if it needs to
+    /// be dropped.
+    pub fn new(name: impl Into<Cow<'static, str>>, layout: Layout, drop: Option<DropFn>) -> Self {
+        Self {
+            name: name.into(),
+            layout,
+            drop,
+            storage_type: StorageType::Table,
+        }
+    }
+
+    /// Stores the component in the given storage, [`StorageType::Table`] by default.
+    pub fn with_storage_type(mut self, storage_type: StorageType) -> Self {
+        self.storage_type = storage_type;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ComponentInfo {
     id: ComponentId,
     name: Cow<'static, str>,
-    type_id: TypeId,
+    /// `None` for components registered with a [`ComponentDescriptor`].
+    type_id: Option<TypeId>,
     pub(crate) layout: Layout,
-    pub(crate) drop: Option<for<'a> unsafe fn(OwningPtr<'a>)>,
+    pub(crate) drop: Option<DropFn>,
+    pub(crate) change_detection: ChangeDetection,
+    pub(crate) stable_rows: bool,
+    pub(crate) storage_type: StorageType,
+    pub(crate) hooks: ComponentHooks,
 }
 
 impl ComponentInfo {
@@ -31,21 +263,94 @@ impl ComponentInfo {
         Self {
             id,
             name: Cow::Borrowed(std::any::type_name::<T>()),
-            type_id: TypeId::of::<T>(),
+            type_id: Some(TypeId::of::<T>()),
             layout: Layout::new::<T>(),
             drop: needs_drop::<T>().then_some(Self::drop_ptr::<T> as _),
+            change_detection: T::CHANGE_DETECTION,
+            stable_rows: T::STABLE_ROWS,
+            storage_type: T::STORAGE_TYPE,
+            hooks: ComponentHooks::default(),
+        }
+    }
+
+    fn from_descriptor(id: ComponentId, descriptor: ComponentDescriptor) -> Self {
+        Self {
+            id,
+            name: descriptor.name,
+            type_id: None,
+            layout: descriptor.layout,
+            drop: descriptor.drop,
+            change_detection: ChangeDetection::PerEntity,
+            stable_rows: false,
+            storage_type: descriptor.storage_type,
+            hooks: ComponentHooks::default(),
         }
     }
 
+    pub(crate) fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    pub(crate) fn type_id(&self) -> Option<TypeId> {
+        self.type_id
+    }
+
     unsafe fn drop_ptr<T>(x: OwningPtr<'_>) {
         x.drop_as::<T>()
     }
 }
 
+/// Assigns [`ComponentId`]s that are shared by every [`Components`] created with it, so that
+/// multiple worlds agree on the ids of their components.
+///
+/// Cloning the registry shares it, [`SharedComponentIds::global`] is shared by the whole process.
+#[derive(Debug, Clone, Default)]
+pub struct SharedComponentIds(Arc<Mutex<SharedIds>>);
+
+#[derive(Debug, Default)]
+struct SharedIds {
+    indices: HashMap<(TypeId, Option<String>), ComponentId>,
+    /// The number of ids handed out to components registered with a [`ComponentDescriptor`].
+    descriptors: usize,
+}
+
+impl SharedComponentIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry shared by all worlds of this process that opt into it.
+    pub fn global() -> Self {
+        static GLOBAL: OnceLock<SharedComponentIds> = OnceLock::new();
+        GLOBAL.get_or_init(Self::new).clone()
+    }
+
+    fn id(&self, type_id: TypeId, label: Option<&str>) -> ComponentId {
+        let mut ids = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        let next = ComponentId::new(ids.indices.len() + ids.descriptors);
+        *ids.indices
+            .entry((type_id, label.map(str::to_owned)))
+            .or_insert(next)
+    }
+
+    /// An id no other component of any world sharing the ids has.
+    fn unique_id(&self) -> ComponentId {
+        let mut ids = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        ids.descriptors += 1;
+        ComponentId::new(ids.indices.len() + ids.descriptors - 1)
+    }
+}
+
 #[derive(Debug)]
 pub struct Components {
-    components: Vec<ComponentInfo>,
+    /// Indexed by [`ComponentId`], which has gaps for ids that are only registered in other
+    /// worlds sharing the ids.
+    components: Vec<Option<ComponentInfo>>,
     indices: HashMap<TypeId, ComponentId>,
+    labeled: HashMap<TypeId, HashMap<String, ComponentId>>,
+    /// The number of components registered with a [`ComponentDescriptor`].
+    descriptors: usize,
+    shared: Option<SharedComponentIds>,
 }
 
 impl Components {
@@ -53,21 +358,98 @@ impl Components {
         Self {
             components: Vec::new(),
             indices: HashMap::new(),
+            labeled: HashMap::new(),
+            descriptors: 0,
+            shared: None,
         }
     }
 
+    /// Creates a registry that takes its ids from `shared`, instead of numbering the components in
+    /// the order they are registered.
+    pub fn with_shared_ids(shared: SharedComponentIds) -> Self {
+        Self {
+            shared: Some(shared),
+            ..Self::new()
+        }
+    }
+
+    /// The shared ids this registry takes its ids from, if any.
+    pub fn shared_ids(&self) -> Option<&SharedComponentIds> {
+        self.shared.as_ref()
+    }
+
+    fn next_id(&self, type_id: TypeId, label: Option<&str>) -> ComponentId {
+        match &self.shared {
+            Some(shared) => shared.id(type_id, label),
+            None => ComponentId::new(self.components.len()),
+        }
+    }
+
+    fn insert_info(&mut self, info: ComponentInfo) {
+        let index = info.id.index();
+        if self.components.len() <= index {
+            self.components.resize_with(index + 1, || None);
+        }
+        self.components[index] = Some(info);
+    }
+
     pub fn register_component<T: Component>(&mut self) -> ComponentId {
         let type_id = TypeId::of::<T>();
-        *self.indices.entry(type_id).or_insert_with(|| {
-            let id = ComponentId::new(self.components.len());
-            let info = ComponentInfo::new::<T>(id);
-            self.components.push(info);
-            id
-        })
+        if let Some(id) = self.indices.get(&type_id) {
+            return *id;
+        }
+
+        let id = self.next_id(type_id, None);
+        let mut info = ComponentInfo::new::<T>(id);
+        T::register_hooks(&mut info.hooks);
+        self.insert_info(info);
+        self.indices.insert(type_id, id);
+        id
+    }
+
+    /// Registers `T` under a label, which creates a component distinct from `T` itself and from
+    /// all other labels of `T`. This allows using the same type for semantically different
+    /// components without a newtype wrapper for each of them.
+    pub fn register_labeled<T: Component>(&mut self, label: &str) -> ComponentId {
+        let type_id = TypeId::of::<T>();
+        if let Some(id) = self.labeled_component_id::<T>(label) {
+            return id;
+        }
+
+        let id = self.next_id(type_id, Some(label));
+        let mut info = ComponentInfo::new::<T>(id);
+        info.name = Cow::Owned(format!("{}[{label}]", info.name));
+        T::register_hooks(&mut info.hooks);
+        self.insert_info(info);
+        self.labeled
+            .entry(type_id)
+            .or_default()
+            .insert(label.to_owned(), id);
+        id
+    }
+
+    /// Registers a component whose type is only known at runtime. Every call registers a new
+    /// component, even if another one with the same name exists.
+    pub fn register_with_descriptor(&mut self, descriptor: ComponentDescriptor) -> ComponentId {
+        let id = match &self.shared {
+            Some(shared) => shared.unique_id(),
+            None => ComponentId::new(self.components.len()),
+        };
+        self.insert_info(ComponentInfo::from_descriptor(id, descriptor));
+        self.descriptors += 1;
+        id
+    }
+
+    pub fn labeled_component_id<T: Component>(&self, label: &str) -> Option<ComponentId> {
+        self.labeled.get(&TypeId::of::<T>())?.get(label).copied()
     }
 
     pub(crate) fn get_info(&self, id: &ComponentId) -> Option<&ComponentInfo> {
-        self.components.get(id.index())
+        self.components.get(id.index())?.as_ref()
+    }
+
+    pub(crate) fn get_info_mut(&mut self, id: &ComponentId) -> Option<&mut ComponentInfo> {
+        self.components.get_mut(id.index())?.as_mut()
     }
 
     pub fn get_id(&self, type_id: TypeId) -> Option<ComponentId> {
@@ -78,63 +460,156 @@ impl Components {
         self.indices.get(&TypeId::of::<T>()).copied()
     }
 
+    /// Returns the type name of the component, if it is registered.
+    pub fn name(&self, id: ComponentId) -> Option<&str> {
+        self.get_info(&id).map(|info| info.name.as_ref())
+    }
+
     pub fn components(&self) -> impl Iterator<Item = ComponentId> + use<'_> {
-        self.components.iter().map(|info| info.id)
+        self.components.iter().flatten().map(|info| info.id)
     }
 
     pub fn len(&self) -> usize {
-        self.components.len()
+        self.indices.len()
+            + self.labeled.values().map(HashMap::len).sum::<usize>()
+            + self.descriptors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Components {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub trait Bundle {
+/// A set of components that is spawned or inserted together.
+///
+/// Implemented for every component and tuples of up to 16 components. Structs can derive it if
+/// all their fields are components or bundles, nested bundles are flattened:
+///
+/// ```
+/// # use quartz::{component::{Bundle, Component}, World};
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Component)]
+/// struct Velocity(f32);
+/// #[derive(Component)]
+/// struct Player;
+///
+/// #[derive(Bundle)]
+/// struct Body {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     body: Body,
+///     tag: Player,
+/// }
+///
+/// let mut world = World::new();
+/// let player = world.spawn(PlayerBundle {
+///     body: Body {
+///         position: Position(0.0),
+///         velocity: Velocity(1.0),
+///     },
+///     tag: Player,
+/// });
+/// assert_eq!(world.query::<(&Position, &Velocity, &Player)>().count(), 1);
+/// ```
+pub trait Bundle: 'static {
     fn get_components(self, func: &mut impl FnMut(OwningPtr<'_>));
+    /// Like [`Bundle::get_components`], but only borrows the components, e.g. to check them before
+    /// they are written.
+    fn get_component_ptrs(&self, func: &mut impl FnMut(Ptr<'_>));
     fn component_ids(components: &mut Components, func: &mut impl FnMut(ComponentId));
-    fn get(self, components: &Components, func: &mut impl FnMut(ComponentId, OwningPtr<'_>));
 }
 
 impl<C: Component> Bundle for C {
     fn get_components(self, func: &mut impl FnMut(OwningPtr<'_>)) {
-        OwningPtr::make(self, |ptr| func(ptr));
+        OwningPtr::make(self, func);
     }
 
-    fn component_ids(components: &mut Components, func: &mut impl FnMut(ComponentId)) {
-        func(components.register_component::<C>());
+    fn get_component_ptrs(&self, func: &mut impl FnMut(Ptr<'_>)) {
+        func(Ptr::from(self));
     }
 
-    fn get(self, components: &Components, func: &mut impl FnMut(ComponentId, OwningPtr<'_>)) {
-        OwningPtr::make(self, |ptr| {
-            func(components.component_id::<C>().unwrap(), ptr)
-        });
+    fn component_ids(components: &mut Components, func: &mut impl FnMut(ComponentId)) {
+        func(components.register_component::<C>());
     }
 }
 
-impl<C0: Component, C1: Component> Bundle for (C0, C1) {
-    fn get_components(self, func: &mut impl FnMut(OwningPtr<'_>)) {
-        OwningPtr::make(self.0, |ptr| func(ptr));
-        OwningPtr::make(self.1, |ptr| func(ptr));
-    }
+impl Bundle for () {
+    fn get_components(self, _func: &mut impl FnMut(OwningPtr<'_>)) {}
 
-    fn component_ids(components: &mut Components, func: &mut impl FnMut(ComponentId)) {
-        func(components.register_component::<C0>());
-        func(components.register_component::<C1>());
-    }
+    fn get_component_ptrs(&self, _func: &mut impl FnMut(Ptr<'_>)) {}
 
-    fn get(self, components: &Components, func: &mut impl FnMut(ComponentId, OwningPtr<'_>)) {
-        OwningPtr::make(self.0, |ptr| {
-            func(components.component_id::<C0>().unwrap(), ptr)
-        });
-        OwningPtr::make(self.1, |ptr| {
-            func(components.component_id::<C1>().unwrap(), ptr)
-        });
-    }
+    fn component_ids(_components: &mut Components, _func: &mut impl FnMut(ComponentId)) {}
+}
+
+/// Implements [`Bundle`] for a tuple of the given components, binding their values to the given
+/// names.
+macro_rules! impl_bundle_tuple {
+    ($(($C:ident, $value:ident)),*) => {
+        impl<$($C: Component),*> Bundle for ($($C,)*) {
+            fn get_components(self, func: &mut impl FnMut(OwningPtr<'_>)) {
+                let ($($value,)*) = self;
+                $(OwningPtr::make($value, &mut *func);)*
+            }
+
+            fn get_component_ptrs(&self, func: &mut impl FnMut(Ptr<'_>)) {
+                let ($($value,)*) = self;
+                $(func(Ptr::from($value));)*
+            }
+
+            fn component_ids(components: &mut Components, func: &mut impl FnMut(ComponentId)) {
+                $(func(components.register_component::<$C>());)*
+            }
+        }
+    };
 }
 
+/// Calls [`impl_bundle_tuple`] for every suffix of the given components.
+macro_rules! impl_bundle_tuples {
+    () => {};
+    ($head:tt $(, $tail:tt)*) => {
+        impl_bundle_tuple!($head $(, $tail)*);
+        impl_bundle_tuples!($($tail),*);
+    };
+}
+
+impl_bundle_tuples!(
+    (C0, value0),
+    (C1, value1),
+    (C2, value2),
+    (C3, value3),
+    (C4, value4),
+    (C5, value5),
+    (C6, value6),
+    (C7, value7),
+    (C8, value8),
+    (C9, value9),
+    (C10, value10),
+    (C11, value11),
+    (C12, value12),
+    (C13, value13),
+    (C14, value14),
+    (C15, value15)
+);
+
 #[cfg(test)]
 mod tests {
-    use std::any::type_name;
+    use std::{alloc::Layout, any::type_name};
 
-    use super::{Component, ComponentId, Components};
+    use super::{
+        Bundle, ChangeDetection, Component, ComponentDescriptor, ComponentId, Components,
+        SharedComponentIds, StorageType,
+    };
 
     impl Component for u8 {}
     impl Component for u32 {}
@@ -150,7 +625,7 @@ mod tests {
         let val: u32 = 0;
         assert_eq!(id, ComponentId::new(0));
         assert_eq!(Some(ComponentId::new(0)), components.component_id::<u32>());
-        assert_eq!("u32", components.components[id.index()].name);
+        assert_eq!(Some("u32"), components.name(id));
 
         let id = components.register_component::<MyComponent>();
         assert_eq!(id, ComponentId::new(1));
@@ -158,10 +633,7 @@ mod tests {
             Some(ComponentId::new(1)),
             components.component_id::<MyComponent>()
         );
-        assert_eq!(
-            type_name::<MyComponent>(),
-            components.components[id.index()].name
-        );
+        assert_eq!(Some(type_name::<MyComponent>()), components.name(id));
 
         let id = components.register_component::<MyComponent>();
         assert_eq!(id, ComponentId::new(1));
@@ -176,4 +648,148 @@ mod tests {
         assert_eq!(id, ComponentId::new(2));
         assert_eq!(Some(ComponentId::new(2)), components.component_id::<u8>());
     }
+
+    #[test]
+    fn labeled_registration() {
+        let mut components = Components::new();
+
+        let plain = components.register_component::<u32>();
+        let reload = components.register_labeled::<u32>("reload");
+        let respawn = components.register_labeled::<u32>("respawn");
+        assert_ne!(plain, reload);
+        assert_ne!(reload, respawn);
+        assert_eq!(components.register_labeled::<u32>("reload"), reload);
+
+        assert_eq!(components.component_id::<u32>(), Some(plain));
+        assert_eq!(
+            components.labeled_component_id::<u32>("respawn"),
+            Some(respawn)
+        );
+        assert_eq!(components.labeled_component_id::<u32>("other"), None);
+        assert_eq!(components.labeled_component_id::<u8>("reload"), None);
+        assert_eq!(components.name(reload), Some("u32[reload]"));
+    }
+
+    #[test]
+    fn shared_ids() {
+        let shared = SharedComponentIds::new();
+        let mut first = Components::with_shared_ids(shared.clone());
+        let mut second = Components::with_shared_ids(shared);
+
+        let id = first.register_component::<u32>();
+        let labeled = first.register_labeled::<u32>("reload");
+        let other = second.register_component::<MyComponent>();
+        assert_eq!(second.register_component::<u32>(), id);
+        assert_eq!(second.register_labeled::<u32>("reload"), labeled);
+        assert_eq!(first.register_component::<MyComponent>(), other);
+
+        let mut third = Components::with_shared_ids(first.shared_ids().unwrap().clone());
+        assert_eq!(third.register_component::<MyComponent>(), other);
+        assert_eq!(third.len(), 1);
+        assert_eq!(third.components().collect::<Vec<_>>(), [other]);
+        assert_eq!(third.name(id), None);
+    }
+
+    #[test]
+    fn descriptor_registration() {
+        let shared = SharedComponentIds::new();
+        let mut first = Components::with_shared_ids(shared.clone());
+        let mut second = Components::with_shared_ids(shared);
+
+        let typed = first.register_component::<u32>();
+        let descriptor = ComponentDescriptor::new("Health", Layout::new::<f32>(), None)
+            .with_storage_type(StorageType::SparseSet);
+        let health = first.register_with_descriptor(descriptor.clone());
+        assert_ne!(health, typed);
+        assert_ne!(first.register_with_descriptor(descriptor), health);
+        assert_ne!(second.register_component::<MyComponent>(), health);
+        assert_eq!(second.register_component::<u32>(), typed);
+
+        let info = first.get_info(&health).unwrap();
+        assert_eq!(info.layout, Layout::new::<f32>());
+        assert_eq!(info.storage_type, StorageType::SparseSet);
+        assert_eq!(info.type_id(), None);
+        assert_eq!(first.name(health), Some("Health"));
+        assert_eq!(first.len(), 3);
+    }
+
+    #[test]
+    fn derive_component() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::{entity::Entity, DeferredWorld, World};
+
+        static REMOVED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Component)]
+        #[component(stable_rows, change_detection = PerTable, on_remove = Self::on_remove)]
+        struct Configured(u32);
+
+        impl Configured {
+            fn on_remove(world: DeferredWorld, entity: Entity, _: ComponentId) {
+                REMOVED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        #[derive(Component)]
+        struct Plain;
+
+        let mut components = Components::new();
+        let configured = components.register_component::<Configured>();
+        let info = components.get_info(&configured).unwrap();
+        assert!(info.stable_rows);
+        assert_eq!(info.change_detection, ChangeDetection::PerTable);
+        assert!(info.hooks.on_remove.is_some() && info.hooks.on_add.is_none());
+
+        let plain = components.register_component::<Plain>();
+        let info = components.get_info(&plain).unwrap();
+        assert!(!info.stable_rows);
+        assert_eq!(info.change_detection, ChangeDetection::PerEntity);
+        assert!(info.hooks.on_remove.is_none());
+
+        let mut world = World::new();
+        let entity = world.spawn((Configured(1), Plain));
+        world.despawn(entity);
+        assert_eq!(REMOVED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn derive_bundle() {
+        use crate::World;
+
+        #[derive(Component, Debug, PartialEq)]
+        struct Health(u32);
+
+        #[derive(Bundle)]
+        struct Stats<T: Component>(Health, T);
+
+        #[derive(Bundle)]
+        struct Unit {
+            stats: Stats<MyComponent>,
+            position: (u8, u32),
+        }
+
+        let mut components = Components::new();
+        let mut ids = Vec::new();
+        Unit::component_ids(&mut components, &mut |id| ids.push(id));
+        assert_eq!(
+            ids,
+            [
+                components.component_id::<Health>().unwrap(),
+                components.component_id::<MyComponent>().unwrap(),
+                components.component_id::<u8>().unwrap(),
+                components.component_id::<u32>().unwrap(),
+            ]
+        );
+
+        let mut world = World::new();
+        let entity = world.spawn(Unit {
+            stats: Stats(Health(10), MyComponent),
+            position: (1, 2),
+        });
+        assert_eq!(world.get::<Health>(entity), Some(&Health(10)));
+        assert_eq!(world.get::<u8>(entity), Some(&1));
+        assert_eq!(world.get::<u32>(entity), Some(&2));
+        assert!(world.get::<MyComponent>(entity).is_some());
+    }
 }
@@ -1,22 +1,190 @@
+use std::{
+    collections::TryReserveError,
+    error::Error,
+    fmt, mem,
+    num::{NonZeroU32, ParseIntError},
+    str::FromStr,
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
 use crate::{
     archetype::ArchetypeId,
+    component::{Bundle, Component, ComponentId},
+    ptr::{MutPtr, Ptr},
     storage::{TableId, TableRow},
+    World,
 };
 
-type Generation = u32;
+type Generation = NonZeroU32;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The integer type of entity indices, which limits how many entities can be alive at once.
+///
+/// This is `u32` by default and `u64` with the `wide_entities` feature, which allows more than
+/// `u32::MAX` entity slots at the cost of larger [`Entity`] ids.
+#[cfg(not(feature = "wide_entities"))]
+pub type EntityIndex = u32;
+/// The integer type of entity indices, which limits how many entities can be alive at once.
+///
+/// This is `u64` with the `wide_entities` feature and `u32` by default.
+#[cfg(feature = "wide_entities")]
+pub type EntityIndex = u64;
+
+/// An identifier of an entity, formatted and parsed as `{index}v{generation}`, e.g. `42v3`.
+///
+/// Generations start at 1, so `Option<Entity>` is as large as `Entity`. Without the
+/// `wide_entities` feature an entity packs into a single `u64`, see [`Entity::to_bits`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(not(feature = "wide_entities"), repr(C, align(8)))]
 pub struct Entity {
+    index: EntityIndex,
     generation: Generation,
-    index: u32,
 }
 
 impl Entity {
-    pub(crate) fn from(generation: Generation, index: u32) -> Self {
-        Self { generation, index }
+    /// An entity that is never alive, used to flag entity references that don't point anywhere.
+    pub const PLACEHOLDER: Self = Self {
+        index: EntityIndex::MAX,
+        generation: Generation::MAX,
+    };
+
+    pub(crate) const fn new(generation: Generation, index: EntityIndex) -> Self {
+        Self { index, generation }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from(generation: u32, index: EntityIndex) -> Self {
+        Self::new(Generation::new(generation).unwrap(), index)
+    }
+
+    /// The slot of the entity, which is reused once the entity is despawned.
+    pub fn index(self) -> EntityIndex {
+        self.index
+    }
+
+    /// How many times the slot of the entity has been reused, starting at 1.
+    pub fn generation(self) -> u32 {
+        self.generation.get()
+    }
+
+    /// Packs the entity into a `u64`, with the index in the low and the generation in the high
+    /// 32 bits. The result is never 0 and is stable across runs, so it can be stored or sent
+    /// over the network and turned back into the entity with [`Entity::from_bits`].
+    #[cfg(not(feature = "wide_entities"))]
+    pub const fn to_bits(self) -> u64 {
+        self.index as u64 | (self.generation.get() as u64) << 32
+    }
+
+    /// Unpacks an entity created by [`Entity::to_bits`].
+    ///
+    /// Returns `None` if the generation in the high 32 bits is 0.
+    #[cfg(not(feature = "wide_entities"))]
+    pub const fn from_bits(bits: u64) -> Option<Self> {
+        match Generation::new((bits >> 32) as u32) {
+            Some(generation) => Some(Self::new(generation, bits as u32)),
+            None => None,
+        }
+    }
+}
+
+/// The generation of a slot after the entity in it was freed, wrapping around to 1.
+fn next_generation(generation: Generation) -> Generation {
+    Generation::new(generation.get().wrapping_add(1)).unwrap_or(Generation::MIN)
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity({self})")
+    }
+}
+
+impl FromStr for Entity {
+    type Err = ParseEntityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, generation) = s
+            .split_once('v')
+            .ok_or(ParseEntityError::MissingSeparator)?;
+        let index = index.parse().map_err(ParseEntityError::Index)?;
+        let generation = generation.parse().map_err(ParseEntityError::Generation)?;
+
+        Ok(Self::new(generation, index))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Entity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The error returned when parsing an [`Entity`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseEntityError {
+    /// The string does not contain the `v` separating index and generation.
+    MissingSeparator,
+    Index(ParseIntError),
+    Generation(ParseIntError),
+}
+
+impl fmt::Display for ParseEntityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSeparator => write!(
+                f,
+                "expected an entity in the form `{{index}}v{{generation}}`"
+            ),
+            Self::Index(err) => write!(f, "invalid entity index: {err}"),
+            Self::Generation(err) => write!(f, "invalid entity generation: {err}"),
+        }
+    }
+}
+
+impl Error for ParseEntityError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::MissingSeparator => None,
+            Self::Index(err) | Self::Generation(err) => Some(err),
+        }
     }
 }
 
+/// The error returned by [`World::get_many_mut`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetManyMutError {
+    /// The entity was passed more than once, which would alias its component.
+    DuplicateEntity(Entity),
+    /// The entity doesn't exist or doesn't have the component.
+    MissingComponent(Entity),
+}
+
+impl fmt::Display for GetManyMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateEntity(entity) => write!(f, "{entity:?} was requested more than once"),
+            Self::MissingComponent(entity) => {
+                write!(f, "{entity:?} does not exist or lacks the component")
+            }
+        }
+    }
+}
+
+impl Error for GetManyMutError {}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct EntityLocation {
     pub archetype_id: ArchetypeId,
@@ -24,13 +192,177 @@ pub struct EntityLocation {
     pub table_row: TableRow,
 }
 
-#[derive(Debug)]
+/// A read-only view of a single alive entity and its components.
+#[derive(Clone, Copy)]
+pub struct EntityRef<'w> {
+    world: &'w World,
+    entity: Entity,
+    location: EntityLocation,
+}
+
+impl<'w> EntityRef<'w> {
+    pub(crate) fn new(world: &'w World, entity: Entity, location: EntityLocation) -> Self {
+        Self {
+            world,
+            entity,
+            location,
+        }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn location(&self) -> EntityLocation {
+        self.location
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&'w T> {
+        let component_id = self.world.component_id::<T>()?;
+        let ptr = self
+            .world
+            .component_ptr(self.entity, &self.location, component_id)?;
+
+        unsafe { Some(ptr.deref::<T>()) }
+    }
+
+    /// Type-erased version of [`EntityRef::get`], see [`World::get_by_id`].
+    pub fn get_by_id(&self, id: ComponentId) -> Option<Ptr<'w>> {
+        self.world.component_ptr(self.entity, &self.location, id)
+    }
+
+    pub fn contains<T: Component>(&self) -> bool {
+        self.world.component_id::<T>().is_some_and(|id| {
+            self.world
+                .archetypes
+                .get_unchecked(self.location.archetype_id)
+                .contains(id)
+                || self.world.has_sparse(self.entity, id)
+        })
+    }
+}
+
+impl fmt::Debug for EntityRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntityRef")
+            .field("entity", &self.entity)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+/// A mutable handle of an entity, see [`World::entity_mut`].
+///
+/// Caches the location of the entity, so it is only looked up again after structural changes.
+pub struct EntityWorldMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+    /// `None` once the entity was despawned, e.g. by a hook.
+    location: Option<EntityLocation>,
+}
+
+impl<'w> EntityWorldMut<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity, location: EntityLocation) -> Self {
+        Self {
+            world,
+            entity,
+            location: Some(location),
+        }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Panics: If the entity was despawned in the meantime, e.g. by a hook.
+    pub fn location(&self) -> EntityLocation {
+        self.location
+            .unwrap_or_else(|| panic!("{:?} was despawned", self.entity))
+    }
+
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Returns a shared view of the entity.
+    pub fn as_ref(&self) -> EntityRef<'_> {
+        EntityRef::new(self.world, self.entity, self.location())
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.as_ref().get()
+    }
+
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        let location = self.location();
+        let component_id = self.world.component_id::<T>()?;
+        let ptr = self
+            .world
+            .component_mut_ptr(self.entity, &location, component_id)?;
+
+        unsafe { Some(ptr.deref_mut::<T>()) }
+    }
+
+    /// Type-erased version of [`EntityWorldMut::get`], see [`World::get_by_id`].
+    pub fn get_by_id(&self, id: ComponentId) -> Option<Ptr<'_>> {
+        self.as_ref().get_by_id(id)
+    }
+
+    /// Type-erased version of [`EntityWorldMut::get_mut`], see [`World::get_mut_by_id`].
+    pub fn get_mut_by_id(&mut self, id: ComponentId) -> Option<MutPtr<'_>> {
+        let location = self.location();
+        self.world.component_mut_ptr(self.entity, &location, id)
+    }
+
+    pub fn contains<T: Component>(&self) -> bool {
+        self.as_ref().contains::<T>()
+    }
+
+    /// Inserts the bundle into the entity, see [`World::insert`].
+    pub fn insert<B: Bundle>(&mut self, bundle: B) -> &mut Self {
+        self.world.insert_at(self.entity, self.location(), bundle);
+        self.update_location();
+        self
+    }
+
+    /// Removes the component from the entity and returns it, see [`World::remove`].
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.location();
+        let removed = self.world.remove::<T>(self.entity);
+        self.update_location();
+        removed
+    }
+
+    pub fn despawn(self) {
+        self.world.despawn(self.entity);
+    }
+
+    /// Looks up the entity again after it may have been moved.
+    fn update_location(&mut self) {
+        self.location = self.world.entities.get(self.entity).copied();
+    }
+}
+
+impl fmt::Debug for EntityWorldMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntityWorldMut")
+            .field("entity", &self.entity)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Entry {
-    Free { next_free: usize },
-    Occupied { loc: EntityLocation },
+    Free,
+    /// Handed out by [`Entities::reserve_entity`], but not spawned yet.
+    Reserved,
+    Occupied {
+        loc: EntityLocation,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct EntityEntry {
     entry: Entry,
     generation: Generation,
@@ -40,19 +372,39 @@ struct EntityEntry {
 #[derive(Debug)]
 pub struct Entities {
     entities: Vec<EntityEntry>,
-    free_head: usize,
+    /// The free slots, the last one is reused first.
+    pending: Vec<usize>,
     len: usize,
+    /// The slots in `pending` below the cursor are still free. Every reservation decrements it,
+    /// taking the slot `pending[cursor - 1]` while the cursor is positive and a fresh slot behind
+    /// the end of `entities` after that.
+    ///
+    /// Reserved slots are marked as [`Entry::Reserved`] the next time the entities are mutated,
+    /// which sets the cursor back to `pending.len()`.
+    free_cursor: AtomicIsize,
 }
 
 impl Entities {
     pub(crate) fn new() -> Self {
         Self {
             entities: Vec::new(),
-            free_head: 0,
+            pending: Vec::new(),
             len: 0,
+            free_cursor: AtomicIsize::new(0),
         }
     }
 
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entities: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.entities.capacity()
+    }
+
     /// Allocate a new entity.
     ///
     /// The closure `f` needs to use the newly created [`Entity`] and use it for further
@@ -62,52 +414,218 @@ impl Entities {
         &mut self,
         f: impl FnOnce(Entity) -> Result<EntityLocation, ()>,
     ) -> Result<Entity, ()> {
-        if let Some(EntityEntry { entry, generation }) = self.entities.get_mut(self.free_head) {
-            if let Entry::Free { next_free } = entry {
-                let entity = Entity::from(*generation, self.free_head as u32);
-                if let Ok(loc) = f(entity) {
-                    self.free_head = *next_free;
-                    *entry = Entry::Occupied { loc };
-                    self.len += 1;
-                    return Ok(entity);
-                }
-            } else {
-                panic!("Entities free list is corrupt, failed to allocate entity!");
+        self.flush_reserved();
+        if let Some(index) = self.pending.pop() {
+            let EntityEntry { entry, generation } = &mut self.entities[index];
+            assert!(
+                matches!(entry, Entry::Free),
+                "Entities free list is corrupt, failed to allocate entity!"
+            );
+            let entity = Entity::new(*generation, Self::to_index(index));
+            let result = f(entity).map(|loc| {
+                *entry = Entry::Occupied { loc };
+                self.len += 1;
+                entity
+            });
+            if result.is_err() {
+                self.pending.push(index);
             }
+            self.sync_cursor();
+            return result;
+        }
+
+        let entity = Entity::new(Generation::MIN, Self::to_index(self.entities.len()));
+        let loc = f(entity)?;
+        self.entities.push(EntityEntry {
+            generation: Generation::MIN,
+            entry: Entry::Occupied { loc },
+        });
+        self.len += 1;
+        Ok(entity)
+    }
+
+    /// Hands out the id of a new entity without mutable access, so entities can be spawned from
+    /// [`Commands`](crate::command::Commands) or several threads at once. Freed slots are reused
+    /// before new ones are added.
+    ///
+    /// The entity is not alive until it is spawned, e.g. by [`Entities::flush`] or
+    /// [`World::flush_entities`].
+    pub fn reserve_entity(&self) -> Entity {
+        let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
+        if cursor > 0 {
+            let index = self.pending[cursor as usize - 1];
+            Entity::new(self.entities[index].generation, Self::to_index(index))
         } else {
-            let entity = Entity::from(0, self.entities.len() as u32);
-            if let Ok(loc) = f(entity) {
-                self.entities.push(EntityEntry {
-                    generation: 0,
-                    entry: Entry::Occupied { loc },
-                });
-                self.free_head = self.entities.len();
+            Entity::new(
+                Generation::MIN,
+                Self::to_index(self.entities.len() + cursor.unsigned_abs()),
+            )
+        }
+    }
+
+    /// Marks the slots of all entities reserved since the last mutation as [`Entry::Reserved`].
+    fn flush_reserved(&mut self) {
+        let cursor = mem::replace(self.free_cursor.get_mut(), 0);
+        let reused = cursor.max(0) as usize;
+        for index in self.pending.drain(reused..) {
+            self.entities[index].entry = Entry::Reserved;
+        }
+        if cursor < 0 {
+            let fresh = self.entities.len() + cursor.unsigned_abs();
+            self.entities.resize(
+                fresh,
+                EntityEntry {
+                    entry: Entry::Reserved,
+                    generation: Generation::MIN,
+                },
+            );
+        }
+        self.sync_cursor();
+    }
+
+    /// Resets the reservation cursor after the free slots changed.
+    fn sync_cursor(&mut self) {
+        *self.free_cursor.get_mut() = self.pending.len() as isize;
+    }
+
+    /// Makes all reserved entities alive, at the location returned by `init` for each of them.
+    ///
+    /// Spawning them this way doesn't place them in any storage, so the locations must be valid
+    /// for the world these entities belong to.
+    pub fn flush(&mut self, mut init: impl FnMut(Entity) -> EntityLocation) {
+        self.flush_reserved();
+        for (index, EntityEntry { entry, generation }) in self.entities.iter_mut().enumerate() {
+            if let Entry::Reserved = entry {
+                *entry = Entry::Occupied {
+                    loc: init(Entity::new(*generation, Self::to_index(index))),
+                };
                 self.len += 1;
-                return Ok(entity);
             }
         }
+    }
 
-        Err(())
+    /// Allocates the entity returned by [`Entities::reserve_entity`], like [`Entities::alloc`].
+    ///
+    /// Returns an error if the entity is not reserved or `f` fails, in which case the entity
+    /// stays reserved.
+    pub(crate) fn alloc_reserved(
+        &mut self,
+        entity: Entity,
+        f: impl FnOnce(Entity) -> Result<EntityLocation, ()>,
+    ) -> Result<Entity, ()> {
+        self.flush_reserved();
+        match self.entities.get_mut(entity.index as usize) {
+            Some(EntityEntry {
+                entry: entry @ Entry::Reserved,
+                generation,
+            }) if *generation == entity.generation => {
+                *entry = Entry::Occupied { loc: f(entity)? };
+                self.len += 1;
+                Ok(entity)
+            }
+            _ => Err(()),
+        }
+    }
+
+    fn to_index(slot: usize) -> EntityIndex {
+        // The placeholder index is never handed out
+        EntityIndex::try_from(slot)
+            .ok()
+            .filter(|index| *index != EntityIndex::MAX)
+            .expect("Too many entities, enable the `wide_entities` feature for more entity slots")
     }
 
+    /// Reserves room for `additional` more entities beyond the slots that can be reused.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.flush_reserved();
+        self.entities
+            .try_reserve(additional.saturating_sub(self.pending.len()))
+    }
+
+    /// Retrieves the location of the entity, if it is alive.
     pub fn get(&self, entity: Entity) -> Option<&EntityLocation> {
-        if let Some(EntityEntry { entry, generation }) = self.entities.get(entity.index as usize) {
-            if let Entry::Occupied { loc } = entry {
-                if *generation == entity.generation {
-                    return Some(loc);
-                }
+        if let Some(EntityEntry {
+            entry: Entry::Occupied { loc },
+            generation,
+        }) = self.entities.get(entity.index as usize)
+        {
+            if *generation == entity.generation {
+                return Some(loc);
             }
         }
 
         None
     }
 
-    fn get_mut(&mut self, entity: Entity) -> Option<&mut EntityLocation> {
-        if let Some(EntityEntry { entry, generation }) = self.entities.get_mut(entity.index as usize) {
-            if let Entry::Occupied { loc } = entry {
-                if *generation == entity.generation {
-                    return Some(loc);
+    /// Like [`Entities::get`], but with the `unchecked-entities` feature, release builds skip
+    /// comparing the generation, so an id of a despawned entity resolves to the entity that
+    /// reused its slot. All other lookups of the world keep checking the generation.
+    pub fn get_unchecked_generation(&self, entity: Entity) -> Option<&EntityLocation> {
+        if cfg!(not(all(
+            feature = "unchecked-entities",
+            not(debug_assertions)
+        ))) {
+            return self.get(entity);
+        }
+
+        match self.entities.get(entity.index as usize) {
+            Some(EntityEntry {
+                entry: Entry::Occupied { loc },
+                ..
+            }) => Some(loc),
+            _ => None,
+        }
+    }
+
+    /// Iterates over all alive entities and their locations in index order. Reserved entities
+    /// are skipped until they are spawned.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &EntityLocation)> + use<'_> {
+        self.entities
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.entry {
+                Entry::Occupied { loc } => {
+                    Some((Entity::new(entry.generation, Self::to_index(index)), loc))
                 }
+                Entry::Free | Entry::Reserved => None,
+            })
+    }
+
+    /// Returns `true` if the entity is alive, always comparing the generation.
+    pub fn contains(&self, entity: Entity) -> bool {
+        matches!(
+            self.entities.get(entity.index as usize),
+            Some(EntityEntry {
+                entry: Entry::Occupied { .. },
+                generation,
+            }) if *generation == entity.generation
+        )
+    }
+
+    /// Retrieves the location of the entity without checking its index or generation.
+    ///
+    /// # Safety
+    /// The entity must be alive. This is only checked with debug assertions.
+    pub(crate) unsafe fn get_unchecked(&self, entity: Entity) -> &EntityLocation {
+        debug_assert!(
+            self.get(entity).is_some(),
+            "{entity:?} is not alive in this world"
+        );
+
+        match &self.entities.get_unchecked(entity.index as usize).entry {
+            Entry::Occupied { loc } => loc,
+            Entry::Free | Entry::Reserved => std::hint::unreachable_unchecked(),
+        }
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut EntityLocation> {
+        if let Some(EntityEntry {
+            entry: Entry::Occupied { loc },
+            generation,
+        }) = self.entities.get_mut(entity.index as usize)
+        {
+            if *generation == entity.generation {
+                return Some(loc);
             }
         }
 
@@ -121,19 +639,19 @@ impl Entities {
     }
 
     pub fn free(&mut self, entity: Entity) -> Option<EntityLocation> {
+        self.flush_reserved();
         if let Some(EntityEntry { entry, generation }) =
             self.entities.get_mut(entity.index as usize)
         {
             if *generation == entity.generation {
                 if let Entry::Occupied { loc } = entry {
-                    let loc = loc.clone();
+                    let loc = *loc;
 
-                    *generation += 1;
-                    *entry = Entry::Free {
-                        next_free: self.free_head,
-                    };
-                    self.free_head = entity.index as usize;
+                    *generation = next_generation(*generation);
+                    *entry = Entry::Free;
+                    self.pending.push(entity.index as usize);
                     self.len -= 1;
+                    self.sync_cursor();
 
                     return Some(loc);
                 }
@@ -143,16 +661,143 @@ impl Entities {
         None
     }
 
+    /// Frees all alive entities, so the lowest indices are allocated again first. Reserved
+    /// entities stay reserved.
+    pub(crate) fn clear(&mut self) {
+        for index in (0..self.entities.len()).rev() {
+            let EntityEntry { entry, generation } = &self.entities[index];
+            if let Entry::Occupied { .. } = entry {
+                self.free(Entity::new(*generation, Self::to_index(index)));
+            }
+        }
+    }
+
+    /// The ids the free slots are reused with, in the order [`Entities::restore`] expects them.
+    /// Slots that are only reserved are included as if they were free.
+    #[cfg(feature = "serde")]
+    pub(crate) fn free_entities(&self) -> Vec<Entity> {
+        let reserved = self
+            .entities
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| matches!(entry.entry, Entry::Reserved));
+        let fresh = (self.entities.len()..)
+            .take(
+                self.free_cursor
+                    .load(Ordering::Relaxed)
+                    .min(0)
+                    .unsigned_abs(),
+            )
+            .map(|index| Entity::new(Generation::MIN, Self::to_index(index)));
+        reserved
+            .map(|(index, entry)| Entity::new(entry.generation, Self::to_index(index)))
+            .chain(fresh)
+            .chain(
+                self.pending.iter().map(|&index| {
+                    Entity::new(self.entities[index].generation, Self::to_index(index))
+                }),
+            )
+            .collect()
+    }
+
+    /// Creates the slots of a saved world, reserving the `alive` entities to be spawned with
+    /// [`Entities::alloc_reserved`] and freeing the others, which are reused last to first.
+    ///
+    /// Returns an error if the entities don't cover every slot up to the highest index exactly
+    /// once.
+    #[cfg(feature = "serde")]
+    pub(crate) fn restore(alive: &[Entity], free: &[Entity]) -> Result<Self, ()> {
+        let mut entries = vec![None; alive.len() + free.len()];
+        for (entity, entry) in alive
+            .iter()
+            .map(|entity| (entity, Entry::Reserved))
+            .chain(free.iter().map(|entity| (entity, Entry::Free)))
+        {
+            match entries.get_mut(entity.index as usize) {
+                Some(slot @ None) => {
+                    *slot = Some(EntityEntry {
+                        entry,
+                        generation: entity.generation,
+                    })
+                }
+                _ => return Err(()),
+            }
+        }
+
+        let pending = free.iter().map(|entity| entity.index as usize).collect();
+        let mut entities = Self {
+            entities: entries.into_iter().map(Option::unwrap).collect(),
+            pending,
+            ..Self::new()
+        };
+        entities.sync_cursor();
+        Ok(entities)
+    }
+
+    /// Moves all alive entities to the lowest indices, keeping their order, and releases the free
+    /// slots behind them.
+    ///
+    /// Returns the previous id, the new id and the location of every moved entity. Ids handed out
+    /// for a slot before stay dead, unless the slot is released and allocated again later.
+    ///
+    /// Panics: If there are reserved entities that have not been spawned yet.
+    pub(crate) fn compact(&mut self) -> Vec<(Entity, Entity, EntityLocation)> {
+        self.flush_reserved();
+        assert!(
+            !self
+                .entities
+                .iter()
+                .any(|entry| matches!(entry.entry, Entry::Reserved)),
+            "Tried to compact the entities while reserved entities are waiting to be spawned"
+        );
+
+        let mut moved = Vec::new();
+        let mut next = 0;
+        for index in 0..self.entities.len() {
+            let EntityEntry {
+                entry: Entry::Occupied { loc },
+                generation,
+            } = self.entities[index]
+            else {
+                continue;
+            };
+
+            if index != next {
+                // All slots between `next` and `index` are free
+                let target = &mut self.entities[next];
+                let new = Entity::new(target.generation, Self::to_index(next));
+                target.entry = Entry::Occupied { loc };
+
+                self.entities[index] = EntityEntry {
+                    entry: Entry::Free,
+                    generation: next_generation(generation),
+                };
+                moved.push((Entity::new(generation, Self::to_index(index)), new, loc));
+            }
+            next += 1;
+        }
+
+        self.entities.truncate(next);
+        self.entities.shrink_to_fit();
+        self.pending.clear();
+        self.sync_cursor();
+        moved
+    }
+
     pub fn len(&self) -> usize {
         self.entities.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{archetype::ArchetypeId, storage::TableId, storage::TableRow};
 
-    use super::{Entities, EntityLocation};
+    use super::{Entities, Entity, EntityIndex, EntityLocation, ParseEntityError};
 
     #[test]
     fn alloc_entity() {
@@ -169,7 +814,7 @@ mod tests {
 
         assert_eq!(entities.len, 1);
         assert_eq!(entity.index, 0);
-        assert_eq!(entity.generation, 0);
+        assert_eq!(entity.generation(), 1);
 
         let entity = entities
             .alloc(|_| {
@@ -183,7 +828,7 @@ mod tests {
 
         assert_eq!(entities.len, 2);
         assert_eq!(entity.index, 1);
-        assert_eq!(entity.generation, 0);
+        assert_eq!(entity.generation(), 1);
 
         entities.free(entity);
 
@@ -202,7 +847,14 @@ mod tests {
 
         assert_eq!(entities.len, 2);
         assert_eq!(double_entity.index, 1);
-        assert_eq!(double_entity.generation, 1);
+        assert_eq!(double_entity.generation(), 2);
+        assert_eq!(
+            entities
+                .iter()
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>(),
+            [Entity::from(1, 0), double_entity]
+        );
         assert_eq!(entities.get(entity), None);
         assert_eq!(
             entities.get(double_entity),
@@ -212,6 +864,13 @@ mod tests {
                 table_row: TableRow(0),
             })
         );
+
+        let stale = entities.get_unchecked_generation(entity);
+        if cfg!(all(feature = "unchecked-entities", not(debug_assertions))) {
+            assert_eq!(stale, entities.get(double_entity));
+        } else {
+            assert_eq!(stale, None);
+        }
     }
 
     #[test]
@@ -241,6 +900,8 @@ mod tests {
 
         assert_eq!(entity1.index, entity2.index);
         assert_ne!(entity1.generation, entity2.generation);
+        assert!(!entities.contains(entity1));
+        assert!(entities.contains(entity2));
 
         entities.free(entity1);
 
@@ -257,4 +918,146 @@ mod tests {
         entities.free(entity2);
         assert_eq!(entities.len, 0);
     }
+
+    #[test]
+    fn reserve_entity() {
+        let location = EntityLocation {
+            archetype_id: ArchetypeId(0),
+            table_id: TableId(0),
+            table_row: TableRow(0),
+        };
+        let mut entities = Entities::new();
+        let e0 = entities.alloc(|_| Ok(location)).unwrap();
+        let e1 = entities.alloc(|_| Ok(location)).unwrap();
+        entities.free(e0);
+        entities.free(e1);
+
+        // Reserving reuses the freed slots first, then adds new ones
+        let reserved = entities.reserve_entity();
+        assert_eq!(reserved, Entity::from(2, 1));
+        assert_eq!(entities.reserve_entity(), Entity::from(2, 0));
+        let fresh = entities.reserve_entity();
+        assert_eq!(fresh, Entity::from(1, 2));
+        assert!(!entities.contains(reserved));
+
+        // Allocating skips the reserved slots
+        let allocated = entities.alloc(|_| Ok(location)).unwrap();
+        assert_eq!(allocated.index, 3);
+
+        assert_eq!(
+            entities.alloc_reserved(reserved, |_| Ok(location)),
+            Ok(reserved)
+        );
+        assert_eq!(entities.get(reserved), Some(&location));
+        assert_eq!(entities.alloc_reserved(reserved, |_| Ok(location)), Err(()));
+        assert_eq!(
+            entities.alloc_reserved(allocated, |_| Ok(location)),
+            Err(())
+        );
+        assert_eq!(entities.len, 2);
+
+        let mut flushed = Vec::new();
+        entities.flush(|entity| {
+            flushed.push(entity);
+            location
+        });
+        assert_eq!(flushed, [Entity::from(2, 0), fresh]);
+        assert!(entities.contains(fresh));
+        assert_eq!(entities.len, 4);
+    }
+
+    #[test]
+    fn reserve_entity_concurrently() {
+        let location = EntityLocation {
+            archetype_id: ArchetypeId(0),
+            table_id: TableId(0),
+            table_row: TableRow(0),
+        };
+        let mut entities = Entities::new();
+        for _ in 0..8 {
+            let entity = entities.alloc(|_| Ok(location)).unwrap();
+            entities.free(entity);
+        }
+
+        let mut reserved = std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..4)
+                            .map(|_| entities.reserve_entity())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        reserved.sort_unstable_by_key(|entity| entity.index());
+        assert!(reserved.iter().map(|entity| entity.index()).eq(0..16));
+
+        entities.flush(|_| location);
+        assert!(reserved.iter().all(|entity| entities.contains(*entity)));
+        assert_eq!(entities.alloc(|_| Ok(location)).unwrap().index, 16);
+    }
+
+    #[test]
+    fn display_and_parse() {
+        let entity = Entity::from(3, 42);
+        assert_eq!(entity.to_string(), "42v3");
+        assert_eq!(format!("{entity:?}"), "Entity(42v3)");
+        assert_eq!("42v3".parse::<Entity>(), Ok(entity));
+
+        assert_eq!(
+            "42".parse::<Entity>(),
+            Err(ParseEntityError::MissingSeparator)
+        );
+        assert!(matches!(
+            "v3".parse::<Entity>(),
+            Err(ParseEntityError::Index(_))
+        ));
+        assert!(matches!(
+            "42v-1".parse::<Entity>(),
+            Err(ParseEntityError::Generation(_))
+        ));
+        assert!(matches!(
+            "42v0".parse::<Entity>(),
+            Err(ParseEntityError::Generation(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "wide_entities"))]
+    fn bits() {
+        assert_eq!(size_of::<Entity>(), 8);
+        assert_eq!(size_of::<Option<Entity>>(), 8);
+
+        let entity = Entity::from(3, 42);
+        assert_eq!(entity.to_bits(), 3 << 32 | 42);
+        assert_eq!(Entity::from_bits(entity.to_bits()), Some(entity));
+        assert_eq!(
+            Entity::from_bits(Entity::PLACEHOLDER.to_bits()),
+            Some(Entity::PLACEHOLDER)
+        );
+        assert_eq!(Entity::from_bits(42), None);
+    }
+
+    #[test]
+    fn index_width() {
+        assert_eq!(Entity::PLACEHOLDER.index(), EntityIndex::MAX);
+        assert_eq!(Entities::to_index(7), 7);
+
+        let wide = format!("{}v1", u64::from(u32::MAX) + 1);
+        assert_eq!(
+            wide.parse::<Entity>().is_ok(),
+            cfg!(feature = "wide_entities")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many entities")]
+    fn index_overflow() {
+        Entities::to_index(EntityIndex::MAX as usize);
+    }
 }
@@ -0,0 +1,460 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+};
+
+use crate::{
+    component::Tick,
+    resource::Resource,
+    system::{SystemAccess, SystemParam},
+    World,
+};
+
+pub use quartz_macros::Event;
+
+/// A message sent between systems, like a collision or an input, see [`Events`].
+///
+/// ```
+/// # use quartz::{
+/// #     event::{Event, EventReader, EventWriter},
+/// #     resource::Resource,
+/// #     schedule::Schedule,
+/// #     system::ResMut,
+/// #     World,
+/// # };
+/// #[derive(Event)]
+/// struct Damage(u32);
+///
+/// #[derive(Resource)]
+/// struct Health(u32);
+///
+/// fn attack(mut damage: EventWriter<Damage>) {
+///     damage.send(Damage(3));
+/// }
+///
+/// fn apply_damage(mut damage: EventReader<Damage>, mut health: ResMut<Health>) {
+///     for Damage(amount) in damage.read() {
+///         health.0 -= amount;
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.add_event::<Damage>();
+/// world.insert_resource(Health(10));
+/// let mut schedule = Schedule::new();
+/// schedule.add_system(attack).add_system(apply_damage);
+///
+/// schedule.run(&mut world);
+/// world.send_event(Damage(4));
+/// schedule.run(&mut world);
+/// assert_eq!(world.get_resource::<Health>().unwrap().0, 0);
+/// ```
+pub trait Event: Send + Sync + 'static {}
+
+/// The events of type `E` sent in the current and the previous frame, stored as a resource.
+///
+/// Every reader keeps track of the events it has read in its own [`EventCursor`], so reading
+/// doesn't consume the events for other readers. Events are dropped in the second
+/// [`Events::update`] after they were sent, which [`Schedule::run`](crate::schedule::Schedule::run)
+/// calls at the start of every frame for the events added with [`World::add_event`]. A reader
+/// has to run at least once per frame to not miss any events.
+pub struct Events<E: Event> {
+    /// The events sent before the last update.
+    previous: EventBuffer<E>,
+    /// The events sent since the last update.
+    current: EventBuffer<E>,
+    /// How many events have been sent in total, which is the id of the next event.
+    count: usize,
+}
+
+struct EventBuffer<E> {
+    events: Vec<E>,
+    /// The id of the first event in the buffer.
+    start: usize,
+}
+
+impl<E> EventBuffer<E> {
+    /// The events with an id of at least `from`.
+    fn since(&self, from: usize) -> &[E] {
+        let skip = from.saturating_sub(self.start).min(self.events.len());
+        &self.events[skip..]
+    }
+}
+
+impl<E: Event> Events<E> {
+    pub fn send(&mut self, event: E) {
+        self.current.events.push(event);
+        self.count += 1;
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Drops the events sent before the previous update and starts a new frame.
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.events.clear();
+        self.current.start = self.count;
+    }
+
+    /// Drops all events. Readers that have not read them yet miss them.
+    pub fn clear(&mut self) {
+        self.previous.events.clear();
+        self.current.events.clear();
+        self.previous.start = self.count;
+        self.current.start = self.count;
+    }
+
+    /// Iterates over all events that have not been dropped yet, in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.previous.events.iter().chain(&self.current.events)
+    }
+
+    /// The number of events that have not been dropped yet.
+    pub fn len(&self) -> usize {
+        self.previous.events.len() + self.current.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<E: Event> Default for Events<E> {
+    fn default() -> Self {
+        Self {
+            previous: EventBuffer {
+                events: Vec::new(),
+                start: 0,
+            },
+            current: EventBuffer {
+                events: Vec::new(),
+                start: 0,
+            },
+            count: 0,
+        }
+    }
+}
+
+impl<E: Event> Resource for Events<E> {}
+
+impl<E: Event> fmt::Debug for Events<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("event", &type_name::<E>())
+            .field("len", &self.len())
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+/// The position of a reader in the [`Events`] of type `E`.
+pub struct EventCursor<E: Event> {
+    /// The id of the next event to read.
+    next: usize,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: Event> EventCursor<E> {
+    /// Reads the events sent since the previous call that have not been dropped yet.
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> {
+        let from = self.next;
+        self.next = events.count;
+        events
+            .previous
+            .since(from)
+            .iter()
+            .chain(events.current.since(from))
+    }
+
+    /// The number of events the next [`EventCursor::read`] returns.
+    pub fn len(&self, events: &Events<E>) -> usize {
+        events.previous.since(self.next).len() + events.current.since(self.next).len()
+    }
+
+    pub fn is_empty(&self, events: &Events<E>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Skips all unread events.
+    pub fn clear(&mut self, events: &Events<E>) {
+        self.next = events.count;
+    }
+}
+
+impl<E: Event> Default for EventCursor<E> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E: Event> fmt::Debug for EventCursor<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventCursor")
+            .field("event", &type_name::<E>())
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+/// Sends events of type `E` in a system.
+///
+/// Panics when the system runs if the event has not been added with [`World::add_event`].
+pub struct EventWriter<'w, E: Event> {
+    events: &'w mut Events<E>,
+}
+
+impl<E: Event> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.send_batch(events);
+    }
+}
+
+// SAFETY: The write of the events resource is registered.
+unsafe impl<E: Event> SystemParam for EventWriter<'_, E> {
+    type State = ();
+    type Item<'w> = EventWriter<'w, E>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        access.add_resource_write::<Events<E>>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        EventWriter {
+            // SAFETY: The access check guarantees that no other parameter of the system accesses
+            // the events.
+            events: world
+                .resources
+                .get_mut_unchecked()
+                .unwrap_or_else(|| missing_event::<E>()),
+        }
+    }
+}
+
+/// Reads the events of type `E` in a system, every event is read once by every system.
+///
+/// Panics when the system runs if the event has not been added with [`World::add_event`].
+pub struct EventReader<'w, E: Event> {
+    cursor: &'w mut EventCursor<E>,
+    events: &'w Events<E>,
+}
+
+impl<'w, E: Event> EventReader<'w, E> {
+    /// Reads the events sent since the previous run of the system, see [`EventCursor::read`].
+    pub fn read(&mut self) -> impl Iterator<Item = &'w E> {
+        self.cursor.read(self.events)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cursor.len(self.events)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cursor.is_empty(self.events)
+    }
+
+    /// Skips all unread events.
+    pub fn clear(&mut self) {
+        self.cursor.clear(self.events);
+    }
+}
+
+// SAFETY: The read of the events resource is registered.
+unsafe impl<E: Event> SystemParam for EventReader<'_, E> {
+    type State = EventCursor<E>;
+    type Item<'w> = EventReader<'w, E>;
+
+    /// Starts at the oldest event that has not been dropped, so a new system also reads the
+    /// events sent in the previous frame.
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        access.add_resource_read::<Events<E>>();
+        EventCursor::default()
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        EventReader {
+            cursor: state,
+            events: world
+                .resources
+                .get()
+                .unwrap_or_else(|| missing_event::<E>()),
+        }
+    }
+}
+
+fn missing_event<E: Event>() -> ! {
+    panic!(
+        "Event {} has not been added to the world, see World::add_event",
+        type_name::<E>()
+    )
+}
+
+/// The update functions of all events added to a world, see [`World::update_events`].
+#[derive(Default)]
+struct EventRegistry {
+    updates: HashMap<TypeId, fn(&mut World)>,
+}
+
+impl Resource for EventRegistry {}
+
+fn update_events<E: Event>(world: &mut World) {
+    if let Some(events) = world.get_resource_mut::<Events<E>>() {
+        events.update();
+    }
+}
+
+impl World {
+    /// Inserts the [`Events`] resource of `E`, which is updated by [`World::update_events`].
+    /// Does nothing if the event was already added.
+    pub fn add_event<E: Event>(&mut self) {
+        if !self.contains_resource::<Events<E>>() {
+            self.insert_resource(Events::<E>::default());
+        }
+        if !self.contains_resource::<EventRegistry>() {
+            self.insert_resource(EventRegistry::default());
+        }
+        self.get_resource_mut::<EventRegistry>()
+            .expect("the registry was just inserted")
+            .updates
+            .insert(TypeId::of::<E>(), update_events::<E>);
+    }
+
+    /// Sends the event to the readers of `E`.
+    ///
+    /// Panics: If the event has not been added with [`World::add_event`].
+    pub fn send_event<E: Event>(&mut self, event: E) {
+        self.get_resource_mut::<Events<E>>()
+            .unwrap_or_else(|| missing_event::<E>())
+            .send(event);
+    }
+
+    /// Calls [`Events::update`] for all events added with [`World::add_event`], which drops the
+    /// events sent before the previous call.
+    pub fn update_events(&mut self) {
+        let updates = self
+            .get_resource::<EventRegistry>()
+            .map(|registry| registry.updates.values().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for update in updates {
+            update(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        resource::Resource,
+        schedule::Schedule,
+        system::{IntoSystem, ResMut, System},
+        World,
+    };
+
+    use super::{Event, EventCursor, EventReader, EventWriter, Events};
+
+    #[derive(Debug, PartialEq, Event)]
+    struct Hit(u32);
+
+    #[test]
+    fn events() {
+        let mut events = Events::default();
+        let mut early = EventCursor::default();
+        events.send(Hit(0));
+        events.send(Hit(1));
+        assert_eq!(early.len(&events), 2);
+        assert_eq!(early.read(&events).collect::<Vec<_>>(), [&Hit(0), &Hit(1)]);
+        assert!(early.is_empty(&events));
+
+        events.update();
+        events.send(Hit(2));
+        let mut late = EventCursor::default();
+        assert_eq!(early.read(&events).collect::<Vec<_>>(), [&Hit(2)]);
+        assert_eq!(
+            late.read(&events).collect::<Vec<_>>(),
+            [&Hit(0), &Hit(1), &Hit(2)]
+        );
+
+        // The events of the first frame are dropped in the second update
+        events.update();
+        events.send(Hit(3));
+        events.update();
+        assert_eq!(events.iter().collect::<Vec<_>>(), [&Hit(3)]);
+        let mut missed = EventCursor::default();
+        assert_eq!(missed.read(&events).collect::<Vec<_>>(), [&Hit(3)]);
+
+        events.send(Hit(4));
+        late.clear(&events);
+        assert!(late.is_empty(&events));
+        events.clear();
+        assert!(events.is_empty());
+        assert!(early.is_empty(&events));
+    }
+
+    #[derive(Default, Resource)]
+    struct Received(Vec<u32>);
+
+    fn send(mut hits: EventWriter<Hit>) {
+        hits.send_batch([Hit(1), Hit(2)]);
+    }
+
+    fn receive(mut hits: EventReader<Hit>, mut received: ResMut<Received>) {
+        received.0.extend(hits.read().map(|hit| hit.0));
+    }
+
+    fn count(hits: EventReader<Hit>, mut received: ResMut<Received>) {
+        received.0.push(hits.len() as u32);
+    }
+
+    #[test]
+    fn event_systems() {
+        let mut world = World::new();
+        world.add_event::<Hit>();
+        world.insert_resource(Received::default());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(send)
+            .add_system(receive)
+            .add_system(count);
+        schedule.run(&mut world);
+        world.send_event(Hit(3));
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get_resource::<Received>().unwrap().0,
+            [1, 2, 2, 3, 1, 2, 5]
+        );
+
+        // A system added later reads the events of the previous frame
+        let mut late = receive.into_system();
+        world.get_resource_mut::<Received>().unwrap().0.clear();
+        world.update_events();
+        late.run(&mut world);
+        assert_eq!(world.get_resource::<Received>().unwrap().0, [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "has not been added to the world")]
+    fn missing_event() {
+        World::new().send_event(Hit(0));
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::HashSet;
+
+use crate::{
+    component::{Component, ComponentHooks, ComponentId},
+    entity::Entity,
+    map_entities::{EntityMapper, MapEntities},
+    query::{Query, QueryFilter, Queryable},
+    DeferredWorld, World,
+};
+
+/// The parent of an entity in the hierarchy.
+///
+/// Inserting, replacing or removing it keeps the [`Children`] of the parents in sync. Inserting a
+/// parent that would create a cycle is undone, the entity is detached instead.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Parent {
+    parent: Entity,
+    policy: DespawnPolicy,
+}
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.parent
+    }
+
+    /// What happens to the child when the parent is despawned.
+    pub fn policy(&self) -> DespawnPolicy {
+        self.policy
+    }
+}
+
+/// What happens to a child when its parent is despawned, chosen per child with
+/// [`World::set_parent_with`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DespawnPolicy {
+    /// The child is despawned together with the parent, including its own descendants.
+    Despawn,
+    /// The child is detached and becomes a root.
+    #[default]
+    Orphan,
+    /// The child is moved to the parent of the parent, or detached if there is none.
+    Reparent,
+}
+
+impl MapEntities for Parent {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        self.parent.map_entities(mapper);
+    }
+}
+
+impl Component for Parent {
+    fn register_hooks(hooks: &mut ComponentHooks) {
+        hooks
+            .on_insert(parent_inserted)
+            .on_replace(parent_removed)
+            .on_remove(parent_removed);
+    }
+}
+
+/// The children of an entity in the hierarchy, in the order they were added.
+///
+/// Maintained automatically from the [`Parent`]s of the children and removed once the last child
+/// is gone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        self.0.map_entities(mapper);
+    }
+}
+
+impl Component for Children {
+    fn register_hooks(hooks: &mut ComponentHooks) {
+        hooks.on_remove(children_removed);
+    }
+}
+
+fn parent_inserted(mut world: DeferredWorld<'_>, child: Entity, id: ComponentId) {
+    let parent = world.get::<Parent>(child).unwrap().get();
+    let mut ancestor = Some(parent);
+    while let Some(entity) = ancestor {
+        if entity == child {
+            world.queue(move |world: &mut World| world.remove_by_ids(child, &[id]));
+            return;
+        }
+        ancestor = world.get::<Parent>(entity).map(Parent::get);
+    }
+
+    world.queue(move |world: &mut World| {
+        if world.get_entity(parent).is_none() {
+            return;
+        }
+        match world.get_mut::<Children>(parent) {
+            Some(children) if !children.0.contains(&child) => children.0.push(child),
+            Some(_) => {}
+            None => world.insert(parent, Children(vec![child])),
+        }
+    });
+}
+
+fn parent_removed(mut world: DeferredWorld<'_>, child: Entity, _: ComponentId) {
+    let parent = world.get::<Parent>(child).unwrap().get();
+    world.queue(move |world: &mut World| {
+        let Some(children) = world.get_mut::<Children>(parent) else {
+            return;
+        };
+        children.0.retain(|entity| *entity != child);
+        if children.0.is_empty() {
+            let id = world.component_id::<Children>().unwrap();
+            world.remove_by_ids(parent, &[id]);
+        }
+    });
+}
+
+/// Applies the [`DespawnPolicy`] of the remaining children when their parent is despawned.
+fn children_removed(mut world: DeferredWorld<'_>, parent: Entity, _: ComponentId) {
+    let children = world.get::<Children>(parent).unwrap().0.clone();
+    let grandparent = world.get::<Parent>(parent).map(Parent::get);
+    world.queue(move |world: &mut World| {
+        let id = world.component_id::<Parent>().unwrap();
+        for child in children {
+            let Some(&Parent {
+                parent: current,
+                policy,
+            }) = world.get::<Parent>(child)
+            else {
+                continue;
+            };
+            if current != parent {
+                continue;
+            }
+
+            match (policy, grandparent) {
+                (DespawnPolicy::Despawn, _) => world.despawn(child),
+                (DespawnPolicy::Reparent, Some(grandparent))
+                    if world.get_entity(grandparent).is_some() =>
+                {
+                    world.insert(
+                        child,
+                        Parent {
+                            parent: grandparent,
+                            policy,
+                        },
+                    );
+                }
+                _ => world.remove_by_ids(child, &[id]),
+            }
+        }
+    });
+}
+
+impl World {
+    /// Makes `parent` the parent of `child`, moving it away from its previous parent. The child
+    /// is orphaned when the parent is despawned.
+    ///
+    /// Panics: If either entity does not exist, or `child` is `parent` itself or one of its
+    /// ancestors.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.set_parent_with(child, parent, DespawnPolicy::default());
+    }
+
+    /// Makes `parent` the parent of `child` like [`World::set_parent`], with the given policy for
+    /// when the parent is despawned.
+    ///
+    /// Panics: If either entity does not exist, or `child` is `parent` itself or one of its
+    /// ancestors.
+    pub fn set_parent_with(&mut self, child: Entity, parent: Entity, policy: DespawnPolicy) {
+        assert!(
+            self.get_entity(parent).is_some(),
+            "Tried to set {parent:?} as a parent, but it does not exist"
+        );
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            assert_ne!(
+                entity, child,
+                "Making {parent:?} the parent of {child:?} would create a cycle"
+            );
+            ancestor = self.get::<Parent>(entity).map(Parent::get);
+        }
+
+        self.insert(child, Parent { parent, policy });
+    }
+
+    /// Appends `child` to the children of `parent`, see [`World::set_parent`].
+    pub fn add_child(&mut self, parent: Entity, child: Entity) {
+        self.set_parent(child, parent);
+    }
+
+    /// Detaches `child` from its parent, if it has one.
+    pub fn remove_parent(&mut self, child: Entity) {
+        if let Some(id) = self.component_id::<Parent>() {
+            self.remove_by_ids(child, &[id]);
+        }
+    }
+
+    /// Detaches the given children from `parent`, ignoring entities that are not its children.
+    pub fn remove_children(&mut self, parent: Entity, children: &[Entity]) {
+        for child in children {
+            if self.get::<Parent>(*child).map(Parent::get) == Some(parent) {
+                self.remove_parent(*child);
+            }
+        }
+    }
+
+    /// Despawns the entity together with all its descendants, regardless of their
+    /// [`DespawnPolicy`]. Descendants are despawned before their parents.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        let mut entities = vec![entity];
+        // Skips entities that in place changes to `Children` made their own descendants
+        let mut visited = HashSet::from([entity]);
+        let mut next = 0;
+        while let Some(entity) = entities.get(next) {
+            if let Some(children) = self.get::<Children>(*entity) {
+                entities.extend(children.iter().filter(|child| visited.insert(*child)));
+            }
+            next += 1;
+        }
+
+        for entity in entities.into_iter().rev() {
+            self.despawn(entity);
+        }
+    }
+}
+
+impl<'world, T: Queryable, F: QueryFilter> Query<'world, T, F> {
+    /// Iterates over the items of all descendants of `root` matched by the query, parents before
+    /// their children. The root itself is not included.
+    ///
+    /// Descendants that are not matched are skipped, but their children are still visited.
+    pub fn iter_descendants(self, root: Entity) -> Descendants<'world, T, F> {
+        let mut stack = Vec::new();
+        if let Some(children) = self.world().get::<Children>(root) {
+            stack.extend(children.iter().rev());
+        }
+
+        Descendants {
+            query: self,
+            stack,
+            visited: HashSet::from([root]),
+        }
+    }
+
+    /// Iterates over the items of all ancestors of `entity` matched by the query, starting with
+    /// its parent. The entity itself is not included.
+    pub fn iter_ancestors(self, entity: Entity) -> Ancestors<'world, T, F> {
+        let next = self.world().get::<Parent>(entity).map(Parent::get);
+        Ancestors {
+            query: self,
+            next,
+            visited: HashSet::new(),
+        }
+    }
+}
+
+/// An iterator over the descendants of an entity, see [`Query::iter_descendants`].
+pub struct Descendants<'world, T: Queryable, F: QueryFilter = ()> {
+    query: Query<'world, T, F>,
+    stack: Vec<Entity>,
+    /// The entities visited so far, including the root, which are skipped if in place changes to
+    /// [`Parent`] or [`Children`] formed a cycle.
+    visited: HashSet<Entity>,
+}
+
+impl<'world, T: Queryable, F: QueryFilter> Iterator for Descendants<'world, T, F> {
+    type Item = T::Item<'world>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.stack.pop() {
+            if !self.visited.insert(entity) {
+                continue;
+            }
+            if let Some(children) = self.query.world().get::<Children>(entity) {
+                self.stack.extend(children.iter().rev());
+            }
+            // SAFETY: Visited entities are skipped, so no item is returned twice.
+            if let Some(item) = unsafe { self.query.get_matched(entity) } {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the ancestors of an entity, see [`Query::iter_ancestors`].
+pub struct Ancestors<'world, T: Queryable, F: QueryFilter = ()> {
+    query: Query<'world, T, F>,
+    next: Option<Entity>,
+    /// The entities visited so far, which end the iteration if in place changes to [`Parent`]
+    /// formed a cycle.
+    visited: HashSet<Entity>,
+}
+
+impl<'world, T: Queryable, F: QueryFilter> Iterator for Ancestors<'world, T, F> {
+    type Item = T::Item<'world>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.next {
+            if !self.visited.insert(entity) {
+                self.next = None;
+                return None;
+            }
+            self.next = self.query.world().get::<Parent>(entity).map(Parent::get);
+            // SAFETY: The iteration ends at the first visited entity, so no item is returned
+            // twice.
+            if let Some(item) = unsafe { self.query.get_matched(entity) } {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{component::Component, entity::Entity, World};
+
+    use super::{Children, DespawnPolicy, Parent};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Marker;
+    impl Component for Marker {}
+
+    fn children(world: &World, entity: Entity) -> Vec<Entity> {
+        world
+            .get::<Children>(entity)
+            .map_or_else(Vec::new, |children| children.iter().collect())
+    }
+
+    #[test]
+    fn set_parent() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        let c = world.spawn(Name("c"));
+
+        world.set_parent(b, a);
+        world.set_parent(c, a);
+        assert_eq!(world.get::<Parent>(b).map(Parent::get), Some(a));
+        assert_eq!(children(&world, a), vec![b, c]);
+
+        world.set_parent(c, b);
+        assert_eq!(children(&world, a), vec![b]);
+        assert_eq!(children(&world, b), vec![c]);
+
+        world.remove_parent(b);
+        assert_eq!(world.get::<Parent>(b), None);
+        assert_eq!(world.get::<Children>(a), None);
+    }
+
+    #[test]
+    fn add_and_remove_children() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        let c = world.spawn(Name("c"));
+        let d = world.spawn(Name("d"));
+
+        world.add_child(a, b);
+        world.add_child(a, c);
+        world.add_child(b, d);
+        assert_eq!(children(&world, a), vec![b, c]);
+
+        world.remove_children(a, &[c, d]);
+        assert_eq!(children(&world, a), vec![b]);
+        assert_eq!(world.get::<Parent>(c), None);
+        assert_eq!(world.get::<Parent>(d).map(Parent::get), Some(b));
+    }
+
+    #[test]
+    fn despawn_recursive() {
+        let mut world = World::new();
+        let root = world.spawn(Name("root"));
+        let parent = world.spawn(Name("parent"));
+        let child = world.spawn(Name("child"));
+        let sibling = world.spawn(Name("sibling"));
+        let outside = world.spawn(Name("outside"));
+        world.set_parent(parent, root);
+        world.set_parent_with(child, parent, DespawnPolicy::Reparent);
+        world.set_parent(sibling, parent);
+        world.set_parent(outside, child);
+        world.remove_parent(outside);
+        world.set_parent(root, outside);
+
+        world.despawn_recursive(root);
+        for entity in [root, parent, child, sibling] {
+            assert!(world.get_entity(entity).is_none());
+        }
+        assert!(world.get_entity(outside).is_some());
+        assert_eq!(world.get::<Children>(outside), None);
+        assert_eq!(world.query::<&Name>().count(), 1);
+    }
+
+    #[test]
+    fn despawn_updates_hierarchy() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        let c = world.spawn(Name("c"));
+        world.set_parent(b, a);
+        world.set_parent(c, a);
+
+        world.despawn(b);
+        assert_eq!(children(&world, a), vec![c]);
+
+        world.despawn(a);
+        assert_eq!(world.get::<Parent>(c), None);
+    }
+
+    #[test]
+    fn despawn_policy() {
+        let mut world = World::new();
+        let root = world.spawn(Name("root"));
+        let parent = world.spawn(Name("parent"));
+        let despawned = world.spawn(Name("despawned"));
+        let grandchild = world.spawn(Name("grandchild"));
+        let orphaned = world.spawn(Name("orphaned"));
+        let reparented = world.spawn(Name("reparented"));
+        world.set_parent(parent, root);
+        world.set_parent_with(despawned, parent, DespawnPolicy::Despawn);
+        world.set_parent_with(grandchild, despawned, DespawnPolicy::Despawn);
+        world.set_parent_with(orphaned, parent, DespawnPolicy::Orphan);
+        world.set_parent_with(reparented, parent, DespawnPolicy::Reparent);
+
+        world.despawn(parent);
+        assert!(world.get_entity(despawned).is_none());
+        assert!(world.get_entity(grandchild).is_none());
+        assert_eq!(world.get::<Parent>(orphaned), None);
+        assert_eq!(world.get::<Parent>(reparented).map(Parent::get), Some(root));
+        assert_eq!(
+            world.get::<Parent>(reparented).map(Parent::policy),
+            Some(DespawnPolicy::Reparent)
+        );
+        assert_eq!(children(&world, root), vec![reparented]);
+
+        // Without a grandparent the child is orphaned
+        world.despawn(root);
+        assert_eq!(world.get::<Parent>(reparented), None);
+        assert!(world.get_entity(reparented).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "would create a cycle")]
+    fn parent_cycle() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        world.set_parent(b, a);
+        world.set_parent(a, b);
+    }
+
+    #[test]
+    fn reject_inserted_cycle() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        let c = world.spawn(Name("c"));
+        world.set_parent(b, a);
+        world.set_parent(c, b);
+
+        world.insert(
+            a,
+            Parent {
+                parent: c,
+                policy: DespawnPolicy::Orphan,
+            },
+        );
+        assert_eq!(world.get::<Parent>(a), None);
+        assert_eq!(children(&world, c), vec![]);
+        assert_eq!(children(&world, a), vec![b]);
+    }
+
+    #[test]
+    fn iterate_cycle() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        world.set_parent(b, a);
+        // In place changes bypass the hooks
+        world.get_mut::<Parent>(b).unwrap().parent = b;
+        world.insert(a, Children(vec![b, a]));
+
+        let names = world
+            .query_mut::<&mut Name>()
+            .iter_ancestors(b)
+            .map(|name| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b"]);
+
+        let names = world
+            .query_mut::<&mut Name>()
+            .iter_descendants(a)
+            .map(|name| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b"]);
+    }
+
+    #[test]
+    fn despawn_recursive_cycle() {
+        let mut world = World::new();
+        let a = world.spawn(Name("a"));
+        let b = world.spawn(Name("b"));
+        world.set_parent(b, a);
+        world.get_mut::<Parent>(b).unwrap().parent = b;
+        world.insert(a, Children(vec![b, a]));
+
+        world.despawn_recursive(a);
+        assert!(!world.contains(a));
+        assert!(!world.contains(b));
+    }
+
+    #[test]
+    fn descendants_and_ancestors() {
+        let mut world = World::new();
+        let root = world.spawn(Name("root"));
+        let a = world.spawn((Name("a"), Marker));
+        let b = world.spawn(Name("b"));
+        let a0 = world.spawn((Name("a0"), Marker));
+        let b0 = world.spawn((Name("b0"), Marker));
+        world.set_parent(a, root);
+        world.set_parent(b, root);
+        world.set_parent(a0, a);
+        world.set_parent(b0, b);
+
+        let names = world
+            .query::<&Name>()
+            .iter_descendants(root)
+            .map(|name| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "a0", "b", "b0"]);
+
+        // Unmatched entities are skipped, but their children are visited
+        let names = world
+            .query::<(&Name, &Marker)>()
+            .iter_descendants(root)
+            .map(|(name, _)| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "a0", "b0"]);
+
+        let names = world
+            .query::<&Name>()
+            .iter_ancestors(a0)
+            .map(|name| name.0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "root"]);
+
+        for name in world.query_mut::<&mut Name>().iter_descendants(b) {
+            name.0 = "changed";
+        }
+        assert_eq!(world.get::<Name>(b0), Some(&Name("changed")));
+    }
+}
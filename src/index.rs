@@ -0,0 +1,574 @@
+use std::{
+    any::{type_name, Any},
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+};
+
+use crate::{
+    component::{Bundle, Component, ComponentId},
+    entity::Entity,
+    ptr::Ptr,
+    query::IncludeDisabled,
+    small_vec::IdList,
+    DeferredWorld, World,
+};
+
+/// A secondary index mapping a key extracted from the component `C` to all entities holding a
+/// component with that key.
+///
+/// Indexes are kept up to date through the `on_insert`, `on_replace` and `on_remove` hooks of
+/// `C`, which means a component can't have an index and user defined hooks at the same time.
+/// Changes made in place through [`World::get_mut`](crate::World::get_mut) or mutable queries
+/// are not tracked, use [`World::modify_component`](crate::World::modify_component) or insert
+/// the component again to update its key.
+pub struct Index<C, K> {
+    extract: fn(&C) -> K,
+    entities: HashMap<K, Vec<Entity>>,
+    unique: Option<UniquePolicy>,
+}
+
+/// How a unique [`Index`] resolves a second entity receiving a key that is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniquePolicy {
+    /// Panic, as the duplicate is considered a bug. Spawns and inserts check the key before the
+    /// component is written, so the world is left unchanged.
+    Panic,
+    /// Remove the component from the entity that previously held the key.
+    Replace,
+}
+
+impl<C, K: Hash + Eq> Index<C, K> {
+    fn new(extract: fn(&C) -> K, unique: Option<UniquePolicy>) -> Self {
+        Self {
+            extract,
+            entities: HashMap::new(),
+            unique,
+        }
+    }
+
+    /// Returns all entities whose component currently maps to `key`.
+    pub fn get(&self, key: &K) -> &[Entity] {
+        self.entities.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entities.contains_key(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entities.keys()
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique.is_some()
+    }
+
+    /// Adds the entity under the key of the component and returns the previous holder of the key
+    /// together with the policy resolving the duplicate, if this is a unique index.
+    fn add(&mut self, entity: Entity, component: &C) -> Option<(Entity, UniquePolicy)> {
+        let entities = self.entities.entry((self.extract)(component)).or_default();
+        let previous = self.unique.zip(entities.first().copied());
+        entities.push(entity);
+
+        previous.map(|(policy, previous)| (previous, policy))
+    }
+
+    /// Returns the holder of the key of the component if it is not `entity` and this is a unique
+    /// index with [`UniquePolicy::Panic`].
+    fn taken(&self, entity: Option<Entity>, component: &C) -> Option<Entity> {
+        if self.unique != Some(UniquePolicy::Panic) {
+            return None;
+        }
+        self.get(&(self.extract)(component))
+            .first()
+            .copied()
+            .filter(|holder| Some(*holder) != entity)
+    }
+
+    fn remove(&mut self, entity: Entity, component: &C) {
+        let key = (self.extract)(component);
+        if let Some(entities) = self.entities.get_mut(&key) {
+            if let Some(index) = entities.iter().position(|e| *e == entity) {
+                entities.swap_remove(index);
+            }
+            if entities.is_empty() {
+                self.entities.remove(&key);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
+
+impl<C, K: fmt::Debug> fmt::Debug for Index<C, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Index")
+            .field("entities", &self.entities)
+            .field("unique", &self.unique)
+            .finish()
+    }
+}
+
+/// Type erased access to all indexes of the component `C`, regardless of their key type.
+trait ComponentIndex<C>: Send + Sync {
+    fn add(&mut self, entity: Entity, component: &C) -> Option<(Entity, UniquePolicy)>;
+    fn taken(&self, entity: Option<Entity>, component: &C) -> Option<Entity>;
+    fn remove(&mut self, entity: Entity, component: &C);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<C: Component, K: Hash + Eq + Send + Sync + 'static> ComponentIndex<C> for Index<C, K> {
+    fn add(&mut self, entity: Entity, component: &C) -> Option<(Entity, UniquePolicy)> {
+        Index::add(self, entity, component)
+    }
+
+    fn taken(&self, entity: Option<Entity>, component: &C) -> Option<Entity> {
+        Index::taken(self, entity, component)
+    }
+
+    fn remove(&mut self, entity: Entity, component: &C) {
+        Index::remove(self, entity, component);
+    }
+
+    fn clear(&mut self) {
+        Index::clear(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+type ComponentIndexes<C> = Vec<Box<dyn ComponentIndex<C>>>;
+
+/// Type erased access to the [`ComponentIndexes`] of any component.
+trait ErasedIndexes: Any + Send + Sync {
+    fn rebuild(&mut self, world: &World);
+
+    /// See [`Index::taken`].
+    ///
+    /// Safety: `component` must point to a `C`.
+    unsafe fn taken(&self, entity: Option<Entity>, component: Ptr<'_>) -> Option<Entity>;
+}
+
+impl<C: Component> ErasedIndexes for ComponentIndexes<C> {
+    unsafe fn taken(&self, entity: Option<Entity>, component: Ptr<'_>) -> Option<Entity> {
+        let component = component.deref::<C>();
+        self.iter().find_map(|index| index.taken(entity, component))
+    }
+
+    fn rebuild(&mut self, world: &World) {
+        for index in self.iter_mut() {
+            index.clear();
+        }
+        for (entity, component) in world.query_filtered::<(Entity, &C), IncludeDisabled>() {
+            for index in self.iter_mut() {
+                // The indexed entities were unique before they were rebuilt
+                index.add(entity, component);
+            }
+        }
+    }
+}
+
+/// Storage of all [`Index`]es of a world, grouped by the indexed component.
+#[derive(Default)]
+pub(crate) struct Indexes {
+    // Every value is a `ComponentIndexes<C>` for the component `C` of the key
+    indexes: HashMap<ComponentId, Box<dyn ErasedIndexes>>,
+    /// The components with a unique index using [`UniquePolicy::Panic`], whose keys are checked
+    /// before they are written.
+    checked: IdList,
+}
+
+impl Indexes {
+    /// Adds a new index and returns `true` if it is the first index of the component.
+    ///
+    /// Does nothing if an index for the same component and key type already exists.
+    pub(crate) fn insert<C: Component, K: Hash + Eq + Send + Sync + 'static>(
+        &mut self,
+        id: ComponentId,
+        index: Index<C, K>,
+    ) -> bool {
+        let is_first = !self.indexes.contains_key(&id);
+        if index.unique == Some(UniquePolicy::Panic) && !self.checked.contains(&id) {
+            self.checked.push(id);
+        }
+        let indexes = (self
+            .indexes
+            .entry(id)
+            .or_insert_with(|| Box::new(ComponentIndexes::<C>::new()))
+            .as_mut() as &mut dyn Any)
+            .downcast_mut::<ComponentIndexes<C>>()
+            .expect("indexes must be stored under the id of their component");
+
+        if !indexes
+            .iter()
+            .any(|index| index.as_any().is::<Index<C, K>>())
+        {
+            indexes.push(Box::new(index));
+        }
+
+        is_first
+    }
+
+    pub(crate) fn get<C: Component, K: Hash + Eq + Send + Sync + 'static>(
+        &self,
+        id: ComponentId,
+    ) -> Option<&Index<C, K>> {
+        (self.indexes.get(&id)?.as_ref() as &dyn Any)
+            .downcast_ref::<ComponentIndexes<C>>()?
+            .iter()
+            .find_map(|index| index.as_any().downcast_ref::<Index<C, K>>())
+    }
+
+    /// Returns `true` if one of the components has a unique index with [`UniquePolicy::Panic`].
+    pub(crate) fn checks(&self, ids: &[ComponentId]) -> bool {
+        ids.iter().any(|id| self.checked.contains(id))
+    }
+
+    /// Returns the holder of the key of the component with the id if it is not `entity` and the
+    /// component has a unique index with [`UniquePolicy::Panic`].
+    ///
+    /// Safety: `component` must point to a value of the component with the id.
+    unsafe fn taken(
+        &self,
+        id: ComponentId,
+        entity: Option<Entity>,
+        component: Ptr<'_>,
+    ) -> Option<Entity> {
+        self.indexes.get(&id)?.taken(entity, component)
+    }
+
+    fn for_each<C: Component>(
+        &mut self,
+        id: ComponentId,
+        mut f: impl FnMut(&mut dyn ComponentIndex<C>),
+    ) {
+        if let Some(indexes) = self.indexes.get_mut(&id).and_then(|indexes| {
+            (indexes.as_mut() as &mut dyn Any).downcast_mut::<ComponentIndexes<C>>()
+        }) {
+            for index in indexes {
+                f(index.as_mut());
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Indexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Indexes")
+            .field("components", &self.indexes.keys())
+            .finish()
+    }
+}
+
+/// Creates the index, fills it with all existing entities holding `C` and installs the hooks
+/// keeping it up to date.
+pub(crate) fn init<C: Component, K: Hash + Eq + Send + Sync + 'static>(
+    world: &mut World,
+    extract: fn(&C) -> K,
+    unique: Option<UniquePolicy>,
+) {
+    let id = world.components.register_component::<C>();
+    if world.indexes.get::<C, K>(id).is_some() {
+        return;
+    }
+
+    let mut index = Index::new(extract, unique);
+    let mut replaced = Vec::new();
+    for (entity, component) in world.query_filtered::<(Entity, &C), IncludeDisabled>() {
+        match index.add(entity, component) {
+            Some((previous, UniquePolicy::Panic)) => {
+                duplicate(type_name::<C>(), Some(entity), previous)
+            }
+            Some((previous, UniquePolicy::Replace)) => replaced.push(previous),
+            None => {}
+        }
+    }
+
+    if world.indexes.insert(id, index) {
+        world
+            .register_component_hooks::<C>()
+            .on_insert(on_insert::<C>)
+            .on_replace(on_remove::<C>)
+            .on_remove(on_remove::<C>);
+    }
+
+    for entity in replaced {
+        world.remove_by_ids(entity, &[id]);
+    }
+}
+
+/// Refills every index from the components currently stored in the world, e.g. after entity ids
+/// and the references in components changed.
+pub(crate) fn rebuild(world: &mut World) {
+    let mut indexes = std::mem::take(&mut world.indexes);
+    for index in indexes.indexes.values_mut() {
+        index.rebuild(world);
+    }
+    world.indexes = indexes;
+}
+
+/// Panics on a duplicate key of a unique index with [`UniquePolicy::Panic`]. `entity` is `None`
+/// for entities that are about to be spawned.
+fn duplicate(name: &str, entity: Option<Entity>, previous: Entity) -> ! {
+    match entity {
+        Some(entity) => panic!(
+            "Tried to give {entity:?} a value of the unique component {name} already held by {previous:?}",
+        ),
+        None => panic!(
+            "Tried to spawn an entity with a value of the unique component {name} already held by {previous:?}",
+        ),
+    }
+}
+
+impl World {
+    /// Panics if a component of the bundle has a key of a unique index with
+    /// [`UniquePolicy::Panic`] that is held by an entity other than `entity`, which is `None` for
+    /// entities that are about to be spawned. Called before anything is written, so the world is
+    /// left unchanged.
+    pub(crate) fn check_unique<B: Bundle>(
+        &self,
+        entity: Option<Entity>,
+        bundle_ids: &[ComponentId],
+        bundle: &B,
+    ) {
+        if !self.indexes.checks(bundle_ids) {
+            return;
+        }
+        let mut ids = bundle_ids.iter();
+        bundle.get_component_ptrs(&mut |ptr| {
+            let id = *ids.next().expect("bundles pass a component for every id");
+            // SAFETY: Bundles pass their components in the order of their ids.
+            unsafe { self.check_unique_by_id(entity, id, ptr) };
+        });
+    }
+
+    /// Like [`World::check_unique`] for a single component.
+    ///
+    /// Safety: `component` must point to a value of the component with the id.
+    pub(crate) unsafe fn check_unique_by_id(
+        &self,
+        entity: Option<Entity>,
+        id: ComponentId,
+        component: Ptr<'_>,
+    ) {
+        if let Some(previous) = self.indexes.taken(id, entity, component) {
+            let name = self.components.name(id).unwrap_or_default();
+            duplicate(name, entity, previous);
+        }
+    }
+}
+
+fn on_insert<C: Component>(mut world: DeferredWorld, entity: Entity, id: ComponentId) {
+    let mut duplicates = Vec::new();
+    update::<C>(&mut world, entity, id, |index, entity, component| {
+        duplicates.extend(index.add(entity, component));
+    });
+
+    // The indexes already list the entity, so they still match the stored components if the
+    // panic is caught
+    for (previous, policy) in duplicates {
+        match policy {
+            UniquePolicy::Panic => duplicate(type_name::<C>(), Some(entity), previous),
+            UniquePolicy::Replace => {
+                world.queue(move |world: &mut World| world.remove_by_ids(previous, &[id]));
+            }
+        }
+    }
+}
+
+fn on_remove<C: Component>(mut world: DeferredWorld, entity: Entity, id: ComponentId) {
+    update::<C>(&mut world, entity, id, |index, entity, component| {
+        index.remove(entity, component)
+    });
+}
+
+fn update<C: Component>(
+    world: &mut DeferredWorld,
+    entity: Entity,
+    id: ComponentId,
+    mut f: impl FnMut(&mut dyn ComponentIndex<C>, Entity, &C),
+) {
+    // Take the indexes out of the world to read the component while updating them
+    let mut indexes = std::mem::take(&mut world.world.indexes);
+    if let Some(component) = world.get::<C>(entity) {
+        indexes.for_each::<C>(id, |index| f(index, entity, component));
+    }
+    world.world.indexes = indexes;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use crate::{component::Component, World};
+
+    use super::UniquePolicy;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct Team(u32);
+    impl Component for Team {}
+
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[test]
+    fn index() {
+        let mut world = World::new();
+        let e0 = world.spawn(Team(0));
+        world.init_index::<Team, u32>(|team| team.0);
+
+        let e1 = world.spawn(Team(1));
+        let e2 = world.spawn((Team(1), Name("e2")));
+
+        let index = world.index::<Team, u32>();
+        assert_eq!(index.get(&0), &[e0]);
+        assert_eq!(index.get(&1), &[e1, e2]);
+        assert_eq!(index.get(&2), &[]);
+
+        world.insert(e1, Team(2));
+        let index = world.index::<Team, u32>();
+        assert_eq!(index.get(&1), &[e2]);
+        assert_eq!(index.get(&2), &[e1]);
+
+        world.insert(e0, Name("e0"));
+        assert_eq!(world.index::<Team, u32>().get(&0), &[e0]);
+
+        world.despawn(e0);
+        let index = world.index::<Team, u32>();
+        assert_eq!(index.get(&0), &[]);
+        assert!(!index.contains_key(&0));
+    }
+
+    #[test]
+    fn modify_indexed() {
+        let mut world = World::new();
+        world.init_index::<Team, u32>(|team| team.0);
+        let e0 = world.spawn(Team(0));
+
+        assert_eq!(
+            world.modify_component::<Team, _>(e0, |team| team.0 = 3),
+            Some(())
+        );
+        let index = world.index::<Team, u32>();
+        assert_eq!(index.get(&0), &[]);
+        assert_eq!(index.get(&3), &[e0]);
+    }
+
+    #[test]
+    fn multiple_indexes() {
+        let mut world = World::new();
+        world.init_index::<Team, u32>(|team| team.0);
+        world.init_index::<Team, bool>(|team| team.0 % 2 == 0);
+
+        let e0 = world.spawn(Team(0));
+        let e1 = world.spawn(Team(1));
+        let e2 = world.spawn(Team(2));
+
+        assert_eq!(world.index::<Team, u32>().get(&2), &[e2]);
+        assert_eq!(world.index::<Team, bool>().get(&true), &[e0, e2]);
+        assert_eq!(world.index::<Team, bool>().get(&false), &[e1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_index() {
+        let mut world = World::new();
+        world.spawn(Team(0));
+
+        let _ = world.index::<Team, u32>();
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct PlayerId(u32);
+    impl Component for PlayerId {}
+
+    #[test]
+    fn unique_replace() {
+        let mut world = World::new();
+        world.init_unique_index::<PlayerId, u32>(|id| id.0, UniquePolicy::Replace);
+
+        let e0 = world.spawn((PlayerId(0), Name("e0")));
+        let e1 = world.spawn(PlayerId(1));
+        assert!(world.index::<PlayerId, u32>().is_unique());
+
+        let e2 = world.spawn(PlayerId(0));
+        assert_eq!(world.get::<PlayerId>(e0), None);
+        assert_eq!(world.get::<Name>(e0), Some(&Name("e0")));
+        assert_eq!(world.get::<PlayerId>(e2), Some(&PlayerId(0)));
+        assert_eq!(world.index::<PlayerId, u32>().get(&0), &[e2]);
+
+        world.insert(e1, PlayerId(0));
+        assert_eq!(world.get::<PlayerId>(e2), None);
+        assert_eq!(world.index::<PlayerId, u32>().get(&0), &[e1]);
+        assert!(!world.index::<PlayerId, u32>().contains_key(&1));
+
+        // Re-inserting the same value keeps the holder
+        world.insert(e1, PlayerId(0));
+        assert_eq!(world.get::<PlayerId>(e1), Some(&PlayerId(0)));
+        assert_eq!(world.index::<PlayerId, u32>().get(&0), &[e1]);
+    }
+
+    #[test]
+    fn unique_existing_duplicates() {
+        let mut world = World::new();
+        let e0 = world.spawn(PlayerId(0));
+        let e1 = world.spawn(PlayerId(0));
+
+        world.init_unique_index::<PlayerId, u32>(|id| id.0, UniquePolicy::Replace);
+
+        assert_eq!(world.get::<PlayerId>(e0), None);
+        assert_eq!(world.get::<PlayerId>(e1), Some(&PlayerId(0)));
+        assert_eq!(world.index::<PlayerId, u32>().get(&0), &[e1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already held by")]
+    fn unique_panic() {
+        let mut world = World::new();
+        world.init_unique_index::<PlayerId, u32>(|id| id.0, UniquePolicy::Panic);
+
+        world.spawn(PlayerId(0));
+        world.spawn(PlayerId(0));
+    }
+
+    #[test]
+    fn unique_panic_leaves_world_unchanged() {
+        let mut world = World::new();
+        world.init_unique_index::<PlayerId, u32>(|id| id.0, UniquePolicy::Panic);
+        let e0 = world.spawn(PlayerId(0));
+        let e1 = world.spawn((PlayerId(1), Name("e1")));
+
+        // Re-inserting the held key is not a duplicate
+        world.insert(e0, PlayerId(0));
+
+        let mut attempt = |f: &dyn Fn(&mut World)| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut world)));
+            assert!(result.is_err());
+        };
+        attempt(&|world| {
+            world.spawn((Name("e2"), PlayerId(0)));
+        });
+        attempt(&|world| world.insert(e1, (Name("renamed"), PlayerId(0))));
+        attempt(&|world| world.insert_batch([(e1, PlayerId(0))]));
+        attempt(&|world| {
+            world.spawn_batch([PlayerId(2), PlayerId(1)]);
+        });
+
+        let index = world.index::<PlayerId, u32>();
+        assert_eq!(index.get(&0), &[e0]);
+        assert_eq!(index.get(&1), &[e1]);
+        assert_eq!(world.get::<PlayerId>(e1), Some(&PlayerId(1)));
+        assert_eq!(world.get::<Name>(e1), Some(&Name("e1")));
+        assert_eq!(world.query::<&Name>().count(), 1);
+        // Bundles spawned before the duplicate in a batch are kept
+        assert_eq!(index.get(&2).len(), 1);
+        assert_eq!(world.query::<&PlayerId>().count(), 3);
+    }
+}
@@ -1,265 +1,3035 @@
-#![feature(alloc_layout_extra)]
 #![allow(unused)]
+// Lets the derive macros refer to `::quartz` from inside this crate
+extern crate self as quartz;
+
 pub mod archetype;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod audit;
+pub mod builder;
+pub mod bundle;
+pub mod cached_query;
+pub mod command;
 pub mod component;
 pub mod entity;
+pub mod event;
+pub mod hierarchy;
+pub mod index;
+pub mod map_entities;
+pub mod observer;
+pub mod persistent;
 pub mod ptr;
 pub mod query;
+pub mod reflect;
+#[cfg(feature = "serde")]
+pub mod replay;
+pub mod resource;
+#[cfg(feature = "serde")]
+pub mod scene;
+pub mod schedule;
+mod small_vec;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod state;
 pub mod storage;
+pub mod system;
+pub mod time;
+pub mod trait_query;
+pub mod variant;
+
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, Scope, ScopedJoinHandle},
+    time::Duration,
+};
 
-use archetype::Archetypes;
-use component::{Bundle, Component, ComponentId, Components};
-use entity::{Entities, Entity, EntityLocation};
-use query::{Query, Queryable};
-use storage::Tables;
+use archetype::{ArchetypeCreated, ArchetypeId, Archetypes};
+use audit::{AuditLog, StructuralChange};
+use builder::WorldBuilder;
+use bundle::Bundles;
+use command::{Command, CommandQueue};
+use component::{
+    Bundle, Component, ComponentDescriptor, ComponentHook, ComponentHooks, ComponentId,
+    ComponentTicks, Components, Disabled, SharedComponentIds, StorageType, Tick,
+};
+use entity::{Entities, Entity, EntityLocation, EntityRef, EntityWorldMut, GetManyMutError};
+use index::{Index, Indexes, UniquePolicy};
+use persistent::PersistentId;
+use ptr::{MutPtr, OwningPtr, Ptr};
+use query::{Access, Query, QueryFilter, Queryable, ReadOnlyQueryable};
+use small_vec::IdList;
+use storage::{
+    ColumnView, RemovalMode, SparseSets, TableId, Tables, TakenComponent, TryReserveError,
+};
+use time::Time;
 
+/// The container of all entities and their components.
+///
+/// The world is `Send + Sync`: components, hooks and commands are required to be thread safe, so
+/// a `&World` can be shared between threads. A shared reference only allows reading, e.g. through
+/// [`World::get`] and [`World::query`], while every mutation requires a `&mut World`. To read the
+/// world from multiple threads at once, see [`World::par_scope`].
 #[derive(Debug)]
 pub struct World {
+    id: WorldId,
     entities: Entities,
     archetypes: Archetypes,
     components: Components,
     tables: Tables,
+    sparse_sets: SparseSets,
+    removal_mode: RemovalMode,
+    indexes: Indexes,
+    command_queue: CommandQueue,
+    bundles: Bundles,
+    entity_refs: map_entities::EntityRefs,
+    trait_impls: trait_query::TraitImpls,
+    resources: resource::Resources,
+    non_send_resources: resource::NonSendResources,
+    observers: observer::Observers,
+    tick: Tick,
+    audit_log: Option<AuditLog>,
+    #[cfg(feature = "serde")]
+    replay_registry: replay::ReplayRegistry,
+    #[cfg(feature = "serde")]
+    recording: Option<replay::Recording>,
 }
 
 impl World {
     pub fn new() -> Self {
-        Self {
+        let mut world = Self {
+            id: WorldId::new(),
             entities: Entities::new(),
             archetypes: Archetypes::default(),
             components: Components::new(),
             tables: Tables::default(),
+            sparse_sets: SparseSets::default(),
+            removal_mode: RemovalMode::default(),
+            indexes: Indexes::default(),
+            command_queue: CommandQueue::default(),
+            bundles: Bundles::default(),
+            entity_refs: map_entities::EntityRefs::default(),
+            trait_impls: trait_query::TraitImpls::default(),
+            resources: resource::Resources::default(),
+            non_send_resources: resource::NonSendResources::default(),
+            observers: observer::Observers::default(),
+            tick: Tick::default(),
+            audit_log: None,
+            #[cfg(feature = "serde")]
+            replay_registry: replay::ReplayRegistry::default(),
+            #[cfg(feature = "serde")]
+            recording: None,
+        };
+        world.insert_resource(Time::default());
+        world
+    }
+
+    /// Configures a world before creating it, e.g. to pre-size its storage.
+    ///
+    /// ```
+    /// # use quartz::World;
+    /// let world = World::builder()
+    ///     .entities_capacity(1_000_000)
+    ///     .table_capacity_hint(1024)
+    ///     .build();
+    /// ```
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder::new()
+    }
+
+    /// Creates a world that takes its [`ComponentId`]s from `shared`, so it agrees on them with
+    /// every other world created with the same ids, e.g. [`SharedComponentIds::global`].
+    pub fn with_shared_component_ids(shared: SharedComponentIds) -> Self {
+        Self {
+            components: Components::with_shared_ids(shared),
+            ..Self::new()
         }
     }
 
     pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
-        self.entities
-            .alloc(|entity| {
-                let mut component_ids = Vec::new();
-                B::component_ids(&mut self.components, &mut |id| {
-                    component_ids.push(id);
+        self.spawn_in(None, bundle)
+    }
+
+    /// Spawns an entity without components, which lives in the empty archetype until components
+    /// are inserted.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::new();
+    /// let entity = world.spawn_empty().insert(Health(10)).id();
+    /// assert_eq!(world.get::<Health>(entity).unwrap().0, 10);
+    /// ```
+    pub fn spawn_empty(&mut self) -> EntityWorldMut<'_> {
+        let entity = self.spawn(());
+        let location = *self
+            .entities
+            .get(entity)
+            .expect("spawned entities are alive");
+        EntityWorldMut::new(self, entity, location)
+    }
+
+    /// Spawns the bundle into the `reserved` entity, or into a new one if `None`.
+    ///
+    /// Panics: If the entity is not reserved, see [`Commands::spawn`](command::Commands::spawn).
+    fn spawn_in<B: Bundle>(&mut self, reserved: Option<Entity>, bundle: B) -> Entity {
+        let bundle_id = self.bundles.register_spawn::<B>(
+            &mut self.components,
+            &mut self.tables,
+            &mut self.archetypes,
+        );
+        let info = self.bundles.get_unchecked(bundle_id);
+        let target = info
+            .spawn_target()
+            .expect("spawned bundles must have a spawn target");
+        let component_ids = info.component_ids().clone();
+        let ticks = ComponentTicks::new(self.tick);
+        self.check_unique(reserved, &component_ids, &bundle);
+        self.sparse_sets
+            .init(target.sparse.iter().flatten().copied(), &self.components);
+
+        let place = |entity| {
+            Ok(target.write(
+                &mut self.tables,
+                &mut self.archetypes,
+                &mut self.sparse_sets,
+                entity,
+                bundle,
+                ticks,
+            ))
+        };
+        let entity = match reserved {
+            Some(entity) => self
+                .entities
+                .alloc_reserved(entity, place)
+                .unwrap_or_else(|_| panic!("Tried to spawn {entity:?}, which is not reserved")),
+            None => self
+                .entities
+                .alloc(place)
+                .expect("entity allocation should not fail"),
+        };
+
+        self.record(StructuralChange::Spawn, entity, &component_ids);
+        self.trigger_hooks(entity, &component_ids, |hooks| hooks.on_add);
+        self.trigger_hooks(entity, &component_ids, |hooks| hooks.on_insert);
+        self.flush();
+
+        entity
+    }
+
+    /// Reserves an entity without mutable access to the world, see [`Entities::reserve_entity`].
+    ///
+    /// The entity is spawned by [`World::flush_entities`], or by [`Commands::spawn`] for
+    /// entities it reserved.
+    ///
+    /// [`Commands::spawn`]: command::Commands::spawn
+    pub fn reserve_entity(&self) -> Entity {
+        self.entities.reserve_entity()
+    }
+
+    /// Spawns all reserved entities without any components.
+    pub fn flush_entities(&mut self) {
+        let bundle_id = self.bundles.register_spawn::<()>(
+            &mut self.components,
+            &mut self.tables,
+            &mut self.archetypes,
+        );
+        let target = self
+            .bundles
+            .get_unchecked(bundle_id)
+            .spawn_target()
+            .expect("spawned bundles must have a spawn target");
+        let ticks = ComponentTicks::new(self.tick);
+
+        let mut spawned = Vec::new();
+        self.entities.flush(|entity| {
+            spawned.push(entity);
+            target.write(
+                &mut self.tables,
+                &mut self.archetypes,
+                &mut self.sparse_sets,
+                entity,
+                (),
+                ticks,
+            )
+        });
+        for entity in spawned {
+            self.record(StructuralChange::Spawn, entity, &[]);
+        }
+    }
+
+    /// Like [`World::spawn`], but returns an error instead of panicking or aborting if there
+    /// is no memory left for the entity.
+    ///
+    /// The world is left unchanged if an error is returned, apart from registering the
+    /// components and archetype of the bundle.
+    pub fn try_spawn<B: Bundle>(&mut self, bundle: B) -> Result<Entity, TryReserveError> {
+        let bundle_id = self.bundles.register_spawn::<B>(
+            &mut self.components,
+            &mut self.tables,
+            &mut self.archetypes,
+        );
+        let target = self
+            .bundles
+            .get_unchecked(bundle_id)
+            .spawn_target()
+            .expect("spawned bundles must have a spawn target");
+        let (table_id, archetype_id) = (target.table_id, target.archetype_id);
+        self.try_reserve_in(table_id, archetype_id, 1)?;
+        self.entities.try_reserve(1)?;
+
+        Ok(self.spawn(bundle))
+    }
+
+    /// Spawns an entity for every bundle and returns them in order.
+    ///
+    /// Faster than calling [`World::spawn`] for every bundle, as the table and archetype of the
+    /// bundle are only looked up once and the storage grows up front for the size hint of the
+    /// iterator. The hooks of the components run once all entities are spawned, unless a component
+    /// has a unique [`Index`] with [`UniquePolicy::Panic`], whose keys have to be checked entity by
+    /// entity, so the bundles are spawned one by one.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..100).map(|i| Position(i as f32)));
+    /// assert_eq!(world.get::<Position>(entities[42]).unwrap().0, 42.0);
+    /// ```
+    pub fn spawn_batch<B: Bundle>(&mut self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        let bundles = bundles.into_iter();
+        let bundle_id = self.bundles.register_spawn::<B>(
+            &mut self.components,
+            &mut self.tables,
+            &mut self.archetypes,
+        );
+        let info = self.bundles.get_unchecked(bundle_id);
+        let target = info
+            .spawn_target()
+            .expect("spawned bundles must have a spawn target");
+        let component_ids = info.component_ids().clone();
+        let ticks = ComponentTicks::new(self.tick);
+        if self.indexes.checks(&component_ids) {
+            return bundles.map(|bundle| self.spawn(bundle)).collect();
+        }
+
+        let additional = bundles.size_hint().0;
+        let (table_id, archetype_id) = (target.table_id, target.archetype_id);
+        let reserved = self
+            .try_reserve_in(table_id, archetype_id, additional)
+            .and_then(|()| Ok(self.entities.try_reserve(additional)?));
+        if let Err(err) = reserved {
+            err.handle();
+        }
+        let target = self
+            .bundles
+            .get_unchecked(bundle_id)
+            .spawn_target()
+            .expect("spawned bundles must have a spawn target");
+        self.sparse_sets
+            .init(target.sparse.iter().flatten().copied(), &self.components);
+
+        let mut entities = Vec::with_capacity(additional);
+        for bundle in bundles {
+            let entity = self
+                .entities
+                .alloc(|entity| {
+                    Ok(target.write(
+                        &mut self.tables,
+                        &mut self.archetypes,
+                        &mut self.sparse_sets,
+                        entity,
+                        bundle,
+                        ticks,
+                    ))
+                })
+                .expect("entity allocation should not fail");
+            entities.push(entity);
+        }
+
+        for entity in &entities {
+            self.record(StructuralChange::Spawn, *entity, &component_ids);
+            self.trigger_hooks(*entity, &component_ids, |hooks| hooks.on_add);
+            self.trigger_hooks(*entity, &component_ids, |hooks| hooks.on_insert);
+        }
+        self.flush();
+
+        entities
+    }
+
+    /// Reserves room for `additional` more entities in the table and archetype.
+    fn try_reserve_in(
+        &mut self,
+        table_id: TableId,
+        archetype_id: ArchetypeId,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.tables
+            .get_mut_unchecked(table_id)
+            .try_reserve(additional)?;
+        self.archetypes
+            .get_mut_unchecked(archetype_id)
+            .try_reserve(additional)?;
+
+        Ok(())
+    }
+
+    /// Inserts the components of the bundle into the entity.
+    ///
+    /// Components the entity does not have yet move it to the matching archetype, components it
+    /// already has are overwritten and the previous values are dropped.
+    ///
+    /// Panics: If the entity does not exist.
+    pub fn insert<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        let location = *self
+            .entities
+            .get(entity)
+            .expect("Tried to insert components into an entity that does not exist");
+        self.insert_at(entity, location, bundle);
+    }
+
+    /// Inserts the bundle into the entity at the given location, see [`World::insert`].
+    fn insert_at<B: Bundle>(&mut self, entity: Entity, location: EntityLocation, bundle: B) {
+        let bundle_id = self.bundles.register::<B>(&mut self.components);
+        let bundle_ids = self
+            .bundles
+            .get_unchecked(bundle_id)
+            .component_ids()
+            .clone();
+
+        self.check_unique(Some(entity), &bundle_ids, &bundle);
+        self.insert_by_ids(entity, location, &bundle_ids, |write| {
+            bundle.get_components(&mut |ptr| write(ptr))
+        });
+    }
+
+    /// Like [`World::insert`], but returns an error instead of panicking or aborting if there is
+    /// no memory left to move the entity to its new archetype.
+    ///
+    /// The entity is left unchanged if an error is returned.
+    ///
+    /// Panics: If the entity does not exist.
+    pub fn try_insert<B: Bundle>(
+        &mut self,
+        entity: Entity,
+        bundle: B,
+    ) -> Result<(), TryReserveError> {
+        let location = *self
+            .entities
+            .get(entity)
+            .expect("Tried to insert components into an entity that does not exist");
+
+        let bundle_id = self.bundles.register::<B>(&mut self.components);
+        let archetype = self.archetypes.get_unchecked(location.archetype_id);
+        let bundle_ids = self.bundles.get_unchecked(bundle_id).component_ids();
+        let added = bundle_ids
+            .iter()
+            .filter(|id| !archetype.contains(**id) && !self.is_sparse(**id))
+            .copied()
+            .collect::<IdList>();
+        if !added.is_empty() {
+            let archetype_id = self.insert_target(location.archetype_id, &added);
+            let table_id = self.archetypes.get_unchecked(archetype_id).table_id();
+            self.try_reserve_in(table_id, archetype_id, 1)?;
+        }
+
+        self.insert(entity, bundle);
+        Ok(())
+    }
+
+    /// Inserts the components with the given ids into the entity, which `write` has to pass in
+    /// the same order.
+    fn insert_by_ids(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        bundle_ids: &[ComponentId],
+        write: impl FnOnce(&mut dyn FnMut(OwningPtr<'_>)),
+    ) {
+        self.insert_by_ids_unflushed(entity, location, bundle_ids, write);
+        self.flush();
+    }
+
+    /// Like [`World::insert_by_ids`], but leaves the commands queued by hooks to the caller.
+    fn insert_by_ids_unflushed(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        bundle_ids: &[ComponentId],
+        write: impl FnOnce(&mut dyn FnMut(OwningPtr<'_>)),
+    ) {
+        let (replaced, added) = self.partition_inserted(entity, location, bundle_ids);
+
+        // Hooks can't make structural changes, so the location stays valid
+        self.trigger_hooks(entity, &replaced, |hooks| hooks.on_replace);
+
+        let moved = added
+            .iter()
+            .filter(|id| !self.is_sparse(**id))
+            .copied()
+            .collect::<IdList>();
+        let location = if moved.is_empty() {
+            location
+        } else {
+            let archetype_id = self.insert_target(location.archetype_id, &moved);
+            self.move_entity(entity, location, archetype_id, None)
+        };
+
+        self.write_inserted(entity, location, bundle_ids, &added, write);
+    }
+
+    /// Splits the ids of a bundle inserted into the entity into the components it already has
+    /// and the ones it gains, and creates the sparse sets of the components stored in one.
+    fn partition_inserted(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        bundle_ids: &[ComponentId],
+    ) -> (IdList, IdList) {
+        let archetype = self.archetypes.get_unchecked(location.archetype_id);
+        let partition = bundle_ids
+            .iter()
+            .partition(|id| archetype.contains(**id) || self.has_sparse(entity, **id));
+        let sparse = bundle_ids
+            .iter()
+            .filter(|id| self.is_sparse(**id))
+            .copied()
+            .collect::<IdList>();
+        self.sparse_sets
+            .init(sparse.iter().copied(), &self.components);
+        partition
+    }
+
+    /// Writes the inserted components into the entity, which already lives in the archetype with
+    /// all `added` table components, and runs their `on_add` and `on_insert` hooks.
+    fn write_inserted(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        bundle_ids: &[ComponentId],
+        added: &[ComponentId],
+        write: impl FnOnce(&mut dyn FnMut(OwningPtr<'_>)),
+    ) {
+        let sparse = bundle_ids
+            .iter()
+            .filter(|id| self.is_sparse(**id))
+            .copied()
+            .collect::<IdList>();
+        let table = self.tables.get_mut_unchecked(location.table_id);
+        let sparse_sets = &mut self.sparse_sets;
+        let row = location.table_row.index();
+        let tick = self.tick;
+        let mut ids = bundle_ids.iter().copied();
+        write(&mut |ptr| unsafe {
+            let id = ids.next().unwrap();
+            if sparse.contains(&id) {
+                sparse_sets
+                    .get_mut(id)
+                    .expect("the sparse set of the component was just created")
+                    .insert(entity, ptr, tick);
+                return;
+            }
+            let column = table
+                .get_column_mut(id)
+                .expect("the selected table must have the correct column for this component");
+            if added.contains(&id) {
+                column.initialize_unchecked(row, ptr, ComponentTicks::new(tick));
+            } else {
+                column.replace_unchecked(row, ptr, tick);
+            }
+        });
+
+        self.record(StructuralChange::Insert, entity, bundle_ids);
+        self.trigger_hooks(entity, added, |hooks| hooks.on_add);
+        self.trigger_hooks(entity, bundle_ids, |hooks| hooks.on_insert);
+    }
+
+    /// Inserts every bundle into its entity, like calling [`World::insert`] for each of them.
+    ///
+    /// Faster for many entities, as the entities are grouped by their archetype and every group
+    /// moves to its new archetype at once, column by column. The `on_replace` hooks of a group
+    /// run before it moves, its `on_add` and `on_insert` hooks after its bundles are written, and
+    /// commands queued by them are applied once all bundles are inserted.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct InRegion;
+    ///
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..100).map(|_| ()));
+    /// world.insert_batch(entities.iter().map(|entity| (*entity, InRegion)));
+    /// assert_eq!(world.query::<&InRegion>().count(), 100);
+    /// ```
+    ///
+    /// Panics: If one of the entities does not exist.
+    pub fn insert_batch<B: Bundle>(&mut self, batch: impl IntoIterator<Item = (Entity, B)>) {
+        let bundle_id = self.bundles.register::<B>(&mut self.components);
+        let bundle_ids = self
+            .bundles
+            .get_unchecked(bundle_id)
+            .component_ids()
+            .clone();
+
+        let mut batch = batch
+            .into_iter()
+            .map(|(entity, bundle)| {
+                let location = *self.entities.get(entity).unwrap_or_else(|| {
+                    panic!("Tried to insert components into {entity:?}, which does not exist")
                 });
-                component_ids.sort_unstable();
+                (location, entity, bundle)
+            })
+            .collect::<Vec<_>>();
 
-                let table_id = self
-                    .tables
-                    .get_id_or_insert(&component_ids, &self.components);
+        // Unique keys have to be checked against the bundles inserted before
+        if self.indexes.checks(&bundle_ids) {
+            for (_, entity, bundle) in batch {
+                let location = *self
+                    .entities
+                    .get(entity)
+                    .expect("hooks can't despawn entities before the batch is inserted");
+                self.check_unique(Some(entity), &bundle_ids, &bundle);
+                self.insert_by_ids_unflushed(entity, location, &bundle_ids, |write| {
+                    bundle.get_components(&mut |ptr| write(ptr))
+                });
+            }
+            self.flush();
+            return;
+        }
 
-                let archetype_id =
-                    self.archetypes
-                        .get_id_or_insert(table_id, &component_ids);
+        // The sort is stable, so entities keep their order within their archetype
+        batch.sort_by_key(|(location, _, _)| location.archetype_id);
 
-                let table_row = {
-                    let table = self.tables.get_mut_unchecked(table_id);
-                    let row = table.allocate(entity);
-                    bundle.get(&mut self.components, &mut |id, ptr| unsafe {
-                        table
-                            .get_column_mut(id)
-                            .expect("the selected table must have the correct column for this component")
-                            .initialize_unchecked(row.index(), ptr);
-                    });
-                    row
-                };
+        let mut seen = HashSet::new();
+        let mut repeated = Vec::new();
+        let mut batch = batch.into_iter().peekable();
+        while let Some(&(location, _, _)) = batch.peek() {
+            let source = location.archetype_id;
+            let group = std::iter::from_fn(|| batch.next_if(|(l, _, _)| l.archetype_id == source))
+                .filter_map(|(location, entity, bundle)| {
+                    // An entity listed more than once is inserted into again after its group
+                    if seen.insert(entity) {
+                        Some((location, entity, bundle))
+                    } else {
+                        repeated.push((entity, bundle));
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            self.insert_group(source, &bundle_ids, group);
+        }
+
+        for (entity, bundle) in repeated {
+            let location = *self
+                .entities
+                .get(entity)
+                .expect("hooks can't despawn entities before the batch is inserted");
+            self.insert_by_ids_unflushed(entity, location, &bundle_ids, |write| {
+                bundle.get_components(&mut |ptr| write(ptr))
+            });
+        }
+
+        self.flush();
+    }
+
+    /// Inserts the bundles into the distinct entities of the archetype, moving all of them to
+    /// the target archetype at once, see [`World::insert_batch`].
+    fn insert_group<B: Bundle>(
+        &mut self,
+        source: ArchetypeId,
+        bundle_ids: &[ComponentId],
+        group: Vec<(EntityLocation, Entity, B)>,
+    ) {
+        let archetype = self.archetypes.get_unchecked(source);
+        let moved = bundle_ids
+            .iter()
+            .filter(|id| !archetype.contains(**id) && !self.is_sparse(**id))
+            .copied()
+            .collect::<IdList>();
+
+        let mut added = Vec::with_capacity(group.len());
+        for (location, entity, _) in &group {
+            debug_assert_eq!(self.entities.get(*entity), Some(location));
+            let (replaced, entity_added) = self.partition_inserted(*entity, *location, bundle_ids);
+            // Hooks can't make structural changes, so the locations stay valid
+            self.trigger_hooks(*entity, &replaced, |hooks| hooks.on_replace);
+            added.push(entity_added);
+        }
+
+        let locations = if moved.is_empty() {
+            group.iter().map(|(location, _, _)| *location).collect()
+        } else {
+            let target = self.insert_target(source, &moved);
+            let table_id = self.archetypes.get_unchecked(target).table_id();
+            if let Err(err) = self.try_reserve_in(table_id, target, group.len()) {
+                err.handle();
+            }
+            let entities = group
+                .iter()
+                .map(|(location, entity, _)| (*entity, *location))
+                .collect::<Vec<_>>();
+            self.move_entities(&entities, target)
+        };
+
+        for (((_, entity, bundle), location), added) in group.into_iter().zip(locations).zip(added)
+        {
+            self.write_inserted(entity, location, bundle_ids, &added, |write| {
+                bundle.get_components(&mut |ptr| write(ptr))
+            });
+        }
+    }
+
+    /// Disables the entity, hiding it from queries that don't explicitly include [`Disabled`]
+    /// entities.
+    ///
+    /// Panics: If the entity does not exist.
+    pub fn disable(&mut self, entity: Entity) {
+        self.insert(entity, Disabled);
+    }
+
+    /// Enables a previously disabled entity again.
+    pub fn enable(&mut self, entity: Entity) {
+        if let Some(id) = self.component_id::<Disabled>() {
+            self.remove_by_ids(entity, &[id]);
+        }
+    }
+
+    /// Removes the component from the entity and returns it, moving the entity to the archetype
+    /// without the component unless it is stored in a sparse set.
+    ///
+    /// Returns `None` if the entity doesn't exist or doesn't have the component.
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        let id = self.component_id::<T>()?;
+        let mut removed = None;
+        self.remove_by_ids_taking(
+            entity,
+            &[id],
+            // SAFETY: The column of the id stores `T`s.
+            Some((id, &mut |ptr| removed = Some(unsafe { ptr.read::<T>() }))),
+        );
+        removed
+    }
+
+    /// Removes the given components from the entity and drops them. Components the entity
+    /// doesn't have are ignored.
+    fn remove_by_ids(&mut self, entity: Entity, ids: &[ComponentId]) {
+        self.remove_by_ids_taking(entity, ids, None);
+    }
+
+    /// Like [`World::remove_by_ids`], but passes the `taken` component to its callback instead of
+    /// dropping it.
+    fn remove_by_ids_taking(
+        &mut self,
+        entity: Entity,
+        ids: &[ComponentId],
+        taken: Option<TakenComponent<'_>>,
+    ) {
+        let Some(location) = self.entities.get(entity).copied() else {
+            return;
+        };
+
+        let archetype = self.archetypes.get_unchecked(location.archetype_id);
+        let removed = ids
+            .iter()
+            .filter(|id| archetype.contains(**id) || self.has_sparse(entity, **id))
+            .copied()
+            .collect::<IdList>();
+        if removed.is_empty() {
+            return;
+        }
+
+        self.record(StructuralChange::Remove, entity, &removed);
+        self.trigger_hooks(entity, &removed, |hooks| hooks.on_remove);
+
+        let mut taken = taken;
+        let mut moved = IdList::new();
+        for &id in removed.iter() {
+            let Some(set) = self.sparse_sets.get_mut(id) else {
+                moved.push(id);
+                continue;
+            };
+            match taken.take_if(|(taken_id, _)| *taken_id == id) {
+                Some((_, take)) => set.remove(entity, Some(take)),
+                None => set.remove(entity, None),
+            };
+        }
+        if !moved.is_empty() {
+            let archetype_id = self.remove_target(location.archetype_id, &moved);
+            self.move_entity(entity, location, archetype_id, taken);
+        }
+        self.flush();
+    }
+
+    /// Returns `true` if the component is stored in a sparse set, see [`StorageType`].
+    fn is_sparse(&self, id: ComponentId) -> bool {
+        self.components
+            .get_info(&id)
+            .is_some_and(|info| info.storage_type == StorageType::SparseSet)
+    }
+
+    /// Returns `true` if the entity has the component in its sparse set.
+    fn has_sparse(&self, entity: Entity, id: ComponentId) -> bool {
+        self.sparse_sets
+            .get(id)
+            .is_some_and(|set| set.contains(entity))
+    }
+
+    /// Looks up the component of the entity at `location`, in its sparse set if it is stored in
+    /// one and in the entity's table otherwise.
+    fn component_ptr(
+        &self,
+        entity: Entity,
+        location: &EntityLocation,
+        id: ComponentId,
+    ) -> Option<Ptr<'_>> {
+        match self.sparse_sets.get(id) {
+            Some(set) => set.get(entity),
+            // SAFETY: The pointer borrows the world, which has to be borrowed mutably to write.
+            None => unsafe {
+                self.tables
+                    .get(location.table_id)?
+                    .get_component(id, location.table_row)
+            },
+        }
+    }
+
+    /// Mutable version of [`World::component_ptr`], which marks the component as changed.
+    fn component_mut_ptr(
+        &mut self,
+        entity: Entity,
+        location: &EntityLocation,
+        id: ComponentId,
+    ) -> Option<MutPtr<'_>> {
+        let tick = self.tick;
+        match self.sparse_sets.get_mut(id) {
+            Some(set) => set.get_mut(entity, tick),
+            // SAFETY: The world is borrowed mutably for the lifetime of the pointer.
+            None => unsafe {
+                self.tables.get_mut(location.table_id)?.get_component_mut(
+                    id,
+                    location.table_row,
+                    tick,
+                )
+            },
+        }
+    }
+
+    /// The archetype entities of `from` move to when the `added` components are inserted, which
+    /// `from` must not contain yet.
+    fn insert_target(&mut self, from: ArchetypeId, added: &[ComponentId]) -> ArchetypeId {
+        let archetype = self.archetypes.get_unchecked(from);
+        if let Some(target) = archetype.insert_edge(added) {
+            return target;
+        }
+
+        let mut component_ids = archetype.components().collect::<IdList>();
+        component_ids.extend(added);
+        component_ids.sort_unstable();
+        let target = self.archetype_with(&component_ids);
+        self.archetypes
+            .get_mut_unchecked(from)
+            .set_insert_edge(added, target);
+        target
+    }
 
-                let location = self.archetypes.get_mut_unchecked(archetype_id).allocate(entity, table_row);
+    /// The archetype entities of `from` move to when the `removed` components, which `from` must
+    /// contain, are removed.
+    fn remove_target(&mut self, from: ArchetypeId, removed: &[ComponentId]) -> ArchetypeId {
+        let archetype = self.archetypes.get_unchecked(from);
+        if let Some(target) = archetype.remove_edge(removed) {
+            return target;
+        }
+
+        let mut component_ids = archetype.components().collect::<IdList>();
+        component_ids.retain(|id| !removed.contains(id));
+        component_ids.sort_unstable();
+        let target = self.archetype_with(&component_ids);
+        self.archetypes
+            .get_mut_unchecked(from)
+            .set_remove_edge(removed, target);
+        target
+    }
+
+    /// The archetype with the sorted components, which is created if it doesn't exist yet.
+    fn archetype_with(&mut self, component_ids: &[ComponentId]) -> ArchetypeId {
+        let table_id = self
+            .tables
+            .get_id_or_insert(component_ids, &self.components);
+        self.archetypes.get_id_or_insert(table_id, component_ids)
+    }
+
+    /// Moves the entities from their archetype to the given one, like [`World::move_entity`],
+    /// but column by column. All entities have to live in the same archetype.
+    fn move_entities(
+        &mut self,
+        entities: &[(Entity, EntityLocation)],
+        archetype_id: ArchetypeId,
+    ) -> Vec<EntityLocation> {
+        let Some((_, first)) = entities.first() else {
+            return Vec::new();
+        };
+        let table_id = self.archetypes.get_unchecked(archetype_id).table_id();
+        let (old_table, new_table) = self.tables.get_2_mut(first.table_id, table_id);
+        let rows = entities
+            .iter()
+            .map(|(_, location)| location.table_row)
+            .collect::<Vec<_>>();
+        // SAFETY: The rows of distinct entities of the archetype are distinct rows of its table.
+        let new_rows =
+            unsafe { old_table.move_rows_to_unchecked(&rows, new_table, self.removal_mode) };
+
+        // Back to front, like the table, so the locations left to remove stay valid
+        let mut removed = entities
+            .iter()
+            .map(|(_, location)| *location)
+            .collect::<Vec<_>>();
+        removed.sort_unstable_by_key(|location| location.table_row);
+        for location in removed.into_iter().rev() {
+            self.remove_from_archetype(location);
+        }
 
-                Ok(location)
+        let archetype = self.archetypes.get_mut_unchecked(archetype_id);
+        entities
+            .iter()
+            .zip(new_rows)
+            .map(|((entity, _), table_row)| {
+                let new_location = archetype.allocate(*entity, table_row);
+                self.entities.set(*entity, new_location);
+                new_location
             })
-            .expect("entity allocation should not fail")
+            .collect()
+    }
+
+    /// Moves the entity into the given archetype and returns its new location.
+    ///
+    /// Components the new archetype does not contain are dropped, apart from the `taken` one, and
+    /// components that only the new archetype contains are left uninitialized and have to be
+    /// initialized by the caller.
+    fn move_entity(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        archetype_id: ArchetypeId,
+        taken: Option<TakenComponent<'_>>,
+    ) -> EntityLocation {
+        let table_id = self.archetypes.get_unchecked(archetype_id).table_id();
+        let (old_table, new_table) = self.tables.get_2_mut(location.table_id, table_id);
+        let table_row = unsafe {
+            old_table.move_to_unchecked(location.table_row, new_table, self.removal_mode, taken)
+        };
+        self.remove_from_archetype(location);
+
+        let new_location = self
+            .archetypes
+            .get_mut_unchecked(archetype_id)
+            .allocate(entity, table_row);
+        self.entities.set(entity, new_location);
+
+        new_location
+    }
+
+    /// Registers a callback that is invoked whenever a new archetype is created in this world.
+    ///
+    /// Archetypes that already exist when the callback is registered are not reported.
+    pub fn on_archetype_created(
+        &mut self,
+        observer: impl FnMut(&ArchetypeCreated) + Send + Sync + 'static,
+    ) {
+        self.archetypes.observe(observer);
+    }
+
+    /// Creates an [`Index`] mapping the key extracted from every `C` to the entities holding it.
+    ///
+    /// Entities that already have the component are indexed right away. Does nothing if an index
+    /// for `C` and `K` already exists.
+    ///
+    /// Panics: If `C` already has user defined `on_insert`, `on_replace` or `on_remove` hooks.
+    pub fn init_index<C: Component, K: Hash + Eq + Send + Sync + 'static>(
+        &mut self,
+        extract: fn(&C) -> K,
+    ) {
+        index::init(self, extract, None);
+    }
+
+    /// Creates an [`Index`] like [`World::init_index`] that additionally only allows a single
+    /// entity per key, resolving duplicates according to the `policy`.
+    ///
+    /// Duplicates among the entities that already exist are resolved right away, in the order the
+    /// entities are stored in the world.
+    ///
+    /// Panics: If `C` already has user defined `on_insert`, `on_replace` or `on_remove` hooks.
+    pub fn init_unique_index<C: Component, K: Hash + Eq + Send + Sync + 'static>(
+        &mut self,
+        extract: fn(&C) -> K,
+        policy: UniquePolicy,
+    ) {
+        index::init(self, extract, Some(policy));
+    }
+
+    /// Retrieves the [`Index`] of `C` by `K`.
+    ///
+    /// Panics: If the index has not been created with [`World::init_index`].
+    pub fn index<C: Component, K: Hash + Eq + Send + Sync + 'static>(&self) -> &Index<C, K> {
+        self.component_id::<C>()
+            .and_then(|id| self.indexes.get(id))
+            .expect("Tried to access an index that has not been initialized")
+    }
+
+    /// Starts tracking which entity holds which [`PersistentId`], see
+    /// [`World::persistent_entity`]. Does nothing if the ids are already tracked.
+    ///
+    /// Panics: If two entities hold the same id, now or later on.
+    pub fn init_persistent_ids(&mut self) {
+        self.init_unique_index::<PersistentId, _>(|id| *id, UniquePolicy::Panic);
+    }
+
+    /// Returns the entity holding the [`PersistentId`], if any.
+    ///
+    /// Panics: If the ids are not tracked, see [`World::init_persistent_ids`].
+    pub fn persistent_entity(&self, id: PersistentId) -> Option<Entity> {
+        self.index::<PersistentId, PersistentId>()
+            .get(&id)
+            .first()
+            .copied()
+    }
+
+    /// Registers the component and returns its lifecycle hooks for configuration.
+    pub fn register_component_hooks<T: Component>(&mut self) -> &mut ComponentHooks {
+        let id = self.components.register_component::<T>();
+        &mut self
+            .components
+            .get_info_mut(&id)
+            .expect("component was just registered")
+            .hooks
+    }
+
+    fn trigger_hooks(
+        &mut self,
+        entity: Entity,
+        component_ids: &[ComponentId],
+        hook: impl Fn(&ComponentHooks) -> Option<ComponentHook>,
+    ) {
+        for id in component_ids {
+            if let Some(hook) = self
+                .components
+                .get_info(id)
+                .and_then(|info| hook(&info.hooks))
+            {
+                hook(DeferredWorld { world: self }, entity, *id);
+            }
+        }
     }
 
     pub fn despawn(&mut self, entity: Entity) {
+        if let Some(location) = self.entities.get(entity) {
+            let mut component_ids = self
+                .archetypes
+                .get_unchecked(location.archetype_id)
+                .components()
+                .collect::<IdList>();
+            component_ids.extend(self.sparse_sets.components_of(entity));
+            self.record(StructuralChange::Despawn, entity, &component_ids);
+            self.trigger_hooks(entity, &component_ids, |hooks| hooks.on_remove);
+        }
+
         if let Some(location) = self.entities.free(entity) {
-            let archetype = self.archetypes.get_mut_unchecked(location.archetype_id);
-            if let Some(swapped_entity) = archetype.swap_remove(location.table_row) {
-                let swap_location = self
-                    .entities
-                    .get(swapped_entity)
-                    .expect("Entity must exist, as it was just swapped");
-
-                self.entities.set(
-                    swapped_entity,
-                    EntityLocation {
-                        table_row: location.table_row,
-                        ..*swap_location
-                    },
-                );
-            }
+            self.observers.remove_target(entity);
+            self.sparse_sets.remove_entity(entity);
+            self.remove_from_archetype(location);
 
             let table = self.tables.get_mut_unchecked(location.table_id);
-            table.swap_remove(location.table_row);
+            table.remove(location.table_row, self.removal_mode);
         }
+        self.flush();
     }
 
-    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
-        let component_id = self.components.component_id::<T>()?;
-        let location = self.entities.get(entity)?;
-        let table = self.tables.get(location.table_id)?;
+    /// Despawns all entities, while keeping the registered components, resources and the
+    /// created archetypes and tables with their allocations, e.g. to load another level.
+    ///
+    /// `on_remove` hooks run for every component as if every entity was despawned, but all
+    /// entities are removed at once instead of moving the rows of the others.
+    pub fn clear_entities(&mut self) {
+        let entities = self
+            .entities
+            .iter()
+            .map(|(entity, location)| (entity, location.archetype_id))
+            .collect::<Vec<_>>();
+        for &(entity, archetype_id) in &entities {
+            let mut component_ids = self
+                .archetypes
+                .get_unchecked(archetype_id)
+                .components()
+                .collect::<IdList>();
+            component_ids.extend(self.sparse_sets.components_of(entity));
+            self.record(StructuralChange::Despawn, entity, &component_ids);
+            self.trigger_hooks(entity, &component_ids, |hooks| hooks.on_remove);
+        }
 
-        unsafe {
-            let ptr = table.get_component(component_id, location.table_row)?;
+        for (entity, _) in entities {
+            self.observers.remove_target(entity);
+        }
+        self.entities.clear();
+        self.archetypes.clear_entities();
+        self.tables.clear_rows();
+        self.sparse_sets.clear();
+        self.flush();
+    }
+
+    /// Removes the entity at `location` from its archetype and updates the locations of all
+    /// entities that were moved to a different row.
+    fn remove_from_archetype(&mut self, location: EntityLocation) {
+        let archetype = self.archetypes.get_mut_unchecked(location.archetype_id);
+        let mode = if self
+            .tables
+            .get_mut_unchecked(location.table_id)
+            .tombstones(self.removal_mode)
+        {
+            RemovalMode::Tombstone
+        } else {
+            self.removal_mode
+        };
+        for (moved_entity, table_row) in archetype.remove(location.table_row, mode) {
+            let moved_location = self
+                .entities
+                .get(moved_entity)
+                .expect("Entity must exist, as it was just moved");
 
-            Some(ptr.deref::<T>())
+            self.entities.set(
+                moved_entity,
+                EntityLocation {
+                    table_row,
+                    ..*moved_location
+                },
+            );
         }
     }
 
-    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
-        let component_id = self.components.component_id::<T>()?;
-        let location = self.entities.get(entity)?;
-        let table = self.tables.get_mut(location.table_id)?;
+    /// Sets how entities are removed from tables when they are despawned or change their
+    /// archetype. Defaults to [`RemovalMode::Swap`].
+    ///
+    /// [`RemovalMode::Shift`] keeps the entities of every table in the order they were added,
+    /// e.g. for turn orders or UI lists, at the cost of removals taking linear time. Switching
+    /// the mode doesn't restore the order of tables that were already reordered.
+    ///
+    /// [`RemovalMode::Tombstone`] never moves an entity to a different row, which keeps rows
+    /// cached by external systems valid. Later spawns into the same table reuse the tombstoned
+    /// rows.
+    pub fn set_removal_mode(&mut self, mode: RemovalMode) {
+        self.removal_mode = mode;
+    }
 
-        unsafe {
-            let ptr = table.get_component_mut(component_id, location.table_row)?;
+    pub fn removal_mode(&self) -> RemovalMode {
+        self.removal_mode
+    }
+
+    /// The id of the world, which is unique among all worlds of the process.
+    pub fn id(&self) -> WorldId {
+        self.id
+    }
 
-            Some(ptr.deref_mut::<T>())
+    /// The current tick of the world.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// Advances the world to the next tick and returns it.
+    ///
+    /// This starts a new frame, whose [`Time`] delta is the wall clock time since the previous
+    /// call.
+    pub fn increment_tick(&mut self) -> Tick {
+        self.tick = Tick::new(self.tick.get().wrapping_add(1));
+        let tick = self.tick;
+        if let Some(time) = self.get_resource_mut::<Time>() {
+            time.update(tick);
         }
+        tick
     }
 
-    pub fn query<'w, T: Queryable<'w>>(&'w self) -> Query<'w, T> {
-        Query::new(self)
+    /// Advances the world to the next tick without starting a new frame, so the changes made
+    /// before and after the call can be told apart. Called after every system run, see
+    /// [`System`](crate::system::System).
+    pub fn increment_change_tick(&mut self) -> Tick {
+        self.tick = Tick::new(self.tick.get().wrapping_add(1));
+        self.tick
     }
 
-    pub fn component_id<T: Component>(&self) -> Option<ComponentId> {
-        self.components.component_id::<T>()
+    /// Advances the world to the next tick like [`World::increment_tick`], but with a fixed
+    /// frame delta, e.g. for fixed timesteps or deterministic simulations.
+    pub fn increment_tick_with(&mut self, delta: Duration) -> Tick {
+        self.tick = Tick::new(self.tick.get().wrapping_add(1));
+        let tick = self.tick;
+        if let Some(time) = self.get_resource_mut::<Time>() {
+            time.update_with(tick, delta);
+        }
+        tick
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use archetype::ArchetypeId;
-    use component::Component;
-    use storage::{TableId, TableRow};
+    /// The frame context of the current tick, stored as a resource so systems can access it
+    /// through [`Res<Time>`](crate::system::Res).
+    ///
+    /// Panics: If the [`Time`] resource was removed.
+    pub fn time(&self) -> &Time {
+        self.get_resource()
+            .expect("the Time resource must not be removed")
+    }
 
-    use super::*;
+    /// Starts recording every spawn, despawn, insert and remove into an [`AuditLog`] holding the
+    /// most recent `capacity` changes. Replaces the previous log, if any.
+    pub fn enable_audit_log(&mut self, capacity: usize) {
+        self.audit_log = Some(AuditLog::new(capacity));
+    }
 
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    struct MyComponent(u32);
-    impl Component for MyComponent {}
+    /// Stops recording structural changes and returns the recorded log.
+    pub fn disable_audit_log(&mut self) -> Option<AuditLog> {
+        self.audit_log.take()
+    }
 
-    #[derive(Debug, PartialEq, Clone, Copy)]
-    struct Position {
-        x: f32,
-        y: f32,
-        z: f32,
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_ref()
     }
-    impl Component for Position {}
 
-    #[test]
-    fn spawn() {
-        let mut world = World::new();
-        let entity = world.spawn(MyComponent(1));
+    fn record(&mut self, change: StructuralChange, entity: Entity, component_ids: &[ComponentId]) {
+        if let Some(log) = &mut self.audit_log {
+            log.record(self.tick, change, entity, component_ids);
+        }
+        #[cfg(feature = "serde")]
+        self.record_replay(change, entity, component_ids);
+    }
 
-        assert_eq!(entity, Entity::from(0, 0));
+    /// Applies all commands queued by hooks.
+    fn flush(&mut self) {
+        while !self.command_queue.is_empty() {
+            let mut queue = std::mem::take(&mut self.command_queue);
+            queue.apply(self);
+            // Keep the allocation around if the applied commands did not queue any new ones
+            if self.command_queue.is_empty() {
+                self.command_queue = queue;
+            }
+        }
+    }
 
-        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(1)));
+    /// Returns `true` if the entity is alive, i.e. it was spawned and not despawned since.
+    ///
+    /// Always compares the generation of the entity, so handles of despawned entities whose
+    /// index was reused are reported as dead.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
     }
 
-    #[test]
-    fn spawn_multiple() {
-        let mut world = World::new();
+    /// The entities of the world, e.g. to reserve entities from other threads.
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
 
-        let e0 = world.spawn(MyComponent(0));
-        let e1 = world.spawn(MyComponent(1));
+    /// Returns a view of the entity, if it is alive.
+    pub fn get_entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        let location = *self.entities.get(entity)?;
+        Some(EntityRef::new(self, entity, location))
+    }
 
-        assert_eq!(e0, Entity::from(0, 0));
-        assert_eq!(e1, Entity::from(0, 1));
+    /// Iterates over views of all alive entities in index order, including [`Disabled`] ones.
+    pub fn iter_entities(&self) -> impl Iterator<Item = EntityRef<'_>> + use<'_> {
+        self.entities
+            .iter()
+            .map(|(entity, location)| EntityRef::new(self, entity, *location))
+    }
 
-        assert_eq!(world.archetypes.len(), 1);
-        assert_eq!(world.components.len(), 1);
-        assert_eq!(world.entities.len(), 2);
-        assert_eq!(world.tables.len(), 1);
+    /// Returns a view of the entity.
+    ///
+    /// Panics: If the entity is not alive, use [`World::get_entity`] to handle that case.
+    pub fn entity(&self, entity: Entity) -> EntityRef<'_> {
+        self.get_entity(entity)
+            .unwrap_or_else(|| panic!("{entity:?} does not exist in this world"))
     }
 
-    #[test]
-    fn spawn_batch() {
-        const BATCH_SIZE: u32 = 1_000;
-        let mut world = World::new();
+    /// Returns a mutable handle of the entity, if it is alive.
+    pub fn get_entity_mut(&mut self, entity: Entity) -> Option<EntityWorldMut<'_>> {
+        let location = *self.entities.get(entity)?;
+        Some(EntityWorldMut::new(self, entity, location))
+    }
 
-        for i in 0..BATCH_SIZE {
-            let entity = world.spawn(MyComponent(i));
-            assert_eq!(entity, Entity::from(0, i));
-            assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(i)));
+    /// Returns a mutable handle of the entity, which looks up the entity only once for multiple
+    /// changes.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Shield;
+    ///
+    /// let mut world = World::new();
+    /// let entity = world.spawn(Health(10));
+    /// let mut player = world.entity_mut(entity);
+    /// player.insert(Shield).get_mut::<Health>().unwrap().0 += 5;
+    /// assert!(player.contains::<Shield>());
+    /// assert_eq!(player.get::<Health>().unwrap().0, 15);
+    /// player.despawn();
+    /// assert!(world.get_entity(entity).is_none());
+    /// ```
+    ///
+    /// Panics: If the entity is not alive, use [`World::get_entity_mut`] to handle that case.
+    pub fn entity_mut(&mut self, entity: Entity) -> EntityWorldMut<'_> {
+        self.get_entity_mut(entity)
+            .unwrap_or_else(|| panic!("{entity:?} does not exist in this world"))
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let component_id = self.components.component_id::<T>()?;
+        let location = self.entities.get(entity)?;
+        let ptr = self.component_ptr(entity, location, component_id)?;
+
+        unsafe { Some(ptr.deref::<T>()) }
+    }
+
+    /// Like [`World::get`], but skips the generation check in release builds with the
+    /// `unchecked-entities` feature, see [`Entities::get_unchecked_generation`]. A stale id then
+    /// returns the component of the entity that reused its slot.
+    pub fn get_unchecked_generation<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let component_id = self.components.component_id::<T>()?;
+        let location = self.entities.get_unchecked_generation(entity)?;
+        // Sparse sets compare the generation, so they are looked up with the entity in the slot
+        let entity = self
+            .tables
+            .get(location.table_id)?
+            .entity(location.table_row);
+        let ptr = self.component_ptr(entity, location, component_id)?;
+
+        unsafe { Some(ptr.deref::<T>()) }
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        let component_id = self.components.component_id::<T>()?;
+        let location = *self.entities.get(entity)?;
+        let ptr = self.component_mut_ptr(entity, &location, component_id)?;
+
+        unsafe { Some(ptr.deref_mut::<T>()) }
+    }
+
+    /// Retrieves the components of several distinct entities mutably at once, e.g. to resolve a
+    /// collision between two entities.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, entity::GetManyMutError, World};
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn(Velocity(1.0));
+    /// let b = world.spawn(Velocity(-2.0));
+    /// let [va, vb] = world.get_many_mut::<Velocity, 2>([a, b]).unwrap();
+    /// std::mem::swap(&mut va.0, &mut vb.0);
+    /// assert_eq!(world.get::<Velocity>(a).unwrap().0, -2.0);
+    /// assert_eq!(
+    ///     world.get_many_mut::<Velocity, 2>([a, a]).err(),
+    ///     Some(GetManyMutError::DuplicateEntity(a))
+    /// );
+    /// ```
+    ///
+    /// No component is marked as changed if an error is returned.
+    pub fn get_many_mut<T: Component, const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[&mut T; N], GetManyMutError> {
+        for (i, entity) in entities.iter().enumerate() {
+            if entities[..i].contains(entity) {
+                return Err(GetManyMutError::DuplicateEntity(*entity));
+            }
+        }
+        // Distinct ids can still resolve to the same entity, e.g. a stale id with unchecked
+        // generations, so the resolved locations must be distinct as well.
+        let mut locations = Vec::with_capacity(N);
+        for entity in &entities {
+            let location = self
+                .entities
+                .get(*entity)
+                .filter(|_| self.get::<T>(*entity).is_some())
+                .ok_or(GetManyMutError::MissingComponent(*entity))?;
+            if locations.contains(location) {
+                return Err(GetManyMutError::DuplicateEntity(*entity));
+            }
+            locations.push(*location);
         }
 
-        assert_eq!(world.archetypes.len(), 1);
-        assert_eq!(world.components.len(), 1);
+        // SAFETY: The entities resolve to distinct rows, so their components don't alias.
+        Ok(entities.map(|entity| unsafe {
+            &mut *(self
+                .get_mut::<T>(entity)
+                .expect("the component was checked to exist") as *mut T)
+        }))
+    }
+
+    /// Type-erased version of [`World::get`] for components that may only be known by their id,
+    /// e.g. ones registered with a [`ComponentDescriptor`].
+    pub fn get_by_id(&self, entity: Entity, id: ComponentId) -> Option<Ptr<'_>> {
+        let location = self.entities.get(entity)?;
+        self.component_ptr(entity, location, id)
+    }
+
+    /// Type-erased version of [`World::get_mut`], which marks the component as changed.
+    pub fn get_mut_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<MutPtr<'_>> {
+        let location = *self.entities.get(entity)?;
+        self.component_mut_ptr(entity, &location, id)
+    }
+
+    /// Registers `T` under a label as a component distinct from `T` and its other labels, see
+    /// [`Components::register_labeled`].
+    pub fn register_labeled_component<T: Component>(&mut self, label: &str) -> ComponentId {
+        self.components.register_labeled::<T>(label)
+    }
+
+    pub fn labeled_component_id<T: Component>(&self, label: &str) -> Option<ComponentId> {
+        self.components.labeled_component_id::<T>(label)
+    }
+
+    /// Inserts the component under the given label into the entity, overwriting a previous value
+    /// with the same label.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// struct Timer(f32);
+    /// impl Component for Timer {}
+    ///
+    /// let mut world = World::new();
+    /// let entity = world.spawn(());
+    /// world.insert_labeled(entity, "reload", Timer(1.5));
+    /// world.insert_labeled(entity, "respawn", Timer(10.0));
+    /// assert_eq!(world.get_labeled::<Timer>(entity, "reload").unwrap().0, 1.5);
+    /// assert!(world.get::<Timer>(entity).is_none());
+    /// ```
+    ///
+    /// Panics: If the entity does not exist.
+    pub fn insert_labeled<T: Component>(&mut self, entity: Entity, label: &str, component: T) {
+        let location = *self
+            .entities
+            .get(entity)
+            .expect("Tried to insert components into an entity that does not exist");
+        let id = self.components.register_labeled::<T>(label);
+        self.insert_by_ids(entity, location, &[id], |write| {
+            OwningPtr::make(component, write)
+        });
+    }
+
+    pub fn get_labeled<T: Component>(&self, entity: Entity, label: &str) -> Option<&T> {
+        let id = self.labeled_component_id::<T>(label)?;
+        let location = self.entities.get(entity)?;
+        let ptr = self.component_ptr(entity, location, id)?;
+
+        unsafe { Some(ptr.deref::<T>()) }
+    }
+
+    pub fn get_labeled_mut<T: Component>(&mut self, entity: Entity, label: &str) -> Option<&mut T> {
+        let id = self.labeled_component_id::<T>(label)?;
+        let location = *self.entities.get(entity)?;
+        let ptr = self.component_mut_ptr(entity, &location, id)?;
+
+        unsafe { Some(ptr.deref_mut::<T>()) }
+    }
+
+    /// Removes the component with the given label from the entity and drops it.
+    pub fn remove_labeled<T: Component>(&mut self, entity: Entity, label: &str) {
+        if let Some(id) = self.labeled_component_id::<T>(label) {
+            self.remove_by_ids(entity, &[id]);
+        }
+    }
+
+    /// Registers a component whose type is only known at runtime, see
+    /// [`Components::register_with_descriptor`].
+    pub fn register_component_with_descriptor(
+        &mut self,
+        descriptor: ComponentDescriptor,
+    ) -> ComponentId {
+        self.components.register_with_descriptor(descriptor)
+    }
+
+    /// Spawns an entity with the components of the given ids, e.g. ones registered with a
+    /// [`ComponentDescriptor`], moving their values out of `components` in the same order.
+    ///
+    /// # Safety
+    /// `components` must yield a pointer for every id, to a value of that component.
+    ///
+    /// Panics: If an id is not registered in this world or occurs more than once.
+    pub unsafe fn spawn_by_ids<'a>(
+        &mut self,
+        ids: &[ComponentId],
+        components: impl IntoIterator<Item = OwningPtr<'a>>,
+    ) -> Entity {
+        let mut sorted = ids.iter().copied().collect::<IdList>();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), ids.len(), "Spawned duplicate components");
+        let sparse = ids
+            .iter()
+            .map(|id| {
+                let info = self
+                    .components
+                    .get_info(id)
+                    .unwrap_or_else(|| panic!("Spawned unregistered component {id:?}"));
+                info.storage_type == StorageType::SparseSet
+            })
+            .collect::<Vec<_>>();
+        sorted.retain(|id| !self.is_sparse(*id));
+        self.sparse_sets.init(
+            ids.iter()
+                .zip(&sparse)
+                .filter(|(_, s)| **s)
+                .map(|(id, _)| *id),
+            &self.components,
+        );
+
+        let archetype_id = self.archetype_with(&sorted);
+        let table_id = self.archetypes.get_unchecked(archetype_id).table_id();
+        let tick = self.tick;
+        let mut components = components.into_iter();
+        let entity = self
+            .entities
+            .alloc(|entity| {
+                let table = self.tables.get_mut_unchecked(table_id);
+                let row = table.allocate(entity);
+                for (&id, &sparse) in ids.iter().zip(&sparse) {
+                    let ptr = components
+                        .next()
+                        .expect("a value must be passed for every component");
+                    if sparse {
+                        self.sparse_sets
+                            .get_mut(id)
+                            .expect("the sparse set of the component was just created")
+                            .insert(entity, ptr, tick);
+                    } else {
+                        table
+                            .get_column_mut(id)
+                            .expect("the table was created with a column for every component")
+                            .initialize_unchecked(row.index(), ptr, ComponentTicks::new(tick));
+                    }
+                }
+                Ok(self
+                    .archetypes
+                    .get_mut_unchecked(archetype_id)
+                    .allocate(entity, row))
+            })
+            .expect("entity allocation should not fail");
+
+        self.record(StructuralChange::Spawn, entity, ids);
+        self.trigger_hooks(entity, ids, |hooks| hooks.on_add);
+        self.trigger_hooks(entity, ids, |hooks| hooks.on_insert);
+        self.flush();
+        entity
+    }
+
+    /// Inserts the component with the given id into the entity, moving its value out of `value`,
+    /// see [`World::insert`].
+    ///
+    /// # Safety
+    /// `value` must point to a value of the component.
+    ///
+    /// Panics: If the entity does not exist or the id is not registered in this world.
+    pub unsafe fn insert_by_id(&mut self, entity: Entity, id: ComponentId, value: OwningPtr<'_>) {
+        let location = *self
+            .entities
+            .get(entity)
+            .expect("Tried to insert components into an entity that does not exist");
+        assert!(
+            self.components.get_info(&id).is_some(),
+            "Inserted unregistered component {id:?}"
+        );
+        self.check_unique_by_id(Some(entity), id, value.as_ref());
+        self.insert_by_ids(entity, location, &[id], |write| write(value));
+    }
+
+    /// Removes the component with the given id from the entity and drops it.
+    pub fn remove_by_id(&mut self, entity: Entity, id: ComponentId) {
+        self.remove_by_ids(entity, &[id]);
+    }
+
+    /// Modifies the entity's `T` component in place, returning the result of `f`.
+    ///
+    /// Unlike [`World::get_mut`], this runs the `on_replace` hooks before and the `on_insert`
+    /// hooks after `f` exactly once, which keeps state maintained by hooks, e.g. an [`Index`], in
+    /// sync. The component is marked as changed. Returns `None` if the entity doesn't exist or
+    /// doesn't have the component.
+    pub fn modify_component<T: Component, R>(
+        &mut self,
+        entity: Entity,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let id = self.component_id::<T>()?;
+        self.get::<T>(entity)?;
+
+        // Hooks can't make structural changes, so the component still exists afterwards
+        self.trigger_hooks(entity, &[id], |hooks| hooks.on_replace);
+        let result = f(self
+            .get_mut::<T>(entity)
+            .expect("component must still exist"));
+        self.trigger_hooks(entity, &[id], |hooks| hooks.on_insert);
+        self.flush();
+
+        Some(result)
+    }
+
+    /// Retrieves multiple components of the entity at once, resolving its location only once.
+    ///
+    /// Takes any read-only [`Queryable`], e.g. `(&A, &B, Option<&C>)`. Returns `None` if the
+    /// entity doesn't exist or lacks a required component.
+    pub fn get_components<Q: ReadOnlyQueryable>(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        // SAFETY: Read-only queryables don't hand out mutable access.
+        unsafe { self.fetch_components::<Q>(entity) }
+    }
+
+    /// Mutable version of [`World::get_components`], e.g. for `(&mut A, &B)`.
+    ///
+    /// Panics: If the queryable accesses a component mutably more than once, e.g. `(&mut A, &A)`.
+    pub fn get_components_mut<Q: Queryable>(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        // SAFETY: The world is borrowed mutably and the access check rejects aliasing terms.
+        unsafe { self.fetch_components::<Q>(entity) }
+    }
+
+    /// Safety: If `Q` accesses components mutably, no other reference to the entity's components
+    /// may be alive for the lifetime of the returned item.
+    unsafe fn fetch_components<Q: Queryable>(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        let state = Q::get_state(self)?;
+        let mut access = Access::default();
+        Q::update_access(&state, &mut access);
+
+        let location = self.entities.get(entity)?;
+        let archetype = self.archetypes.get_unchecked(location.archetype_id);
+        if !access.required().iter().all(|id| archetype.contains(*id))
+            || !access
+                .any_of()
+                .all(|ids| ids.iter().any(|id| archetype.contains(*id)))
+        {
+            return None;
+        }
+        let table = self.tables.get(location.table_id)?;
+        if !Q::matches_row(self, &state, table, location.table_row) {
+            return None;
+        }
+        Some(Q::fetch(
+            self,
+            &state,
+            table,
+            location.table_row,
+            self.tick(),
+        ))
+    }
+
+    /// Retrieves the component of the entity without checking that the entity is alive or has the
+    /// component, for hot loops over entities that were already validated.
+    ///
+    /// # Safety
+    /// The entity must be alive and have a `T` component. This is only checked with debug
+    /// assertions.
+    pub unsafe fn get_unchecked<T: Component>(&self, entity: Entity) -> &T {
+        let location = self.entities.get_unchecked(entity);
+        let ptr = self
+            .component_id::<T>()
+            .and_then(|id| match T::STORAGE_TYPE {
+                StorageType::Table => self
+                    .tables
+                    .get_unchecked_raw(location.table_id)
+                    .get_component(id, location.table_row),
+                StorageType::SparseSet => self.sparse_sets.get(id)?.get(entity),
+            });
+        debug_assert!(
+            ptr.is_some(),
+            "{entity:?} does not have a {} component",
+            std::any::type_name::<T>()
+        );
+
+        ptr.unwrap_unchecked().deref::<T>()
+    }
+
+    /// Mutable version of [`World::get_unchecked`].
+    ///
+    /// # Safety
+    /// The entity must be alive and have a `T` component. This is only checked with debug
+    /// assertions.
+    pub unsafe fn get_unchecked_mut<T: Component>(&mut self, entity: Entity) -> &mut T {
+        let location = *self.entities.get_unchecked(entity);
+        let tick = self.tick;
+        let ptr = self
+            .component_id::<T>()
+            .and_then(|id| match T::STORAGE_TYPE {
+                StorageType::Table => self
+                    .tables
+                    .get_mut_unchecked(location.table_id)
+                    .get_component_mut(id, location.table_row, tick),
+                StorageType::SparseSet => self.sparse_sets.get_mut(id)?.get_mut(entity, tick),
+            });
+        debug_assert!(
+            ptr.is_some(),
+            "{entity:?} does not have a {} component",
+            std::any::type_name::<T>()
+        );
+
+        ptr.unwrap_unchecked().deref_mut::<T>()
+    }
+
+    /// Returns the ticks at which the entity's `T` component was added and last changed, at the
+    /// granularity configured by [`Component::CHANGE_DETECTION`].
+    ///
+    /// Components are marked as changed when they are inserted and whenever they are accessed
+    /// mutably, e.g. through [`World::get_mut`] or a `&mut T` query.
+    pub fn component_ticks<T: Component>(&self, entity: Entity) -> Option<ComponentTicks> {
+        let component_id = self.components.component_id::<T>()?;
+        let location = self.entities.get(entity)?;
+        match self.sparse_sets.get(component_id) {
+            Some(set) => set.get_ticks(entity),
+            None => self
+                .tables
+                .get(location.table_id)?
+                .get_ticks(component_id, location.table_row),
+        }
+    }
+
+    pub fn query<T: ReadOnlyQueryable>(&self) -> Query<'_, T> {
+        Query::new(self)
+    }
+
+    /// Returns a view of the raw memory of the `T` column of every non-empty table, e.g. to copy
+    /// instance data straight into a GPU buffer.
+    ///
+    /// Tables of disabled entities are left out, like in queries.
+    ///
+    /// Panics: If `T` is stored in a [`StorageType::SparseSet`], which has no table columns.
+    pub fn column_views<T: Component>(&self) -> impl Iterator<Item = ColumnView<'_>> {
+        assert!(
+            T::STORAGE_TYPE == StorageType::Table,
+            "{} is stored in a sparse set and has no table columns",
+            std::any::type_name::<T>()
+        );
+        let id = self.component_id::<T>();
+        let disabled = self.component_id::<Disabled>();
+        self.archetypes
+            .iter()
+            .filter(move |archetype| id.is_some_and(|id| archetype.contains(id)))
+            .filter(move |archetype| !disabled.is_some_and(|id| archetype.contains(id)))
+            .filter_map(move |archetype| {
+                let table_id = archetype.table_id();
+                let table = self.tables.get(table_id)?;
+                ColumnView::new(table, table_id, id?, &self.components)
+                    .filter(|view| !view.is_empty())
+            })
+    }
+
+    pub fn query_mut<T: Queryable>(&mut self) -> Query<'_, T> {
+        Query::new(self)
+    }
+
+    pub fn query_filtered<T: ReadOnlyQueryable, F: QueryFilter>(&self) -> Query<'_, T, F> {
+        Query::new(self)
+    }
+
+    pub fn query_filtered_mut<T: Queryable, F: QueryFilter>(&mut self) -> Query<'_, T, F> {
+        Query::new(self)
+    }
+
+    /// Runs `f` with a [`ParScope`] that spawns scoped threads reading the world, e.g. to run
+    /// independent read-only queries in parallel. All threads are joined before this returns.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// struct Health(u32);
+    /// impl Component for Health {}
+    ///
+    /// let mut world = World::new();
+    /// world.spawn(Health(10));
+    ///
+    /// let total = world.par_scope(|scope| {
+    ///     let handle = scope.spawn(|world| world.query::<&Health>().map(|h| h.0).sum::<u32>());
+    ///     handle.join().unwrap()
+    /// });
+    /// assert_eq!(total, 10);
+    /// ```
+    pub fn par_scope<'env, R>(
+        &'env self,
+        f: impl for<'scope> FnOnce(&ParScope<'scope, 'env>) -> R,
+    ) -> R {
+        thread::scope(|scope| f(&ParScope { scope, world: self }))
+    }
+
+    pub fn component_id<T: Component>(&self) -> Option<ComponentId> {
+        self.components.component_id::<T>()
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Identifies a [`World`], see [`World::id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorldId(usize);
+
+impl WorldId {
+    fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Spawns threads with read-only access to the world, see [`World::par_scope`].
+pub struct ParScope<'scope, 'env: 'scope> {
+    scope: &'scope Scope<'scope, 'env>,
+    world: &'env World,
+}
+
+impl<'scope, 'env> ParScope<'scope, 'env> {
+    /// Spawns a thread running `f` with a shared reference to the world.
+    pub fn spawn<T: Send + 'scope>(
+        &self,
+        f: impl FnOnce(&'env World) -> T + Send + 'scope,
+    ) -> ScopedJoinHandle<'scope, T> {
+        let world = self.world;
+        self.scope.spawn(move || f(world))
+    }
+
+    pub fn world(&self) -> &'env World {
+        self.world
+    }
+}
+
+/// A [`World`] that only allows access which doesn't change the structure of the world.
+///
+/// It is handed to component hooks, which run in the middle of structural changes.
+pub struct DeferredWorld<'w> {
+    world: &'w mut World,
+}
+
+impl DeferredWorld<'_> {
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.world.get(entity)
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.world.get_mut(entity)
+    }
+
+    pub fn component_id<T: Component>(&self) -> Option<ComponentId> {
+        self.world.component_id::<T>()
+    }
+
+    /// Queues a command that is applied once the current structural change has completed.
+    pub fn queue(&mut self, command: impl Command) {
+        self.world.command_queue.push(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::Layout,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use archetype::ArchetypeId;
+    use component::{ChangeDetection, Component};
+    use entity::EntityIndex;
+    use query::Without;
+    use storage::TableRow;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct MyComponent(u32);
+    impl Component for MyComponent {}
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct Position {
+        x: f32,
+        y: f32,
+        z: f32,
+    }
+    impl Component for Position {}
+
+    #[test]
+    fn spawn() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(1));
+
+        assert_eq!(entity, Entity::from(1, 0));
+
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(1)));
+    }
+
+    #[test]
+    fn spawn_multiple() {
+        let mut world = World::new();
+
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(MyComponent(1));
+
+        assert_eq!(e0, Entity::from(1, 0));
+        assert_eq!(e1, Entity::from(1, 1));
+
+        assert_eq!(world.archetypes.len(), 1);
+        assert_eq!(world.components.len(), 1);
+        assert_eq!(world.entities.len(), 2);
+        assert_eq!(world.tables.len(), 1);
+    }
+
+    #[test]
+    fn spawn_batch() {
+        const BATCH_SIZE: u32 = 1_000;
+        let mut world = World::new();
+
+        for i in 0..BATCH_SIZE {
+            let entity = world.spawn(MyComponent(i));
+            assert_eq!(entity, Entity::from(1, i as EntityIndex));
+            assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(i)));
+        }
+
+        assert_eq!(world.archetypes.len(), 1);
+        assert_eq!(world.components.len(), 1);
         assert_eq!(world.entities.len(), BATCH_SIZE as usize);
         assert_eq!(world.tables.len(), 1);
     }
 
     #[test]
-    fn spawn_bundle() {
+    fn spawn_batch_iter() {
+        let mut world = World::new();
+        let first = world.spawn(MyComponent(0));
+        world.despawn(first);
+        world.enable_audit_log(16);
+
+        let entities = world.spawn_batch((1..4).map(|i| {
+            (
+                MyComponent(i),
+                Position {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+            )
+        }));
+        assert_eq!(entities.len(), 3);
+        // The freed slot is reused first
+        assert_eq!(entities[0], Entity::from(2, 0));
+        for (i, entity) in entities.iter().enumerate() {
+            assert_eq!(
+                world.get::<MyComponent>(*entity),
+                Some(&MyComponent(i as u32 + 1))
+            );
+        }
+        assert_eq!(world.audit_log().unwrap().len(), 3);
+
+        // Iterators without a size hint grow the storage as they go
+        let more = world.spawn_batch((0..10).filter(|i| i % 2 == 0).map(MyComponent));
+        assert_eq!(more.len(), 5);
+        assert_eq!(world.query::<&MyComponent>().count(), 8);
+        assert!(world
+            .spawn_batch(std::iter::empty::<MyComponent>())
+            .is_empty());
+    }
+
+    #[test]
+    fn spawn_wide_bundle() {
+        #[derive(Debug, PartialEq)]
+        struct Wide<const N: usize>(usize);
+        impl<const N: usize> Component for Wide<N> {}
+
+        let mut world = World::new();
+        let e0 = world.spawn((
+            Wide::<0>(0),
+            Wide::<1>(1),
+            Wide::<2>(2),
+            Wide::<3>(3),
+            Wide::<4>(4),
+            Wide::<5>(5),
+            Wide::<6>(6),
+            Wide::<7>(7),
+            Wide::<8>(8),
+            Wide::<9>(9),
+            Wide::<10>(10),
+            Wide::<11>(11),
+            Wide::<12>(12),
+            Wide::<13>(13),
+            Wide::<14>(14),
+            Wide::<15>(15),
+        ));
+        assert_eq!(world.get::<Wide<0>>(e0), Some(&Wide(0)));
+        assert_eq!(world.get::<Wide<7>>(e0), Some(&Wide(7)));
+        assert_eq!(world.get::<Wide<15>>(e0), Some(&Wide(15)));
+
+        // The order of the components doesn't change the archetype
+        let e1 = world.spawn((Wide::<2>(20), Wide::<1>(10), Wide::<0>(0)));
+        let e2 = world.spawn((Wide::<0>(0), Wide::<1>(1), Wide::<2>(2)));
+        assert_eq!(world.get::<Wide<2>>(e1), Some(&Wide(20)));
+        assert_eq!(world.get::<Wide<1>>(e1), Some(&Wide(10)));
+        assert_eq!(
+            world.entities.get(e1).unwrap().archetype_id,
+            world.entities.get(e2).unwrap().archetype_id
+        );
+        assert_eq!(world.archetypes.len(), 2);
+    }
+
+    #[test]
+    fn spawn_bundle() {
+        let mut world = World::new();
+
+        let entity = world.spawn((
+            MyComponent(0),
+            Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            },
+        ));
+        assert_eq!(entity, Entity::from(1, 0));
+
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(0)));
+        assert_eq!(
+            world.get::<Position>(entity),
+            Some(&Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            }),
+        );
+    }
+
+    #[test]
+    fn spawn_cached_bundle() {
+        let mut world = World::new();
+        let position = Position {
+            x: 0.0,
+            y: 1.0,
+            z: 2.0,
+        };
+
+        let e0 = world.spawn((MyComponent(0), position));
+        let e1 = world.spawn((MyComponent(1), position));
+        // Same components in a different order share the archetype
+        let e2 = world.spawn((position, MyComponent(2)));
+        assert_eq!(world.bundles.len(), 2);
+        assert_eq!(world.archetypes.len(), 1);
+
+        let location = *world.entities.get(e0).unwrap();
+        assert_eq!(
+            world.entities.get(e1).unwrap().archetype_id,
+            location.archetype_id
+        );
+        assert_eq!(
+            world.entities.get(e2).unwrap().archetype_id,
+            location.archetype_id
+        );
+        assert_eq!(world.get::<MyComponent>(e2), Some(&MyComponent(2)));
+    }
+
+    #[test]
+    fn entity_ref() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+
+        let entity_ref = world.entity(entity);
+        assert_eq!(entity_ref.id(), entity);
+        assert_eq!(entity_ref.get::<MyComponent>(), Some(&MyComponent(0)));
+        assert_eq!(entity_ref.get::<Position>(), None);
+        assert!(entity_ref.contains::<MyComponent>());
+        assert!(!entity_ref.contains::<Position>());
+
+        world.despawn(entity);
+        assert!(world.get_entity(entity).is_none());
+    }
+
+    #[test]
+    fn iter_entities() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(());
+        let e2 = world.spawn((MyComponent(2), Disabled));
+        world.despawn(e1);
+        let e3 = world.spawn(MyComponent(3));
+
+        assert!(world.contains(e0) && !world.contains(e1));
+        assert!(!world.contains(Entity::PLACEHOLDER));
+
+        let entities = world
+            .iter_entities()
+            .map(|entity| (entity.id(), entity.get::<MyComponent>().map(|c| c.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(entities, [(e0, Some(0)), (e3, Some(3)), (e2, Some(2))]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist in this world")]
+    fn entity_missing() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+        world.despawn(entity);
+
+        world.entity(entity);
+    }
+
+    #[test]
+    fn entity_world_mut() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+        let position = Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+
+        let mut entity_mut = world.entity_mut(entity);
+        let archetype_id = entity_mut.location().archetype_id;
+        entity_mut.insert(position);
+        assert_ne!(entity_mut.location().archetype_id, archetype_id);
+        assert_eq!(entity_mut.get::<Position>(), Some(&position));
+        entity_mut.get_mut::<MyComponent>().unwrap().0 = 5;
+        assert_eq!(entity_mut.remove::<Position>(), Some(position));
+        assert_eq!(entity_mut.location().archetype_id, archetype_id);
+        assert_eq!(entity_mut.remove::<Position>(), None);
+        assert!(!entity_mut.contains::<Position>());
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(5)));
+
+        world.entity_mut(entity).despawn();
+        assert!(world.get_entity_mut(entity).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "was despawned")]
+    fn entity_world_mut_despawned_by_hook() {
+        let mut world = World::new();
+        world.register_component_hooks::<Position>().on_insert = Some(|mut world, entity, _| {
+            world.queue(move |world: &mut World| world.despawn(entity));
+        });
+        let entity = world.spawn(MyComponent(0));
+
+        let mut entity_mut = world.entity_mut(entity);
+        entity_mut.insert(Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        entity_mut.get::<MyComponent>();
+    }
+
+    #[test]
+    fn persistent_entity() {
+        let mut world = World::new();
+        world.init_persistent_ids();
+        let id = PersistentId::new();
+        let e0 = world.spawn((MyComponent(0), id));
+        let e1 = world.spawn(MyComponent(1));
+        assert_eq!(world.persistent_entity(id), Some(e0));
+
+        world.despawn(e0);
+        assert_eq!(world.persistent_entity(id), None);
+
+        world.insert(e1, id);
+        assert_eq!(world.persistent_entity(id), Some(e1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_persistent_id() {
+        let mut world = World::new();
+        world.init_persistent_ids();
+        let id = PersistentId::new();
+        world.spawn(id);
+        world.spawn(id);
+    }
+
+    #[test]
+    fn audit_log() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        world.enable_audit_log(16);
+
+        let e1 = world.spawn(MyComponent(1));
+        world.increment_tick();
+        world.insert(
+            e1,
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+        world.despawn(e0);
+
+        let my_component = world.component_id::<MyComponent>().unwrap();
+        let position = world.component_id::<Position>().unwrap();
+        let records = world
+            .audit_log()
+            .unwrap()
+            .records()
+            .map(|record| {
+                (
+                    record.tick.get(),
+                    record.change,
+                    record.entity,
+                    record.components.to_vec(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                (0, StructuralChange::Spawn, e1, vec![my_component]),
+                (1, StructuralChange::Insert, e1, vec![position]),
+                (1, StructuralChange::Despawn, e0, vec![my_component]),
+            ]
+        );
+
+        let log = world.disable_audit_log().unwrap();
+        assert_eq!(log.entity_records(e1).count(), 2);
+        world.spawn(MyComponent(2));
+        assert!(world.audit_log().is_none());
+    }
+
+    #[test]
+    fn frame_time() {
+        let mut world = World::new();
+        assert_eq!(world.tick(), Tick::new(0));
+
+        world.increment_tick_with(Duration::from_millis(20));
+        world.increment_tick_with(Duration::from_millis(10));
+        assert_eq!(world.tick(), Tick::new(2));
+        assert_eq!(world.time().tick(), world.tick());
+        assert_eq!(world.time().delta(), Duration::from_millis(10));
+        assert_eq!(world.time().elapsed(), Duration::from_millis(30));
+
+        world.increment_tick();
+        assert_eq!(world.time().tick(), Tick::new(3));
+    }
+
+    #[test]
+    fn change_ticks() {
+        struct Coarse(u32);
+        impl Component for Coarse {
+            const CHANGE_DETECTION: ChangeDetection = ChangeDetection::PerTable;
+        }
+
+        let mut world = World::new();
+        let e0 = world.spawn((MyComponent(0), Coarse(0)));
+        let e1 = world.spawn((MyComponent(1), Coarse(1)));
+        assert_eq!(
+            world.component_ticks::<MyComponent>(e0),
+            Some(ComponentTicks::new(Tick::new(0)))
+        );
+
+        world.increment_tick();
+        world.get_mut::<MyComponent>(e0).unwrap().0 = 2;
+        world.get_mut::<Coarse>(e0).unwrap().0 = 2;
+        let ticks = world.component_ticks::<MyComponent>(e0).unwrap();
+        assert_eq!((ticks.added, ticks.changed), (Tick::new(0), Tick::new(1)));
+        assert_eq!(
+            world.component_ticks::<MyComponent>(e1).unwrap().changed,
+            Tick::new(0)
+        );
+        // Coarse changes are reported for every entity in the table
+        assert_eq!(
+            world.component_ticks::<Coarse>(e1).unwrap().changed,
+            Tick::new(1)
+        );
+
+        world.increment_tick();
+        for (entity, component) in world.query_mut::<(Entity, &mut MyComponent)>() {
+            if entity == e1 {
+                component.0 += 1;
+            }
+        }
+        assert_eq!(
+            world.component_ticks::<MyComponent>(e1).unwrap().changed,
+            Tick::new(2)
+        );
+
+        world.increment_tick();
+        world.insert(e1, MyComponent(5));
+        world.insert(
+            e1,
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+        assert_eq!(
+            world.component_ticks::<MyComponent>(e1),
+            Some(ComponentTicks {
+                added: Tick::new(0),
+                changed: Tick::new(3)
+            })
+        );
+        assert_eq!(
+            world.component_ticks::<Position>(e1),
+            Some(ComponentTicks::new(Tick::new(3)))
+        );
+        assert_eq!(world.component_ticks::<Position>(e0), None);
+    }
+
+    #[test]
+    fn world_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<World>();
+    }
+
+    #[test]
+    fn par_scope() {
+        let mut world = World::new();
+        for i in 0..100 {
+            world.spawn(MyComponent(i));
+        }
+        world.spawn(Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        });
+
+        let (count, sum) = world.par_scope(|scope| {
+            let count = scope.spawn(|world| world.query::<&Position>().count());
+            let sum = scope.spawn(|world| world.query::<&MyComponent>().map(|c| c.0).sum::<u32>());
+            (count.join().unwrap(), sum.join().unwrap())
+        });
+        assert_eq!(count, 1);
+        assert_eq!(sum, (0..100).sum::<u32>());
+    }
+
+    #[test]
+    fn modify_component() {
+        static REPLACED: AtomicUsize = AtomicUsize::new(0);
+        static INSERTED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut world = World::new();
+        world
+            .register_component_hooks::<MyComponent>()
+            .on_replace(|world, entity, _| {
+                assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(0)));
+                REPLACED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_insert(|_, _, _| {
+                INSERTED.fetch_add(1, Ordering::Relaxed);
+            });
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 1);
+
+        world.increment_tick();
+        let old =
+            world.modify_component::<MyComponent, _>(e0, |c| std::mem::replace(c, MyComponent(1)));
+        assert_eq!(old, Some(MyComponent(0)));
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(1)));
+        assert_eq!(REPLACED.load(Ordering::Relaxed), 1);
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            world.component_ticks::<MyComponent>(e0).unwrap().changed,
+            Tick::new(1)
+        );
+
+        assert_eq!(world.modify_component::<MyComponent, _>(e1, |_| ()), None);
+        world.despawn(e0);
+        assert_eq!(world.modify_component::<MyComponent, _>(e0, |_| ()), None);
+        assert_eq!(REPLACED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn labeled_components() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        world.insert_labeled(e0, "reload", MyComponent(1));
+        world.insert_labeled(e0, "respawn", MyComponent(2));
+        world.insert_labeled(e0, "reload", MyComponent(3));
+
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(0)));
+        assert_eq!(
+            world.get_labeled::<MyComponent>(e0, "reload"),
+            Some(&MyComponent(3))
+        );
+        world
+            .get_labeled_mut::<MyComponent>(e0, "respawn")
+            .unwrap()
+            .0 = 4;
+        assert_eq!(
+            world.get_labeled::<MyComponent>(e0, "respawn"),
+            Some(&MyComponent(4))
+        );
+        assert_eq!(world.get_labeled::<MyComponent>(e0, "other"), None);
+        let archetype_id = world.entity(e0).location().archetype_id;
+        assert_eq!(
+            world
+                .archetypes
+                .get_unchecked(archetype_id)
+                .components()
+                .count(),
+            3
+        );
+
+        world.remove_labeled::<MyComponent>(e0, "reload");
+        assert_eq!(world.get_labeled::<MyComponent>(e0, "reload"), None);
+        assert_eq!(
+            world.get_labeled::<MyComponent>(e0, "respawn"),
+            Some(&MyComponent(4))
+        );
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(0)));
+    }
+
+    #[test]
+    fn get_components() {
+        let mut world = World::new();
+        let position = Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let e0 = world.spawn((MyComponent(0), position));
+        let e1 = world.spawn(MyComponent(1));
+
+        assert_eq!(
+            world.get_components::<(Entity, &MyComponent, &Position)>(e0),
+            Some((e0, &MyComponent(0), &position))
+        );
+        assert_eq!(world.get_components::<(&MyComponent, &Position)>(e1), None);
+        assert_eq!(
+            world.get_components::<(&MyComponent, Option<&Position>)>(e1),
+            Some((&MyComponent(1), None))
+        );
+        assert_eq!(world.get_components::<&Disabled>(e0), None);
+
+        let (component, position) = world
+            .get_components_mut::<(&mut MyComponent, &mut Position)>(e0)
+            .unwrap();
+        component.0 = 5;
+        position.x = 5.0;
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(5)));
+        assert_eq!(world.get::<Position>(e0).unwrap().x, 5.0);
+
+        world.despawn(e0);
+        assert_eq!(world.get_components::<&MyComponent>(e0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with a previous &mut access")]
+    fn get_components_aliasing() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+        world.get_components_mut::<(&mut MyComponent, &MyComponent)>(entity);
+    }
+
+    #[test]
+    fn get_many_mut() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((
+            MyComponent(1),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+        let e2 = world.spawn(Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        world.increment_tick();
+
+        let [c0, c1] = world.get_many_mut::<MyComponent, 2>([e0, e1]).unwrap();
+        std::mem::swap(c0, c1);
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(1)));
+        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(0)));
+
+        world.increment_tick();
+        assert_eq!(
+            world.get_many_mut::<MyComponent, 3>([e0, e1, e0]),
+            Err(GetManyMutError::DuplicateEntity(e0))
+        );
+        assert_eq!(
+            world.get_many_mut::<MyComponent, 2>([e0, e2]),
+            Err(GetManyMutError::MissingComponent(e2))
+        );
+        assert_ne!(
+            world.component_ticks::<MyComponent>(e0).unwrap().changed,
+            world.tick()
+        );
+        assert_eq!(world.get_many_mut::<MyComponent, 0>([]), Ok([]));
+
+        #[derive(Component, Debug, PartialEq)]
+        #[component(storage = SparseSet)]
+        struct Charge(u32);
+        let s0 = world.spawn(Charge(0));
+        let s1 = world.spawn(Charge(1));
+        let [c0, c1] = world.get_many_mut::<Charge, 2>([s0, s1]).unwrap();
+        std::mem::swap(c0, c1);
+        assert_eq!(world.get::<Charge>(s0), Some(&Charge(1)));
+    }
+
+    #[test]
+    fn get_by_id() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(1));
+        let other = world.spawn(());
+        let id = world.component_id::<MyComponent>().unwrap();
+        world.increment_tick();
+
+        let ptr = world.get_by_id(entity, id).unwrap();
+        assert_eq!(unsafe { ptr.deref::<MyComponent>() }, &MyComponent(1));
+        assert!(world.get_by_id(other, id).is_none());
+        assert!(world.entity(entity).get_by_id(id).is_some());
+
+        let ptr = world.get_mut_by_id(entity, id).unwrap();
+        unsafe { ptr.deref_mut::<MyComponent>().0 = 2 };
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(2)));
+        assert_eq!(
+            world
+                .component_ticks::<MyComponent>(entity)
+                .unwrap()
+                .changed,
+            world.tick()
+        );
+        let mut entity_mut = world.entity_mut(entity);
+        let ptr = entity_mut.get_mut_by_id(id).unwrap();
+        unsafe { ptr.deref_mut::<MyComponent>().0 = 3 };
+        assert_eq!(entity_mut.get::<MyComponent>(), Some(&MyComponent(3)));
+
+        world.despawn(entity);
+        assert!(world.get_by_id(entity, id).is_none());
+        assert!(world.get_mut_by_id(entity, id).is_none());
+    }
+
+    #[test]
+    fn world_get_unchecked() {
+        let mut world = World::new();
+        let entity = world.spawn((
+            MyComponent(0),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+
+        unsafe {
+            assert_eq!(world.get_unchecked::<MyComponent>(entity), &MyComponent(0));
+            world.get_unchecked_mut::<MyComponent>(entity).0 = 42;
+        }
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(42)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn world_get_unchecked_missing() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+        world.despawn(entity);
+
+        unsafe {
+            world.get_unchecked::<MyComponent>(entity);
+        }
+    }
+
+    #[test]
+    fn world_get() {
+        let mut world = World::new();
+
+        let entity = world.spawn(Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        });
+
+        assert_eq!(
+            world.get::<Position>(entity),
+            Some(&Position {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            })
+        );
+
+        let comp = world
+            .get_mut::<Position>(entity)
+            .expect("Was spawned a few instructions ago");
+        comp.z = 42.0;
+
+        assert_eq!(
+            world.get::<Position>(entity),
+            Some(&Position {
+                x: 1.0,
+                y: 2.0,
+                z: 42.0,
+            })
+        );
+    }
+
+    #[test]
+    fn despawn() {
+        let mut world = World::new();
+
+        let e0 = world.spawn(Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        });
+        let e1 = world.spawn(Position {
+            x: 2.0,
+            y: 3.0,
+            z: 4.0,
+        });
+
+        assert_eq!(
+            world.get::<Position>(e0),
+            Some(&Position {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            })
+        );
+        assert_eq!(
+            world.entities.get(e0),
+            Some(&EntityLocation {
+                archetype_id: ArchetypeId(0),
+                table_id: TableId(0),
+                table_row: TableRow(0)
+            })
+        );
+        assert_eq!(
+            world.get::<Position>(e1),
+            Some(&Position {
+                x: 2.0,
+                y: 3.0,
+                z: 4.0,
+            })
+        );
+        assert_eq!(
+            world.entities.get(e1),
+            Some(&EntityLocation {
+                archetype_id: ArchetypeId(0),
+                table_id: TableId(0),
+                table_row: TableRow(1)
+            })
+        );
+
+        world.despawn(e0);
+
+        assert_eq!(world.get::<Position>(e0), None);
+        assert_eq!(world.entities.get(e0), None);
+        assert_eq!(
+            world.entities.get(e1),
+            Some(&EntityLocation {
+                archetype_id: ArchetypeId(0),
+                table_id: TableId(0),
+                table_row: TableRow(0)
+            })
+        );
+        assert_eq!(
+            world.get::<Position>(e1),
+            Some(&Position {
+                x: 2.0,
+                y: 3.0,
+                z: 4.0,
+            })
+        );
+
+        world.despawn(e1);
+        assert_eq!(world.get::<Position>(e1), None);
+        assert_eq!(world.entities.get(e1), None);
+    }
+
+    #[test]
+    fn stale_entities() {
+        #[derive(Component, Debug, PartialEq)]
+        #[component(storage = SparseSet)]
+        struct Charge(u32);
+
+        let mut world = World::new();
+        let stale = world.spawn((MyComponent(0), Charge(0)));
+        world.despawn(stale);
+        let reused = world.spawn((MyComponent(1), Charge(1)));
+        assert_eq!(stale.index(), reused.index());
+
+        // Safe lookups and changes always check the generation
+        world.despawn(stale);
+        assert_eq!(world.get::<MyComponent>(stale), None);
+        assert!(world
+            .get_many_mut::<MyComponent, 2>([stale, reused])
+            .is_err());
+        assert_eq!(world.get::<MyComponent>(reused), Some(&MyComponent(1)));
+
+        let unchecked = (
+            world.get_unchecked_generation::<MyComponent>(stale),
+            world.get_unchecked_generation::<Charge>(stale),
+        );
+        if cfg!(all(feature = "unchecked-entities", not(debug_assertions))) {
+            assert_eq!(unchecked, (Some(&MyComponent(1)), Some(&Charge(1))));
+        } else {
+            assert_eq!(unchecked, (None, None));
+        }
+    }
+
+    #[test]
+    fn clear_entities() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        static REMOVED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked;
+        impl Component for Tracked {
+            const STABLE_ROWS: bool = true;
+        }
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        #[derive(Component)]
+        #[component(storage = SparseSet)]
+        struct Stunned;
+
+        let mut world = World::new();
+        world
+            .register_component_hooks::<Tracked>()
+            .on_remove(|_, _, _| {
+                REMOVED.fetch_add(1, Ordering::Relaxed);
+            });
+        let entities = (0..4)
+            .map(|i| world.spawn((MyComponent(i), Tracked)))
+            .collect::<Vec<_>>();
+        world.despawn(entities[1]);
+        world.insert(entities[2], Stunned);
+        world.spawn(Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        let archetypes = world.archetypes.len();
+        let reserved = world.reserve_entity();
+
+        world.clear_entities();
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 4);
+        assert_eq!(REMOVED.load(Ordering::Relaxed), 4);
+        assert!(world.iter_entities().next().is_none());
+        assert!(world.get::<MyComponent>(entities[0]).is_none());
+        assert_eq!(world.query::<&MyComponent>().len(), 0);
+        assert_eq!(world.query::<&Stunned>().count(), 0);
+        assert_eq!(world.archetypes.len(), archetypes);
+
+        let entity = world.spawn((MyComponent(7), Tracked));
+        assert_eq!(entity.index(), 0);
+        assert_ne!(entity, entities[0]);
+        assert_eq!(world.entities.get(entity).unwrap().table_row, TableRow(0));
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(7)));
+        world.spawn_in(Some(reserved), MyComponent(8));
+        assert_eq!(world.get::<MyComponent>(reserved), Some(&MyComponent(8)));
+    }
+
+    #[test]
+    fn insert_batch() {
+        static INSERTED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, Component)]
+        #[component(on_insert = count_insert)]
+        struct Marker(u32);
+
+        fn count_insert(_: DeferredWorld, _: Entity, _: ComponentId) {
+            INSERTED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut world = World::new();
+        let plain = world.spawn_batch((0..3).map(MyComponent));
+        let positioned = world.spawn((
+            MyComponent(3),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+        let marked = world.spawn((MyComponent(4), Marker(0)));
+        let archetypes = world.archetypes.len();
+
+        world.insert_batch(
+            [plain[2], positioned, plain[0], marked, plain[2]]
+                .into_iter()
+                .zip(1..)
+                .map(|(entity, i)| (entity, Marker(i))),
+        );
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 6);
+        assert_eq!(world.archetypes.len(), archetypes + 1);
+        assert_eq!(world.get::<Marker>(plain[0]), Some(&Marker(3)));
+        assert_eq!(world.get::<Marker>(plain[1]), None);
+        assert_eq!(world.get::<Marker>(plain[2]), Some(&Marker(5)));
+        assert_eq!(world.get::<Marker>(positioned), Some(&Marker(2)));
+        assert_eq!(world.get::<Marker>(marked), Some(&Marker(4)));
+        assert_eq!(world.get::<MyComponent>(plain[2]), Some(&MyComponent(2)));
+        assert_eq!(world.query::<(&MyComponent, &Marker)>().count(), 4);
+    }
+
+    #[test]
+    fn insert_batch_removal_modes() {
+        #[derive(Debug, PartialEq)]
+        struct Stunned(u32);
+        impl Component for Stunned {
+            const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+        }
+
+        for mode in [
+            RemovalMode::Swap,
+            RemovalMode::Shift,
+            RemovalMode::Tombstone,
+        ] {
+            let mut world = World::builder().removal_mode(mode).build();
+            let entities = world.spawn_batch((0..6).map(MyComponent));
+            world.insert(entities[1], Stunned(0));
+
+            let position = |i: u32| Position {
+                x: i as f32,
+                y: 0.0,
+                z: 0.0,
+            };
+            world
+                .insert_batch([4, 1, 5].map(|i| (entities[i as usize], (position(i), Stunned(i)))));
+
+            let moved = world
+                .query::<(Entity, &Position, &Stunned)>()
+                .map(|(entity, position, stunned)| (entity, position.x, stunned.0))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                moved,
+                [
+                    (entities[4], 4.0, 4),
+                    (entities[1], 1.0, 1),
+                    (entities[5], 5.0, 5)
+                ]
+            );
+            for (i, entity) in entities.iter().enumerate() {
+                assert_eq!(
+                    world.get::<MyComponent>(*entity),
+                    Some(&MyComponent(i as u32))
+                );
+            }
+            assert_eq!(world.query::<&MyComponent>().count(), 6);
+            assert_eq!(
+                world.query_filtered::<Entity, Without<Position>>().count(),
+                3
+            );
+        }
+    }
+
+    #[test]
+    fn spawn_empty() {
+        let mut world = World::new();
+        let empty = world.spawn_empty().id();
+        let location = *world.entities.get(empty).unwrap();
+        assert_eq!(
+            world
+                .archetypes
+                .get_unchecked(location.archetype_id)
+                .entity_count(),
+            1
+        );
+        assert_eq!(world.iter_entities().count(), 1);
+        assert!(world.entity(empty).get::<MyComponent>().is_none());
+
+        let other = world.spawn_empty().id();
+        assert_eq!(
+            world.entities.get(other).unwrap().archetype_id,
+            location.archetype_id
+        );
+        world.insert(empty, MyComponent(3));
+        assert_eq!(world.get::<MyComponent>(empty), Some(&MyComponent(3)));
+        assert_eq!(world.entities.get(other).unwrap().table_row, TableRow(0));
+        assert_eq!(world.query::<Entity>().count(), 2);
+    }
+
+    #[test]
+    fn flush_entities() {
+        let mut world = World::new();
+        let despawned = world.spawn(MyComponent(0));
+        world.despawn(despawned);
+
+        let reserved = std::thread::scope(|scope| {
+            let world = &world;
+            let thread = scope.spawn(|| world.reserve_entity());
+            [world.reserve_entity(), thread.join().unwrap()]
+        });
+        assert!(reserved.iter().all(|entity| !world.contains(*entity)));
+        assert!(reserved.iter().any(|entity| entity.index() == 0));
+
+        let spawned = world.spawn(MyComponent(1));
+        assert!(!reserved.contains(&spawned));
+
+        world.flush_entities();
+        assert!(reserved.iter().all(|entity| world.contains(*entity)));
+        assert_eq!(world.iter_entities().count(), 3);
+        world.insert(reserved[0], MyComponent(2));
+        assert_eq!(world.get::<MyComponent>(reserved[0]), Some(&MyComponent(2)));
+    }
+
+    #[test]
+    fn swap_remove() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(MyComponent(1));
+        let e2 = world.spawn(MyComponent(2));
+
+        world.despawn(e0);
+        assert_eq!(world.get::<MyComponent>(e2), Some(&MyComponent(2)));
+        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
+        assert_eq!(world.get::<MyComponent>(e0), None);
+
+        world.despawn(e2);
+        assert_eq!(world.get::<MyComponent>(e2), None);
+        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
+        assert_eq!(world.get::<MyComponent>(e0), None);
+
+        world.despawn(e1);
+        assert_eq!(world.get::<MyComponent>(e2), None);
+        assert_eq!(world.get::<MyComponent>(e1), None);
+        assert_eq!(world.get::<MyComponent>(e0), None);
+    }
+
+    #[test]
+    fn stable_rows() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Anchor;
+        impl Component for Anchor {
+            const STABLE_ROWS: bool = true;
+        }
+        impl Drop for Anchor {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
         let mut world = World::new();
+        let entities = (0..4)
+            .map(|i| world.spawn((MyComponent(i), Anchor)))
+            .collect::<Vec<_>>();
+        let rows = |world: &World| {
+            entities
+                .iter()
+                .filter_map(|entity| world.get_entity(*entity))
+                .map(|entity| entity.location().table_row)
+                .collect::<Vec<_>>()
+        };
+        let before = rows(&world);
 
-        let entity = world.spawn((
-            MyComponent(0),
+        world.despawn(entities[1]);
+        world.insert(
+            entities[2],
             Position {
                 x: 0.0,
-                y: 1.0,
-                z: 2.0,
+                y: 0.0,
+                z: 0.0,
             },
-        ));
-        assert_eq!(entity, Entity::from(0, 0));
+        );
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+        assert_eq!(rows(&world)[0], before[0]);
+        assert_eq!(rows(&world)[2], before[3]);
 
-        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(0)));
+        let query = world.query_filtered::<&MyComponent, Without<Position>>();
+        assert_eq!(query.len(), 2);
         assert_eq!(
-            world.get::<Position>(entity),
-            Some(&Position {
-                x: 0.0,
-                y: 1.0,
-                z: 2.0,
-            }),
+            query.rev().collect::<Vec<_>>(),
+            vec![&MyComponent(3), &MyComponent(0)]
         );
+
+        // New entities reuse the tombstoned rows
+        let reused = world.spawn((MyComponent(4), Anchor));
+        assert!(before.contains(&world.entity(reused).location().table_row));
+        assert_eq!(world.query::<&MyComponent>().count(), 4);
+        assert_eq!(world.query::<&MyComponent>().map(|c| c.0).sum::<u32>(), 9);
+
+        drop(world);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 5);
     }
 
     #[test]
-    fn world_get() {
-        let mut world = World::new();
+    fn tombstone_removal() {
+        let mut world = World::builder()
+            .removal_mode(RemovalMode::Tombstone)
+            .build();
+        let entities = (0..4)
+            .map(|i| world.spawn(MyComponent(i)))
+            .collect::<Vec<_>>();
+        let row = |world: &World, entity| world.entity(entity).location().table_row;
+        let before = entities.iter().map(|e| row(&world, *e)).collect::<Vec<_>>();
 
-        let entity = world.spawn(Position {
-            x: 1.0,
-            y: 2.0,
-            z: 3.0,
-        });
+        world.despawn(entities[0]);
+        world.insert(entities[1], Disabled);
+        assert_eq!(row(&world, entities[2]), before[2]);
+        assert_eq!(row(&world, entities[3]), before[3]);
+        assert_eq!(world.query::<&MyComponent>().len(), 2);
 
-        assert_eq!(
-            world.get::<Position>(entity),
-            Some(&Position {
-                x: 1.0,
-                y: 2.0,
-                z: 3.0,
-            })
-        );
+        // Switching the mode keeps tombstoning until the holes are filled
+        world.set_removal_mode(RemovalMode::Swap);
+        world.despawn(entities[2]);
+        assert_eq!(row(&world, entities[3]), before[3]);
 
-        let comp = world
-            .get_mut::<Position>(entity)
-            .expect("Was spawned a few instructions ago");
-        comp.z = 42.0;
+        let spawned = (4..7)
+            .map(|i| world.spawn(MyComponent(i)))
+            .collect::<Vec<_>>();
+        let mut reused = spawned.iter().map(|e| row(&world, *e)).collect::<Vec<_>>();
+        reused.sort_unstable();
+        assert_eq!(reused, before[..3]);
+        assert_eq!(world.query::<&MyComponent>().map(|c| c.0).sum::<u32>(), 18);
+
+        world.despawn(entities[3]);
+        assert_eq!(world.query::<&MyComponent>().len(), 3);
+    }
 
+    #[test]
+    fn shift_remove() {
+        let mut world = World::new();
+        world.set_removal_mode(RemovalMode::Shift);
+        let entities = (0..5)
+            .map(|i| world.spawn(MyComponent(i)))
+            .collect::<Vec<_>>();
+
+        world.despawn(entities[1]);
+        world.insert(entities[2], Disabled);
+        let order = world.query::<Entity>().collect::<Vec<_>>();
+        assert_eq!(order, vec![entities[0], entities[3], entities[4]]);
+        for (i, entity) in entities.iter().enumerate().skip(2) {
+            assert_eq!(
+                world.get::<MyComponent>(*entity),
+                Some(&MyComponent(i as u32))
+            );
+        }
+
+        world.enable(entities[2]);
+        let order = world.query::<Entity>().collect::<Vec<_>>();
         assert_eq!(
-            world.get::<Position>(entity),
-            Some(&Position {
-                x: 1.0,
-                y: 2.0,
-                z: 42.0,
-            })
+            order,
+            vec![entities[0], entities[3], entities[4], entities[2]]
         );
     }
 
     #[test]
-    fn despawn() {
+    fn insert() {
         let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(MyComponent(1));
 
-        let e0 = world.spawn(Position {
-            x: 1.0,
-            y: 2.0,
-            z: 3.0,
-        });
-        let e1 = world.spawn(Position {
-            x: 2.0,
-            y: 3.0,
-            z: 4.0,
-        });
+        world.insert(
+            e0,
+            Position {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+        );
 
+        assert_eq!(world.archetypes.len(), 2);
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(0)));
         assert_eq!(
             world.get::<Position>(e0),
             Some(&Position {
@@ -269,77 +3039,303 @@ mod tests {
             })
         );
         assert_eq!(
-            world.entities.get(e0),
+            world.entities.get(e1),
             Some(&EntityLocation {
                 archetype_id: ArchetypeId(0),
                 table_id: TableId(0),
                 table_row: TableRow(0)
             })
         );
+        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
+        assert_eq!(world.get::<Position>(e1), None);
+
+        world.insert(e0, MyComponent(42));
+        assert_eq!(world.archetypes.len(), 2);
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(42)));
+    }
+
+    #[test]
+    fn sparse_set_components() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Component, Debug, PartialEq)]
+        #[component(storage = SparseSet)]
+        struct Stunned(u32);
+
+        impl Drop for Stunned {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Stunned(1)));
+        let location = world.entities.get(e0).copied();
+        assert_eq!(world.archetypes.len(), 1);
+        assert_eq!(world.entities.get(e1).unwrap().table_row, TableRow(1));
+
+        world.insert(e0, Stunned(0));
+        assert_eq!(world.entities.get(e0).copied(), location);
+        assert_eq!(world.get::<Stunned>(e0).map(|stunned| stunned.0), Some(0));
+        world.get_mut::<Stunned>(e1).unwrap().0 = 10;
+        assert_eq!(world.get::<Stunned>(e1).map(|stunned| stunned.0), Some(10));
+        assert!(world.entity(e1).contains::<Stunned>());
+
         assert_eq!(
-            world.get::<Position>(e1),
-            Some(&Position {
-                x: 2.0,
-                y: 3.0,
-                z: 4.0,
-            })
-        );
-        assert_eq!(
-            world.entities.get(e1),
-            Some(&EntityLocation {
-                archetype_id: ArchetypeId(0),
-                table_id: TableId(0),
-                table_row: TableRow(1)
-            })
+            world.remove::<Stunned>(e0).map(|stunned| stunned.0),
+            Some(0)
         );
+        assert_eq!(world.entities.get(e0).copied(), location);
+        assert_eq!(world.get::<Stunned>(e0), None);
+        assert_eq!(world.remove::<Stunned>(e0).map(|stunned| stunned.0), None);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
 
         world.despawn(e0);
+        let mapping = world.compact_entities();
+        let e1 = mapping.get(&e1).copied().unwrap_or(e1);
+        assert_eq!(world.get::<Stunned>(e1).map(|stunned| stunned.0), Some(10));
+        world.despawn(e1);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+        assert_eq!(world.archetypes.len(), 1);
+    }
 
-        assert_eq!(world.get::<Position>(e0), None);
-        assert_eq!(world.entities.get(e0), None);
-        assert_eq!(
-            world.entities.get(e1),
-            Some(&EntityLocation {
-                archetype_id: ArchetypeId(0),
-                table_id: TableId(0),
-                table_row: TableRow(0)
+    #[test]
+    fn dynamic_components() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        unsafe fn drop_name(ptr: OwningPtr<'_>) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            ptr.drop_as::<String>();
+        }
+
+        let mut world = World::new();
+        let name = world.register_component_with_descriptor(ComponentDescriptor::new(
+            "Name",
+            Layout::new::<String>(),
+            Some(drop_name),
+        ));
+        let marker = world.register_component_with_descriptor(
+            ComponentDescriptor::new("Marker", Layout::new::<()>(), None)
+                .with_storage_type(StorageType::SparseSet),
+        );
+        let position = world.components.register_component::<MyComponent>();
+
+        let entity = OwningPtr::make(String::from("a"), |name_ptr| {
+            OwningPtr::make(MyComponent(1), |position_ptr| unsafe {
+                world.spawn_by_ids(&[name, position], [name_ptr, position_ptr])
             })
+        });
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(1)));
+        let ptr = world.get_by_id(entity, name).unwrap();
+        assert_eq!(unsafe { ptr.deref::<String>() }, "a");
+
+        OwningPtr::make(String::from("b"), |ptr| unsafe {
+            world.insert_by_id(entity, name, ptr)
+        });
+        OwningPtr::make((), |ptr| unsafe { world.insert_by_id(entity, marker, ptr) });
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+        let ptr = world.get_by_id(entity, name).unwrap();
+        assert_eq!(unsafe { ptr.deref::<String>() }, "b");
+        assert!(world.has_sparse(entity, marker));
+
+        world.remove_by_id(entity, marker);
+        assert!(!world.has_sparse(entity, marker));
+        world.despawn(entity);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn archetype_edges() {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Other(u32);
+        impl Component for Other {}
+
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(MyComponent(1));
+        let from = world.entity(e0).location().archetype_id;
+
+        world.insert(e0, Other(0));
+        let to = world.entity(e0).location().archetype_id;
+        let other = world.component_id::<Other>().unwrap();
+        assert_eq!(
+            world.archetypes.get_unchecked(from).insert_edge(&[other]),
+            Some(to)
         );
+
+        // The cached edge leads to the same archetype
+        world.insert(e1, Other(1));
+        assert_eq!(world.entity(e1).location().archetype_id, to);
+
+        world.remove::<Other>(e0);
+        assert_eq!(world.entity(e0).location().archetype_id, from);
         assert_eq!(
-            world.get::<Position>(e1),
-            Some(&Position {
-                x: 2.0,
-                y: 3.0,
-                z: 4.0,
-            })
+            world.archetypes.get_unchecked(to).remove_edge(&[other]),
+            Some(from)
         );
 
-        world.despawn(e1);
-        assert_eq!(world.get::<Position>(e1), None);
-        assert_eq!(world.entities.get(e1), None);
+        // Adding the same components in another order reaches the same archetype
+        let e2 = world.spawn(Other(2));
+        world.insert(e2, MyComponent(2));
+        assert_eq!(world.entity(e2).location().archetype_id, to);
+        assert_eq!(world.archetypes.len(), 3);
     }
 
     #[test]
-    fn swap_remove() {
+    fn remove() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Tracked(u32);
+        impl Component for Tracked {}
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        for mode in [RemovalMode::Swap, RemovalMode::Tombstone] {
+            DROPPED.store(0, Ordering::Relaxed);
+            let mut world = World::new();
+            world.set_removal_mode(mode);
+            let e0 = world.spawn((MyComponent(0), Tracked(0)));
+            let e1 = world.spawn((MyComponent(1), Tracked(1)));
+
+            let removed = world.remove::<Tracked>(e0).unwrap();
+            assert_eq!(removed.0, 0);
+            assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+            drop(removed);
+            assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+
+            assert!(world.remove::<Tracked>(e0).is_none());
+            assert!(world.remove::<Position>(e0).is_none());
+            assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(0)));
+            assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
+            assert_eq!(world.get::<Tracked>(e1).map(|t| t.0), Some(1));
+            assert_eq!(world.query::<&Tracked>().count(), 1);
+
+            assert_eq!(world.remove::<MyComponent>(e1), Some(MyComponent(1)));
+            drop(world);
+            assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+        }
+    }
+
+    #[test]
+    fn component_hooks() {
+        static ADDED: AtomicUsize = AtomicUsize::new(0);
+        static INSERTED: AtomicUsize = AtomicUsize::new(0);
+        static REPLACED: AtomicUsize = AtomicUsize::new(0);
+        static REMOVED: AtomicUsize = AtomicUsize::new(0);
+
         let mut world = World::new();
+        world
+            .register_component_hooks::<MyComponent>()
+            .on_add(|_, _, _| {
+                ADDED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_insert(|_, _, _| {
+                INSERTED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_replace(|world, entity, _| {
+                // The previous value is still readable
+                assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(0)));
+                REPLACED.fetch_add(1, Ordering::Relaxed);
+            })
+            .on_remove(|_, _, _| {
+                REMOVED.fetch_add(1, Ordering::Relaxed);
+            });
+
         let e0 = world.spawn(MyComponent(0));
-        let e1 = world.spawn(MyComponent(1));
-        let e2 = world.spawn(MyComponent(2));
+        let e1 = world.spawn(Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        assert_eq!(ADDED.load(Ordering::Relaxed), 1);
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 1);
 
-        world.despawn(e0);
-        assert_eq!(world.get::<MyComponent>(e2), Some(&MyComponent(2)));
-        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
-        assert_eq!(world.get::<MyComponent>(e0), None);
+        world.insert(e1, MyComponent(0));
+        assert_eq!(ADDED.load(Ordering::Relaxed), 2);
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 2);
+        assert_eq!(REPLACED.load(Ordering::Relaxed), 0);
 
-        world.despawn(e2);
-        assert_eq!(world.get::<MyComponent>(e2), None);
-        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(1)));
-        assert_eq!(world.get::<MyComponent>(e0), None);
+        world.insert(e0, MyComponent(1));
+        assert_eq!(ADDED.load(Ordering::Relaxed), 2);
+        assert_eq!(INSERTED.load(Ordering::Relaxed), 3);
+        assert_eq!(REPLACED.load(Ordering::Relaxed), 1);
 
+        world.despawn(e0);
         world.despawn(e1);
-        assert_eq!(world.get::<MyComponent>(e2), None);
-        assert_eq!(world.get::<MyComponent>(e1), None);
-        assert_eq!(world.get::<MyComponent>(e0), None);
+        assert_eq!(REMOVED.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn archetype_created() {
+        let created = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+
+        let observed = created.clone();
+        world.on_archetype_created(move |event| {
+            observed
+                .lock()
+                .unwrap()
+                .push((event.id, event.components.to_vec()));
+        });
+
+        let e0 = world.spawn(MyComponent(1));
+        world.insert(
+            e0,
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+        world.spawn((
+            MyComponent(2),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+
+        let my_component = world.component_id::<MyComponent>().unwrap();
+        let position = world.component_id::<Position>().unwrap();
+        assert_eq!(
+            *created.lock().unwrap(),
+            vec![(ArchetypeId(1), vec![my_component, position])]
+        );
+    }
+
+    #[test]
+    fn drop_components() {
+        struct DropCounter(Arc<AtomicUsize>);
+        impl Component for DropCounter {}
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::new();
+        let e0 = world.spawn(DropCounter(drops.clone()));
+        let e1 = world.spawn(DropCounter(drops.clone()));
+        let e2 = world.spawn(DropCounter(drops.clone()));
+
+        world.despawn(e0);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+        world.insert(e1, DropCounter(drops.clone()));
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        world.insert(e2, MyComponent(0));
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        drop(world);
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
     }
 
     // #[test]
@@ -391,4 +3387,105 @@ mod tests {
     //     );
     //     assert_eq!(query.next(), None);
     // }
+
+    #[test]
+    fn try_spawn() {
+        let mut world = World::new();
+        let position = Position {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+
+        let entity = world.try_spawn(MyComponent(1)).unwrap();
+        world.try_insert(entity, position).unwrap();
+        world.try_insert(entity, MyComponent(2)).unwrap();
+
+        assert_eq!(world.get::<MyComponent>(entity), Some(&MyComponent(2)));
+        assert_eq!(world.get::<Position>(entity), Some(&position));
+    }
+
+    #[test]
+    fn shared_component_ids() {
+        let mut first = World::with_shared_component_ids(SharedComponentIds::global());
+        let mut second = World::with_shared_component_ids(SharedComponentIds::global());
+
+        second.spawn(MyComponent(1));
+        first.spawn((
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            MyComponent(2),
+        ));
+        second.spawn(Position {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        });
+
+        assert_eq!(
+            first.component_id::<MyComponent>(),
+            second.component_id::<MyComponent>()
+        );
+        assert_eq!(
+            first.component_id::<Position>(),
+            second.component_id::<Position>()
+        );
+        let mut local = World::new();
+        local.spawn(Position {
+            x: 2.0,
+            y: 2.0,
+            z: 2.0,
+        });
+        assert_eq!(local.component_id::<Position>(), Some(ComponentId::new(0)));
+    }
+
+    #[test]
+    fn column_views() {
+        #[derive(Debug, PartialEq)]
+        #[repr(C)]
+        struct Instance([f32; 4]);
+        impl Component for Instance {}
+
+        let mut world = World::builder()
+            .removal_mode(RemovalMode::Tombstone)
+            .build();
+        let entities = (0..3)
+            .map(|i| world.spawn(Instance([i as f32; 4])))
+            .collect::<Vec<_>>();
+        world.spawn((Instance([3.0; 4]), MyComponent(3)));
+        world.spawn((Instance([4.0; 4]), Disabled));
+
+        let views = world.column_views::<Instance>().collect::<Vec<_>>();
+        assert_eq!(views.len(), 2);
+        let view = views[0];
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.stride(), 16);
+        assert_eq!(view.entities(), entities);
+        assert_eq!(view.as_slice::<Instance>().unwrap()[1], Instance([1.0; 4]));
+        assert_eq!(view.as_slice::<MyComponent>(), None);
+        let bytes = unsafe { std::slice::from_raw_parts(view.ptr(), view.len() * view.stride()) };
+        assert_eq!(bytes[32..36], 2.0f32.to_ne_bytes());
+
+        world.despawn(entities[1]);
+        let view = world.column_views::<Instance>().next().unwrap();
+        assert!(!view.is_dense());
+        assert!(!view.is_live(1));
+        assert_eq!(view.as_slice::<Instance>(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no table columns")]
+    fn column_views_sparse() {
+        struct Marker;
+        impl Component for Marker {
+            const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+        }
+
+        let mut world = World::new();
+        world.spawn(Marker);
+        let _ = world.column_views::<Marker>();
+    }
 }
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use crate::{
+    component::{Component, ComponentId},
+    entity::Entity,
+    hierarchy::{Children, Parent},
+    query::{IncludeDisabled, Query},
+    World,
+};
+
+pub use quartz_macros::MapEntities;
+
+/// Maps an entity reference to another entity.
+pub trait EntityMapper {
+    fn map_entity(&mut self, entity: Entity) -> Entity;
+}
+
+impl<F: FnMut(Entity) -> Entity> EntityMapper for F {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self(entity)
+    }
+}
+
+/// Maps the keys of the map to their values, e.g. from the entities of a saved world to the
+/// entities they were loaded as. Entities that are not in the map are kept.
+impl EntityMapper for HashMap<Entity, Entity> {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.get(&entity).copied().unwrap_or(entity)
+    }
+}
+
+/// Values holding [`Entity`] references that can be visited and rewritten, e.g. to flag
+/// references to despawned entities with [`World::clear_dangling_entities`].
+///
+/// Can be derived for types whose fields of type `Entity`, `Option<Entity>` and `Vec<Entity>`
+/// hold the references, other fields implementing `MapEntities` are included with `#[entities]`:
+///
+/// ```
+/// # use quartz::{entity::Entity, map_entities::MapEntities};
+/// #[derive(MapEntities)]
+/// struct Squad {
+///     leader: Entity,
+///     members: Vec<Entity>,
+///     #[entities]
+///     formation: [Entity; 3],
+///     name: String,
+/// }
+/// ```
+pub trait MapEntities {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M);
+}
+
+impl MapEntities for Entity {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        *self = mapper.map_entity(*self);
+    }
+}
+
+impl<T: MapEntities> MapEntities for Option<T> {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        if let Some(value) = self {
+            value.map_entities(mapper);
+        }
+    }
+}
+
+impl<T: MapEntities> MapEntities for Vec<T> {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        for value in self {
+            value.map_entities(mapper);
+        }
+    }
+}
+
+impl<T: MapEntities, const N: usize> MapEntities for [T; N] {
+    fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+        for value in self {
+            value.map_entities(mapper);
+        }
+    }
+}
+
+/// Maps the references in every `T`.
+///
+/// Takes the world by shared reference, as components are written through the query while the
+/// mapper may read the world. The caller must have exclusive access to the world.
+fn map_component<T: Component + MapEntities>(
+    world: &World,
+    mut mapper: &mut dyn FnMut(Entity) -> Entity,
+) {
+    if world.component_id::<T>().is_none() {
+        return;
+    }
+    for component in Query::<&mut T, IncludeDisabled>::new(world) {
+        component.map_entities(&mut mapper);
+    }
+}
+
+/// Maps the references in the `T` of a single entity, if it has one.
+fn map_entity_component<T: Component + MapEntities>(
+    world: &mut World,
+    entity: Entity,
+    mut mapper: &mut dyn FnMut(Entity) -> Entity,
+) {
+    if let Some(component) = world.get_mut::<T>(entity) {
+        component.map_entities(&mut mapper);
+    }
+}
+
+/// Maps the references in all components of one type, see [`map_component`].
+type MapComponent = fn(&World, &mut dyn FnMut(Entity) -> Entity);
+
+/// Maps the references in the component of one entity, see [`map_entity_component`].
+type MapEntityComponent = fn(&mut World, Entity, &mut dyn FnMut(Entity) -> Entity);
+
+/// The components registered with [`World::register_entity_refs`].
+#[derive(Debug, Default)]
+pub(crate) struct EntityRefs {
+    components: HashMap<ComponentId, (MapComponent, MapEntityComponent)>,
+}
+
+impl World {
+    /// Registers `T` as holding entity references, which are checked by
+    /// [`World::clear_dangling_entities`].
+    pub fn register_entity_refs<T: Component + MapEntities>(&mut self) {
+        let id = self.components.register_component::<T>();
+        self.entity_refs
+            .components
+            .insert(id, (map_component::<T>, map_entity_component::<T>));
+    }
+
+    /// Maps the references in the components of the entity that are registered with
+    /// [`World::register_entity_refs`], e.g. after copying the components from another world.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use quartz::{component::Component, entity::Entity, map_entities::MapEntities, World};
+    /// #[derive(Component, MapEntities)]
+    /// struct Follow(Entity);
+    ///
+    /// let mut world = World::new();
+    /// world.register_entity_refs::<Follow>();
+    /// let [old, new] = [world.spawn(()), world.spawn(())];
+    /// let follower = world.spawn(Follow(old));
+    ///
+    /// world.map_entity_refs(follower, &mut HashMap::from([(old, new)]));
+    /// assert_eq!(world.get::<Follow>(follower).unwrap().0, new);
+    /// ```
+    pub fn map_entity_refs(&mut self, entity: Entity, mapper: &mut impl EntityMapper) {
+        let mut mapper = |entity| mapper.map_entity(entity);
+        let maps = self
+            .entity_refs
+            .components
+            .values()
+            .map(|(_, map)| *map)
+            .collect::<Vec<_>>();
+        for map in maps {
+            map(self, entity, &mut mapper);
+        }
+    }
+
+    /// Maps the references in the component of the entity, if it is registered with
+    /// [`World::register_entity_refs`].
+    pub(crate) fn map_component_refs(
+        &mut self,
+        entity: Entity,
+        id: ComponentId,
+        mapper: &mut dyn FnMut(Entity) -> Entity,
+    ) {
+        if let Some(&(_, map)) = self.entity_refs.components.get(&id) {
+            map(self, entity, mapper);
+        }
+    }
+
+    /// Replaces every reference to a despawned entity in the components registered with
+    /// [`World::register_entity_refs`] with [`Entity::PLACEHOLDER`] and returns how many
+    /// references were replaced.
+    ///
+    /// Running this once per frame keeps targeting and AI code from acting on stale entities.
+    pub fn clear_dangling_entities(&mut self) -> usize {
+        let world = &*self;
+        let mut cleared = 0;
+        let mut mapper = |entity: Entity| {
+            if entity == Entity::PLACEHOLDER || world.entities.contains(entity) {
+                entity
+            } else {
+                cleared += 1;
+                Entity::PLACEHOLDER
+            }
+        };
+
+        for (map, _) in world.entity_refs.components.values() {
+            map(world, &mut mapper);
+        }
+        cleared
+    }
+
+    /// Moves all entities to the lowest entity indices and releases the unused slots, which
+    /// shrinks the memory used for entities and improves lookup locality after heavy churn.
+    ///
+    /// Moved entities get a new id. References to them are updated in the hierarchy, in all
+    /// [`Index`](crate::index::Index)es, in the targets of observers and in the components
+    /// registered with
+    /// [`World::register_entity_refs`]. All other ids held outside the world become invalid and
+    /// can be mapped with the returned map from previous to new ids. Stale ids of the released
+    /// slots may be handed out again later.
+    ///
+    /// Panics: If entities reserved by [`Commands`](crate::command::Commands) have not been
+    /// spawned yet, i.e. their command queue has not been applied.
+    pub fn compact_entities(&mut self) -> HashMap<Entity, Entity> {
+        self.flush();
+        let moved = self.entities.compact();
+        for (_, entity, location) in moved.iter() {
+            self.tables
+                .get_mut_unchecked(location.table_id)
+                .set_entity(location.table_row, *entity);
+            self.archetypes
+                .get_mut_unchecked(location.archetype_id)
+                .set_entity(location.table_row, *entity);
+        }
+
+        let map = moved
+            .into_iter()
+            .map(|(previous, entity, _)| (previous, entity))
+            .collect::<HashMap<_, _>>();
+        let mut mapper = |entity: Entity| map.get(&entity).copied().unwrap_or(entity);
+        self.sparse_sets.map_entities(&mut mapper);
+
+        let world = &*self;
+        map_component::<Parent>(world, &mut mapper);
+        map_component::<Children>(world, &mut mapper);
+        for (map, _) in world.entity_refs.components.values() {
+            map(world, &mut mapper);
+        }
+
+        self.observers.map_targets(&mut mapper);
+
+        // Keys may be extracted from the mapped references, so the indexes are refilled
+        crate::index::rebuild(self);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        component::Component,
+        entity::Entity,
+        hierarchy::{Children, Parent},
+        World,
+    };
+
+    use super::{EntityMapper, MapEntities};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Target(Entity);
+    impl Component for Target {}
+
+    impl MapEntities for Target {
+        fn map_entities<M: EntityMapper>(&mut self, mapper: &mut M) {
+            self.0.map_entities(mapper);
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, MapEntities)]
+    struct Allies(Vec<Entity>, Option<Entity>);
+    impl Component for Allies {}
+
+    #[derive(Debug, PartialEq, Eq, MapEntities)]
+    struct Squad {
+        leader: Entity,
+        name: &'static str,
+        #[entities]
+        target: Target,
+    }
+
+    #[derive(Debug, PartialEq, Eq, MapEntities)]
+    enum Order {
+        Idle,
+        Follow(Entity),
+        Attack { target: Option<Entity>, count: u32 },
+    }
+
+    #[test]
+    fn derive_map_entities() {
+        let a = Entity::from(1, 0);
+        let b = Entity::from(1, 1);
+        let mut mapper = |entity: Entity| if entity == a { b } else { entity };
+
+        let mut squad = Squad {
+            leader: a,
+            name: "squad",
+            target: Target(a),
+        };
+        squad.map_entities(&mut mapper);
+        assert_eq!(
+            squad,
+            Squad {
+                leader: b,
+                name: "squad",
+                target: Target(b),
+            }
+        );
+
+        let mut orders = [
+            Order::Idle,
+            Order::Follow(a),
+            Order::Attack {
+                target: Some(a),
+                count: 2,
+            },
+        ];
+        orders
+            .iter_mut()
+            .for_each(|order| order.map_entities(&mut mapper));
+        assert_eq!(
+            orders,
+            [
+                Order::Idle,
+                Order::Follow(b),
+                Order::Attack {
+                    target: Some(b),
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn map_entity_refs() {
+        let mut world = World::new();
+        world.register_entity_refs::<Target>();
+        world.register_entity_refs::<Allies>();
+
+        let [a, b, c] = [(); 3].map(|()| world.spawn(()));
+        let entity = world.spawn((Target(a), Allies(vec![a, b], None)));
+        let other = world.spawn(Target(a));
+
+        let mut map = HashMap::from([(a, c), (b, a)]);
+        assert_eq!(map.map_entity(c), c);
+        world.map_entity_refs(entity, &mut map);
+        assert_eq!(world.get::<Target>(entity), Some(&Target(c)));
+        assert_eq!(world.get::<Allies>(entity), Some(&Allies(vec![c, a], None)));
+        assert_eq!(world.get::<Target>(other), Some(&Target(a)));
+    }
+
+    #[test]
+    fn clear_dangling_entities() {
+        let mut world = World::new();
+        world.register_entity_refs::<Target>();
+        world.register_entity_refs::<Allies>();
+
+        let a = world.spawn(Target(Entity::PLACEHOLDER));
+        let b = world.spawn(Target(a));
+        let c = world.spawn(Allies(vec![a, b], Some(b)));
+        world.insert(a, Target(b));
+
+        assert_eq!(world.clear_dangling_entities(), 0);
+
+        world.despawn(b);
+        assert_eq!(world.clear_dangling_entities(), 3);
+        assert_eq!(world.get::<Target>(a), Some(&Target(Entity::PLACEHOLDER)));
+        assert_eq!(
+            world.get::<Allies>(c),
+            Some(&Allies(
+                vec![a, Entity::PLACEHOLDER],
+                Some(Entity::PLACEHOLDER)
+            ))
+        );
+
+        assert_eq!(world.clear_dangling_entities(), 0);
+    }
+
+    #[test]
+    fn compact_entities() {
+        let mut world = World::new();
+        world.register_entity_refs::<Target>();
+        world.init_index::<Target, Entity>(|target| target.0);
+
+        let entities = (0..8)
+            .map(|_| world.spawn(Target(Entity::PLACEHOLDER)))
+            .collect::<Vec<_>>();
+        for entity in &entities[..6] {
+            world.despawn(*entity);
+        }
+        let (a, b) = (entities[6], entities[7]);
+        world.insert(a, Target(b));
+        world.insert(b, Target(a));
+        world.set_parent(b, a);
+
+        let map = world.compact_entities();
+        assert_eq!(map.len(), 2);
+        assert_eq!(world.entities.len(), 2);
+        let (a, b) = (map[&a], map[&b]);
+        assert_eq!(world.get::<Target>(a), Some(&Target(b)));
+        assert_eq!(world.get::<Target>(b), Some(&Target(a)));
+        assert_eq!(world.get::<Parent>(b).map(Parent::get), Some(a));
+        assert_eq!(
+            world.get::<Children>(a).map(|c| c.as_slice().to_vec()),
+            Some(vec![b])
+        );
+        assert_eq!(world.index::<Target, Entity>().get(&a), &[b]);
+
+        let found = world.query::<(Entity, &Target)>().collect::<Vec<_>>();
+        assert_eq!(found.len(), 2);
+        assert!(found.contains(&(a, &Target(b))) && found.contains(&(b, &Target(a))));
+
+        world.remove_parent(b);
+        world.despawn(a);
+        let map = world.compact_entities();
+        assert_eq!(world.entities.len(), 1);
+        assert_eq!(world.get::<Target>(map[&b]), Some(&Target(a)));
+        assert!(world.compact_entities().is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+};
+
+use crate::{entity::Entity, event::Event, World};
+
+/// Identifies an observer registered with [`World::observe`] or [`World::observe_entity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+/// The event passed to an observer, together with the entity it targets.
+pub struct Trigger<'a, E: Event> {
+    event: &'a E,
+    target: Entity,
+}
+
+impl<'a, E: Event> Trigger<'a, E> {
+    pub fn event(&self) -> &'a E {
+        self.event
+    }
+
+    pub fn target(&self) -> Entity {
+        self.target
+    }
+}
+
+impl<E: Event + fmt::Debug> fmt::Debug for Trigger<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trigger")
+            .field("event", self.event)
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+type ObserverFn<E> = Box<dyn FnMut(Trigger<'_, E>, &mut World) + Send + Sync>;
+
+struct Observer<E: Event> {
+    id: ObserverId,
+    /// Observes only the events targeting this entity if set, or all events of `E` otherwise.
+    target: Option<Entity>,
+    /// Taken out while the observer runs.
+    run: Option<ObserverFn<E>>,
+}
+
+/// The observers of the event `E`, in the order they were registered.
+struct ObserverList<E: Event> {
+    observers: Vec<Observer<E>>,
+}
+
+/// The type erased operations on an [`ObserverList`].
+trait AnyObserverList: Send + Sync {
+    fn remove(&mut self, id: ObserverId) -> bool;
+
+    fn remove_target(&mut self, target: Entity);
+
+    fn map_targets(&mut self, mapper: &mut dyn FnMut(Entity) -> Entity);
+
+    fn len(&self) -> usize;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: Event> AnyObserverList for ObserverList<E> {
+    fn remove(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|observer| observer.id != id);
+        self.observers.len() != len
+    }
+
+    fn remove_target(&mut self, target: Entity) {
+        self.observers
+            .retain(|observer| observer.target != Some(target));
+    }
+
+    fn map_targets(&mut self, mapper: &mut dyn FnMut(Entity) -> Entity) {
+        for target in self
+            .observers
+            .iter_mut()
+            .filter_map(|observer| observer.target.as_mut())
+        {
+            *target = mapper(*target);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The observers of a world, indexed by the type of their event.
+#[derive(Default)]
+pub(crate) struct Observers {
+    lists: HashMap<TypeId, Box<dyn AnyObserverList>>,
+    next_id: u64,
+}
+
+impl Observers {
+    fn list_mut<E: Event>(&mut self) -> Option<&mut ObserverList<E>> {
+        self.lists.get_mut(&TypeId::of::<E>()).map(|list| {
+            list.as_any_mut()
+                .downcast_mut()
+                .expect("observers are stored by event type")
+        })
+    }
+
+    fn insert<E: Event>(&mut self, target: Option<Entity>, run: ObserverFn<E>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        if self.list_mut::<E>().is_none() {
+            self.lists.insert(
+                TypeId::of::<E>(),
+                Box::new(ObserverList::<E> {
+                    observers: Vec::new(),
+                }),
+            );
+        }
+        self.list_mut::<E>()
+            .expect("the list was just inserted")
+            .observers
+            .push(Observer {
+                id,
+                target,
+                run: Some(run),
+            });
+        id
+    }
+
+    /// Removes the observers of the despawned entity.
+    pub(crate) fn remove_target(&mut self, target: Entity) {
+        for list in self.lists.values_mut() {
+            list.remove_target(target);
+        }
+    }
+
+    /// Updates the observed entities after they moved, see [`World::compact_entities`].
+    pub(crate) fn map_targets(&mut self, mapper: &mut impl FnMut(Entity) -> Entity) {
+        for list in self.lists.values_mut() {
+            list.map_targets(mapper);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field(
+                "observers",
+                &self.lists.values().map(|list| list.len()).sum::<usize>(),
+            )
+            .finish()
+    }
+}
+
+impl World {
+    /// Registers an observer that runs whenever an event of type `E` is triggered, regardless of
+    /// its target, see [`World::trigger`].
+    ///
+    /// ```
+    /// # use quartz::{component::Component, event::Event, observer::Trigger, World};
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// #[derive(Event)]
+    /// struct OnDamage(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.observe(|trigger: Trigger<OnDamage>, world: &mut World| {
+    ///     let health = world.get_mut::<Health>(trigger.target()).unwrap();
+    ///     health.0 = health.0.saturating_sub(trigger.event().0);
+    ///     if health.0 == 0 {
+    ///         world.despawn(trigger.target());
+    ///     }
+    /// });
+    ///
+    /// let entity = world.spawn(Health(5));
+    /// world.trigger(entity, OnDamage(3));
+    /// assert_eq!(world.get::<Health>(entity).unwrap().0, 2);
+    /// world.trigger(entity, OnDamage(3));
+    /// assert!(world.get_entity(entity).is_none());
+    /// ```
+    pub fn observe<E: Event>(
+        &mut self,
+        observer: impl FnMut(Trigger<'_, E>, &mut World) + Send + Sync + 'static,
+    ) -> ObserverId {
+        self.observers.insert(None, Box::new(observer))
+    }
+
+    /// Registers an observer that runs whenever an event of type `E` is triggered for the entity.
+    /// The observer is removed when the entity is despawned.
+    pub fn observe_entity<E: Event>(
+        &mut self,
+        entity: Entity,
+        observer: impl FnMut(Trigger<'_, E>, &mut World) + Send + Sync + 'static,
+    ) -> ObserverId {
+        self.observers.insert(Some(entity), Box::new(observer))
+    }
+
+    /// Removes the observer, returns `false` if it was already removed.
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers
+            .lists
+            .values_mut()
+            .any(|list| list.remove(id))
+    }
+
+    /// Runs the observers of `E` that observe all events or the `target` right away, in the
+    /// order they were registered.
+    ///
+    /// Observers may trigger further events. An observer that triggers its own event doesn't
+    /// observe it again, and observers registered while the event is triggered only observe later
+    /// events.
+    pub fn trigger<E: Event>(&mut self, target: Entity, event: E) {
+        let Some(list) = self.observers.list_mut::<E>() else {
+            return;
+        };
+        let ids = list
+            .observers
+            .iter()
+            .filter(|observer| observer.target.is_none() || observer.target == Some(target))
+            .map(|observer| observer.id)
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            let Some(mut run) = self.observers.list_mut::<E>().and_then(|list| {
+                list.observers
+                    .iter_mut()
+                    .find(|observer| observer.id == id)
+                    .and_then(|observer| observer.run.take())
+            }) else {
+                // Removed or running already
+                continue;
+            };
+
+            run(
+                Trigger {
+                    event: &event,
+                    target,
+                },
+                self,
+            );
+
+            // The observer is dropped if it was removed while it ran
+            if let Some(observer) = self
+                .observers
+                .list_mut::<E>()
+                .and_then(|list| list.observers.iter_mut().find(|observer| observer.id == id))
+            {
+                observer.run = Some(run);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{component::Component, entity::Entity, event::Event, World};
+
+    use super::Trigger;
+
+    #[derive(Debug, PartialEq, Event)]
+    struct Ping(u32);
+
+    #[derive(Component)]
+    struct Marker;
+
+    #[test]
+    fn observers() {
+        let mut world = World::new();
+        let e0 = world.spawn(Marker);
+        let e1 = world.spawn(Marker);
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let global = world.observe({
+            let log = log.clone();
+            move |trigger: Trigger<Ping>, _: &mut World| {
+                log.lock()
+                    .unwrap()
+                    .push(("global", trigger.target(), trigger.event().0));
+            }
+        });
+        world.observe_entity(e1, {
+            let log = log.clone();
+            move |trigger: Trigger<Ping>, _: &mut World| {
+                log.lock()
+                    .unwrap()
+                    .push(("e1", trigger.target(), trigger.event().0));
+            }
+        });
+
+        world.trigger(e0, Ping(0));
+        world.trigger(e1, Ping(1));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [("global", e0, 0), ("global", e1, 1), ("e1", e1, 1)]
+        );
+
+        log.lock().unwrap().clear();
+        assert!(world.remove_observer(global));
+        assert!(!world.remove_observer(global));
+        world.despawn(e1);
+        world.trigger(e1, Ping(2));
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn compacted_target() {
+        let mut world = World::new();
+        let removed = world.spawn(Marker);
+        let entity = world.spawn(Marker);
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        world.observe_entity(entity, {
+            let hits = hits.clone();
+            move |trigger: Trigger<Ping>, _: &mut World| hits.lock().unwrap().push(trigger.target())
+        });
+
+        world.despawn(removed);
+        let entity = world.compact_entities()[&entity];
+        world.trigger(entity, Ping(0));
+        assert_eq!(*hits.lock().unwrap(), [entity]);
+    }
+
+    #[test]
+    fn nested_triggers() {
+        let mut world = World::new();
+        let entity = world.spawn(Marker);
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        world.observe({
+            let log = log.clone();
+            move |trigger: Trigger<Ping>, world: &mut World| {
+                let count = trigger.event().0;
+                log.lock().unwrap().push(count);
+                // Doesn't run this observer again
+                world.trigger(trigger.target(), Ping(count + 1));
+            }
+        });
+        world.observe({
+            let log = log.clone();
+            move |trigger: Trigger<Ping>, world: &mut World| {
+                log.lock().unwrap().push(trigger.event().0 + 10);
+                if trigger.event().0 == 0 {
+                    world.observe(|_: Trigger<Ping>, _: &mut World| panic!("registered late"));
+                }
+            }
+        });
+
+        world.trigger(entity, Ping(0));
+        assert_eq!(*log.lock().unwrap(), [0, 11, 10]);
+    }
+}
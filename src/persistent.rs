@@ -0,0 +1,166 @@
+use std::{
+    error::Error,
+    fmt,
+    hash::{BuildHasher, Hasher, RandomState},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::component::Component;
+
+/// A stable identity of an entity in the form of a random (version 4) UUID.
+///
+/// Unlike [`Entity`](crate::entity::Entity), which is only valid for the lifetime of a single
+/// world, a `PersistentId` survives saving and loading and can be shared between processes. The
+/// world keeps a map from ids to entities, see
+/// [`World::persistent_entity`](crate::World::persistent_entity).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PersistentId(u128);
+
+impl Component for PersistentId {}
+
+impl PersistentId {
+    /// Generates a new random id.
+    pub fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos());
+        let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        // Every `RandomState` is seeded with fresh random keys
+        let mut random = || {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u64(counter);
+            hasher.finish() as u128
+        };
+        let bits = (random() << 64) | random();
+
+        // Set the version (4) and variant (RFC 4122) bits
+        let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+        let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+
+        Self(bits)
+    }
+
+    pub const fn from_u128(bits: u128) -> Self {
+        Self(bits)
+    }
+
+    pub const fn as_u128(self) -> u128 {
+        self.0
+    }
+}
+
+impl Default for PersistentId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the id in the hyphenated UUID form, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+impl fmt::Display for PersistentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            bits >> 96,
+            (bits >> 80) & 0xffff,
+            (bits >> 64) & 0xffff,
+            (bits >> 48) & 0xffff,
+            bits & 0xffff_ffff_ffff,
+        )
+    }
+}
+
+impl fmt::Debug for PersistentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PersistentId({self})")
+    }
+}
+
+impl FromStr for PersistentId {
+    type Err = ParsePersistentIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups = s.split('-').collect::<Vec<_>>();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len()
+            || groups
+                .iter()
+                .zip(lengths)
+                .any(|(group, len)| group.len() != len)
+        {
+            return Err(ParsePersistentIdError);
+        }
+
+        let mut bits = 0;
+        for group in groups {
+            if !group.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return Err(ParsePersistentIdError);
+            }
+            let value = u128::from_str_radix(group, 16).map_err(|_| ParsePersistentIdError)?;
+            bits = (bits << (group.len() * 4)) | value;
+        }
+
+        Ok(Self(bits))
+    }
+}
+
+/// The error returned when parsing a [`PersistentId`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePersistentIdError;
+
+impl fmt::Display for ParsePersistentIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a hyphenated UUID like `67e55044-10b1-426f-9247-bb680e5fe0c8`"
+        )
+    }
+}
+
+impl Error for ParsePersistentIdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParsePersistentIdError, PersistentId};
+
+    #[test]
+    fn generate() {
+        let a = PersistentId::new();
+        let b = PersistentId::new();
+        assert_ne!(a, b);
+
+        // Version 4, RFC 4122 variant
+        assert_eq!((a.as_u128() >> 76) & 0xf, 4);
+        assert_eq!((a.as_u128() >> 62) & 0x3, 2);
+    }
+
+    #[test]
+    fn display_and_parse() {
+        let id = PersistentId::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8);
+        assert_eq!(id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!("67e55044-10b1-426f-9247-bb680e5fe0c8".parse(), Ok(id));
+
+        let id = PersistentId::new();
+        assert_eq!(id.to_string().parse(), Ok(id));
+
+        assert_eq!(
+            "67e55044-10b1-426f-9247".parse::<PersistentId>(),
+            Err(ParsePersistentIdError)
+        );
+        assert_eq!(
+            "67e55044-10b1-426f-9247-bb680e5fe0cg".parse::<PersistentId>(),
+            Err(ParsePersistentIdError)
+        );
+        assert_eq!(
+            "+7e55044-10b1-426f-9247-bb680e5fe0c8".parse::<PersistentId>(),
+            Err(ParsePersistentIdError)
+        );
+    }
+}
@@ -25,6 +25,10 @@ macro_rules! impl_ptr {
         }
 
         impl $ptr<'_> {
+            /// Offsets the pointer by `count` bytes.
+            ///
+            /// # Safety
+            /// The resulting pointer must stay within the bounds of the same allocation.
             #[inline]
             pub unsafe fn byte_offset(self, count: isize) -> Self {
                 Self(
@@ -33,6 +37,10 @@ macro_rules! impl_ptr {
                 )
             }
 
+            /// Advances the pointer by `count` bytes.
+            ///
+            /// # Safety
+            /// The resulting pointer must stay within the bounds of the same allocation.
             #[inline]
             pub unsafe fn byte_add(self, count: usize) -> Self {
                 Self(
@@ -56,11 +64,19 @@ impl_ptr!(MutPtr);
 impl_ptr!(OwningPtr);
 
 impl<'a> Ptr<'a> {
+    /// Creates a new [`Ptr`] from a raw pointer.
+    ///
+    /// # Safety
+    /// `inner` must point to valid data that is not mutated for the lifetime `'a`.
     #[inline]
     pub unsafe fn new(inner: NonNull<u8>) -> Self {
         Self(inner, PhantomData)
     }
 
+    /// Converts the pointer into a reference of type `T`.
+    ///
+    /// # Safety
+    /// The pointee must be a valid, properly aligned `T`.
     #[inline]
     pub unsafe fn deref<T>(self) -> &'a T {
         let ptr = self.as_ptr().cast::<T>();
@@ -81,16 +97,29 @@ impl<'a, T: ?Sized> From<&'a T> for Ptr<'a> {
 }
 
 impl<'a> MutPtr<'a> {
+    /// Creates a new [`MutPtr`] from a raw pointer.
+    ///
+    /// # Safety
+    /// `inner` must point to valid data that is exclusively accessible for the lifetime `'a`.
     #[inline]
     pub unsafe fn new(inner: NonNull<u8>) -> Self {
         Self(inner, PhantomData)
     }
 
+    /// Promotes the pointer to an [`OwningPtr`], taking ownership of the pointee.
+    ///
+    /// # Safety
+    /// The pointee must not be used again after it has been moved out or dropped through the
+    /// returned pointer.
     #[inline]
     pub unsafe fn promote(self) -> OwningPtr<'a> {
         OwningPtr(self.0, PhantomData)
     }
 
+    /// Converts the pointer into a mutable reference of type `T`.
+    ///
+    /// # Safety
+    /// The pointee must be a valid, properly aligned `T`.
     #[inline]
     pub unsafe fn deref_mut<T>(self) -> &'a mut T {
         let ptr = self.as_ptr().cast::<T>();
@@ -116,17 +145,29 @@ impl<'a, T: ?Sized> From<&'a mut T> for MutPtr<'a> {
 }
 
 impl<'a> OwningPtr<'a> {
+    /// Creates a new [`OwningPtr`] from a raw pointer.
+    ///
+    /// # Safety
+    /// `inner` must point to valid data that the returned pointer may take ownership of.
     #[inline]
     pub unsafe fn new(inner: NonNull<u8>) -> Self {
         Self(inner, PhantomData)
     }
 
+    /// Moves the pointee out as a value of type `T`.
+    ///
+    /// # Safety
+    /// The pointee must be a valid, properly aligned `T`.
     #[inline]
     pub unsafe fn read<T>(self) -> T {
         let ptr = self.as_ptr().cast::<T>();
         unsafe { ptr.read() }
     }
 
+    /// Drops the pointee as a value of type `T`.
+    ///
+    /// # Safety
+    /// The pointee must be a valid, properly aligned `T`.
     #[inline]
     pub unsafe fn drop_as<T>(self) {
         let ptr = self.as_ptr().cast::<T>();
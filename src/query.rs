@@ -1,229 +1,3399 @@
-use std::marker::PhantomData;
+use std::{
+    any::type_name, borrow::Borrow, cmp::Ordering, error::Error, fmt, marker::PhantomData, mem,
+    num::NonZero, ops::Deref, thread, vec,
+};
 
 use crate::{
     archetype::ArchetypeId,
-    component::{Component, ComponentId},
+    component::{Component, ComponentId, ComponentTicks, Components, Disabled, StorageType, Tick},
     entity::Entity,
+    ptr::MutPtr,
+    small_vec::IdList,
     storage::{Table, TableId, TableRow},
-    World,
+    World, WorldId,
 };
 
-pub trait Queryable<'w> {
-    type Item;
+#[cfg(doc)]
+use crate::system::System;
+
+/// The set of components a query reads, writes and requires to be present.
+///
+/// Every query term registers its accesses here, which is used to reject queries that would
+/// alias a component mutably, e.g. `(&mut Position, &Position)`.
+#[derive(Debug, Default, Clone)]
+pub struct Access {
+    reads: IdList,
+    writes: IdList,
+    required: IdList,
+    excluded: IdList,
+    /// Groups of components of which at least one has to be present.
+    any_of: Vec<IdList>,
+    include_disabled: bool,
+}
+
+impl Access {
+    /// Registers a shared read of the component.
+    ///
+    /// Panics: If the component is already written by this query.
+    pub fn add_read(&mut self, id: ComponentId, name: &str) {
+        assert!(
+            !self.writes.contains(&id),
+            "&{name} conflicts with a previous &mut access in this query"
+        );
+        if !self.reads.contains(&id) {
+            self.reads.push(id);
+        }
+    }
+
+    /// Registers an exclusive write of the component.
+    ///
+    /// Panics: If the component is already read or written by this query.
+    pub fn add_write(&mut self, id: ComponentId, name: &str) {
+        assert!(
+            !self.reads.contains(&id) && !self.writes.contains(&id),
+            "&mut {name} conflicts with a previous access in this query"
+        );
+        self.writes.push(id);
+    }
+
+    /// Registers the reads and writes of a nested term, e.g. of [`AnyOf`], whose type is named
+    /// by `name`.
+    ///
+    /// Panics: If they conflict with a previous access in this query.
+    pub fn add_nested(&mut self, nested: &Access, name: &str) {
+        for id in nested.reads() {
+            assert!(
+                !self.writes.contains(id),
+                "{name} conflicts with a previous &mut access in this query"
+            );
+            if !self.reads.contains(id) {
+                self.reads.push(*id);
+            }
+        }
+        for id in nested.writes() {
+            assert!(
+                !self.reads.contains(id) && !self.writes.contains(id),
+                "{name} conflicts with a previous access in this query"
+            );
+            self.writes.push(*id);
+        }
+    }
+
+    /// Requires the component to be present for an archetype to match.
+    pub fn add_required(&mut self, id: ComponentId) {
+        self.required.push(id);
+    }
+
+    /// Requires the component to be absent for an archetype to match.
+    pub fn add_excluded(&mut self, id: ComponentId) {
+        self.excluded.push(id);
+    }
+
+    /// Requires at least one of the components to be present for an archetype to match. An empty
+    /// group matches no archetype.
+    pub fn add_any_of(&mut self, ids: &[ComponentId]) {
+        self.any_of.push(ids.iter().copied().collect());
+    }
+
+    /// Matches entities regardless of whether they are [`Disabled`] or not.
+    pub fn include_disabled(&mut self) {
+        self.include_disabled = true;
+    }
+
+    /// Returns `true` if [`Disabled`] entities should not be skipped.
+    pub fn includes_disabled(&self) -> bool {
+        self.include_disabled
+    }
+
+    /// Returns `true` if any term of the query reads, writes, requires or excludes the component.
+    pub fn mentions(&self, id: ComponentId) -> bool {
+        self.reads.contains(&id)
+            || self.writes.contains(&id)
+            || self.required.contains(&id)
+            || self.excluded.contains(&id)
+            || self.any_of.iter().any(|ids| ids.contains(&id))
+    }
+
+    pub fn reads(&self) -> &[ComponentId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[ComponentId] {
+        &self.writes
+    }
+
+    pub fn has_read(&self, id: ComponentId) -> bool {
+        self.reads.contains(&id)
+    }
+
+    pub fn has_write(&self, id: ComponentId) -> bool {
+        self.writes.contains(&id)
+    }
+
+    pub fn required(&self) -> &[ComponentId] {
+        &self.required
+    }
+
+    pub fn excluded(&self) -> &[ComponentId] {
+        &self.excluded
+    }
+
+    /// The groups of components of which each needs at least one present, see
+    /// [`Access::add_any_of`].
+    pub fn any_of(&self) -> impl Iterator<Item = &[ComponentId]> {
+        self.any_of.iter().map(|ids| &**ids)
+    }
+}
+
+pub trait Queryable {
+    type Item<'w>;
     type State;
 
+    /// `true` if every row of a matched table has an item. Terms of components stored in a
+    /// [`StorageType::SparseSet`] are not part of the archetypes, so they check every row with
+    /// [`Queryable::matches_row`] instead.
+    const IS_DENSE: bool = true;
+
+    /// Registers the components of the term, so its state can be created before any of them is
+    /// spawned, e.g. for the queries of a [`System`].
+    fn register_components(components: &mut Components) {}
+
     fn init_state(world: &World) -> Self::State;
-    fn fetch(world: &'w World, state: &Self::State, table: &'w Table, row: TableRow) -> Self::Item;
-    fn get_component_ids(world: &World) -> Vec<ComponentId>;
+    /// Like [`Queryable::init_state`], but returns `None` instead of panicking if a required
+    /// component is not registered in the world.
+    fn get_state(world: &World) -> Option<Self::State>;
+    fn update_access(state: &Self::State, access: &mut Access);
+
+    /// Returns `true` if the row of a matched table has an item, i.e. its entity has the sparse
+    /// set components of the term.
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        true
+    }
+
+    /// Fetches the item of the row, `since` is the tick from which on changes are reported, see
+    /// [`Ref`].
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w>;
 }
 
+/// Marker for [`Queryable`]s that only ever read component data.
+///
+/// [`World::query`] takes the world by shared reference and therefore only accepts read-only
+/// queries; mutable terms have to go through [`World::query_mut`].
+///
+/// ```compile_fail
+/// # use quartz::{component::Component, World};
+/// struct Position(f32);
+/// impl Component for Position {}
+///
+/// let world = World::new();
+/// let _ = world.query::<&mut Position>();
+/// ```
+///
+/// # Safety
+/// Implementors must not hand out mutable access to any component in
+/// [`Queryable::fetch`].
+pub unsafe trait ReadOnlyQueryable: Queryable {}
+
 pub struct ComponentFetcher<'w> {
     table: Option<&'w Table>,
 }
 
-impl<'w, T: Component> Queryable<'w> for &T {
-    type Item = &'w T;
+/// Returns `true` if the entity of the row has the component in its sparse set.
+fn sparse_contains(world: &World, id: ComponentId, table: &Table, row: TableRow) -> bool {
+    world
+        .sparse_sets
+        .get(id)
+        .is_some_and(|set| set.contains(table.entity(row)))
+}
+
+/// Retrieves a mutable pointer to the `T` of the row from its table or sparse set and marks it as
+/// changed.
+///
+/// Safety: No other reference to the component may be alive for the lifetime of the pointer.
+unsafe fn get_mut_ptr<'w, T: Component>(
+    world: &'w World,
+    id: ComponentId,
+    table: &'w Table,
+    row: TableRow,
+) -> Option<MutPtr<'w>> {
+    match T::STORAGE_TYPE {
+        StorageType::Table => table.get_component_mut_ptr(id, row, world.tick()),
+        StorageType::SparseSet => world
+            .sparse_sets
+            .get(id)?
+            .get_mut_ptr(table.entity(row), world.tick()),
+    }
+}
+
+/// Returns the change ticks of the `T` of the row from its table or sparse set.
+fn get_ticks<T: Component>(
+    world: &World,
+    id: ComponentId,
+    table: &Table,
+    row: TableRow,
+) -> Option<ComponentTicks> {
+    match T::STORAGE_TYPE {
+        StorageType::Table => table.get_ticks(id, row),
+        StorageType::SparseSet => world.sparse_sets.get(id)?.get_ticks(table.entity(row)),
+    }
+}
+
+impl Queryable for Entity {
+    type Item<'w> = Entity;
+    type State = ();
+
+    fn init_state(world: &World) -> Self::State {}
+
+    fn get_state(world: &World) -> Option<Self::State> {
+        Some(())
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {}
+
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        table.entity(row)
+    }
+}
+
+unsafe impl ReadOnlyQueryable for Entity {}
+
+impl<T: Component> Queryable for &T {
+    type Item<'w> = &'w T;
     type State = ComponentId;
 
+    const IS_DENSE: bool = matches!(T::STORAGE_TYPE, StorageType::Table);
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
     fn init_state(world: &World) -> Self::State {
         world
             .component_id::<T>()
             .expect("Tried to query a Component that has not been spawned in the world")
     }
 
-    fn fetch(world: &'w World, state: &Self::State, table: &'w Table, row: TableRow) -> Self::Item {
+    fn get_state(world: &World) -> Option<Self::State> {
+        world.component_id::<T>()
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        access.add_read(*state, type_name::<T>());
+        if Self::IS_DENSE {
+            access.add_required(*state);
+        }
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        Self::IS_DENSE || sparse_contains(world, *state, table, row)
+    }
+
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
         unsafe {
-            let ptr = table
-                .get_component(*state, row)
-                .expect("failed to receive item from table");
-            ptr.deref()
+            let ptr = if Self::IS_DENSE {
+                table.get_component(*state, row)
+            } else {
+                world
+                    .sparse_sets
+                    .get(*state)
+                    .and_then(|set| set.get(table.entity(row)))
+            };
+            ptr.expect("failed to receive item from table").deref()
         }
     }
+}
+
+unsafe impl<T: Component> ReadOnlyQueryable for &T {}
+
+impl<T: Component> Queryable for &mut T {
+    type Item<'w> = &'w mut T;
+    type State = ComponentId;
+
+    const IS_DENSE: bool = matches!(T::STORAGE_TYPE, StorageType::Table);
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
 
-    fn get_component_ids(world: &World) -> Vec<ComponentId> {
-        vec![world
+    fn init_state(world: &World) -> Self::State {
+        world
             .component_id::<T>()
-            .expect("Tried to query a Component that has not been spawned in the world")]
+            .expect("Tried to query a Component that has not been spawned in the world")
+    }
+
+    fn get_state(world: &World) -> Option<Self::State> {
+        world.component_id::<T>()
     }
-}
 
-impl<'w, T1: Component, T2: Component> Queryable<'w> for (&T1, &T2) {
-    type Item = (&'w T1, &'w T2);
-    type State = (ComponentId, ComponentId);
+    fn update_access(state: &Self::State, access: &mut Access) {
+        access.add_write(*state, type_name::<T>());
+        if Self::IS_DENSE {
+            access.add_required(*state);
+        }
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        Self::IS_DENSE || sparse_contains(world, *state, table, row)
+    }
 
-    fn fetch(world: &'w World, state: &Self::State, table: &'w Table, row: TableRow) -> Self::Item {
-        let (id0, id1) = state;
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        // SAFETY: The access check guarantees this is the only term touching this column and the
+        // query yields every row at most once.
         unsafe {
-            let ptr1 = table
-                .get_component(*id0, row)
-                .expect("failed to receive item from table");
-            let ptr2 = table
-                .get_component(*id1, row)
-                .expect("failed to receive item from table");
-            (ptr1.deref(), ptr2.deref())
+            get_mut_ptr::<T>(world, *state, table, row)
+                .expect("failed to receive item from table")
+                .deref_mut()
         }
     }
+}
 
-    fn init_state(world: &World) -> Self::State {
-        (
-            world
-                .component_id::<T1>()
-                .expect("Tried to query a Component that has not been spawned in the world"),
-            world
-                .component_id::<T2>()
-                .expect("Tried to query a Component that has not been spawned in the world"),
-        )
+/// A shared reference to a component that also tells whether the component was added or changed
+/// since the query last ran, reported like the changes of [`Changed`].
+///
+/// ```
+/// # use quartz::{component::Component, query::Ref, World};
+/// struct Position(f32);
+/// impl Component for Position {}
+///
+/// let mut world = World::new();
+/// let old = world.spawn(Position(0.0));
+/// world.increment_tick();
+/// world.spawn(Position(1.0));
+/// world.get_mut::<Position>(old).unwrap().0 = 2.0;
+///
+/// for position in world.query::<Ref<Position>>() {
+///     assert!(position.is_changed());
+///     assert_eq!(position.is_added(), position.0 == 1.0);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Ref<'w, T> {
+    value: &'w T,
+    ticks: ComponentTicks,
+    since: Tick,
+}
+
+impl<'w, T> Ref<'w, T> {
+    /// Returns `true` if the component was added since the query last ran.
+    pub fn is_added(&self) -> bool {
+        self.ticks.added >= self.since
     }
 
-    fn get_component_ids(world: &World) -> Vec<ComponentId> {
-        vec![
-            world
-                .component_id::<T1>()
-                .expect("Component needs to be initialized for this world"),
-            world
-                .component_id::<T2>()
-                .expect("Component needs to be initialized for this world"),
-        ]
+    /// Returns `true` if the component was added or mutably accessed since the query last ran.
+    pub fn is_changed(&self) -> bool {
+        self.ticks.changed >= self.since
+    }
+
+    pub fn ticks(&self) -> ComponentTicks {
+        self.ticks
+    }
+
+    pub fn into_inner(self) -> &'w T {
+        self.value
     }
 }
 
-pub struct Query<'world, T: Queryable<'world>> {
-    world: &'world World,
-    matched_tables: Vec<TableId>,
-    current_table: usize,
-    current_row: TableRow,
-    state: T::State,
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
 }
 
-impl<'world, T: Queryable<'world>> Query<'world, T> {
-    pub(crate) fn new(world: &'world World) -> Self {
-        let mut matched_tables: Vec<TableId> = Vec::new();
-        let component_ids = T::get_component_ids(world);
-        let (archetype_ids, matched_tables) = world.archetypes.get_query_archetypes(&component_ids);
-        let state = T::init_state(world);
+impl<T: Component> Queryable for Ref<'_, T> {
+    type Item<'w> = Ref<'w, T>;
+    type State = ComponentId;
 
-        Self {
-            world,
-            matched_tables,
-            current_table: 0,
-            current_row: TableRow(0),
-            state,
+    const IS_DENSE: bool = <&T>::IS_DENSE;
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world
+            .component_id::<T>()
+            .expect("Tried to query a Component that has not been spawned in the world")
+    }
+
+    fn get_state(world: &World) -> Option<Self::State> {
+        world.component_id::<T>()
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        <&T>::update_access(state, access);
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        <&T>::matches_row(world, state, table, row)
+    }
+
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        Ref {
+            value: <&T>::fetch(world, state, table, row, since),
+            ticks: get_ticks::<T>(world, *state, table, row)
+                .expect("failed to receive ticks from table"),
+            since,
         }
     }
 }
 
-impl<'world, T: Queryable<'world>> Iterator for Query<'world, T> {
-    type Item = T::Item;
+unsafe impl<T: Component> ReadOnlyQueryable for Ref<'_, T> {}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.current_table >= self.matched_tables.len() {
-            return None;
-        }
-        let table = {
-            let table_id = self.matched_tables[self.current_table];
-            self.world.tables.get(table_id)?
-        };
-        if self.current_row >= table.len() {
-            self.current_table += 1;
-            self.current_row = TableRow(0);
-            return self.next();
+impl<T: Component> Queryable for Option<&T> {
+    type Item<'w> = Option<&'w T>;
+    type State = Option<ComponentId>;
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world.component_id::<T>()
+    }
+
+    fn get_state(world: &World) -> Option<Self::State> {
+        Some(world.component_id::<T>())
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        if let Some(id) = state {
+            access.add_read(*id, type_name::<T>());
         }
+    }
 
-        let row = self.current_row;
-        self.current_row += 1;
-        Some(T::fetch(self.world, &self.state, table, row))
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        unsafe {
+            state
+                .and_then(|id| {
+                    if <&T>::IS_DENSE {
+                        table.get_component(id, row)
+                    } else {
+                        world.sparse_sets.get(id)?.get(table.entity(row))
+                    }
+                })
+                .map(|ptr| ptr.deref())
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{component::Component, entity::Entity, World};
+unsafe impl<T: Component> ReadOnlyQueryable for Option<&T> {}
 
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    struct MyComponent(u32);
-    impl Component for MyComponent {}
+impl<T: Component> Queryable for Option<&mut T> {
+    type Item<'w> = Option<&'w mut T>;
+    type State = Option<ComponentId>;
 
-    #[derive(Debug, PartialEq, Clone, Copy)]
-    struct Position {
-        x: f32,
-        y: f32,
-        z: f32,
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
     }
-    impl Component for Position {}
 
-    #[test]
-    fn query() {
-        let mut world = World::new();
-        let entity = world.spawn(MyComponent(1));
+    fn init_state(world: &World) -> Self::State {
+        world.component_id::<T>()
+    }
 
-        assert_eq!(entity, Entity::from(0, 0));
+    fn get_state(world: &World) -> Option<Self::State> {
+        Some(world.component_id::<T>())
+    }
 
-        let mut query = world.query::<&MyComponent>();
-        assert_eq!(query.next(), Some(&MyComponent(1)));
-        assert_eq!(query.next(), None);
+    fn update_access(state: &Self::State, access: &mut Access) {
+        if let Some(id) = state {
+            access.add_write(*id, type_name::<T>());
+        }
     }
 
-    #[test]
-    fn multi_query() {
-        let mut world = World::new();
-        let entity = world.spawn(MyComponent(1));
-        let entity2 = world.spawn((
-            MyComponent(1337),
-            Position {
-                x: 0.0,
-                y: 1.0,
-                z: 2.0,
-            },
-        ));
+    fn fetch<'w>(
+        world: &'w World,
+        state: &Self::State,
+        table: &'w Table,
+        row: TableRow,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        // SAFETY: See the `&mut T` implementation.
+        unsafe {
+            state
+                .and_then(|id| get_mut_ptr::<T>(world, id, table, row))
+                .map(|ptr| ptr.deref_mut())
+        }
+    }
+}
 
-        assert_eq!(entity, Entity::from(0, 0));
-        assert_eq!(entity2, Entity::from(0, 1));
+/// Implements [`Queryable`] for a tuple of the given terms, binding their states to the given
+/// names.
+macro_rules! impl_queryable_tuple {
+    ($(($Q:ident, $state:ident)),*) => {
+        impl<$($Q: Queryable),*> Queryable for ($($Q,)*) {
+            type Item<'w> = ($($Q::Item<'w>,)*);
+            type State = ($($Q::State,)*);
 
-        let mut query = world.query::<&MyComponent>();
-        assert_eq!(query.next(), Some(&MyComponent(1)));
-        assert_eq!(query.next(), Some(&MyComponent(1337)));
-        assert_eq!(query.next(), None);
+            const IS_DENSE: bool = $($Q::IS_DENSE)&&*;
 
-        let mut query = world.query::<&Position>();
-        assert_eq!(
-            query.next(),
-            Some(&Position {
-                x: 0.0,
-                y: 1.0,
-                z: 2.0,
-            })
-        );
-        assert_eq!(query.next(), None);
+            fn register_components(components: &mut Components) {
+                $($Q::register_components(components);)*
+            }
 
-        let mut query = world.query::<(&MyComponent, &Position)>();
-        assert_eq!(
-            query.next(),
-            Some((
-                &MyComponent(1337),
-                &Position {
-                    x: 0.0,
-                    y: 1.0,
-                    z: 2.0,
-                }
-            ))
-        );
-        assert_eq!(query.next(), None);
-    }
+            fn init_state(world: &World) -> Self::State {
+                ($($Q::init_state(world),)*)
+            }
 
-    #[test]
-    fn large_query() {
-        const ENTITY_COUNT: u32 = 1000;
-        let mut world = World::new();
+            fn get_state(world: &World) -> Option<Self::State> {
+                Some(($($Q::get_state(world)?,)*))
+            }
 
-        for i in 0..ENTITY_COUNT {
-            world.spawn(MyComponent(i));
+            fn update_access(state: &Self::State, access: &mut Access) {
+                let ($($state,)*) = state;
+                $($Q::update_access($state, access);)*
+            }
+
+            fn matches_row(
+                world: &World,
+                state: &Self::State,
+                table: &Table,
+                row: TableRow,
+            ) -> bool {
+                let ($($state,)*) = state;
+                $($Q::matches_row(world, $state, table, row))&&*
+            }
+
+            fn fetch<'w>(
+                world: &'w World,
+                state: &Self::State,
+                table: &'w Table,
+                row: TableRow,
+                since: Tick,
+            ) -> Self::Item<'w> {
+                let ($($state,)*) = state;
+                ($($Q::fetch(world, $state, table, row, since),)*)
+            }
         }
 
-        let mut count = 0;
-        for (i, e) in world.query::<&MyComponent>().enumerate() {
-            assert_eq!(e, &MyComponent(i as u32));
-            count += 1;
+        unsafe impl<$($Q: ReadOnlyQueryable),*> ReadOnlyQueryable for ($($Q,)*) {}
+    };
+}
+
+/// Calls [`impl_queryable_tuple`] for every suffix of the given terms.
+macro_rules! impl_queryable_tuples {
+    () => {};
+    ($head:tt $(, $tail:tt)*) => {
+        impl_queryable_tuple!($head $(, $tail)*);
+        impl_queryable_tuples!($($tail),*);
+    };
+}
+
+impl_queryable_tuples!(
+    (Q0, state0),
+    (Q1, state1),
+    (Q2, state2),
+    (Q3, state3),
+    (Q4, state4),
+    (Q5, state5),
+    (Q6, state6),
+    (Q7, state7),
+    (Q8, state8),
+    (Q9, state9),
+    (Q10, state10),
+    (Q11, state11),
+    (Q12, state12),
+    (Q13, state13),
+    (Q14, state14),
+    (Q15, state15)
+);
+
+/// Matches entities that have at least one of the terms of the tuple, yielding an `Option` for
+/// every term, e.g. `AnyOf<(&Health, &mut Shield)>`.
+///
+/// ```
+/// # use quartz::{component::Component, query::AnyOf, World};
+/// #[derive(Debug, PartialEq)]
+/// struct Health(u32);
+/// impl Component for Health {}
+/// #[derive(Debug, PartialEq)]
+/// struct Shield(u32);
+/// impl Component for Shield {}
+///
+/// let mut world = World::new();
+/// world.spawn(Health(3));
+/// world.spawn((Health(1), Shield(2)));
+/// world.spawn(Shield(5));
+/// world.spawn(());
+///
+/// let items = world.query::<AnyOf<(&Health, &Shield)>>().collect::<Vec<_>>();
+/// assert_eq!(items.len(), 3);
+/// assert_eq!(items[1], (Some(&Health(1)), Some(&Shield(2))));
+/// ```
+pub struct AnyOf<Q>(PhantomData<Q>);
+
+/// Implements [`Queryable`] for [`AnyOf`] of a tuple of the given terms, binding their states to
+/// the given names.
+macro_rules! impl_any_of {
+    ($(($Q:ident, $state:ident)),+) => {
+        impl<$($Q: Queryable),+> Queryable for AnyOf<($($Q,)+)> {
+            type Item<'w> = ($(Option<$Q::Item<'w>>,)+);
+            /// The state of every term together with its access, which is checked per table.
+            /// `None` for terms of components that are not registered, which never match.
+            type State = ($(Option<($Q::State, Access)>,)+);
+
+            const IS_DENSE: bool = false;
+
+            fn register_components(components: &mut Components) {
+                $($Q::register_components(components);)+
+            }
+
+            fn init_state(world: &World) -> Self::State {
+                ($($Q::get_state(world).map(|state| {
+                    let mut access = Access::default();
+                    $Q::update_access(&state, &mut access);
+                    (state, access)
+                }),)+)
+            }
+
+            fn get_state(world: &World) -> Option<Self::State> {
+                Some(Self::init_state(world))
+            }
+
+            fn update_access(state: &Self::State, access: &mut Access) {
+                let ($($state,)+) = state;
+                let terms = [$($state.as_ref().map(|(_, access)| access)),+];
+                for term in terms.iter().flatten() {
+                    access.add_nested(term, type_name::<Self>());
+                }
+                if terms.iter().flatten().all(|term| !term.required().is_empty()) {
+                    let ids = terms
+                        .iter()
+                        .flatten()
+                        .flat_map(|term| term.required())
+                        .copied()
+                        .collect::<IdList>();
+                    access.add_any_of(&ids);
+                }
+            }
+
+            fn matches_row(
+                world: &World,
+                state: &Self::State,
+                table: &Table,
+                row: TableRow,
+            ) -> bool {
+                let ($($state,)+) = state;
+                $($state.as_ref().is_some_and(|(state, access)| {
+                    table_matches(access, table) && $Q::matches_row(world, state, table, row)
+                }))||+
+            }
+
+            fn fetch<'w>(
+                world: &'w World,
+                state: &Self::State,
+                table: &'w Table,
+                row: TableRow,
+                since: Tick,
+            ) -> Self::Item<'w> {
+                let ($($state,)+) = state;
+                ($($state.as_ref().and_then(|(state, access)| {
+                    (table_matches(access, table) && $Q::matches_row(world, state, table, row))
+                        .then(|| $Q::fetch(world, state, table, row, since))
+                }),)+)
+            }
         }
-        assert_eq!(count, ENTITY_COUNT);
+
+        unsafe impl<$($Q: ReadOnlyQueryable),+> ReadOnlyQueryable for AnyOf<($($Q,)+)> {}
+    };
+}
+
+/// Calls [`impl_any_of`] for every suffix of the given terms.
+macro_rules! impl_any_ofs {
+    () => {};
+    ($head:tt $(, $tail:tt)*) => {
+        impl_any_of!($head $(, $tail)*);
+        impl_any_ofs!($($tail),*);
+    };
+}
+
+impl_any_ofs!(
+    (Q0, state0),
+    (Q1, state1),
+    (Q2, state2),
+    (Q3, state3),
+    (Q4, state4),
+    (Q5, state5),
+    (Q6, state6),
+    (Q7, state7)
+);
+
+/// A filter narrowing down the entities matched by a [`Query`] without fetching any data.
+pub trait QueryFilter {
+    type State;
+
+    /// `true` if the filter only narrows down the matched archetypes, so it never rejects a row
+    /// of a matched table in [`QueryFilter::filter_row`].
+    const IS_ARCHETYPAL: bool = true;
+
+    /// `true` if the filter only checks components stored in tables, see
+    /// [`Queryable::IS_DENSE`].
+    const IS_DENSE: bool = true;
+
+    /// Registers the components of the filter, see [`Queryable::register_components`].
+    fn register_components(components: &mut Components) {}
+
+    fn init_state(world: &World) -> Self::State;
+    fn update_access(state: &Self::State, access: &mut Access);
+
+    /// Returns `true` if the entity of the row of a matched table has or lacks the sparse set
+    /// components the filter checks, see [`Queryable::matches_row`].
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        true
+    }
+
+    /// Returns `true` if the row of a matched table passes the filter. `since` is the tick from
+    /// which on changes are reported, see [`Changed`].
+    fn filter_row(
+        world: &World,
+        state: &Self::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        true
+    }
+}
+
+/// Marker for [`QueryFilter`]s that only narrow down the matched archetypes, which lets a
+/// [`Query`] count its items from the table lengths. Queries involving sparse set components
+/// still know their exact number of items, but have to count them row by row.
+pub trait ArchetypeFilter: QueryFilter {}
+
+/// Filters for entities that have the component `T`.
+pub struct With<T>(PhantomData<T>);
+
+/// Filters for entities that do not have the component `T`.
+pub struct Without<T>(PhantomData<T>);
+
+impl QueryFilter for () {
+    type State = ();
+
+    fn init_state(_world: &World) -> Self::State {}
+
+    fn update_access(_state: &Self::State, _access: &mut Access) {}
+}
+
+impl ArchetypeFilter for () {}
+
+impl<T: Component> QueryFilter for With<T> {
+    type State = ComponentId;
+
+    const IS_DENSE: bool = matches!(T::STORAGE_TYPE, StorageType::Table);
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world
+            .component_id::<T>()
+            .expect("Tried to filter on a Component that has not been spawned in the world")
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        if Self::IS_DENSE {
+            access.add_required(*state);
+        }
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        Self::IS_DENSE || sparse_contains(world, *state, table, row)
+    }
+}
+
+impl<T: Component> ArchetypeFilter for With<T> {}
+
+impl<T: Component> QueryFilter for Without<T> {
+    type State = Option<ComponentId>;
+
+    const IS_DENSE: bool = matches!(T::STORAGE_TYPE, StorageType::Table);
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world.component_id::<T>()
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        // A component that was never spawned can't be present on any archetype
+        if let Some(id) = state.filter(|_| Self::IS_DENSE) {
+            access.add_excluded(id);
+        }
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        Self::IS_DENSE || !state.is_some_and(|id| sparse_contains(world, id, table, row))
+    }
+}
+
+impl<T: Component> ArchetypeFilter for Without<T> {}
+
+/// Filters for entities whose component `T` was added or mutably accessed since the query last
+/// ran, e.g. to only process modified entities.
+///
+/// A [`QueryState`] reports the changes made from the tick of its previous run on, which
+/// includes all components on its first run. Changes made during that tick are reported again,
+/// so none made after the previous run are missed. Queries of [`System`]s report exactly the
+/// changes made after their previous run, as every system run advances the world tick. Queries
+/// created directly from the world, like [`World::query_filtered`], report the changes made
+/// during the current [`World::tick`]. Every `&mut T` item a query hands out counts as a change,
+/// whether it was written or not.
+///
+/// ```
+/// # use quartz::{component::Component, query::Changed, World};
+/// struct Position(f32);
+/// impl Component for Position {}
+///
+/// let mut world = World::new();
+/// let moved = world.spawn(Position(0.0));
+/// world.spawn(Position(1.0));
+/// world.increment_tick();
+///
+/// world.get_mut::<Position>(moved).unwrap().0 += 1.0;
+/// let changed = world.query_filtered::<&Position, Changed<Position>>();
+/// assert_eq!(changed.map(|p| p.0).collect::<Vec<_>>(), [1.0]);
+/// ```
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Changed<T> {
+    type State = ComponentId;
+
+    const IS_ARCHETYPAL: bool = false;
+    const IS_DENSE: bool = <With<T>>::IS_DENSE;
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world
+            .component_id::<T>()
+            .expect("Tried to filter on a Component that has not been spawned in the world")
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        <With<T>>::update_access(state, access);
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        <With<T>>::matches_row(world, state, table, row)
+    }
+
+    fn filter_row(
+        world: &World,
+        state: &Self::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        get_ticks::<T>(world, *state, table, row).is_some_and(|ticks| ticks.changed >= since)
+    }
+}
+
+/// Filters for entities the component `T` was added to since the query last ran, reported like
+/// the changes of [`Changed`]. Overwriting a component only counts as a change.
+pub struct Added<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Added<T> {
+    type State = ComponentId;
+
+    const IS_ARCHETYPAL: bool = false;
+    const IS_DENSE: bool = <With<T>>::IS_DENSE;
+
+    fn register_components(components: &mut Components) {
+        components.register_component::<T>();
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        world
+            .component_id::<T>()
+            .expect("Tried to filter on a Component that has not been spawned in the world")
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        <With<T>>::update_access(state, access);
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        <With<T>>::matches_row(world, state, table, row)
+    }
+
+    fn filter_row(
+        world: &World,
+        state: &Self::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        get_ticks::<T>(world, *state, table, row).is_some_and(|ticks| ticks.added >= since)
+    }
+}
+
+/// Includes [`Disabled`] entities in a query, which are skipped by default.
+///
+/// Use `With<Disabled>` instead to only match disabled entities.
+pub struct IncludeDisabled;
+
+impl QueryFilter for IncludeDisabled {
+    type State = ();
+
+    fn init_state(_world: &World) -> Self::State {}
+
+    fn update_access(_state: &Self::State, access: &mut Access) {
+        access.include_disabled();
+    }
+}
+
+impl ArchetypeFilter for IncludeDisabled {}
+
+impl<F0: QueryFilter, F1: QueryFilter> QueryFilter for (F0, F1) {
+    type State = (F0::State, F1::State);
+
+    const IS_ARCHETYPAL: bool = F0::IS_ARCHETYPAL && F1::IS_ARCHETYPAL;
+    const IS_DENSE: bool = F0::IS_DENSE && F1::IS_DENSE;
+
+    fn register_components(components: &mut Components) {
+        F0::register_components(components);
+        F1::register_components(components);
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        (F0::init_state(world), F1::init_state(world))
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        F0::update_access(&state.0, access);
+        F1::update_access(&state.1, access);
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        F0::matches_row(world, &state.0, table, row) && F1::matches_row(world, &state.1, table, row)
+    }
+
+    fn filter_row(
+        world: &World,
+        state: &Self::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        F0::filter_row(world, &state.0, table, row, since)
+            && F1::filter_row(world, &state.1, table, row, since)
+    }
+}
+
+impl<F0: ArchetypeFilter, F1: ArchetypeFilter> ArchetypeFilter for (F0, F1) {}
+
+impl<F0: QueryFilter, F1: QueryFilter, F2: QueryFilter> QueryFilter for (F0, F1, F2) {
+    type State = (F0::State, F1::State, F2::State);
+
+    const IS_ARCHETYPAL: bool = F0::IS_ARCHETYPAL && F1::IS_ARCHETYPAL && F2::IS_ARCHETYPAL;
+    const IS_DENSE: bool = F0::IS_DENSE && F1::IS_DENSE && F2::IS_DENSE;
+
+    fn register_components(components: &mut Components) {
+        F0::register_components(components);
+        F1::register_components(components);
+        F2::register_components(components);
+    }
+
+    fn init_state(world: &World) -> Self::State {
+        (
+            F0::init_state(world),
+            F1::init_state(world),
+            F2::init_state(world),
+        )
+    }
+
+    fn update_access(state: &Self::State, access: &mut Access) {
+        F0::update_access(&state.0, access);
+        F1::update_access(&state.1, access);
+        F2::update_access(&state.2, access);
+    }
+
+    fn matches_row(world: &World, state: &Self::State, table: &Table, row: TableRow) -> bool {
+        F0::matches_row(world, &state.0, table, row)
+            && F1::matches_row(world, &state.1, table, row)
+            && F2::matches_row(world, &state.2, table, row)
+    }
+
+    fn filter_row(
+        world: &World,
+        state: &Self::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        F0::filter_row(world, &state.0, table, row, since)
+            && F1::filter_row(world, &state.1, table, row, since)
+            && F2::filter_row(world, &state.2, table, row, since)
+    }
+}
+
+impl<F0: ArchetypeFilter, F1: ArchetypeFilter, F2: ArchetypeFilter> ArchetypeFilter
+    for (F0, F1, F2)
+{
+}
+
+/// Filters for entities that pass at least one of the filters of the tuple, e.g.
+/// `Or<(With<Player>, With<Enemy>)>`. The filters can be nested, e.g.
+/// `Or<((With<Player>, Changed<Health>), Without<Shield>)>`.
+///
+/// ```
+/// # use quartz::{component::Component, entity::Entity, query::{Or, With}, World};
+/// struct Player;
+/// impl Component for Player {}
+/// struct Enemy;
+/// impl Component for Enemy {}
+/// struct Tree;
+/// impl Component for Tree {}
+///
+/// let mut world = World::new();
+/// let player = world.spawn(Player);
+/// let enemy = world.spawn(Enemy);
+/// world.spawn(Tree);
+///
+/// let units = world.query_filtered::<Entity, Or<(With<Player>, With<Enemy>)>>();
+/// assert_eq!(units.collect::<Vec<_>>(), [player, enemy]);
+/// ```
+///
+/// Every filter is checked for every entity of the tables matched by any of them, so tables
+/// are only skipped as a whole if every filter requires a component.
+pub struct Or<F>(PhantomData<F>);
+
+/// Returns `true` if the table has all required components, at least one of every any-of group
+/// and none of the excluded components of the access, like
+/// [`Archetype::matches`](crate::archetype::Archetype::matches).
+fn table_matches(access: &Access, table: &Table) -> bool {
+    access.required().iter().all(|id| table.has_column(*id))
+        && access
+            .any_of()
+            .all(|ids| ids.iter().any(|id| table.has_column(*id)))
+        && !access.excluded().iter().any(|id| table.has_column(*id))
+}
+
+/// Narrows down the archetypes matched by an [`Or`] with the accesses of its filters. Matched
+/// archetypes must contain a component required by one of the filters, unless one of them
+/// doesn't require any.
+fn add_or_access(access: &mut Access, filters: &[&Access]) {
+    if filters.iter().any(|filter| filter.includes_disabled()) {
+        access.include_disabled();
+    }
+    if filters.iter().all(|filter| !filter.required().is_empty()) {
+        let ids = filters
+            .iter()
+            .flat_map(|filter| filter.required())
+            .copied()
+            .collect::<IdList>();
+        access.add_any_of(&ids);
+    }
+}
+
+macro_rules! impl_or_filter {
+    ($($F:ident $i:tt),+) => {
+        impl<$($F: QueryFilter),+> QueryFilter for Or<($($F,)+)> {
+            /// The state of every filter together with its access, which is checked per table.
+            type State = ($(($F::State, Access),)+);
+
+            const IS_ARCHETYPAL: bool = $($F::IS_ARCHETYPAL)&&+;
+            const IS_DENSE: bool = false;
+
+            fn register_components(components: &mut Components) {
+                $($F::register_components(components);)+
+            }
+
+            fn init_state(world: &World) -> Self::State {
+                ($({
+                    let state = $F::init_state(world);
+                    let mut access = Access::default();
+                    $F::update_access(&state, &mut access);
+                    (state, access)
+                },)+)
+            }
+
+            fn update_access(state: &Self::State, access: &mut Access) {
+                add_or_access(access, &[$(&state.$i.1),+]);
+            }
+
+            fn matches_row(
+                world: &World,
+                state: &Self::State,
+                table: &Table,
+                row: TableRow,
+            ) -> bool {
+                $(
+                    (table_matches(&state.$i.1, table)
+                        && $F::matches_row(world, &state.$i.0, table, row))
+                )||+
+            }
+
+            fn filter_row(
+                world: &World,
+                state: &Self::State,
+                table: &Table,
+                row: TableRow,
+                since: Tick,
+            ) -> bool {
+                $(
+                    (table_matches(&state.$i.1, table)
+                        && $F::matches_row(world, &state.$i.0, table, row)
+                        && $F::filter_row(world, &state.$i.0, table, row, since))
+                )||+
+            }
+        }
+
+        impl<$($F: ArchetypeFilter),+> ArchetypeFilter for Or<($($F,)+)> {}
+    };
+}
+
+impl_or_filter!(F0 0, F1 1);
+impl_or_filter!(F0 0, F1 1, F2 2);
+impl_or_filter!(F0 0, F1 1, F2 2, F3 3);
+
+/// The resolved components and matched tables of a query, which can be stored without borrowing
+/// the world and reused to avoid constructing the query again every time it is run.
+///
+/// Tables created after the state are matched incrementally on the next use. A state can only be
+/// used with the world it was created from, using it with another world panics.
+///
+/// ```
+/// # use quartz::{component::Component, query::QueryState, World};
+/// struct Position(f32);
+/// impl Component for Position {}
+///
+/// struct Movement {
+///     positions: QueryState<&'static mut Position>,
+/// }
+///
+/// let mut world = World::new();
+/// world.spawn(Position(0.0));
+/// let mut movement = Movement {
+///     positions: QueryState::new(&world),
+/// };
+///
+/// world.spawn(Position(1.0));
+/// for position in movement.positions.iter_mut(&mut world) {
+///     position.0 += 1.0;
+/// }
+/// assert_eq!(movement.positions.iter_mut(&mut world).map(|p| p.0).sum::<f32>(), 3.0);
+/// ```
+pub struct QueryState<T: Queryable, F: QueryFilter = ()> {
+    /// The world the state was created for, the only one it can be used with.
+    world_id: WorldId,
+    fetch_state: T::State,
+    filter_state: F::State,
+    /// Sorted ids of the tables of all matched archetypes.
+    matched_tables: Vec<TableId>,
+    /// The number of archetypes that have already been checked for a match.
+    seen_archetypes: usize,
+    /// The world tick of the previous run, from which on [`Changed`] reports changes.
+    last_run: Tick,
+}
+
+impl<T: Queryable, F: QueryFilter> QueryState<T, F> {
+    pub fn new(world: &World) -> Self {
+        let fetch_state = T::init_state(world);
+        let filter_state = F::init_state(world);
+        let (access, _) = Query::<T, F>::access(world, &fetch_state, &filter_state);
+        let (_, matched_tables) = world.archetypes.get_query_archetypes(&access);
+
+        Self {
+            world_id: world.id(),
+            fetch_state,
+            filter_state,
+            matched_tables,
+            seen_archetypes: world.archetypes.len(),
+            last_run: Tick::default(),
+        }
+    }
+
+    /// Matches the archetypes that were created since the last update.
+    ///
+    /// Panics: If the state was created for another world.
+    pub fn update_archetypes(&mut self, world: &World) {
+        self.validate_world(world);
+        let archetypes = &world.archetypes;
+        if archetypes.len() == self.seen_archetypes {
+            return;
+        }
+
+        let (access, _) = Query::<T, F>::access(world, &self.fetch_state, &self.filter_state);
+        for archetype in archetypes.iter().skip(self.seen_archetypes) {
+            if archetype.matches(&access) {
+                if let Err(index) = self.matched_tables.binary_search(&archetype.table_id()) {
+                    self.matched_tables.insert(index, archetype.table_id());
+                }
+            }
+        }
+        self.seen_archetypes = archetypes.len();
+    }
+
+    /// Panics: If the state was created for another world.
+    pub fn iter<'w>(&'w mut self, world: &'w World) -> Query<'w, T, F>
+    where
+        T: ReadOnlyQueryable,
+    {
+        let since = self.start_run(world);
+        Query::from_state(world, QueryStateRef::Borrowed(self), since)
+    }
+
+    /// Panics: If the state was created for another world.
+    pub fn iter_mut<'w>(&'w mut self, world: &'w mut World) -> Query<'w, T, F> {
+        let since = self.start_run(world);
+        Query::from_state(world, QueryStateRef::Borrowed(self), since)
+    }
+
+    /// The access of the query's terms and filters.
+    pub(crate) fn access(&self, world: &World) -> Access {
+        self.validate_world(world);
+        Query::<T, F>::access(world, &self.fetch_state, &self.filter_state).0
+    }
+
+    /// Creates the query of a system, which reports the changes made from `since` on.
+    ///
+    /// Safety: For queries with mutable terms, no other reference to the accessed components may
+    /// be alive while the query or its items are.
+    pub(crate) unsafe fn query_since<'w>(
+        &'w mut self,
+        world: &'w World,
+        since: Tick,
+    ) -> Query<'w, T, F> {
+        self.update_archetypes(world);
+        Query::from_state(world, QueryStateRef::Borrowed(self), since)
+    }
+
+    /// Updates the matched archetypes and returns the tick of the previous run.
+    fn start_run(&mut self, world: &World) -> Tick {
+        self.update_archetypes(world);
+        mem::replace(&mut self.last_run, world.tick())
+    }
+
+    fn validate_world(&self, world: &World) {
+        assert_eq!(
+            self.world_id,
+            world.id(),
+            "Tried to use a QueryState with a world it was not created for"
+        );
+    }
+}
+
+/// The state of a [`Query`], either created for it or borrowed from a [`QueryState`].
+enum QueryStateRef<'w, T: Queryable, F: QueryFilter> {
+    Owned(QueryState<T, F>),
+    Borrowed(&'w QueryState<T, F>),
+}
+
+impl<T: Queryable, F: QueryFilter> Deref for QueryStateRef<'_, T, F> {
+    type Target = QueryState<T, F>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Owned(state) => state,
+            Self::Borrowed(state) => state,
+        }
+    }
+}
+
+pub struct Query<'world, T: Queryable, F: QueryFilter = ()> {
+    world: &'world World,
+    state: QueryStateRef<'world, T, F>,
+    current_table: usize,
+    current_row: TableRow,
+    /// Index into `matched_tables` of the table the back cursor is in.
+    back_table: usize,
+    /// Exclusive end row of the back cursor inside `back_table`.
+    back_row: TableRow,
+    /// The tick from which on [`Changed`] reports changes.
+    since: Tick,
+}
+
+impl<'world, T: Queryable, F: QueryFilter> Query<'world, T, F> {
+    pub(crate) fn new(world: &'world World) -> Self {
+        Self::from_state(
+            world,
+            QueryStateRef::Owned(QueryState::new(world)),
+            world.tick(),
+        )
+    }
+
+    fn from_state(world: &'world World, state: QueryStateRef<'world, T, F>, since: Tick) -> Self {
+        let back_table = state.matched_tables.len().saturating_sub(1);
+        let back_row = state
+            .matched_tables
+            .last()
+            .and_then(|id| world.tables.get(*id))
+            .map_or(TableRow(0), |table| TableRow(table.len()));
+
+        Self {
+            world,
+            state,
+            current_table: 0,
+            current_row: TableRow(0),
+            back_table,
+            back_row,
+            since,
+        }
+    }
+
+    /// Collects the access of all terms and filters. Also returns the [`Disabled`] component if it
+    /// is implicitly excluded.
+    pub(crate) fn access(
+        world: &World,
+        state: &T::State,
+        filter_state: &F::State,
+    ) -> (Access, Option<ComponentId>) {
+        let mut access = Access::default();
+        T::update_access(state, &mut access);
+        F::update_access(filter_state, &mut access);
+        let mut implicit_disabled = None;
+        if let Some(disabled) = world.component_id::<Disabled>() {
+            if !access.includes_disabled() && !access.mentions(disabled) {
+                access.add_excluded(disabled);
+                implicit_disabled = Some(disabled);
+            }
+        }
+        (access, implicit_disabled)
+    }
+
+    /// Reborrows the query as a query for a subset of its terms, e.g. a `(&A, &mut B)` query as an
+    /// `&A` query, to pass it to a function taking the narrower query.
+    ///
+    /// The lens yields the items of the entities the query hasn't yielded yet, so they can't
+    /// alias the items it already returned, and keeps its filter. Iterating the lens doesn't
+    /// advance the query. Terms of the new query that only check rows, like sparse set
+    /// components, are checked again, those of the old query are not.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, query::Query, World};
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// fn total(positions: Query<&Position>) -> f32 {
+    ///     positions.map(|position| position.0).sum()
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.spawn((Position(1.0), Velocity(1.0)));
+    /// world.spawn(Position(5.0));
+    ///
+    /// let mut query = world.query_mut::<(&mut Position, &Velocity)>();
+    /// assert_eq!(total(query.transmute_lens()), 1.0);
+    /// ```
+    ///
+    /// Panics: If the new query accesses a component the query doesn't, writes a component the
+    /// query only reads, or requires a component the query doesn't, e.g. an `Option<&A>` term as
+    /// `&A`.
+    pub fn transmute_lens<U: Queryable>(&mut self) -> Query<'_, U, F> {
+        let world = self.world;
+        let fetch_state = U::init_state(world);
+        let filter_state = F::init_state(world);
+
+        let access = self.state.access(world);
+        let (lens_access, _) = Query::<U, F>::access(world, &fetch_state, &filter_state);
+        let name = |id: ComponentId| world.components.name(id).unwrap_or("<unknown>");
+        for &id in lens_access.reads() {
+            assert!(
+                access.has_read(id) || access.has_write(id),
+                "{} can't be transmuted to {}, which reads {}",
+                type_name::<T>(),
+                type_name::<U>(),
+                name(id)
+            );
+        }
+        for &id in lens_access.writes() {
+            assert!(
+                access.has_write(id),
+                "{} can't be transmuted to {}, which writes {}",
+                type_name::<T>(),
+                type_name::<U>(),
+                name(id)
+            );
+        }
+        // The lens iterates the tables matched by the query, so it can't require more of them
+        for &id in lens_access.required() {
+            assert!(
+                access.required().contains(&id),
+                "{} can't be transmuted to {}, which requires {}",
+                type_name::<T>(),
+                type_name::<U>(),
+                name(id)
+            );
+        }
+        for ids in lens_access.any_of() {
+            assert!(
+                ids.iter().any(|id| access.required().contains(id))
+                    || access
+                        .any_of()
+                        .any(|group| group.iter().all(|id| ids.contains(id))),
+                "{} can't be transmuted to {}, which requires one of {}",
+                type_name::<T>(),
+                type_name::<U>(),
+                ids.iter()
+                    .map(|id| name(*id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let state = QueryState {
+            world_id: world.id(),
+            fetch_state,
+            filter_state,
+            matched_tables: self.state.matched_tables.clone(),
+            seen_archetypes: self.state.seen_archetypes,
+            last_run: self.state.last_run,
+        };
+        Query {
+            world,
+            state: QueryStateRef::Owned(state),
+            current_table: self.current_table,
+            current_row: self.current_row,
+            back_table: self.back_table,
+            back_row: self.back_row,
+            since: self.since,
+        }
+    }
+
+    /// Explains which archetypes of the world this query matches and why all others are
+    /// rejected, e.g. to find out why a query doesn't return any entities.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// struct Position(f32);
+    /// impl Component for Position {}
+    /// struct Velocity(f32);
+    /// impl Component for Velocity {}
+    ///
+    /// let mut world = World::new();
+    /// world.spawn(Position(0.0));
+    /// world.spawn(Velocity(1.0));
+    ///
+    /// let explanation = world.query::<(&Position, &Velocity)>().explain();
+    /// assert_eq!(explanation.estimated_rows(), 0);
+    /// assert_eq!(explanation.rejected().count(), 2);
+    /// println!("{explanation}");
+    /// ```
+    pub fn explain(&self) -> QueryExplanation {
+        let (access, implicit_disabled) = Self::access(
+            self.world,
+            &self.state.fetch_state,
+            &self.state.filter_state,
+        );
+        let components = &self.world.components;
+        let name = |id: ComponentId| {
+            components
+                .name(id)
+                .expect("query components must be registered")
+                .to_owned()
+        };
+
+        let archetypes = self
+            .world
+            .archetypes
+            .iter()
+            .map(|archetype| {
+                let mut rejections = Vec::new();
+                for &id in access.required() {
+                    if !archetype.contains(id) {
+                        rejections.push(Rejection::Missing { id, name: name(id) });
+                    }
+                }
+                for ids in access.any_of() {
+                    if !ids.iter().any(|id| archetype.contains(*id)) {
+                        rejections.push(Rejection::MissingAny {
+                            names: ids.iter().map(|id| name(*id)).collect(),
+                        });
+                    }
+                }
+                for &id in access.excluded() {
+                    if archetype.contains(id) {
+                        rejections.push(if Some(id) == implicit_disabled {
+                            Rejection::Disabled
+                        } else {
+                            Rejection::Excluded { id, name: name(id) }
+                        });
+                    }
+                }
+                ArchetypeExplanation {
+                    archetype: archetype.id(),
+                    rows: archetype.entity_count(),
+                    rejections,
+                }
+            })
+            .collect();
+
+        QueryExplanation {
+            query: type_name::<T>(),
+            filter: type_name::<F>(),
+            archetypes,
+        }
+    }
+
+    fn table(&self, index: usize) -> &'world Table {
+        self.world
+            .tables
+            .get(self.state.matched_tables[index])
+            .expect("matched tables must exist in the world")
+    }
+
+    fn matches(&self, table_id: TableId) -> bool {
+        self.state.matched_tables.binary_search(&table_id).is_ok()
+    }
+
+    /// `true` if every live row of a matched table holds an item.
+    const DENSE: bool = T::IS_DENSE && F::IS_DENSE && F::IS_ARCHETYPAL;
+
+    /// Returns `true` if the row of a matched table holds an item, i.e. it is live and passes
+    /// the row filters.
+    fn matches_row(&self, table: &Table, row: TableRow) -> bool {
+        table.is_live(row)
+            && Self::live_row_matches(
+                self.world,
+                &self.state.fetch_state,
+                &self.state.filter_state,
+                table,
+                row,
+                self.since,
+            )
+    }
+
+    /// Returns `true` if the live row of a matched table holds an item, i.e. its entity has the
+    /// sparse set components of the terms and filters and the row passes the row filters.
+    pub(crate) fn live_row_matches(
+        world: &World,
+        fetch_state: &T::State,
+        filter_state: &F::State,
+        table: &Table,
+        row: TableRow,
+        since: Tick,
+    ) -> bool {
+        (T::IS_DENSE || T::matches_row(world, fetch_state, table, row))
+            && (F::IS_DENSE || F::matches_row(world, filter_state, table, row))
+            && (F::IS_ARCHETYPAL || F::filter_row(world, filter_state, table, row, since))
+    }
+
+    /// Fetches the item of the entity without checking that the entity is alive or matched by
+    /// this query, for hot loops over entities that were already validated.
+    ///
+    /// # Safety
+    /// The entity must be alive and matched by this query. This is only checked with debug
+    /// assertions. For queries with mutable terms, no other item of the entity may be alive.
+    pub unsafe fn get_unchecked(&self, entity: Entity) -> T::Item<'world> {
+        let location = self.world.entities.get_unchecked(entity);
+        debug_assert!(
+            self.matches(location.table_id),
+            "{entity:?} is not matched by this query"
+        );
+
+        let table = self.world.tables.get_unchecked_raw(location.table_id);
+        T::fetch(
+            self.world,
+            &self.state.fetch_state,
+            table,
+            location.table_row,
+            self.since,
+        )
+    }
+
+    pub(crate) fn world(&self) -> &'world World {
+        self.world
+    }
+
+    /// Fetches the item of the entity, if it is alive and matched by this query.
+    ///
+    /// Safety: For queries with mutable terms, no other item of the entity may be alive.
+    pub(crate) unsafe fn get_matched(&self, entity: Entity) -> Option<T::Item<'world>> {
+        let location = self.world.entities.get(entity)?;
+        if !self.matches(location.table_id) {
+            return None;
+        }
+
+        let table = self.world.tables.get(location.table_id)?;
+        if !Self::live_row_matches(
+            self.world,
+            &self.state.fetch_state,
+            &self.state.filter_state,
+            table,
+            location.table_row,
+            self.since,
+        ) {
+            return None;
+        }
+        Some(T::fetch(
+            self.world,
+            &self.state.fetch_state,
+            table,
+            location.table_row,
+            self.since,
+        ))
+    }
+
+    /// Advances the front cursor by up to `max` rows of a single table and returns the table
+    /// together with the claimed row range.
+    fn next_rows(&mut self, max: usize) -> Option<(&'world Table, TableRow, TableRow)> {
+        loop {
+            if self.current_table >= self.state.matched_tables.len()
+                || self.current_table > self.back_table
+            {
+                return None;
+            }
+
+            let table = self.table(self.current_table);
+            let end = if self.current_table == self.back_table {
+                self.back_row
+            } else {
+                TableRow(table.len())
+            };
+
+            if self.current_row < end {
+                let start = self.current_row;
+                self.current_row = TableRow(start.index().saturating_add(max).min(end.index()));
+                return Some((table, start, self.current_row));
+            }
+
+            if self.current_table == self.back_table {
+                return None;
+            }
+            self.current_table += 1;
+            self.current_row = TableRow(0);
+        }
+    }
+
+    /// The row ranges left between the front and the back cursor, without advancing them.
+    fn remaining_rows(&self) -> impl Iterator<Item = (&'world Table, TableRow, TableRow)> + '_ {
+        let tables = if self.current_table < self.state.matched_tables.len() {
+            self.current_table..self.back_table + 1
+        } else {
+            0..0
+        };
+        tables.filter_map(|index| {
+            let table = self.table(index);
+            let start = if index == self.current_table {
+                self.current_row
+            } else {
+                TableRow(0)
+            };
+            let end = if index == self.back_table {
+                self.back_row
+            } else {
+                TableRow(table.len())
+            };
+            (start < end).then_some((table, start, end))
+        })
+    }
+
+    /// The number of remaining items. Computed from the table lengths without fetching any,
+    /// unless the filter checks single rows.
+    fn remaining(&self) -> usize {
+        self.remaining_rows()
+            .map(|(table, start, end)| {
+                if Self::DENSE {
+                    table.live_rows(start, end)
+                } else {
+                    (start.index()..end.index())
+                        .filter(|row| self.matches_row(table, TableRow(*row)))
+                        .count()
+                }
+            })
+            .sum()
+    }
+
+    /// Returns `true` if there are no remaining items, e.g. to check whether any entity with a
+    /// component exists. Only looks at the table lengths, unless the filter checks single rows.
+    pub fn is_empty(&self) -> bool {
+        if Self::DENSE {
+            return self.remaining() == 0;
+        }
+        !self.remaining_rows().any(|(table, start, end)| {
+            (start.index()..end.index()).any(|row| self.matches_row(table, TableRow(row)))
+        })
+    }
+
+    /// Returns `true` if the predicate holds for any remaining item, stopping at the first one it
+    /// holds for.
+    pub fn any(&mut self, mut predicate: impl FnMut(T::Item<'world>) -> bool) -> bool {
+        while let Some((table, start, end)) = self.next_rows(usize::MAX) {
+            for row in start.index()..end.index() {
+                if !self.matches_row(table, TableRow(row)) {
+                    continue;
+                }
+                if predicate(T::fetch(
+                    self.world,
+                    &self.state.fetch_state,
+                    table,
+                    TableRow(row),
+                    self.since,
+                )) {
+                    self.current_row = TableRow(row + 1);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns `true` if the predicate holds for all remaining items, stopping at the first one it
+    /// does not hold for.
+    pub fn all(&mut self, mut predicate: impl FnMut(T::Item<'world>) -> bool) -> bool {
+        !self.any(|item| !predicate(item))
+    }
+
+    /// Returns the only remaining item, e.g. of a component that only the camera or the player
+    /// has, or an error if there is no item or more than one.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, query::QuerySingleError, World};
+    /// #[derive(Component)]
+    /// struct Player(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn(Player(3));
+    /// world.query_mut::<&mut Player>().single().0 += 1;
+    /// assert_eq!(world.query::<&Player>().single().0, 4);
+    ///
+    /// world.spawn(Player(0));
+    /// assert!(matches!(
+    ///     world.query::<&Player>().get_single(),
+    ///     Err(QuerySingleError::MultipleEntities(_))
+    /// ));
+    /// ```
+    pub fn get_single(mut self) -> Result<T::Item<'world>, QuerySingleError> {
+        let item = self
+            .next()
+            .ok_or(QuerySingleError::NoEntities(type_name::<Self>()))?;
+        if self.is_empty() {
+            Ok(item)
+        } else {
+            Err(QuerySingleError::MultipleEntities(type_name::<Self>()))
+        }
+    }
+
+    /// Returns the only remaining item, like [`Query::get_single`].
+    ///
+    /// Panics: If there is no item or more than one.
+    pub fn single(self) -> T::Item<'world> {
+        self.get_single().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Iterates over the remaining items in slices of up to `size` items, returned by
+    /// [`QueryBatches::next_batch`].
+    ///
+    /// A batch never spans multiple tables, so the entities of a batch are stored next to each
+    /// other, which suits uploading them to the GPU or processing them with SIMD kernels.
+    ///
+    /// Panics: If `size` is zero.
+    pub fn iter_batched(self, size: usize) -> QueryBatches<'world, T, F> {
+        assert!(size > 0, "Batches must contain at least one item");
+        QueryBatches {
+            query: self,
+            size,
+            items: Vec::with_capacity(size),
+        }
+    }
+
+    /// Iterates over the remaining items ordered by the key extracted from every item, e.g. to
+    /// draw sprites by their layer.
+    ///
+    /// The items are collected and sorted before the first one is returned. Items only borrow the
+    /// components, so no component is copied. The sort is stable, so items with equal keys keep
+    /// their storage order.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct Layer(i32);
+    /// #[derive(Component)]
+    /// struct Sprite(&'static str);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn((Sprite("front"), Layer(2)));
+    /// world.spawn((Sprite("back"), Layer(-1)));
+    /// world.spawn((Sprite("middle"), Layer(0)));
+    ///
+    /// let sprites = world
+    ///     .query::<(&Sprite, &Layer)>()
+    ///     .iter_sorted_by_key(|(_, layer)| layer.0)
+    ///     .map(|(sprite, _)| sprite.0)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(sprites, ["back", "middle", "front"]);
+    /// ```
+    pub fn iter_sorted_by_key<K: Ord>(
+        self,
+        mut key: impl FnMut(&T::Item<'world>) -> K,
+    ) -> vec::IntoIter<T::Item<'world>> {
+        self.iter_sorted_by(|a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Like [`Query::iter_sorted_by_key`], but orders the items with a comparison function.
+    pub fn iter_sorted_by(
+        self,
+        compare: impl FnMut(&T::Item<'world>, &T::Item<'world>) -> Ordering,
+    ) -> vec::IntoIter<T::Item<'world>> {
+        let mut items = self.collect::<Vec<_>>();
+        items.sort_by(compare);
+        items.into_iter()
+    }
+}
+
+/// The minimum number of items folded by a single thread in [`Query::par_fold`].
+const PAR_MIN_CHUNK_SIZE: usize = 1024;
+
+impl<'world, T: Queryable, F: QueryFilter> Query<'world, T, F>
+where
+    T::State: Sync,
+    F::State: Sync,
+{
+    /// Folds the remaining items on multiple threads.
+    ///
+    /// The items are split into chunks, each folded into a partial result starting at
+    /// `identity()`. The partial results are then combined with `reduce` in iteration order.
+    /// Small queries are folded on the calling thread.
+    pub fn par_fold<A, I, G, R>(mut self, identity: I, fold: G, reduce: R) -> A
+    where
+        A: Send,
+        I: Fn() -> A + Sync,
+        G: Fn(A, T::Item<'world>) -> A + Sync,
+        R: Fn(A, A) -> A,
+    {
+        let mut ranges = Vec::new();
+        while let Some(range) = self.next_rows(usize::MAX) {
+            ranges.push(range);
+        }
+        let total = ranges
+            .iter()
+            .map(|(table, start, end)| table.live_rows(*start, *end))
+            .sum::<usize>();
+        let threads = thread::available_parallelism().map_or(1, NonZero::get);
+        let chunk_size = total.div_ceil(threads).max(PAR_MIN_CHUNK_SIZE);
+
+        // Split the ranges into chunks of `chunk_size` rows, which may span multiple tables
+        let mut chunks = vec![Vec::new()];
+        let mut chunk_len = 0;
+        for (table, mut start, end) in ranges {
+            while start < end {
+                if chunk_len == chunk_size {
+                    chunks.push(Vec::new());
+                    chunk_len = 0;
+                }
+                let len = (end.index() - start.index()).min(chunk_size - chunk_len);
+                let stop = TableRow(start.index() + len);
+                chunks.last_mut().unwrap().push((table, start, stop));
+                chunk_len += len;
+                start = stop;
+            }
+        }
+
+        let world = self.world;
+        let state = &self.state.fetch_state;
+        let filter_state = &self.state.filter_state;
+        let since = self.since;
+        let fold_chunk = |chunk: &[(&'world Table, TableRow, TableRow)]| {
+            let mut acc = identity();
+            for (table, start, end) in chunk {
+                for row in start.index()..end.index() {
+                    let row_matches = table.is_live(TableRow(row))
+                        && Self::live_row_matches(
+                            world,
+                            state,
+                            filter_state,
+                            table,
+                            TableRow(row),
+                            since,
+                        );
+                    if row_matches {
+                        acc = fold(acc, T::fetch(world, state, table, TableRow(row), since));
+                    }
+                }
+            }
+            acc
+        };
+
+        let (first, rest) = chunks.split_first().unwrap();
+        if rest.is_empty() {
+            return fold_chunk(first);
+        }
+
+        thread::scope(|scope| {
+            let handles = rest
+                .iter()
+                .map(|chunk| scope.spawn(|| fold_chunk(chunk)))
+                .collect::<Vec<_>>();
+            let acc = fold_chunk(first);
+
+            handles.into_iter().fold(acc, |acc, handle| {
+                let partial = handle
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err));
+                reduce(acc, partial)
+            })
+        })
+    }
+
+    /// Maps the remaining items on multiple threads and combines the results with `reduce`, see
+    /// [`Query::par_fold`]. Returns `None` if there are no items.
+    pub fn par_map_reduce<A, M, R>(self, map: M, reduce: R) -> Option<A>
+    where
+        A: Send,
+        M: Fn(T::Item<'world>) -> A + Sync,
+        R: Fn(A, A) -> A + Sync,
+    {
+        let combine = |a: Option<A>, b: Option<A>| match (a, b) {
+            (Some(a), Some(b)) => Some(reduce(a, b)),
+            (a, b) => a.or(b),
+        };
+
+        self.par_fold(|| None, |acc, item| combine(acc, Some(map(item))), combine)
+    }
+}
+
+/// The error returned by [`Query::get_single`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuerySingleError {
+    /// No entity matches the query, which is named by the field.
+    NoEntities(&'static str),
+    /// More than one entity matches the query, which is named by the field.
+    MultipleEntities(&'static str),
+}
+
+impl fmt::Display for QuerySingleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoEntities(query) => write!(f, "no entity matches the query {query}"),
+            Self::MultipleEntities(query) => {
+                write!(f, "more than one entity matches the query {query}")
+            }
+        }
+    }
+}
+
+impl Error for QuerySingleError {}
+
+/// The reason an archetype is not matched by a query, see [`Query::explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// The archetype lacks a component that a term or filter requires.
+    Missing { id: ComponentId, name: String },
+    /// The archetype lacks all components of which a term requires at least one, e.g. the
+    /// implementors of a queried trait.
+    MissingAny { names: Vec<String> },
+    /// The archetype has a component that a filter excludes.
+    Excluded { id: ComponentId, name: String },
+    /// The archetype stores [`Disabled`] entities, which are skipped unless the query includes
+    /// them, e.g. with [`IncludeDisabled`].
+    Disabled,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { name, .. } => write!(f, "missing {name}"),
+            Self::MissingAny { names } => write!(f, "missing any of [{}]", names.join(", ")),
+            Self::Excluded { name, .. } => write!(f, "excluded by Without<{name}>"),
+            Self::Disabled => write!(f, "disabled entities"),
+        }
+    }
+}
+
+/// How a query relates to a single archetype, see [`Query::explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchetypeExplanation {
+    pub archetype: ArchetypeId,
+    /// The number of entities currently stored in the archetype.
+    pub rows: usize,
+    /// Every reason the archetype is rejected, empty if it is matched.
+    pub rejections: Vec<Rejection>,
+}
+
+impl ArchetypeExplanation {
+    pub fn is_matched(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+/// Diagnostics of which archetypes a query matches, returned by [`Query::explain`].
+///
+/// The [`Display`](fmt::Display) implementation prints a human readable report.
+#[derive(Debug, Clone)]
+pub struct QueryExplanation {
+    query: &'static str,
+    filter: &'static str,
+    archetypes: Vec<ArchetypeExplanation>,
+}
+
+impl QueryExplanation {
+    /// All archetypes of the world, ordered by their id.
+    pub fn archetypes(&self) -> &[ArchetypeExplanation] {
+        &self.archetypes
+    }
+
+    pub fn matched(&self) -> impl Iterator<Item = &ArchetypeExplanation> {
+        self.archetypes.iter().filter(|a| a.is_matched())
+    }
+
+    pub fn rejected(&self) -> impl Iterator<Item = &ArchetypeExplanation> {
+        self.archetypes.iter().filter(|a| !a.is_matched())
+    }
+
+    /// The number of entities the query currently yields.
+    pub fn estimated_rows(&self) -> usize {
+        self.matched().map(|a| a.rows).sum()
+    }
+}
+
+impl fmt::Display for QueryExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Query<{}, {}> matches {} of {} archetypes with {} rows",
+            self.query,
+            self.filter,
+            self.matched().count(),
+            self.archetypes.len(),
+            self.estimated_rows()
+        )?;
+        for explanation in &self.archetypes {
+            write!(
+                f,
+                "  archetype {} ({} rows): ",
+                explanation.archetype.index(),
+                explanation.rows
+            )?;
+            if explanation.is_matched() {
+                writeln!(f, "matched")?;
+            } else {
+                write!(f, "rejected")?;
+                for (i, rejection) in explanation.rejections.iter().enumerate() {
+                    let separator = if i == 0 { ": " } else { ", " };
+                    write!(f, "{separator}{rejection}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over batches of query items, see [`Query::iter_batched`].
+pub struct QueryBatches<'world, T: Queryable, F: QueryFilter = ()> {
+    query: Query<'world, T, F>,
+    size: usize,
+    /// The items of the current batch, reused for every batch.
+    items: Vec<T::Item<'world>>,
+}
+
+impl<'world, T: Queryable, F: QueryFilter> QueryBatches<'world, T, F> {
+    /// Returns the next batch, which holds `size` items unless the table or the query runs out
+    /// of items first. Batches are never empty.
+    pub fn next_batch(&mut self) -> Option<&mut [T::Item<'world>]> {
+        self.items.clear();
+        let mut batch_table = None;
+        while self.items.len() < self.size {
+            let Some((table, start, end)) = self.query.next_rows(self.size - self.items.len())
+            else {
+                break;
+            };
+            if batch_table.is_some_and(|index| index != self.query.current_table) {
+                // The rows belong to the next table, so they are left for the next batch
+                self.query.current_row = start;
+                break;
+            }
+
+            for row in (start.index()..end.index()).map(TableRow) {
+                if self.query.matches_row(table, row) {
+                    self.items.push(T::fetch(
+                        self.query.world,
+                        &self.query.state.fetch_state,
+                        table,
+                        row,
+                        self.query.since,
+                    ));
+                }
+            }
+            if !self.items.is_empty() {
+                batch_table = Some(self.query.current_table);
+            }
+        }
+
+        (!self.items.is_empty()).then_some(&mut self.items[..])
+    }
+}
+
+impl<'world, T: ReadOnlyQueryable, F: QueryFilter> Query<'world, T, F> {
+    /// Fetches the item of the entity, if it is alive and matched by this query.
+    pub fn get(&self, entity: Entity) -> Option<T::Item<'world>> {
+        // SAFETY: Read-only items can alias.
+        unsafe { self.get_matched(entity) }
+    }
+
+    /// Fetches the items of the given entities in their order, skipping entities that are not
+    /// alive or not matched by this query.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::new();
+    /// let team = [world.spawn(Health(3)), world.spawn(()), world.spawn(Health(1))];
+    ///
+    /// let query = world.query::<&Health>();
+    /// let health = query.iter_many(&team).map(|health| health.0).collect::<Vec<_>>();
+    /// assert_eq!(health, [3, 1]);
+    /// ```
+    pub fn iter_many<I: IntoIterator<Item: Borrow<Entity>>>(
+        &self,
+        entities: I,
+    ) -> QueryManyIter<'_, 'world, T, F, I::IntoIter> {
+        QueryManyIter {
+            query: self,
+            entities: entities.into_iter(),
+        }
+    }
+}
+
+/// The items of a list of entities, see [`Query::iter_many`].
+pub struct QueryManyIter<'q, 'world, T: ReadOnlyQueryable, F: QueryFilter, I> {
+    query: &'q Query<'world, T, F>,
+    entities: I,
+}
+
+impl<'world, T: ReadOnlyQueryable, F: QueryFilter, I: Iterator<Item: Borrow<Entity>>> Iterator
+    for QueryManyIter<'_, 'world, T, F, I>
+{
+    type Item = T::Item<'world>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entities
+            .by_ref()
+            .find_map(|entity| self.query.get(*entity.borrow()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.entities.size_hint().1)
+    }
+}
+
+impl<'world, T: Queryable, F: QueryFilter> Iterator for Query<'world, T, F> {
+    type Item = T::Item<'world>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (table, row, _) = self.next_rows(1)?;
+            if self.matches_row(table, row) {
+                return Some(T::fetch(
+                    self.world,
+                    &self.state.fetch_state,
+                    table,
+                    row,
+                    self.since,
+                ));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if F::IS_ARCHETYPAL {
+            let remaining = self.remaining();
+            return (remaining, Some(remaining));
+        }
+        let rows = self
+            .remaining_rows()
+            .map(|(table, start, end)| table.live_rows(start, end))
+            .sum();
+        (0, Some(rows))
+    }
+
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    /// Walks the remaining items table by table instead of item by item, which also speeds up
+    /// the iterator adapters built on top of it, like `for_each`, `sum`, `min_by_key` and
+    /// `max_by_key`.
+    fn fold<B, G>(mut self, init: B, mut f: G) -> B
+    where
+        G: FnMut(B, Self::Item) -> B,
+    {
+        let mut acc = init;
+        while let Some((table, start, end)) = self.next_rows(usize::MAX) {
+            for row in start.index()..end.index() {
+                if self.matches_row(table, TableRow(row)) {
+                    acc = f(
+                        acc,
+                        T::fetch(
+                            self.world,
+                            &self.state.fetch_state,
+                            table,
+                            TableRow(row),
+                            self.since,
+                        ),
+                    );
+                }
+            }
+        }
+        acc
+    }
+}
+
+impl<T: Queryable, F: ArchetypeFilter> ExactSizeIterator for Query<'_, T, F> {}
+
+impl<'world, T: Queryable, F: QueryFilter> DoubleEndedIterator for Query<'world, T, F> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current_table >= self.state.matched_tables.len()
+                || self.current_table > self.back_table
+            {
+                return None;
+            }
+
+            let table = self.table(self.back_table);
+            let start = if self.current_table == self.back_table {
+                self.current_row
+            } else {
+                TableRow(0)
+            };
+
+            if self.back_row > start {
+                self.back_row -= 1;
+                if self.matches_row(table, self.back_row) {
+                    return Some(T::fetch(
+                        self.world,
+                        &self.state.fetch_state,
+                        table,
+                        self.back_row,
+                        self.since,
+                    ));
+                }
+                continue;
+            }
+
+            if self.current_table == self.back_table {
+                return None;
+            }
+            self.back_table -= 1;
+            self.back_row = TableRow(self.table(self.back_table).len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::type_name;
+
+    use crate::{
+        component::{Component, Disabled, StorageType},
+        entity::Entity,
+        query::Query,
+        World,
+    };
+
+    use super::{
+        Added, AnyOf, Changed, IncludeDisabled, Or, QueryBatches, QuerySingleError, QueryState,
+        Ref, Rejection, With, Without,
+    };
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct MyComponent(u32);
+    impl Component for MyComponent {}
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct Position {
+        x: f32,
+        y: f32,
+        z: f32,
+    }
+    impl Component for Position {}
+
+    #[test]
+    fn query() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(1));
+
+        assert_eq!(entity, Entity::from(1, 0));
+
+        let mut query = world.query::<&MyComponent>();
+        assert_eq!(query.next(), Some(&MyComponent(1)));
+        assert_eq!(query.next(), None);
+    }
+
+    #[test]
+    fn single() {
+        let mut world = World::new();
+        let player = world.spawn((
+            MyComponent(1),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+        world.spawn(MyComponent(2));
+
+        assert_eq!(world.query::<(Entity, &Position)>().single().0, player);
+        assert!(matches!(
+            world.query::<&MyComponent>().get_single(),
+            Err(QuerySingleError::MultipleEntities(_))
+        ));
+        assert_eq!(
+            world
+                .query_filtered::<&MyComponent, Without<Position>>()
+                .get_single(),
+            Ok(&MyComponent(2))
+        );
+
+        world.despawn(player);
+        let err = world.query::<&Position>().get_single().unwrap_err();
+        assert_eq!(
+            err,
+            QuerySingleError::NoEntities(type_name::<Query<&Position>>())
+        );
+        assert!(err.to_string().starts_with("no entity matches the query"));
+    }
+
+    #[test]
+    fn multi_query() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(1));
+        let entity2 = world.spawn((
+            MyComponent(1337),
+            Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            },
+        ));
+
+        assert_eq!(entity, Entity::from(1, 0));
+        assert_eq!(entity2, Entity::from(1, 1));
+
+        let mut query = world.query::<&MyComponent>();
+        assert_eq!(query.next(), Some(&MyComponent(1)));
+        assert_eq!(query.next(), Some(&MyComponent(1337)));
+        assert_eq!(query.next(), None);
+
+        let mut query = world.query::<&Position>();
+        assert_eq!(
+            query.next(),
+            Some(&Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            })
+        );
+        assert_eq!(query.next(), None);
+
+        let mut query = world.query::<(&MyComponent, &Position)>();
+        assert_eq!(
+            query.next(),
+            Some((
+                &MyComponent(1337),
+                &Position {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 2.0,
+                }
+            ))
+        );
+        assert_eq!(query.next(), None);
+    }
+
+    #[test]
+    fn large_query() {
+        const ENTITY_COUNT: u32 = 1000;
+        let mut world = World::new();
+
+        for i in 0..ENTITY_COUNT {
+            world.spawn(MyComponent(i));
+        }
+
+        let mut count = 0;
+        for (i, e) in world.query::<&MyComponent>().enumerate() {
+            assert_eq!(e, &MyComponent(i as u32));
+            count += 1;
+        }
+        assert_eq!(count, ENTITY_COUNT);
+    }
+
+    #[test]
+    fn query_rev() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        world.spawn(MyComponent(1));
+        world.spawn((
+            MyComponent(2),
+            Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            },
+        ));
+
+        let items = world.query::<&MyComponent>().rev().collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![&MyComponent(2), &MyComponent(1), &MyComponent(0)]
+        );
+    }
+
+    #[test]
+    fn query_double_ended() {
+        let mut world = World::new();
+        for i in 0..4 {
+            world.spawn(MyComponent(i));
+        }
+        world.spawn((
+            MyComponent(4),
+            Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            },
+        ));
+
+        let mut query = world.query::<&MyComponent>();
+        assert_eq!(query.next_back(), Some(&MyComponent(4)));
+        assert_eq!(query.next(), Some(&MyComponent(0)));
+        assert_eq!(query.next_back(), Some(&MyComponent(3)));
+        assert_eq!(query.next(), Some(&MyComponent(1)));
+        assert_eq!(query.next_back(), Some(&MyComponent(2)));
+        assert_eq!(query.next(), None);
+        assert_eq!(query.next_back(), None);
+    }
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct Velocity(f32);
+    impl Component for Velocity {}
+
+    #[test]
+    fn mixed_query() {
+        let mut world = World::new();
+        let e0 = world.spawn((
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Velocity(1.0),
+        ));
+        let e1 = world.spawn((
+            Position {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            Velocity(2.0),
+        ));
+
+        for (position, velocity) in world.query_mut::<(&mut Position, &Velocity)>() {
+            position.x += velocity.0;
+        }
+
+        assert_eq!(world.get::<Position>(e0).map(|p| p.x), Some(1.0));
+        assert_eq!(world.get::<Position>(e1).map(|p| p.x), Some(3.0));
+    }
+
+    #[test]
+    fn optional_query() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        world.spawn((MyComponent(1), Velocity(1.0)));
+
+        let mut query = world.query::<(&MyComponent, Option<&Velocity>)>();
+        assert_eq!(query.next(), Some((&MyComponent(0), None)));
+        assert_eq!(query.next(), Some((&MyComponent(1), Some(&Velocity(1.0)))));
+        assert_eq!(query.next(), None);
+
+        for (component, velocity, position) in
+            world.query_mut::<(&mut MyComponent, Option<&mut Velocity>, Option<&Position>)>()
+        {
+            assert_eq!(position, None);
+            if let Some(velocity) = velocity {
+                velocity.0 = component.0 as f32 * 10.0;
+            }
+            component.0 += 1;
+        }
+
+        let mut query = world.query::<(&MyComponent, Option<&Velocity>)>();
+        assert_eq!(query.next(), Some((&MyComponent(1), None)));
+        assert_eq!(query.next(), Some((&MyComponent(2), Some(&Velocity(10.0)))));
+        assert_eq!(query.next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflicting_query() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+
+        let _ = world.query_mut::<(&mut MyComponent, &MyComponent)>();
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflicting_mut_query() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+
+        let _ = world.query_mut::<(&mut MyComponent, Option<&mut MyComponent>)>();
+    }
+
+    #[test]
+    fn query_get() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Velocity(1.0)));
+        let e2 = world.spawn(Velocity(2.0));
+        world.despawn(e2);
+
+        let query = world.query::<(&MyComponent, &Velocity)>();
+        assert_eq!(query.get(e0), None);
+        assert_eq!(query.get(e1), Some((&MyComponent(1), &Velocity(1.0))));
+        assert_eq!(query.get(e2), None);
+        assert_eq!(
+            unsafe { query.get_unchecked(e1) },
+            (&MyComponent(1), &Velocity(1.0))
+        );
+
+        let query = world.query_mut::<&mut MyComponent>();
+        unsafe { query.get_unchecked(e0) }.0 = 42;
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(42)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn query_get_unchecked_unmatched() {
+        let mut world = World::new();
+        let entity = world.spawn(MyComponent(0));
+
+        let query = world.query::<&Velocity>();
+        unsafe {
+            query.get_unchecked(entity);
+        }
+    }
+
+    #[test]
+    fn batched_query() {
+        let mut world = World::new();
+        for i in 0..3 {
+            world.spawn(MyComponent(i));
+        }
+        for i in 3..5 {
+            world.spawn((MyComponent(i), Velocity(0.0)));
+        }
+
+        let collect = |mut batches: QueryBatches<&MyComponent>| {
+            let mut values = Vec::new();
+            while let Some(batch) = batches.next_batch() {
+                values.push(batch.iter().map(|c| c.0).collect::<Vec<_>>());
+            }
+            values
+        };
+        let batches = collect(world.query::<&MyComponent>().iter_batched(2));
+        assert_eq!(batches, vec![vec![0, 1], vec![2], vec![3, 4]]);
+
+        let mut query = world.query::<&MyComponent>();
+        query.next();
+        query.next_back();
+        assert_eq!(collect(query.iter_batched(8)), vec![vec![1, 2], vec![3]]);
+
+        let mut changed = World::new();
+        let entities = (0..8)
+            .map(|i| changed.spawn(MyComponent(i)))
+            .collect::<Vec<_>>();
+        changed.increment_tick();
+        for i in [1, 2, 5, 7] {
+            changed.get_mut::<MyComponent>(entities[i]).unwrap();
+        }
+        let mut batches = changed
+            .query_filtered::<&MyComponent, Changed<MyComponent>>()
+            .iter_batched(2);
+        let mut values = Vec::new();
+        while let Some(batch) = batches.next_batch() {
+            values.push(batch.iter().map(|c| c.0).collect::<Vec<_>>());
+        }
+        assert_eq!(values, vec![vec![1, 2], vec![5, 7]]);
+
+        let mut batches = world.query_mut::<&mut MyComponent>().iter_batched(4);
+        while let Some(batch) = batches.next_batch() {
+            for component in batch {
+                component.0 *= 10;
+            }
+        }
+        let values = world
+            .query::<&MyComponent>()
+            .map(|c| c.0)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [0, 10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn aggregate_query() {
+        let mut world = World::new();
+        for i in 0..3 {
+            world.spawn((MyComponent(i), Velocity(i as f32)));
+        }
+        world.spawn(MyComponent(10));
+
+        let total = world.query::<&MyComponent>().fold(0, |acc, c| acc + c.0);
+        assert_eq!(total, 13);
+
+        let total: f32 = world.query::<&Velocity>().map(|v| v.0).sum();
+        assert_eq!(total, 3.0);
+
+        let min = world.query::<&MyComponent>().min_by_key(|c| c.0);
+        let max = world.query::<&MyComponent>().max_by_key(|c| c.0);
+        assert_eq!(min, Some(&MyComponent(0)));
+        assert_eq!(max, Some(&MyComponent(10)));
+
+        // Only the remaining items are folded
+        let mut query = world.query::<&MyComponent>();
+        query.next();
+        query.next_back();
+        assert_eq!(query.fold(0, |acc, c| acc + c.0), 3);
+    }
+
+    #[test]
+    fn parallel_fold() {
+        const COUNT: u32 = 10_000;
+        let mut world = World::new();
+        for i in 0..COUNT {
+            if i % 2 == 0 {
+                world.spawn(MyComponent(i));
+            } else {
+                world.spawn((MyComponent(i), Velocity(1.0)));
+            }
+        }
+
+        let total = world.query::<&MyComponent>().par_fold(
+            || 0u64,
+            |acc, c| acc + c.0 as u64,
+            |a, b| a + b,
+        );
+        assert_eq!(total, (0..COUNT as u64).sum::<u64>());
+
+        // Partial results are combined in iteration order
+        let ids = world.query::<&MyComponent>().par_fold(
+            Vec::new,
+            |mut acc, c| {
+                acc.push(c.0);
+                acc
+            },
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        );
+        let expected = world
+            .query::<&MyComponent>()
+            .map(|c| c.0)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+
+        let max = world
+            .query::<&MyComponent>()
+            .par_map_reduce(|c| c.0, u32::max);
+        assert_eq!(max, Some(COUNT - 1));
+        assert_eq!(
+            world
+                .query_filtered::<&MyComponent, Without<MyComponent>>()
+                .par_map_reduce(|c| c.0, u32::max),
+            None
+        );
+
+        world
+            .query_mut::<&mut MyComponent>()
+            .par_fold(|| (), |_, c| c.0 = 0, |_, _| ());
+        assert!(world.query::<&MyComponent>().all(|c| c.0 == 0));
+    }
+
+    #[test]
+    fn short_circuit_query() {
+        let mut world = World::new();
+        for i in 0..4 {
+            world.spawn(MyComponent(i));
+        }
+        world.spawn((MyComponent(4), Velocity(1.0)));
+
+        let mut query = world.query::<&MyComponent>();
+        assert_eq!(query.len(), 5);
+        assert!(query.any(|c| c.0 == 1));
+        // The query resumes after the matching item
+        assert_eq!(query.len(), 3);
+        assert_eq!(query.next(), Some(&MyComponent(2)));
+
+        assert!(world.query::<&MyComponent>().all(|c| c.0 < 5));
+        assert!(!world.query::<&MyComponent>().all(|c| c.0 < 4));
+        assert!(!world.query::<&MyComponent>().any(|c| c.0 > 4));
+
+        assert!(!world.query_filtered::<Entity, With<Velocity>>().is_empty());
+        assert!(world
+            .query_filtered::<Entity, (With<Velocity>, Without<MyComponent>)>()
+            .is_empty());
+        assert_eq!(world.query::<&Velocity>().count(), 1);
+
+        let mut query = world.query::<&MyComponent>();
+        query.next();
+        query.next_back();
+        assert_eq!(query.len(), 3);
+        assert_eq!(query.count(), 3);
+    }
+
+    #[test]
+    fn query_state() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let mut state = QueryState::<(Entity, &MyComponent)>::new(&world);
+        let e1 = world.spawn((MyComponent(1), Velocity(1.0)));
+        let mut velocities = QueryState::<&mut Velocity, Without<Position>>::new(&world);
+        assert_eq!(velocities.iter_mut(&mut world).count(), 1);
+
+        let e2 = world.spawn((MyComponent(2), Disabled));
+        assert_eq!(
+            state.iter(&world).collect::<Vec<_>>(),
+            vec![(e0, &MyComponent(0)), (e1, &MyComponent(1))]
+        );
+        assert_eq!(state.iter(&world).get(e2), None);
+
+        world.spawn((
+            Velocity(2.0),
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        ));
+        let e3 = world.spawn(Velocity(3.0));
+        for velocity in velocities.iter_mut(&mut world) {
+            velocity.0 *= 2.0;
+        }
+        assert_eq!(world.get::<Velocity>(e1), Some(&Velocity(2.0)));
+        assert_eq!(world.get::<Velocity>(e3), Some(&Velocity(6.0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "world it was not created for")]
+    fn query_state_other_world() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        let mut state = QueryState::<&MyComponent>::new(&world);
+
+        let mut other = World::new();
+        other.spawn(Velocity(1.0));
+        state.iter(&other).for_each(drop);
+    }
+
+    #[test]
+    fn explain_query() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        world.spawn(MyComponent(1));
+        world.spawn((MyComponent(2), Velocity(1.0)));
+        let disabled = world.spawn(MyComponent(3));
+        world.insert(disabled, Disabled);
+        world.spawn(Velocity(0.0));
+
+        let velocity = world.component_id::<Velocity>().unwrap();
+        let query = world.query_filtered::<&MyComponent, Without<Velocity>>();
+        let explanation = query.explain();
+        assert_eq!(explanation.archetypes().len(), 4);
+        assert_eq!(explanation.matched().count(), 1);
+        assert_eq!(explanation.estimated_rows(), 2);
+        assert_eq!(explanation.estimated_rows(), query.count());
+
+        let rejections = explanation
+            .rejected()
+            .map(|a| a.rejections.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rejections,
+            vec![
+                vec![Rejection::Excluded {
+                    id: velocity,
+                    name: type_name::<Velocity>().to_owned()
+                }],
+                vec![Rejection::Disabled],
+                vec![
+                    Rejection::Missing {
+                        id: world.component_id::<MyComponent>().unwrap(),
+                        name: type_name::<MyComponent>().to_owned()
+                    },
+                    Rejection::Excluded {
+                        id: velocity,
+                        name: type_name::<Velocity>().to_owned()
+                    }
+                ],
+            ]
+        );
+
+        let report = explanation.to_string();
+        assert!(report.contains("matches 1 of 4 archetypes with 2 rows"));
+        assert!(report.contains("rejected: disabled entities"));
+    }
+
+    #[test]
+    fn filtered_query() {
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+        world.spawn((MyComponent(1), Velocity(1.0)));
+        world.spawn((
+            MyComponent(2),
+            Position {
+                x: 0.0,
+                y: 1.0,
+                z: 2.0,
+            },
+        ));
+
+        let items = world
+            .query_filtered::<&MyComponent, With<Velocity>>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(1)]);
+
+        let items = world
+            .query_filtered::<&MyComponent, Without<Velocity>>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(0), &MyComponent(2)]);
+
+        let items = world
+            .query_filtered::<&MyComponent, (Without<Velocity>, Without<Position>)>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(0)]);
+
+        for component in world.query_filtered_mut::<&mut MyComponent, With<Position>>() {
+            component.0 = 42;
+        }
+        let items = world.query::<&MyComponent>().collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![&MyComponent(0), &MyComponent(1), &MyComponent(42)]
+        );
+    }
+
+    #[test]
+    fn filter_unspawned_component() {
+        struct Unspawned;
+        impl Component for Unspawned {}
+
+        let mut world = World::new();
+        world.spawn(MyComponent(0));
+
+        let items = world
+            .query_filtered::<&MyComponent, Without<Unspawned>>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(0)]);
+    }
+
+    #[test]
+    fn disabled_query() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(MyComponent(1));
+
+        world.disable(e0);
+        let items = world.query::<&MyComponent>().collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(1)]);
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(0)));
+
+        let items = world
+            .query::<(&MyComponent, Option<&Disabled>)>()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![(&MyComponent(1), None), (&MyComponent(0), Some(&Disabled))]
+        );
+
+        world.enable(e0);
+        let items = world.query::<&MyComponent>().collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(1), &MyComponent(0)]);
+    }
+
+    #[test]
+    fn include_disabled_query() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        world.spawn(MyComponent(1));
+        world.disable(e0);
+
+        let items = world
+            .query_filtered::<&MyComponent, IncludeDisabled>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(1), &MyComponent(0)]);
+
+        let items = world
+            .query_filtered::<&MyComponent, With<Disabled>>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(0)]);
+
+        let items = world
+            .query_filtered::<&MyComponent, Without<Disabled>>()
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec![&MyComponent(1)]);
+    }
+
+    #[test]
+    fn changed_query() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Velocity(1.0)));
+        world.spawn(Velocity(2.0));
+        let mut state = QueryState::<Entity, Changed<MyComponent>>::new(&world);
+        assert_eq!(state.iter(&world).collect::<Vec<_>>(), vec![e0, e1]);
+        assert_eq!(state.iter(&world).count(), 2);
+
+        // Changes of the tick of the previous run are reported again
+        world.increment_tick();
+        assert_eq!(state.iter(&world).count(), 2);
+        assert!(state.iter(&world).is_empty());
+        assert!(world
+            .query_filtered::<Entity, Changed<MyComponent>>()
+            .is_empty());
+
+        world.get_mut::<MyComponent>(e1).unwrap().0 += 1;
+        let changed = world.query_filtered::<Entity, Changed<MyComponent>>();
+        assert_eq!(changed.size_hint(), (0, Some(2)));
+        assert_eq!(changed.collect::<Vec<_>>(), vec![e1]);
+        assert_eq!(state.iter(&world).collect::<Vec<_>>(), vec![e1]);
+        assert_eq!(
+            world
+                .query_filtered::<Entity, (Changed<MyComponent>, With<Velocity>)>()
+                .get(e0),
+            None
+        );
+
+        world.increment_tick();
+        for velocity in world.query_filtered_mut::<&mut Velocity, With<MyComponent>>() {
+            velocity.0 += 1.0;
+        }
+        assert_eq!(
+            world
+                .query_filtered::<&Velocity, Changed<Velocity>>()
+                .collect::<Vec<_>>(),
+            vec![&Velocity(2.0)]
+        );
+        assert_eq!(state.iter(&world).collect::<Vec<_>>(), vec![e1]);
+        assert!(state.iter(&world).next().is_none());
+
+        world.increment_tick();
+        world.insert(e0, MyComponent(5));
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Changed<MyComponent>>()
+                .rev()
+                .collect::<Vec<_>>(),
+            vec![e0]
+        );
+        assert_eq!(state.iter(&world).collect::<Vec<_>>(), vec![e0]);
+    }
+
+    #[test]
+    fn added_query() {
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        world.increment_tick();
+        let e1 = world.spawn(MyComponent(1));
+        world.insert(e0, MyComponent(2));
+        world.insert(e0, Velocity(0.0));
+
+        let added = world
+            .query_filtered::<Entity, Added<MyComponent>>()
+            .collect::<Vec<_>>();
+        assert_eq!(added, vec![e1]);
+        let refs = world
+            .query::<(Entity, Ref<MyComponent>)>()
+            .map(|(entity, component)| (entity, component.is_added(), component.is_changed()))
+            .collect::<Vec<_>>();
+        assert_eq!(refs, vec![(e1, true, true), (e0, false, true)]);
+
+        let mut state = QueryState::<Ref<MyComponent>, Added<MyComponent>>::new(&world);
+        assert_eq!(state.iter(&world).count(), 2);
+        world.increment_tick();
+        assert_eq!(state.iter(&world).count(), 1);
+        world.increment_tick();
+        assert!(state.iter(&world).is_empty());
+        let component = world.query::<Ref<MyComponent>>().next().unwrap();
+        assert!(!component.is_changed());
+        assert_eq!(*component.into_inner(), MyComponent(1));
+    }
+
+    #[test]
+    fn wide_tuple_query() {
+        let mut world = World::new();
+        let e0 = world.spawn((MyComponent(0), Velocity(1.0)));
+
+        let (entity, component, velocity, position, .., last) = world
+            .query::<(
+                Entity,
+                &MyComponent,
+                Option<&Velocity>,
+                Option<&Position>,
+                Ref<MyComponent>,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                Entity,
+                &Velocity,
+            )>()
+            .next()
+            .unwrap();
+        assert_eq!(entity, e0);
+        assert_eq!(component, &MyComponent(0));
+        assert_eq!(velocity, Some(&Velocity(1.0)));
+        assert_eq!(position, None);
+        assert_eq!(last, &Velocity(1.0));
+
+        for (component, (velocity,)) in world.query_mut::<(&mut MyComponent, (&mut Velocity,))>() {
+            component.0 += 1;
+            velocity.0 *= 2.0;
+        }
+        assert_eq!(
+            world.get_components::<(&MyComponent, &Velocity)>(e0),
+            Some((&MyComponent(1), &Velocity(2.0)))
+        );
+    }
+
+    #[test]
+    fn sparse_set_terms() {
+        #[derive(Debug, PartialEq)]
+        struct Stunned(u32);
+        impl Component for Stunned {
+            const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+        }
+
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Stunned(1)));
+        let e2 = world.spawn(Velocity(2.0));
+        world.insert(e2, Stunned(2));
+        world.increment_tick();
+
+        let query = world.query::<(Entity, &MyComponent, &Stunned)>();
+        assert_eq!(query.len(), 1);
+        assert_eq!(
+            query.collect::<Vec<_>>(),
+            vec![(e1, &MyComponent(1), &Stunned(1))]
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, With<Stunned>>()
+                .collect::<Vec<_>>(),
+            vec![e1, e2]
+        );
+        let query = world.query_filtered::<Entity, (With<MyComponent>, Without<Stunned>)>();
+        assert_eq!(query.len(), 1);
+        assert_eq!(query.collect::<Vec<_>>(), vec![e0]);
+        assert_eq!(
+            world
+                .query::<(&MyComponent, Option<&Stunned>)>()
+                .map(|(_, stunned)| stunned.map(|stunned| stunned.0))
+                .collect::<Vec<_>>(),
+            vec![None, Some(1)]
+        );
+
+        for stunned in world.query_mut::<&mut Stunned>() {
+            stunned.0 += 10;
+        }
+        world.insert(e0, Stunned(0));
+        assert_eq!(
+            world
+                .query_filtered::<&Stunned, Changed<Stunned>>()
+                .map(|stunned| stunned.0)
+                .collect::<Vec<_>>(),
+            vec![0, 11, 12]
+        );
+        assert_eq!(
+            world
+                .query_filtered::<Entity, Added<Stunned>>()
+                .collect::<Vec<_>>(),
+            vec![e0]
+        );
+    }
+
+    #[test]
+    fn or_filter() {
+        #[derive(Debug, PartialEq)]
+        struct Stunned;
+        impl Component for Stunned {
+            const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+        }
+
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn(Velocity(1.0));
+        let e2 = world.spawn((MyComponent(2), Velocity(2.0)));
+        let e3 = world.spawn((
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Stunned,
+        ));
+        world.spawn(Position {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        world.increment_tick();
+
+        let query = world.query_filtered::<Entity, Or<(With<MyComponent>, With<Velocity>)>>();
+        assert_eq!(query.len(), 3);
+        assert_eq!(query.collect::<Vec<_>>(), vec![e0, e1, e2]);
+
+        let query = world.query_filtered::<Entity, Or<(With<Velocity>, With<Stunned>)>>();
+        assert_eq!(query.len(), 3);
+        assert_eq!(query.collect::<Vec<_>>(), vec![e1, e2, e3]);
+
+        world.get_mut::<MyComponent>(e2).unwrap().0 += 1;
+        world.get_mut::<Velocity>(e1).unwrap().0 += 1.0;
+        type Filter = Or<((With<Velocity>, Changed<MyComponent>), Without<Velocity>)>;
+        let changed = world.query_filtered::<Entity, Filter>().collect::<Vec<_>>();
+        assert_eq!(changed.len(), 4);
+        assert!(!changed.contains(&e1) && changed.contains(&e2));
+
+        world.increment_tick();
+        let mut state =
+            QueryState::<Entity, Or<(Changed<MyComponent>, Changed<Velocity>)>>::new(&world);
+        assert_eq!(state.iter(&world).count(), 3);
+        world.get_mut::<Velocity>(e1).unwrap().0 += 1.0;
+        world.increment_tick();
+        assert_eq!(state.iter(&world).collect::<Vec<_>>(), vec![e1]);
+    }
+
+    #[test]
+    fn any_of() {
+        #[derive(Debug, PartialEq)]
+        struct Stunned(u32);
+        impl Component for Stunned {
+            const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+        }
+
+        let mut world = World::new();
+        let e0 = world.spawn(MyComponent(0));
+        let e1 = world.spawn((MyComponent(1), Velocity(1.0)));
+        let e2 = world.spawn(Velocity(2.0));
+        let e3 = world.spawn((
+            Position {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Stunned(3),
+        ));
+        world.spawn(Position {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        });
+
+        let items = world
+            .query::<(Entity, AnyOf<(&MyComponent, &Velocity)>)>()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                (e0, (Some(&MyComponent(0)), None)),
+                (e1, (Some(&MyComponent(1)), Some(&Velocity(1.0)))),
+                (e2, (None, Some(&Velocity(2.0)))),
+            ]
+        );
+
+        for (velocity, stunned) in world.query_mut::<AnyOf<(&mut Velocity, &mut Stunned)>>() {
+            if let Some(velocity) = velocity {
+                velocity.0 *= 2.0;
+            }
+            if let Some(stunned) = stunned {
+                stunned.0 += 1;
+            }
+        }
+        assert_eq!(world.query::<AnyOf<(&Velocity, &Stunned)>>().len(), 3);
+        assert_eq!(world.get::<Velocity>(e2), Some(&Velocity(4.0)));
+        assert_eq!(world.get::<Stunned>(e3), Some(&Stunned(4)));
+        assert_eq!(
+            world.get_components::<AnyOf<(&MyComponent, &Position)>>(e2),
+            None
+        );
+
+        struct Unregistered;
+        impl Component for Unregistered {}
+        assert_eq!(
+            world
+                .query::<AnyOf<(&Velocity, &Unregistered)>>()
+                .map(|(velocity, _)| velocity.unwrap().0)
+                .collect::<Vec<_>>(),
+            vec![2.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn iter_sorted() {
+        let mut world = World::new();
+        let first = world.spawn(MyComponent(3));
+        world.spawn((MyComponent(1), Velocity(0.0)));
+        world.spawn(MyComponent(2));
+        world.spawn((MyComponent(1), Velocity(1.0)));
+
+        let sorted = world
+            .query::<&MyComponent>()
+            .iter_sorted_by_key(|component| component.0)
+            .map(|component| component.0)
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, [1, 1, 2, 3]);
+
+        // Equal keys keep their storage order
+        let velocities = world
+            .query::<(&MyComponent, &Velocity)>()
+            .iter_sorted_by_key(|(component, _)| component.0)
+            .map(|(_, velocity)| velocity.0)
+            .collect::<Vec<_>>();
+        assert_eq!(velocities, [0.0, 1.0]);
+
+        for (i, component) in world
+            .query_mut::<&mut MyComponent>()
+            .iter_sorted_by(|a, b| b.0.cmp(&a.0))
+            .enumerate()
+        {
+            component.0 = i as u32;
+        }
+        assert_eq!(world.get::<MyComponent>(first), Some(&MyComponent(0)));
+    }
+
+    #[test]
+    fn transmute_lens() {
+        fn double(components: Query<&mut MyComponent>) {
+            for component in components {
+                component.0 *= 2;
+            }
+        }
+
+        let mut world = World::new();
+        let position = Position {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let e0 = world.spawn((MyComponent(1), Velocity(1.0)));
+        let e1 = world.spawn((MyComponent(2), Velocity(1.0), position));
+        world.spawn(MyComponent(4));
+
+        let mut query = world.query_mut::<(Entity, &mut MyComponent, &Velocity)>();
+        let (first, component, _) = query.next().unwrap();
+        assert_eq!(first, e0);
+        // Only the entities the query hasn't yielded yet are part of the lens
+        double(query.transmute_lens());
+        let entities = query.transmute_lens::<Entity>().collect::<Vec<_>>();
+        assert_eq!(entities, [e1]);
+        component.0 = 10;
+        assert_eq!(query.count(), 1);
+
+        assert_eq!(world.get::<MyComponent>(e0), Some(&MyComponent(10)));
+        assert_eq!(world.get::<MyComponent>(e1), Some(&MyComponent(4)));
+
+        // The filter is kept
+        let mut query = world.query_filtered::<&Velocity, Without<Position>>();
+        let lens = query.transmute_lens::<Entity>();
+        assert_eq!(lens.collect::<Vec<_>>(), [e0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "which writes")]
+    fn transmute_lens_to_write() {
+        let mut world = World::new();
+        world.spawn((MyComponent(1), Velocity(1.0)));
+        world
+            .query::<(&MyComponent, &Velocity)>()
+            .transmute_lens::<&mut MyComponent>();
+    }
+
+    #[test]
+    #[should_panic(expected = "which reads")]
+    fn transmute_lens_to_other_component() {
+        let mut world = World::new();
+        world.spawn((MyComponent(1), Velocity(1.0)));
+        world.query::<&MyComponent>().transmute_lens::<&Velocity>();
+    }
+
+    #[test]
+    #[should_panic(expected = "which requires")]
+    fn transmute_lens_to_required() {
+        let mut world = World::new();
+        world.spawn((MyComponent(1), Velocity(1.0)));
+        world.spawn(MyComponent(2));
+        world
+            .query::<(Option<&Velocity>, &MyComponent)>()
+            .transmute_lens::<&Velocity>();
+    }
+
+    #[test]
+    fn iter_many() {
+        let mut world = World::new();
+        let a = world.spawn(MyComponent(0));
+        let b = world.spawn((MyComponent(1), Velocity(1.0)));
+        let c = world.spawn(Velocity(2.0));
+        let d = world.spawn(MyComponent(3));
+        world.despawn(d);
+
+        let query = world.query::<&MyComponent>();
+        let items = query.iter_many([b, c, d, a, b]).collect::<Vec<_>>();
+        assert_eq!(items, [&MyComponent(1), &MyComponent(0), &MyComponent(1)]);
+
+        let query = world.query_filtered::<Entity, With<Velocity>>();
+        let entities = vec![a, b, c];
+        assert_eq!(query.iter_many(&entities).collect::<Vec<_>>(), [b, c]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn any_of_conflict() {
+        let mut world = World::new();
+        world.spawn((MyComponent(0), Velocity(0.0)));
+        world.query_mut::<(&MyComponent, AnyOf<(&mut MyComponent, &Velocity)>)>();
     }
 }
@@ -0,0 +1,684 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    error::Error,
+    fmt,
+};
+
+use crate::{component::Component, entity::Entity, resource::Resource, World};
+
+pub use quartz_macros::Reflect;
+
+/// A value that can be inspected and mutated without knowing its type at compile time.
+///
+/// Structs derive it together with [`Struct`], [`Typed`] and [`FromReflect`] through
+/// `#[derive(Reflect)]`, which requires every field to implement `Reflect` and `FromReflect`.
+/// Primitives, `String`, [`Entity`], `Option` and `Vec` are reflected as opaque values.
+///
+/// ```
+/// # use quartz::reflect::{Reflect, ReflectRef};
+/// #[derive(Reflect)]
+/// struct Position {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// let mut position = Position { x: 1.0, y: 2.0 };
+/// let ReflectRef::Struct(reflected) = position.reflect_ref() else {
+///     unreachable!()
+/// };
+/// let names = reflected.iter_fields().map(|(name, _)| name).collect::<Vec<_>>();
+/// assert_eq!(names, ["x", "y"]);
+/// assert_eq!(reflected.field("y").unwrap().downcast_ref::<f32>(), Some(&2.0));
+/// ```
+pub trait Reflect: Any {
+    fn type_name(&self) -> &'static str;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn reflect_ref(&self) -> ReflectRef<'_>;
+
+    fn reflect_mut(&mut self) -> ReflectMut<'_>;
+
+    /// Copies the value, into a [`DynamicStruct`] for structs.
+    fn clone_value(&self) -> Box<dyn Reflect>;
+}
+
+impl dyn Reflect {
+    pub fn is<T: Reflect>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+}
+
+impl fmt::Debug for dyn Reflect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reflect_ref() {
+            ReflectRef::Struct(value) => {
+                let mut debug = f.debug_struct(value.type_name());
+                for (name, field) in value.iter_fields() {
+                    debug.field(name, &field);
+                }
+                debug.finish()
+            }
+            ReflectRef::Value(value) => write!(f, "Reflect({})", value.type_name()),
+        }
+    }
+}
+
+/// The shape of a reflected value.
+pub enum ReflectRef<'a> {
+    Struct(&'a dyn Struct),
+    /// An opaque value without inspectable fields.
+    Value(&'a dyn Reflect),
+}
+
+/// The shape of a mutably borrowed reflected value.
+pub enum ReflectMut<'a> {
+    Struct(&'a mut dyn Struct),
+    /// An opaque value without inspectable fields.
+    Value(&'a mut dyn Reflect),
+}
+
+/// A reflected struct with named fields. Fields of tuple structs are named by their position.
+pub trait Struct: Reflect {
+    fn field(&self, name: &str) -> Option<&dyn Reflect>;
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect>;
+
+    fn field_at(&self, index: usize) -> Option<&dyn Reflect>;
+
+    fn field_at_mut(&mut self, index: usize) -> Option<&mut dyn Reflect>;
+
+    fn name_at(&self, index: usize) -> Option<&str>;
+
+    fn field_len(&self) -> usize;
+}
+
+impl dyn Struct {
+    /// Iterates over the names and values of the fields, in declaration order.
+    pub fn iter_fields(&self) -> impl Iterator<Item = (&str, &dyn Reflect)> {
+        (0..self.field_len())
+            .filter_map(|index| Some((self.name_at(index)?, self.field_at(index)?)))
+    }
+}
+
+/// A struct built at runtime, which can be turned into a concrete type with [`FromReflect`].
+#[derive(Debug, Default)]
+pub struct DynamicStruct {
+    fields: Vec<(String, Box<dyn Reflect>)>,
+}
+
+impl DynamicStruct {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies the fields of the struct.
+    pub fn from_struct(value: &dyn Struct) -> Self {
+        Self {
+            fields: value
+                .iter_fields()
+                .map(|(name, field)| (name.to_owned(), field.clone_value()))
+                .collect(),
+        }
+    }
+
+    /// Sets the field, replacing the previous value if the field exists.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Reflect) {
+        self.insert_boxed(name, Box::new(value));
+    }
+
+    pub fn insert_boxed(&mut self, name: impl Into<String>, value: Box<dyn Reflect>) {
+        let name = name.into();
+        match self.fields.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, field)) => *field = value,
+            None => self.fields.push((name, value)),
+        }
+    }
+}
+
+impl Reflect for DynamicStruct {
+    fn type_name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn reflect_ref(&self) -> ReflectRef<'_> {
+        ReflectRef::Struct(self)
+    }
+
+    fn reflect_mut(&mut self) -> ReflectMut<'_> {
+        ReflectMut::Struct(self)
+    }
+
+    fn clone_value(&self) -> Box<dyn Reflect> {
+        Box::new(Self::from_struct(self))
+    }
+}
+
+impl Struct for DynamicStruct {
+    fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        let (_, value) = self.fields.iter().find(|(field, _)| field == name)?;
+        Some(&**value)
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        let (_, value) = self.fields.iter_mut().find(|(field, _)| field == name)?;
+        Some(&mut **value)
+    }
+
+    fn field_at(&self, index: usize) -> Option<&dyn Reflect> {
+        Some(&*self.fields.get(index)?.1)
+    }
+
+    fn field_at_mut(&mut self, index: usize) -> Option<&mut dyn Reflect> {
+        Some(&mut *self.fields.get_mut(index)?.1)
+    }
+
+    fn name_at(&self, index: usize) -> Option<&str> {
+        Some(&self.fields.get(index)?.0)
+    }
+
+    fn field_len(&self) -> usize {
+        self.fields.len()
+    }
+}
+
+/// A type whose structure is known without a value, e.g. to build a [`DynamicStruct`] for it in
+/// an editor.
+pub trait Typed: Reflect {
+    fn type_info() -> TypeInfo;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeInfo {
+    Struct {
+        type_id: TypeId,
+        type_name: &'static str,
+        fields: Vec<NamedField>,
+    },
+    Value {
+        type_id: TypeId,
+        type_name: &'static str,
+    },
+}
+
+impl TypeInfo {
+    pub fn type_id(&self) -> TypeId {
+        match self {
+            Self::Struct { type_id, .. } | Self::Value { type_id, .. } => *type_id,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Struct { type_name, .. } | Self::Value { type_name, .. } => type_name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamedField {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+}
+
+impl NamedField {
+    pub fn new<T: Reflect>(name: &'static str) -> Self {
+        Self {
+            name,
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+        }
+    }
+}
+
+/// Creates a concrete value from a reflected one, which may be a [`DynamicStruct`].
+pub trait FromReflect: Reflect + Sized {
+    /// Returns `None` if the value doesn't have the shape of `Self`, e.g. because a field is
+    /// missing or has another type.
+    fn from_reflect(value: &dyn Reflect) -> Option<Self>;
+}
+
+macro_rules! impl_reflect_value {
+    ($($ty:ty),*) => {
+        $(
+            impl Reflect for $ty {
+                fn type_name(&self) -> &'static str {
+                    type_name::<Self>()
+                }
+
+                fn as_any(&self) -> &dyn Any {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any {
+                    self
+                }
+
+                fn reflect_ref(&self) -> ReflectRef<'_> {
+                    ReflectRef::Value(self)
+                }
+
+                fn reflect_mut(&mut self) -> ReflectMut<'_> {
+                    ReflectMut::Value(self)
+                }
+
+                fn clone_value(&self) -> Box<dyn Reflect> {
+                    Box::new(self.clone())
+                }
+            }
+
+            impl Typed for $ty {
+                fn type_info() -> TypeInfo {
+                    TypeInfo::Value {
+                        type_id: TypeId::of::<Self>(),
+                        type_name: type_name::<Self>(),
+                    }
+                }
+            }
+
+            impl FromReflect for $ty {
+                fn from_reflect(value: &dyn Reflect) -> Option<Self> {
+                    value.downcast_ref::<Self>().cloned()
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value!(
+    bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, String,
+    Entity
+);
+
+/// Implements the reflection traits for a generic container as an opaque value.
+macro_rules! impl_reflect_container {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: Clone + 'static> Reflect for $ty<T> {
+                fn type_name(&self) -> &'static str {
+                    type_name::<Self>()
+                }
+
+                fn as_any(&self) -> &dyn Any {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any {
+                    self
+                }
+
+                fn reflect_ref(&self) -> ReflectRef<'_> {
+                    ReflectRef::Value(self)
+                }
+
+                fn reflect_mut(&mut self) -> ReflectMut<'_> {
+                    ReflectMut::Value(self)
+                }
+
+                fn clone_value(&self) -> Box<dyn Reflect> {
+                    Box::new(self.clone())
+                }
+            }
+
+            impl<T: Clone + 'static> Typed for $ty<T> {
+                fn type_info() -> TypeInfo {
+                    TypeInfo::Value {
+                        type_id: TypeId::of::<Self>(),
+                        type_name: type_name::<Self>(),
+                    }
+                }
+            }
+
+            impl<T: Clone + 'static> FromReflect for $ty<T> {
+                fn from_reflect(value: &dyn Reflect) -> Option<Self> {
+                    value.downcast_ref::<Self>().cloned()
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_container!(Option, Vec);
+
+/// The error returned when a reflected value can't be converted to the registered type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FromReflectError {
+    pub type_name: &'static str,
+}
+
+impl fmt::Display for FromReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the reflected value can't be converted to {}",
+            self.type_name
+        )
+    }
+}
+
+impl Error for FromReflectError {}
+
+/// Accesses a component of an entity as a reflected value, created by
+/// [`TypeRegistry::register_component`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReflectComponent {
+    get: fn(&World, Entity) -> Option<&dyn Reflect>,
+    get_mut: fn(&mut World, Entity) -> Option<&mut dyn Reflect>,
+    insert: fn(&mut World, Entity, &dyn Reflect) -> Result<(), FromReflectError>,
+}
+
+impl ReflectComponent {
+    fn new<T: Component + FromReflect>() -> Self {
+        Self {
+            get: |world, entity| Some(world.get::<T>(entity)?),
+            get_mut: |world, entity| Some(world.get_mut::<T>(entity)?),
+            insert: |world, entity, value| {
+                let component = T::from_reflect(value).ok_or(FromReflectError {
+                    type_name: type_name::<T>(),
+                })?;
+                world.insert(entity, component);
+                Ok(())
+            },
+        }
+    }
+
+    pub fn reflect<'w>(&self, world: &'w World, entity: Entity) -> Option<&'w dyn Reflect> {
+        (self.get)(world, entity)
+    }
+
+    /// Marks the component as changed, like [`World::get_mut`].
+    pub fn reflect_mut<'w>(
+        &self,
+        world: &'w mut World,
+        entity: Entity,
+    ) -> Option<&'w mut dyn Reflect> {
+        (self.get_mut)(world, entity)
+    }
+
+    /// Converts the value, which may be a [`DynamicStruct`], and inserts it into the entity.
+    ///
+    /// Panics: If the entity does not exist, like [`World::insert`].
+    pub fn insert(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        value: &dyn Reflect,
+    ) -> Result<(), FromReflectError> {
+        (self.insert)(world, entity, value)
+    }
+}
+
+/// The type information and functions registered for a type in the [`TypeRegistry`].
+#[derive(Debug, Clone)]
+pub struct TypeRegistration {
+    info: TypeInfo,
+    from_reflect: fn(&dyn Reflect) -> Option<Box<dyn Reflect>>,
+    component: Option<ReflectComponent>,
+}
+
+impl TypeRegistration {
+    pub fn info(&self) -> &TypeInfo {
+        &self.info
+    }
+
+    /// Converts the value to the registered type, see [`FromReflect`].
+    pub fn from_reflect(&self, value: &dyn Reflect) -> Option<Box<dyn Reflect>> {
+        (self.from_reflect)(value)
+    }
+
+    /// How to access the type as a component, if it was registered as one.
+    pub fn component(&self) -> Option<&ReflectComponent> {
+        self.component.as_ref()
+    }
+}
+
+/// The reflected types known to editors, serializers and other tools, looked up by their
+/// [`TypeId`] or type name.
+#[derive(Debug, Default)]
+pub struct TypeRegistry {
+    registrations: HashMap<TypeId, TypeRegistration>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl Resource for TypeRegistry {}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the type, replacing a previous registration.
+    pub fn register<T: Typed + FromReflect>(&mut self) {
+        self.insert(TypeRegistration {
+            info: T::type_info(),
+            from_reflect: |value| Some(Box::new(T::from_reflect(value)?)),
+            component: None,
+        });
+    }
+
+    /// Registers the type like [`TypeRegistry::register`], allowing it to be accessed as a
+    /// component through [`TypeRegistration::component`].
+    pub fn register_component<T: Component + Typed + FromReflect>(&mut self) {
+        self.insert(TypeRegistration {
+            info: T::type_info(),
+            from_reflect: |value| Some(Box::new(T::from_reflect(value)?)),
+            component: Some(ReflectComponent::new::<T>()),
+        });
+    }
+
+    fn insert(&mut self, registration: TypeRegistration) {
+        let type_id = registration.info.type_id();
+        self.names.insert(registration.info.type_name(), type_id);
+        self.registrations.insert(type_id, registration);
+    }
+
+    pub fn get(&self, type_id: TypeId) -> Option<&TypeRegistration> {
+        self.registrations.get(&type_id)
+    }
+
+    pub fn get_with_name(&self, type_name: &str) -> Option<&TypeRegistration> {
+        self.get(*self.names.get(type_name)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeRegistration> {
+        self.registrations.values()
+    }
+
+    /// Iterates over the components of the entity that are registered as reflected components.
+    pub fn reflect_components<'w>(
+        &self,
+        world: &'w World,
+        entity: Entity,
+    ) -> impl Iterator<Item = &'w dyn Reflect> + use<'_, 'w> {
+        self.iter()
+            .filter_map(move |registration| registration.component()?.reflect(world, entity))
+    }
+
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::{component::Component, entity::Entity, World};
+
+    use super::{
+        DynamicStruct, FromReflect, NamedField, Reflect, ReflectMut, ReflectRef, Struct, TypeInfo,
+        TypeRegistry, Typed,
+    };
+
+    #[derive(Debug, PartialEq, Reflect)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {}
+
+    #[derive(Debug, PartialEq, Reflect)]
+    struct Target(Entity, Option<u32>);
+    impl Component for Target {}
+
+    #[derive(Debug, PartialEq, Reflect)]
+    struct Player {
+        name: String,
+        position: Position,
+    }
+
+    fn as_struct(value: &dyn Reflect) -> &dyn Struct {
+        match value.reflect_ref() {
+            ReflectRef::Struct(value) => value,
+            ReflectRef::Value(_) => panic!("{} is not a struct", value.type_name()),
+        }
+    }
+
+    #[test]
+    fn struct_fields() {
+        let mut player = Player {
+            name: "a".into(),
+            position: Position { x: 1.0, y: 2.0 },
+        };
+
+        let reflected = as_struct(&player);
+        assert_eq!(reflected.field_len(), 2);
+        assert_eq!(reflected.name_at(1), Some("position"));
+        let position = as_struct(reflected.field("position").unwrap());
+        assert_eq!(position.field_at(0).unwrap().downcast_ref(), Some(&1.0f32));
+        assert!(reflected.field("missing").is_none());
+
+        let ReflectMut::Struct(reflected) = player.reflect_mut() else {
+            unreachable!()
+        };
+        *reflected
+            .field_mut("name")
+            .unwrap()
+            .downcast_mut::<String>()
+            .unwrap() = "b".into();
+        assert_eq!(player.name, "b");
+
+        let target = Target(Entity::PLACEHOLDER, Some(3));
+        let names = as_struct(&target)
+            .iter_fields()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["0", "1"]);
+    }
+
+    #[test]
+    fn dynamic_structs() {
+        let mut position = DynamicStruct::new();
+        position.insert("x", 3.0f32);
+        assert_eq!(Position::from_reflect(&position), None);
+        position.insert("y", 4.0f32);
+        position.insert("x", 5.0f32);
+
+        let mut player = DynamicStruct::new();
+        player.insert("name", String::from("a"));
+        player.insert("position", position);
+        assert_eq!(
+            Player::from_reflect(&player),
+            Some(Player {
+                name: "a".into(),
+                position: Position { x: 5.0, y: 4.0 },
+            })
+        );
+
+        let cloned = Position { x: 1.0, y: 2.0 }.clone_value();
+        assert!(cloned.is::<DynamicStruct>());
+        assert_eq!(
+            Position::from_reflect(&*cloned),
+            Some(Position { x: 1.0, y: 2.0 })
+        );
+        assert_eq!(
+            format!("{cloned:?}"),
+            "quartz::reflect::DynamicStruct { x: Reflect(f32), y: Reflect(f32) }"
+        );
+    }
+
+    #[test]
+    fn type_info() {
+        assert_eq!(
+            Position::type_info(),
+            TypeInfo::Struct {
+                type_id: TypeId::of::<Position>(),
+                type_name: std::any::type_name::<Position>(),
+                fields: vec![NamedField::new::<f32>("x"), NamedField::new::<f32>("y")],
+            }
+        );
+        assert_eq!(u32::type_info().type_id(), TypeId::of::<u32>());
+    }
+
+    #[test]
+    fn reflect_components() {
+        let mut registry = TypeRegistry::new();
+        registry.register_component::<Position>();
+        registry.register_component::<Target>();
+        registry.register::<Player>();
+        assert_eq!(registry.len(), 3);
+
+        let mut world = World::new();
+        let entity = world.spawn(Position { x: 1.0, y: 2.0 });
+
+        let registration = registry
+            .get_with_name(std::any::type_name::<Position>())
+            .unwrap();
+        let component = registration.component().unwrap();
+        let reflected = component.reflect_mut(&mut world, entity).unwrap();
+        let ReflectMut::Struct(reflected) = reflected.reflect_mut() else {
+            unreachable!()
+        };
+        *reflected
+            .field_mut("x")
+            .unwrap()
+            .downcast_mut::<f32>()
+            .unwrap() = 7.0;
+        assert_eq!(world.get(entity), Some(&Position { x: 7.0, y: 2.0 }));
+
+        let mut target = DynamicStruct::new();
+        target.insert("0", entity);
+        target.insert("1", Some(2u32));
+        let component = registry.get(TypeId::of::<Target>()).unwrap().component();
+        component
+            .unwrap()
+            .insert(&mut world, entity, &target)
+            .unwrap();
+        assert_eq!(world.get(entity), Some(&Target(entity, Some(2))));
+        assert!(component
+            .unwrap()
+            .insert(&mut world, entity, &DynamicStruct::new())
+            .is_err());
+
+        assert_eq!(registry.reflect_components(&world, entity).count(), 2);
+        assert!(registry
+            .get(TypeId::of::<Player>())
+            .unwrap()
+            .component()
+            .is_none());
+    }
+}
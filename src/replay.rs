@@ -0,0 +1,384 @@
+use std::{
+    any::{type_name, Any},
+    collections::HashMap,
+    error::Error,
+    fmt,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    audit::StructuralChange,
+    component::{Component, ComponentId, Tick},
+    entity::Entity,
+    map_entities::EntityMapper,
+    ptr::Ptr,
+    World,
+};
+
+/// The recorded structural changes of a world, which can be serialized and replayed into another
+/// world with [`Recording::replay`].
+///
+/// Only components registered with [`World::register_replay_component`] are recorded, all others
+/// are silently left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    changes: Vec<RecordedChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedChange {
+    pub tick: Tick,
+    pub change: StructuralChange,
+    /// The entity in the recorded world.
+    pub entity: Entity,
+    pub components: Vec<RecordedComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedComponent {
+    /// The type name of the component, used to find it in the replaying world.
+    pub name: String,
+    /// The serialized value for spawns and inserts, `None` for despawns and removes.
+    pub value: Option<Value>,
+}
+
+impl Recording {
+    pub fn changes(&self) -> &[RecordedChange] {
+        &self.changes
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Applies the recorded changes to the world in order, spawning a new entity for every
+    /// recorded spawn. Ticks are advanced alongside the recording, so the world must not be ahead
+    /// of it.
+    ///
+    /// Every recorded component must be registered in the world with
+    /// [`World::register_replay_component`]. References to recorded entities are mapped to the
+    /// replayed ones in the components registered with [`World::register_entity_refs`].
+    pub fn replay(&self, world: &mut World) -> Result<(), ReplayError> {
+        let mut entities = HashMap::new();
+
+        for change in &self.changes {
+            while world.tick() < change.tick {
+                world.increment_tick();
+            }
+
+            let entity = if change.change == StructuralChange::Spawn {
+                let entity = world.spawn(());
+                entities.insert(change.entity, entity);
+                entity
+            } else {
+                *entities
+                    .get(&change.entity)
+                    .ok_or(ReplayError::UnknownEntity(change.entity))?
+            };
+
+            for component in &change.components {
+                let id = *world
+                    .replay_registry
+                    .names
+                    .get(&component.name)
+                    .ok_or_else(|| ReplayError::UnknownComponent(component.name.clone()))?;
+
+                match (&change.change, &component.value) {
+                    (StructuralChange::Spawn | StructuralChange::Insert, Some(value)) => {
+                        let fns = &world.replay_registry.components[&id];
+                        let insert = fns.insert;
+                        let value = (fns.deserialize)(value).map_err(ReplayError::Deserialize)?;
+                        insert(world, entity, value);
+                        world.map_component_refs(entity, id, &mut |entity| {
+                            entities.map_entity(entity)
+                        });
+                    }
+                    (StructuralChange::Remove, _) => world.remove_by_ids(entity, &[id]),
+                    _ => {}
+                }
+            }
+
+            if change.change == StructuralChange::Despawn {
+                world.despawn(entity);
+                entities.remove(&change.entity);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The error returned when a [`Recording`] can't be replayed into a world.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The component is not registered in the replaying world.
+    UnknownComponent(String),
+    /// The recording changes an entity it did not spawn.
+    UnknownEntity(Entity),
+    Deserialize(serde_json::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownComponent(name) => {
+                write!(f, "component {name} is not registered for replay")
+            }
+            Self::UnknownEntity(entity) => {
+                write!(f, "{entity} was not spawned by the recording")
+            }
+            Self::Deserialize(err) => write!(f, "failed to deserialize component: {err}"),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Deserialize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A component deserialized by [`ReplayFns::deserialize`], to be inserted with
+/// [`ReplayFns::insert`].
+pub(crate) type DeserializedComponent = Box<dyn Any + Send>;
+
+pub(crate) struct ReplayFns {
+    pub(crate) name: &'static str,
+    pub(crate) serialize: unsafe fn(Ptr<'_>) -> Value,
+    pub(crate) deserialize: fn(&Value) -> Result<DeserializedComponent, serde_json::Error>,
+    /// Inserts a component returned by `deserialize` into the entity.
+    pub(crate) insert: fn(&mut World, Entity, DeserializedComponent),
+}
+
+/// The components a world knows how to record and replay.
+#[derive(Default)]
+pub(crate) struct ReplayRegistry {
+    pub(crate) components: HashMap<ComponentId, ReplayFns>,
+    pub(crate) names: HashMap<String, ComponentId>,
+}
+
+impl ReplayRegistry {
+    fn register<T: Component + Serialize + DeserializeOwned>(&mut self, id: ComponentId) {
+        self.names.insert(type_name::<T>().to_owned(), id);
+        self.components.insert(
+            id,
+            ReplayFns {
+                name: type_name::<T>(),
+                serialize: |ptr| {
+                    serde_json::to_value(unsafe { ptr.deref::<T>() })
+                        .expect("components must serialize into a JSON value")
+                },
+                deserialize: |value| Ok(Box::new(T::deserialize(value)?)),
+                insert: |world, entity, value| {
+                    let value = value
+                        .downcast::<T>()
+                        .expect("deserialized components must have the registered type");
+                    world.insert(entity, *value);
+                },
+            },
+        );
+    }
+}
+
+impl fmt::Debug for ReplayRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names.keys()).finish()
+    }
+}
+
+impl World {
+    /// Allows the component to be recorded and replayed, see [`World::start_recording`], and to be
+    /// saved and loaded, see [`World::save`].
+    pub fn register_replay_component<T: Component + Serialize + DeserializeOwned>(&mut self) {
+        let id = self.components.register_component::<T>();
+        self.replay_registry.register::<T>(id);
+    }
+
+    /// Starts recording every spawn, despawn, insert and remove, replacing the previous recording,
+    /// if any.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    /// Stops recording structural changes and returns the recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub(crate) fn record_replay(
+        &mut self,
+        change: StructuralChange,
+        entity: Entity,
+        component_ids: &[ComponentId],
+    ) {
+        if self.recording.is_none() {
+            return;
+        }
+
+        let with_values = matches!(change, StructuralChange::Spawn | StructuralChange::Insert);
+
+        let components = component_ids
+            .iter()
+            .filter_map(|id| {
+                let fns = self.replay_registry.components.get(id)?;
+                let value = if with_values {
+                    // SAFETY: The pointer is only read as the component it was registered for.
+                    self.get_by_id(entity, *id)
+                        .map(|ptr| unsafe { (fns.serialize)(ptr) })
+                } else {
+                    None
+                };
+
+                Some(RecordedComponent {
+                    name: fns.name.to_owned(),
+                    value,
+                })
+            })
+            .collect();
+
+        let recording = self
+            .recording
+            .as_mut()
+            .expect("recording was checked above");
+        recording.changes.push(RecordedChange {
+            tick: self.tick,
+            change,
+            entity,
+            components,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        component::{Component, StorageType},
+        entity::Entity,
+        map_entities::MapEntities,
+        World,
+    };
+
+    use super::{Recording, ReplayError};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, MapEntities)]
+    struct Target(Entity);
+    impl Component for Target {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+    impl Component for Name {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Stunned(u32);
+    impl Component for Stunned {
+        const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+    }
+
+    fn register(world: &mut World) {
+        world.register_replay_component::<Health>();
+        world.register_replay_component::<Name>();
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut world = World::new();
+        register(&mut world);
+        world.start_recording();
+
+        let e0 = world.spawn((Health(10), Name("a".into())));
+        let e1 = world.spawn(Health(20));
+        world.increment_tick();
+        world.insert(e1, Health(15));
+        world.insert(e1, Name("b".into()));
+        world.despawn(e0);
+
+        let recording = world.stop_recording().unwrap();
+        assert_eq!(recording.len(), 5);
+
+        let json = serde_json::to_string(&recording).unwrap();
+        let recording: Recording = serde_json::from_str(&json).unwrap();
+
+        let mut replayed = World::new();
+        register(&mut replayed);
+        recording.replay(&mut replayed).unwrap();
+
+        assert_eq!(replayed.tick(), world.tick());
+        let items = replayed.query::<(&Health, &Name)>().collect::<Vec<_>>();
+        assert_eq!(items, vec![(&Health(15), &Name("b".into()))]);
+    }
+
+    #[test]
+    fn replay_entity_refs() {
+        let mut world = World::new();
+        register(&mut world);
+        world.register_replay_component::<Target>();
+        world.register_entity_refs::<Target>();
+        world.spawn(Health(0));
+        world.start_recording();
+
+        let a = world.spawn(Health(10));
+        let b = world.spawn(Target(a));
+        world.insert(a, Target(b));
+
+        let mut replayed = World::new();
+        register(&mut replayed);
+        replayed.register_replay_component::<Target>();
+        replayed.register_entity_refs::<Target>();
+        world
+            .stop_recording()
+            .unwrap()
+            .replay(&mut replayed)
+            .unwrap();
+
+        let (a, _) = replayed.query::<(Entity, &Health)>().next().unwrap();
+        let Target(b) = *replayed.get::<Target>(a).unwrap();
+        assert_eq!(replayed.get::<Target>(b), Some(&Target(a)));
+    }
+
+    #[test]
+    fn replay_sparse_components() {
+        let mut world = World::new();
+        register(&mut world);
+        world.register_replay_component::<Stunned>();
+        world.start_recording();
+
+        let e0 = world.spawn((Health(10), Stunned(2)));
+        world.insert(e0, Stunned(3));
+        let recording = world.stop_recording().unwrap();
+
+        let mut replayed = World::new();
+        register(&mut replayed);
+        replayed.register_replay_component::<Stunned>();
+        recording.replay(&mut replayed).unwrap();
+
+        let items = replayed.query::<(&Health, &Stunned)>().collect::<Vec<_>>();
+        assert_eq!(items, vec![(&Health(10), &Stunned(3))]);
+    }
+
+    #[test]
+    fn unregistered_component() {
+        let mut world = World::new();
+        register(&mut world);
+        world.start_recording();
+        world.spawn(Health(10));
+
+        let recording = world.stop_recording().unwrap();
+        let result = recording.replay(&mut World::new());
+        assert!(matches!(result, Err(ReplayError::UnknownComponent(_))));
+    }
+}
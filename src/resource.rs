@@ -0,0 +1,364 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+    fmt,
+    mem::{self, ManuallyDrop},
+    thread::{self, ThreadId},
+};
+
+use crate::World;
+
+pub use quartz_macros::Resource;
+
+/// A world-global singleton, like the input state or the settings of a game.
+///
+/// Resources are stored by type, separately from the entity tables, so there is at most one
+/// value of every resource type in a world.
+///
+/// ```
+/// # use quartz::{resource::Resource, World};
+/// #[derive(Resource)]
+/// struct Gravity(f32);
+///
+/// let mut world = World::new();
+/// world.insert_resource(Gravity(-9.81));
+/// world.get_resource_mut::<Gravity>().unwrap().0 *= 2.0;
+/// assert_eq!(world.get_resource::<Gravity>().unwrap().0, -19.62);
+/// ```
+pub trait Resource: Send + Sync + 'static {}
+
+/// The resources of a world, indexed by their type.
+#[derive(Default)]
+pub(crate) struct Resources {
+    /// Holds a [`ResourceCell`] of every resource type.
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+/// Stores a resource so systems can borrow it mutably through a shared world, see
+/// [`Resources::get_mut_unchecked`].
+struct ResourceCell<R>(UnsafeCell<R>);
+
+// SAFETY: The cell is only accessed mutably through `&mut Resources` or by the callers of
+// `Resources::get_mut_unchecked`, which guarantee exclusive access.
+unsafe impl<R: Sync> Sync for ResourceCell<R> {}
+
+impl Resources {
+    fn insert<R: Resource>(&mut self, value: R) -> Option<R> {
+        self.values
+            .insert(
+                TypeId::of::<R>(),
+                Box::new(ResourceCell(UnsafeCell::new(value))),
+            )
+            .map(|previous| downcast::<R>(previous).0.into_inner())
+    }
+
+    fn remove<R: Resource>(&mut self) -> Option<R> {
+        self.values
+            .remove(&TypeId::of::<R>())
+            .map(|value| downcast::<R>(value).0.into_inner())
+    }
+
+    fn cell<R: Resource>(&self) -> Option<&ResourceCell<R>> {
+        self.values
+            .get(&TypeId::of::<R>())
+            .map(|value| value.downcast_ref().expect("resources are stored by type"))
+    }
+
+    pub(crate) fn get<R: Resource>(&self) -> Option<&R> {
+        // SAFETY: Mutable borrows of the resource require `&mut self` or exclusive access.
+        self.cell().map(|cell| unsafe { &*cell.0.get() })
+    }
+
+    fn get_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        self.values.get_mut(&TypeId::of::<R>()).map(|value| {
+            value
+                .downcast_mut::<ResourceCell<R>>()
+                .expect("resources are stored by type")
+                .0
+                .get_mut()
+        })
+    }
+
+    /// Borrows the resource mutably through a shared reference.
+    ///
+    /// # Safety
+    /// No other reference to the resource may be alive while the returned one is.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_mut_unchecked<R: Resource>(&self) -> Option<&mut R> {
+        self.cell().map(|cell| &mut *cell.0.get())
+    }
+
+    fn contains<R: Resource>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<R>())
+    }
+}
+
+impl fmt::Debug for Resources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resources")
+            .field("resources", &self.values.len())
+            .finish()
+    }
+}
+
+fn downcast<R: Resource>(value: Box<dyn Any + Send + Sync>) -> Box<ResourceCell<R>> {
+    value.downcast().expect("resources are stored by type")
+}
+
+/// A resource that is not `Send`, together with the thread it was inserted on, which is the only
+/// thread allowed to access or drop it.
+struct NonSendValue {
+    value: ManuallyDrop<Box<dyn Any>>,
+    name: &'static str,
+    thread: ThreadId,
+}
+
+impl NonSendValue {
+    /// Panics: If called on another thread than the one the resource was inserted on.
+    fn check_thread(&self) {
+        assert!(
+            self.thread == thread::current().id(),
+            "Tried to access the non-send resource {} from another thread than the one it was \
+             inserted on",
+            self.name
+        );
+    }
+
+    fn into_inner<R: 'static>(mut self) -> R {
+        self.check_thread();
+        // SAFETY: The value is not dropped by `self`, which is forgotten right after.
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        mem::forget(self);
+        *value.downcast().expect("resources are stored by type")
+    }
+}
+
+impl Drop for NonSendValue {
+    fn drop(&mut self) {
+        if self.thread == thread::current().id() {
+            // SAFETY: The value is never used again.
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        } else if !thread::panicking() {
+            panic!(
+                "Tried to drop the non-send resource {} on another thread than the one it was \
+                 inserted on",
+                self.name
+            );
+        }
+        // While unwinding the value is leaked instead, to not abort with a double panic
+    }
+}
+
+/// The resources of a world that are not `Send`, see [`World::insert_non_send_resource`].
+#[derive(Default)]
+pub(crate) struct NonSendResources {
+    values: HashMap<TypeId, NonSendValue>,
+}
+
+// SAFETY: Every access to a value, including dropping it, is checked to happen on the thread that
+// inserted it.
+unsafe impl Send for NonSendResources {}
+unsafe impl Sync for NonSendResources {}
+
+impl NonSendResources {
+    fn insert<R: 'static>(&mut self, value: R) -> Option<R> {
+        let value = NonSendValue {
+            value: ManuallyDrop::new(Box::new(value)),
+            name: type_name::<R>(),
+            thread: thread::current().id(),
+        };
+        self.values
+            .insert(TypeId::of::<R>(), value)
+            .map(NonSendValue::into_inner)
+    }
+
+    fn remove<R: 'static>(&mut self) -> Option<R> {
+        self.values
+            .remove(&TypeId::of::<R>())
+            .map(NonSendValue::into_inner)
+    }
+
+    fn get<R: 'static>(&self) -> Option<&R> {
+        self.values.get(&TypeId::of::<R>()).map(|value| {
+            value.check_thread();
+            value
+                .value
+                .downcast_ref()
+                .expect("resources are stored by type")
+        })
+    }
+
+    fn get_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        self.values.get_mut(&TypeId::of::<R>()).map(|value| {
+            value.check_thread();
+            value
+                .value
+                .downcast_mut()
+                .expect("resources are stored by type")
+        })
+    }
+
+    fn contains<R: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<R>())
+    }
+}
+
+impl fmt::Debug for NonSendResources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NonSendResources")
+            .field("resources", &self.values.len())
+            .finish()
+    }
+}
+
+impl World {
+    /// Inserts the resource, returning the previous value of its type if there was one.
+    pub fn insert_resource<R: Resource>(&mut self, value: R) -> Option<R> {
+        self.resources.insert(value)
+    }
+
+    /// Removes the resource and returns it, if it exists.
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.resources.remove()
+    }
+
+    pub fn get_resource<R: Resource>(&self) -> Option<&R> {
+        self.resources.get()
+    }
+
+    pub fn get_resource_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        self.resources.get_mut()
+    }
+
+    pub fn contains_resource<R: Resource>(&self) -> bool {
+        self.resources.contains::<R>()
+    }
+
+    /// Inserts a resource that is not `Send`, like a window or GPU handle, returning the previous
+    /// value of its type if there was one.
+    ///
+    /// The resource is owned by the calling thread. The world stays `Send + Sync`, but accessing
+    /// or dropping the resource on any other thread panics, so a world holding non-send resources
+    /// has to remove them before it is dropped elsewhere.
+    ///
+    /// ```should_panic
+    /// # use std::rc::Rc;
+    /// # use quartz::World;
+    /// let mut world = World::new();
+    /// world.insert_non_send_resource(Rc::new(1));
+    /// std::thread::scope(|scope| {
+    ///     scope.spawn(|| world.get_non_send_resource::<Rc<i32>>().is_some());
+    /// });
+    /// ```
+    ///
+    /// Panics: If a previous value of the type was inserted on another thread.
+    pub fn insert_non_send_resource<R: 'static>(&mut self, value: R) -> Option<R> {
+        self.non_send_resources.insert(value)
+    }
+
+    /// Removes the non-send resource and returns it, if it exists.
+    ///
+    /// Panics: If the resource was inserted on another thread.
+    pub fn remove_non_send_resource<R: 'static>(&mut self) -> Option<R> {
+        self.non_send_resources.remove()
+    }
+
+    /// Panics: If the resource was inserted on another thread.
+    pub fn get_non_send_resource<R: 'static>(&self) -> Option<&R> {
+        self.non_send_resources.get()
+    }
+
+    /// Panics: If the resource was inserted on another thread.
+    pub fn get_non_send_resource_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        self.non_send_resources.get_mut()
+    }
+
+    pub fn contains_non_send_resource<R: 'static>(&self) -> bool {
+        self.non_send_resources.contains::<R>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, thread};
+
+    use crate::World;
+
+    use super::Resource;
+
+    #[derive(Debug, PartialEq, Resource)]
+    struct Score(u32);
+
+    #[derive(Debug, PartialEq, Resource)]
+    struct Settings {
+        volume: f32,
+    }
+
+    #[test]
+    fn resources() {
+        let mut world = World::new();
+        assert!(!world.contains_resource::<Score>());
+        assert_eq!(world.insert_resource(Score(1)), None);
+        world.insert_resource(Settings { volume: 0.5 });
+
+        world.get_resource_mut::<Score>().unwrap().0 += 1;
+        assert_eq!(world.insert_resource(Score(10)), Some(Score(2)));
+        assert_eq!(world.get_resource::<Score>(), Some(&Score(10)));
+        assert_eq!(
+            world.get_resource::<Settings>(),
+            Some(&Settings { volume: 0.5 })
+        );
+
+        assert_eq!(world.remove_resource::<Score>(), Some(Score(10)));
+        assert_eq!(world.remove_resource::<Score>(), None);
+        assert!(world.get_resource_mut::<Score>().is_none());
+        assert!(world.contains_resource::<Settings>());
+
+        // Resources are not entities
+        assert_eq!(world.entities.len(), 0);
+    }
+
+    #[test]
+    fn non_send_resources() {
+        let mut world = World::new();
+        let handle = Rc::new(Cell::new(1));
+        assert_eq!(world.insert_non_send_resource(handle.clone()), None);
+        world
+            .get_non_send_resource_mut::<Rc<Cell<i32>>>()
+            .unwrap()
+            .set(2);
+        assert_eq!(handle.get(), 2);
+
+        thread::scope(|scope| {
+            let world = &world;
+            let access =
+                scope.spawn(move || world.get_non_send_resource::<Rc<Cell<i32>>>().is_some());
+            assert!(access.join().is_err());
+            assert!(scope
+                .spawn(move || world.contains_non_send_resource::<Rc<Cell<i32>>>())
+                .join()
+                .unwrap());
+        });
+
+        let removed = world.remove_non_send_resource::<Rc<Cell<i32>>>().unwrap();
+        assert!(Rc::ptr_eq(&removed, &handle));
+        assert!(!world.contains_non_send_resource::<Rc<Cell<i32>>>());
+        drop(removed);
+        assert_eq!(Rc::strong_count(&handle), 1);
+
+        // Dropping the world on the owning thread drops the resource
+        world.insert_non_send_resource(handle.clone());
+        drop(world);
+        assert_eq!(Rc::strong_count(&handle), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tried to drop the non-send resource")]
+    fn drop_non_send_resource_on_other_thread() {
+        let mut world = World::new();
+        world.insert_non_send_resource(Rc::new(()));
+        let err = thread::spawn(move || drop(world)).join().unwrap_err();
+        std::panic::resume_unwind(err);
+    }
+}
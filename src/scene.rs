@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entity::Entity,
+    snapshot::{LoadError, SavedEntity},
+    World,
+};
+
+/// A group of entities with their components that can be spawned into a world any number of
+/// times, e.g. a prefab loaded from an asset file.
+///
+/// The entities of a scene are only ids local to the scene. Every [`Scene::spawn_into`] spawns
+/// new entities for them and maps references to them in the components registered with
+/// [`World::register_entity_refs`]. References to entities outside the scene are kept.
+///
+/// Only components registered with [`World::register_replay_component`] are part of a scene.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<SavedEntity>,
+}
+
+impl Scene {
+    /// Creates a scene from the entities of the world, which are identified by their current ids
+    /// in the scene.
+    ///
+    /// Panics: If one of the entities does not exist.
+    pub fn extract(world: &World, entities: impl IntoIterator<Item = Entity>) -> Self {
+        let entities = entities
+            .into_iter()
+            .map(|entity| {
+                assert!(world.contains(entity), "{entity:?} does not exist");
+                world.save_entity(entity)
+            })
+            .collect();
+        Self { entities }
+    }
+
+    /// Spawns a new entity for every entity of the scene and returns the map from scene entities
+    /// to the spawned ones.
+    ///
+    /// Nothing is spawned if a component is not registered in the world or fails to deserialize.
+    ///
+    /// ```
+    /// # use quartz::{component::Component, entity::Entity, map_entities::MapEntities};
+    /// # use quartz::{scene::Scene, World};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Component, MapEntities, Serialize, Deserialize)]
+    /// struct Follow(Entity);
+    ///
+    /// let mut world = World::new();
+    /// world.register_replay_component::<Follow>();
+    /// world.register_entity_refs::<Follow>();
+    ///
+    /// let leader = world.spawn(());
+    /// let follower = world.spawn(Follow(leader));
+    /// let scene = Scene::extract(&world, [leader, follower]);
+    ///
+    /// let map = scene.spawn_into(&mut world).unwrap();
+    /// assert_eq!(world.get::<Follow>(map[&follower]).unwrap().0, map[&leader]);
+    /// ```
+    pub fn spawn_into(&self, world: &mut World) -> Result<HashMap<Entity, Entity>, LoadError> {
+        let components = world.deserialize_saved(&self.entities)?;
+
+        let mut map = self
+            .entities
+            .iter()
+            .map(|saved| (saved.entity, world.spawn(())))
+            .collect::<HashMap<_, _>>();
+        for (saved, components) in self.entities.iter().zip(components) {
+            let entity = map[&saved.entity];
+            world.insert_saved(entity, components);
+            world.map_entity_refs(entity, &mut map);
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        component::Component,
+        entity::Entity,
+        map_entities::MapEntities,
+        snapshot::{LoadError, SavedComponent, SavedEntity},
+        World,
+    };
+
+    use super::Scene;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, MapEntities)]
+    struct Target(Entity);
+    impl Component for Target {}
+
+    fn register(world: &mut World) {
+        world.register_replay_component::<Health>();
+        world.register_replay_component::<Target>();
+        world.register_entity_refs::<Target>();
+    }
+
+    #[test]
+    fn spawn_scene_twice() {
+        let mut world = World::new();
+        register(&mut world);
+        let outside = world.spawn(Health(0));
+        let a = world.spawn(Health(10));
+        let b = world.spawn((Health(20), Target(a)));
+        world.insert(a, Target(outside));
+
+        let json = serde_json::to_string(&Scene::extract(&world, [a, b])).unwrap();
+        let scene: Scene = serde_json::from_str(&json).unwrap();
+
+        let mut other = World::new();
+        register(&mut other);
+        other.spawn(Health(0));
+        let first = scene.spawn_into(&mut other).unwrap();
+        let second = scene.spawn_into(&mut other).unwrap();
+        assert_eq!(other.iter_entities().count(), 5);
+
+        for map in [&first, &second] {
+            assert_eq!(other.get::<Health>(map[&a]), Some(&Health(10)));
+            assert_eq!(other.get::<Health>(map[&b]), Some(&Health(20)));
+            assert_eq!(other.get::<Target>(map[&b]), Some(&Target(map[&a])));
+            assert_eq!(other.get::<Target>(map[&a]), Some(&Target(outside)));
+        }
+        assert_ne!(first[&a], second[&a]);
+    }
+
+    #[test]
+    fn unknown_component() {
+        let mut scene = Scene::extract(&World::new(), []);
+        scene.entities.push(SavedEntity {
+            entity: Entity::PLACEHOLDER,
+            components: vec![SavedComponent {
+                name: "Unknown".into(),
+                value: serde_json::Value::Null,
+            }],
+        });
+
+        let mut world = World::new();
+        let result = scene.spawn_into(&mut world);
+        assert!(matches!(result, Err(LoadError::UnknownComponent(_))));
+        assert_eq!(world.iter_entities().count(), 0);
+    }
+}
@@ -0,0 +1,766 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::{
+    system::{
+        IntoSystem, IsExclusiveFunctionSystem, IsFunctionSystem, System, SystemParamFunction,
+    },
+    World,
+};
+
+/// Runs a sequence of systems against a world, in the order they were added unless they are
+/// ordered with [`IntoSystemConfig::before`], [`IntoSystemConfig::after`] or system sets.
+///
+/// Systems run one at a time on the calling thread, there is no parallel executor. Ordering
+/// constraints only decide the total order the systems run in, systems that don't access the
+/// same data are not batched to run at the same time.
+///
+/// ```
+/// # use quartz::{
+/// #     component::Component,
+/// #     query::Query,
+/// #     schedule::Schedule,
+/// #     system::Res,
+/// #     time::Time,
+/// #     World,
+/// # };
+/// # use std::time::Duration;
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Component)]
+/// struct Velocity(f32);
+///
+/// fn movement(query: Query<(&mut Position, &Velocity)>, time: Res<Time>) {
+///     for (position, velocity) in query {
+///         position.0 += velocity.0 * time.delta_secs();
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn((Position(0.0), Velocity(2.0)));
+/// let mut schedule = Schedule::new();
+/// schedule.add_system(movement);
+///
+/// // The first frame has no delta
+/// schedule.run(&mut world);
+/// assert_eq!(world.get::<Position>(entity).unwrap().0, 0.0);
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Box<dyn System>>,
+    nodes: Vec<SystemNode>,
+    sets: HashMap<SetLabel, Ordering>,
+    /// The order the systems run in, built on the next run after the schedule changed.
+    order: Option<Vec<usize>>,
+}
+
+/// The sets of a system, the sets it is ordered against and the conditions it runs under.
+struct SystemNode {
+    sets: Vec<SetLabel>,
+    ordering: Ordering,
+    conditions: Vec<Condition>,
+}
+
+/// Decides whether a system runs, see [`IntoSystemConfig::run_if`].
+type Condition = Box<dyn Fn(&World) -> bool + Send + Sync>;
+
+#[derive(Default)]
+struct Ordering {
+    before: Vec<SetLabel>,
+    after: Vec<SetLabel>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system, optionally configured with [`IntoSystemConfig::in_set`],
+    /// [`IntoSystemConfig::before`], [`IntoSystemConfig::after`] and [`IntoSystemConfig::run_if`].
+    ///
+    /// Systems run in the order they were added, unless that order breaks an ordering constraint.
+    pub fn add_system<Marker>(&mut self, system: impl IntoSystemConfig<Marker>) -> &mut Self {
+        let config = system.into_config();
+        let mut sets = config.sets;
+        sets.extend(config.system.type_set());
+        self.systems.push(config.system);
+        self.nodes.push(SystemNode {
+            sets,
+            ordering: config.ordering,
+            conditions: config.conditions,
+        });
+        self.order = None;
+        self
+    }
+
+    /// Orders all systems of a set relative to other sets or systems.
+    ///
+    /// ```
+    /// # use quartz::{
+    /// #     resource::Resource,
+    /// #     schedule::{IntoSystemConfig, IntoSystemSetConfig, Schedule, SystemSet},
+    /// #     system::ResMut,
+    /// #     World,
+    /// # };
+    /// #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    /// struct Physics;
+    /// #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    /// struct Rendering;
+    ///
+    /// #[derive(Default, Resource)]
+    /// struct Log(Vec<&'static str>);
+    ///
+    /// fn draw(mut log: ResMut<Log>) {
+    ///     log.0.push("draw");
+    /// }
+    /// fn gravity(mut log: ResMut<Log>) {
+    ///     log.0.push("gravity");
+    /// }
+    /// fn collide(mut log: ResMut<Log>) {
+    ///     log.0.push("collide");
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// world.insert_resource(Log::default());
+    /// let mut schedule = Schedule::new();
+    /// schedule
+    ///     .configure_set(Physics.before(Rendering))
+    ///     .add_system(draw.in_set(Rendering))
+    ///     .add_system(collide.in_set(Physics).after(gravity))
+    ///     .add_system(gravity.in_set(Physics));
+    ///
+    /// schedule.run(&mut world);
+    /// assert_eq!(world.get_resource::<Log>().unwrap().0, ["gravity", "collide", "draw"]);
+    /// ```
+    pub fn configure_set(&mut self, set: impl IntoSystemSetConfig) -> &mut Self {
+        let config = set.into_config();
+        let ordering = self.sets.entry(config.set).or_default();
+        ordering.before.extend(config.ordering.before);
+        ordering.after.extend(config.ordering.after);
+        self.order = None;
+        self
+    }
+
+    /// Sorts the systems by their ordering constraints, which [`Schedule::run`] does on its own
+    /// when the schedule changed.
+    ///
+    /// Systems that aren't ordered relative to each other keep the order they were added in.
+    /// Constraints on sets without systems are ignored.
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        if self.order.is_some() {
+            return Ok(());
+        }
+
+        let mut members = HashMap::<&SetLabel, Vec<usize>>::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            for set in &node.sets {
+                members.entry(set).or_default().push(index);
+            }
+        }
+
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        let mut add_edges = |from: &[usize], to: &SetLabel| {
+            for &before in from {
+                for &after in members.get(to).map_or(&[][..], Vec::as_slice) {
+                    successors[before].push(after);
+                }
+            }
+        };
+        for (index, node) in self.nodes.iter().enumerate() {
+            for set in &node.ordering.before {
+                add_edges(&[index], set);
+            }
+        }
+        for (set, ordering) in &self.sets {
+            let systems = members.get(set).map_or(&[][..], Vec::as_slice);
+            for before in &ordering.before {
+                add_edges(systems, before);
+            }
+        }
+        // Edges are added from the earlier to the later system, so `after` is handled by swapping
+        let mut add_edges = |from: &SetLabel, to: &[usize]| {
+            for &before in members.get(from).map_or(&[][..], Vec::as_slice) {
+                for &after in to {
+                    successors[before].push(after);
+                }
+            }
+        };
+        for (index, node) in self.nodes.iter().enumerate() {
+            for set in &node.ordering.after {
+                add_edges(set, &[index]);
+            }
+        }
+        for (set, ordering) in &self.sets {
+            let systems = members.get(set).map_or(&[][..], Vec::as_slice);
+            for after in &ordering.after {
+                add_edges(after, systems);
+            }
+        }
+
+        let order = topological_sort(&successors).map_err(|cycle| {
+            ScheduleBuildError::Cycle(
+                cycle
+                    .into_iter()
+                    .map(|index| self.systems[index].name().to_owned())
+                    .collect(),
+            )
+        })?;
+        self.order = Some(order);
+        Ok(())
+    }
+
+    /// Starts a new frame with [`World::increment_tick`] and [`World::update_events`] and runs
+    /// every system once, in order.
+    ///
+    /// Panics: If the ordering constraints of the systems form a cycle, see [`Schedule::build`].
+    pub fn run(&mut self, world: &mut World) {
+        world.increment_tick();
+        world.update_events();
+        self.run_systems(world);
+    }
+
+    /// Runs every system whose conditions hold once, in order, within the current frame.
+    ///
+    /// Panics: If the ordering constraints of the systems form a cycle, see [`Schedule::build`].
+    pub(crate) fn run_systems(&mut self, world: &mut World) {
+        if let Err(err) = self.build() {
+            panic!("{err}");
+        }
+        let order = self.order.as_ref().expect("the schedule was built");
+        for &index in order {
+            if self.nodes[index]
+                .conditions
+                .iter()
+                .all(|condition| condition(world))
+            {
+                self.systems[index].run(world);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+}
+
+/// Sorts the nodes so every node comes before its successors, preferring lower indices, or
+/// returns the nodes of a cycle in order.
+fn topological_sort(successors: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+    let mut in_degree = vec![0; successors.len()];
+    for &successor in successors.iter().flatten() {
+        in_degree[successor] += 1;
+    }
+
+    let mut ready = (0..successors.len())
+        .filter(|&node| in_degree[node] == 0)
+        .map(Reverse)
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::with_capacity(successors.len());
+    while let Some(Reverse(node)) = ready.pop() {
+        order.push(node);
+        for &successor in &successors[node] {
+            in_degree[successor] -= 1;
+            if in_degree[successor] == 0 {
+                ready.push(Reverse(successor));
+            }
+        }
+    }
+    if order.len() == successors.len() {
+        return Ok(order);
+    }
+
+    // Every node left has a predecessor that is left, so walking backwards from any of them
+    // eventually repeats a node
+    let mut predecessors = vec![None; successors.len()];
+    for (node, node_successors) in successors.iter().enumerate() {
+        if in_degree[node] > 0 {
+            for &successor in node_successors {
+                predecessors[successor] = Some(node);
+            }
+        }
+    }
+    let mut path = Vec::new();
+    let mut node = (0..successors.len())
+        .find(|&node| in_degree[node] > 0)
+        .expect("a node is left");
+    while !path.contains(&node) {
+        path.push(node);
+        node = predecessors[node].expect("nodes left have a predecessor left");
+    }
+    let start = path.iter().position(|&visited| visited == node).unwrap();
+    let mut cycle = path.split_off(start);
+    cycle.reverse();
+    // Starts the cycle at the system added first, so the error doesn't depend on the walk
+    let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+    cycle.rotate_left(first);
+    Err(cycle)
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let order = match &self.order {
+            Some(order) => order.clone(),
+            None => (0..self.systems.len()).collect(),
+        };
+        f.debug_list()
+            .entries(order.into_iter().map(|index| self.systems[index].name()))
+            .finish()
+    }
+}
+
+/// The error returned when the systems of a [`Schedule`] can't be ordered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleBuildError {
+    /// The named systems have to run before each other, each before the next and the last before
+    /// the first.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for ScheduleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(systems) => {
+                write!(f, "systems have to run before themselves: ")?;
+                for system in systems {
+                    write!(f, "{system} -> ")?;
+                }
+                write!(f, "{}", systems[0])
+            }
+        }
+    }
+}
+
+impl Error for ScheduleBuildError {}
+
+/// A label for a group of systems, used to order all of them at once, e.g. to run physics before
+/// rendering.
+///
+/// Every function system is also in a set of its own, so other systems can be ordered relative to
+/// it by passing the function, see [`IntoSystemSet`].
+pub trait SystemSet: fmt::Debug + Clone + Eq + Hash + Send + Sync + 'static {}
+
+pub use quartz_macros::SystemSet;
+
+/// The object safe part of [`SystemSet`].
+trait DynSystemSet: fmt::Debug + Send + Sync + 'static {
+    fn as_any(&self) -> &dyn Any;
+
+    fn dyn_eq(&self, other: &dyn DynSystemSet) -> bool;
+
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+
+    fn dyn_clone(&self) -> Box<dyn DynSystemSet>;
+}
+
+impl<S: SystemSet> DynSystemSet for S {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn DynSystemSet) -> bool {
+        other.as_any().downcast_ref::<S>() == Some(self)
+    }
+
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<S>().hash(&mut state);
+        self.hash(&mut state);
+    }
+
+    fn dyn_clone(&self) -> Box<dyn DynSystemSet> {
+        Box::new(self.clone())
+    }
+}
+
+/// A type erased [`SystemSet`].
+#[derive(Debug)]
+pub struct SetLabel(Box<dyn DynSystemSet>);
+
+impl SetLabel {
+    pub fn new(set: impl SystemSet) -> Self {
+        Self(Box::new(set))
+    }
+}
+
+impl Clone for SetLabel {
+    fn clone(&self) -> Self {
+        Self(self.0.dyn_clone())
+    }
+}
+
+impl PartialEq for SetLabel {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.dyn_eq(&*other.0)
+    }
+}
+
+impl Eq for SetLabel {}
+
+impl Hash for SetLabel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.dyn_hash(state);
+    }
+}
+
+/// The set only the function system of `F` is in.
+pub struct SystemTypeSet<F>(PhantomData<fn() -> F>);
+
+impl<F> SystemTypeSet<F> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<F> Default for SystemTypeSet<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> fmt::Debug for SystemTypeSet<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SystemTypeSet<{}>", type_name::<F>())
+    }
+}
+
+impl<F> Clone for SystemTypeSet<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for SystemTypeSet<F> {}
+
+impl<F> PartialEq for SystemTypeSet<F> {
+    fn eq(&self, other: &Self) -> bool {
+        true
+    }
+}
+
+impl<F> Eq for SystemTypeSet<F> {}
+
+impl<F> Hash for SystemTypeSet<F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {}
+}
+
+impl<F: 'static> SystemSet for SystemTypeSet<F> {}
+
+/// Conversion into a [`SystemSet`], implemented for sets and for functions, which are converted
+/// to the [`SystemTypeSet`] of their system.
+pub trait IntoSystemSet<Marker> {
+    type Set: SystemSet;
+
+    fn into_system_set(self) -> Self::Set;
+}
+
+impl<S: SystemSet> IntoSystemSet<()> for S {
+    type Set = S;
+
+    fn into_system_set(self) -> Self::Set {
+        self
+    }
+}
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> IntoSystemSet<(IsFunctionSystem, Marker)>
+    for F
+{
+    type Set = SystemTypeSet<F>;
+
+    fn into_system_set(self) -> Self::Set {
+        SystemTypeSet::new()
+    }
+}
+
+impl<F: FnMut(&mut World) + Send + Sync + 'static> IntoSystemSet<IsExclusiveFunctionSystem> for F {
+    type Set = SystemTypeSet<F>;
+
+    fn into_system_set(self) -> Self::Set {
+        SystemTypeSet::new()
+    }
+}
+
+/// A system with the sets it is in and ordered against, see [`IntoSystemConfig`].
+pub struct SystemConfig {
+    system: Box<dyn System>,
+    sets: Vec<SetLabel>,
+    ordering: Ordering,
+    conditions: Vec<Condition>,
+}
+
+/// Configures how a system is added to a [`Schedule`], implemented for everything that
+/// implements [`IntoSystem`].
+pub trait IntoSystemConfig<Marker>: Sized {
+    fn into_config(self) -> SystemConfig;
+
+    /// Adds the system to the set, so it is ordered like the set.
+    fn in_set(self, set: impl SystemSet) -> SystemConfig {
+        let mut config = self.into_config();
+        config.sets.push(SetLabel::new(set));
+        config
+    }
+
+    /// Runs the system before every system in the set.
+    fn before<M>(self, set: impl IntoSystemSet<M>) -> SystemConfig {
+        let mut config = self.into_config();
+        config
+            .ordering
+            .before
+            .push(SetLabel::new(set.into_system_set()));
+        config
+    }
+
+    /// Runs the system after every system in the set.
+    fn after<M>(self, set: impl IntoSystemSet<M>) -> SystemConfig {
+        let mut config = self.into_config();
+        config
+            .ordering
+            .after
+            .push(SetLabel::new(set.into_system_set()));
+        config
+    }
+
+    /// Only runs the system in frames where the condition holds, e.g. [`in_state`].
+    ///
+    /// [`in_state`]: crate::state::in_state
+    fn run_if(self, condition: impl Fn(&World) -> bool + Send + Sync + 'static) -> SystemConfig {
+        let mut config = self.into_config();
+        config.conditions.push(Box::new(condition));
+        config
+    }
+}
+
+impl IntoSystemConfig<()> for SystemConfig {
+    fn into_config(self) -> SystemConfig {
+        self
+    }
+}
+
+impl<Marker, S: IntoSystem<Marker>> IntoSystemConfig<Marker> for S {
+    fn into_config(self) -> SystemConfig {
+        SystemConfig {
+            system: Box::new(self.into_system()),
+            sets: Vec::new(),
+            ordering: Ordering::default(),
+            conditions: Vec::new(),
+        }
+    }
+}
+
+/// A set with the sets it is ordered against, see [`IntoSystemSetConfig`].
+pub struct SystemSetConfig {
+    set: SetLabel,
+    ordering: Ordering,
+}
+
+/// Configures the ordering of a set with [`Schedule::configure_set`], implemented for every
+/// [`SystemSet`].
+pub trait IntoSystemSetConfig: Sized {
+    fn into_config(self) -> SystemSetConfig;
+
+    /// Runs the systems of the set before every system in the other set.
+    fn before<M>(self, set: impl IntoSystemSet<M>) -> SystemSetConfig {
+        let mut config = self.into_config();
+        config
+            .ordering
+            .before
+            .push(SetLabel::new(set.into_system_set()));
+        config
+    }
+
+    /// Runs the systems of the set after every system in the other set.
+    fn after<M>(self, set: impl IntoSystemSet<M>) -> SystemSetConfig {
+        let mut config = self.into_config();
+        config
+            .ordering
+            .after
+            .push(SetLabel::new(set.into_system_set()));
+        config
+    }
+}
+
+impl IntoSystemSetConfig for SystemSetConfig {
+    fn into_config(self) -> SystemSetConfig {
+        self
+    }
+}
+
+impl<S: SystemSet> IntoSystemSetConfig for S {
+    fn into_config(self) -> SystemSetConfig {
+        SystemSetConfig {
+            set: SetLabel::new(self),
+            ordering: Ordering::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        command::Commands,
+        component::{Component, Tick},
+        query::{Added, Query},
+        resource::Resource,
+        system::ResMut,
+        World,
+    };
+
+    use super::{IntoSystemConfig, IntoSystemSetConfig, Schedule, ScheduleBuildError, SystemSet};
+
+    #[derive(Component)]
+    struct Health(u32);
+
+    #[derive(Default, Resource)]
+    struct Log(Vec<String>);
+
+    #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+    enum Stage {
+        Input,
+        Physics,
+        Rendering,
+    }
+
+    fn log(name: &'static str) -> impl FnMut(ResMut<Log>) + Send + Sync + 'static {
+        move |mut log: ResMut<Log>| log.0.push(name.to_owned())
+    }
+
+    fn damage(query: Query<&mut Health>) {
+        for health in query {
+            health.0 = health.0.saturating_sub(1);
+        }
+    }
+
+    fn report(query: Query<&Health>, mut log: ResMut<Log>) {
+        let total = query.map(|health| health.0).sum::<u32>();
+        log.0.push(format!("total {total}"));
+    }
+
+    fn greet(query: Query<&Health, Added<Health>>, mut log: ResMut<Log>) {
+        let count = query.count();
+        log.0.push(format!("added {count}"));
+    }
+
+    #[test]
+    fn run_schedule() {
+        let mut world = World::new();
+        world.spawn(Health(3));
+        world.insert_resource(Log::default());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(damage)
+            .add_system(report)
+            .add_system(greet);
+        assert_eq!(schedule.len(), 3);
+
+        schedule.run(&mut world);
+        assert_eq!(world.time().tick(), Tick::new(1));
+        world.spawn(Health(10));
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get_resource::<Log>().unwrap().0,
+            ["total 2", "added 1", "total 10", "added 1", "total 8", "added 0",]
+        );
+    }
+
+    #[test]
+    fn ordered_sets() {
+        let mut world = World::new();
+        world.spawn(Health(3));
+        world.insert_resource(Log::default());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_set(Stage::Rendering.after(Stage::Physics))
+            .configure_set(Stage::Input.before(Stage::Physics))
+            .add_system(log("draw").in_set(Stage::Rendering))
+            .add_system(log("unordered"))
+            .add_system(log("collide").in_set(Stage::Physics).after(greet))
+            .add_system(greet)
+            .add_system(log("gravity").in_set(Stage::Physics).before(Stage::Physics))
+            .add_system(log("input").in_set(Stage::Input));
+        assert!(
+            matches!(schedule.build(), Err(ScheduleBuildError::Cycle(systems)) if systems.len() == 1)
+        );
+
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_set(Stage::Rendering.after(Stage::Physics))
+            .configure_set(Stage::Input.before(Stage::Physics))
+            .add_system(log("draw").in_set(Stage::Rendering))
+            .add_system(log("unordered"))
+            .add_system(log("collide").in_set(Stage::Physics).after(greet))
+            .add_system(greet.in_set(Stage::Physics))
+            .add_system(log("input").in_set(Stage::Input));
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get_resource::<Log>().unwrap().0,
+            ["unordered", "input", "added 1", "collide", "draw"]
+        );
+    }
+
+    #[test]
+    fn exclusive_after_commands() {
+        fn spawn(mut commands: Commands) {
+            commands.spawn(Health(1));
+        }
+
+        fn count(world: &mut World) {
+            let count = world.query::<&Health>().count();
+            world
+                .get_resource_mut::<Log>()
+                .unwrap()
+                .0
+                .push(format!("count {count}"));
+        }
+
+        let mut world = World::new();
+        world.spawn(Health(1));
+        world.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(count.after(spawn))
+            .add_system(spawn)
+            .add_system(count.before(spawn));
+        schedule.run(&mut world);
+        assert_eq!(
+            world.get_resource::<Log>().unwrap().0,
+            ["count 1", "count 2"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "systems have to run before themselves: ")]
+    fn ordering_cycle() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_set(Stage::Physics.before(Stage::Rendering))
+            .add_system(damage.in_set(Stage::Physics))
+            .add_system(report.in_set(Stage::Rendering).before(greet))
+            .add_system(greet.before(damage));
+
+        let Err(ScheduleBuildError::Cycle(systems)) = schedule.build() else {
+            panic!("the schedule has a cycle");
+        };
+        assert_eq!(systems.len(), 3);
+        assert!(systems[0].ends_with("damage"));
+        assert!(systems[1].ends_with("report"));
+        assert!(systems[2].ends_with("greet"));
+        schedule.run(&mut world);
+    }
+}
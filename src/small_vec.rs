@@ -0,0 +1,213 @@
+use std::{
+    borrow::Borrow,
+    fmt,
+    hash::{Hash, Hasher},
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+};
+
+/// A vector that stores up to `N` elements inline and only allocates once it grows beyond that.
+///
+/// Used for the short lists of component ids built on every spawn, insert and query, which
+/// would otherwise allocate every time.
+pub(crate) struct SmallVec<T: Copy, const N: usize> {
+    len: usize,
+    inline: [MaybeUninit<T>; N],
+    /// Holds all elements once there are more than `N` of them.
+    heap: Vec<T>,
+}
+
+/// The component ids of a single archetype, bundle or query.
+pub(crate) type IdList = SmallVec<crate::component::ComponentId, 8>;
+
+impl<T: Copy, const N: usize> SmallVec<T, N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            len: 0,
+            inline: [const { MaybeUninit::uninit() }; N],
+            heap: Vec::new(),
+        }
+    }
+
+    fn spilled(&self) -> bool {
+        self.len > N
+    }
+
+    pub(crate) fn push(&mut self, value: T) {
+        if self.len < N {
+            self.inline[self.len].write(value);
+        } else {
+            if self.len == N {
+                self.heap.reserve(N * 2);
+                self.heap.extend(self.inline.iter().map(|value| {
+                    // SAFETY: All inline elements are initialized once `len` reaches `N`.
+                    unsafe { value.assume_init() }
+                }));
+            }
+            self.heap.push(value);
+        }
+        self.len += 1;
+    }
+
+    /// Keeps only the elements for which `keep` returns `true`, preserving their order.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let mut kept = 0;
+        for index in 0..self.len {
+            let value = self[index];
+            if keep(&value) {
+                self[kept] = value;
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    /// Removes consecutive repeated elements.
+    pub(crate) fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        let mut kept = 0;
+        for index in 0..self.len {
+            if kept == 0 || self[kept - 1] != self[index] {
+                self[kept] = self[index];
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        if self.spilled() && len <= N {
+            for (slot, value) in self.inline.iter_mut().zip(&self.heap) {
+                slot.write(*value);
+            }
+            self.heap.clear();
+        } else if self.spilled() {
+            self.heap.truncate(len);
+        }
+        self.len = len;
+    }
+}
+
+impl<T: Copy, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        if self.spilled() {
+            &self.heap
+        } else {
+            // SAFETY: The first `len` inline elements are initialized.
+            unsafe { std::slice::from_raw_parts(self.inline.as_ptr().cast(), self.len) }
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if self.spilled() {
+            &mut self.heap
+        } else {
+            // SAFETY: The first `len` inline elements are initialized.
+            unsafe { std::slice::from_raw_parts_mut(self.inline.as_mut_ptr().cast(), self.len) }
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> Clone for SmallVec<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().copied().collect()
+    }
+}
+
+impl<T: Copy + PartialEq, const N: usize> PartialEq for SmallVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Copy + Eq, const N: usize> Eq for SmallVec<T, N> {}
+
+/// Hashes like the slice, so maps keyed by small vectors can be looked up by slices.
+impl<T: Copy + Hash, const N: usize> Hash for SmallVec<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: Copy, const N: usize> Borrow<[T]> for SmallVec<T, N> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Copy + fmt::Debug, const N: usize> fmt::Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Copy, const N: usize> Extend<T> for SmallVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a, const N: usize> Extend<&'a T> for SmallVec<T, N> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<'a, T: Copy, const N: usize> IntoIterator for &'a SmallVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Copy, const N: usize> FromIterator<T> for SmallVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SmallVec;
+
+    #[test]
+    fn spill_and_shrink() {
+        let mut vec = SmallVec::<u32, 4>::new();
+        vec.extend([3, 1, 3]);
+        assert_eq!(&*vec, [3, 1, 3]);
+        assert!(!vec.spilled());
+
+        vec.extend(&[2, 2, 5]);
+        assert!(vec.spilled());
+        vec.sort_unstable();
+        vec.dedup();
+        assert_eq!(&*vec, [1, 2, 3, 5]);
+        assert!(!vec.spilled());
+
+        vec.retain(|value| value % 2 == 1);
+        assert_eq!(&*vec, [1, 3, 5]);
+        assert_eq!(vec.clone().iter().sum::<u32>(), 9);
+        assert_eq!(format!("{vec:?}"), "[1, 3, 5]");
+    }
+}
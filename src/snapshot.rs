@@ -0,0 +1,288 @@
+use std::{error::Error, fmt, io};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    component::ComponentId,
+    entity::{Entities, Entity},
+    replay::DeserializedComponent,
+    World,
+};
+
+/// The entities of a world with their components, taken by [`World::snapshot`] and written by
+/// [`World::save`].
+///
+/// Only components registered with [`World::register_replay_component`] are saved, all others
+/// are silently left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub entities: Vec<SavedEntity>,
+    /// The free entity slots, with the generation they are reused with, in the order the world
+    /// reuses them last to first.
+    pub free: Vec<Entity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub entity: Entity,
+    pub components: Vec<SavedComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedComponent {
+    /// The type name of the component, used to find it in the loading world.
+    pub name: String,
+    pub value: Value,
+}
+
+/// The error returned when a [`Snapshot`] can't be loaded into a world.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The component is not registered in the loading world.
+    UnknownComponent(String),
+    /// The saved entities don't cover every entity slot exactly once.
+    InvalidEntities,
+    Deserialize(serde_json::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownComponent(name) => {
+                write!(f, "component {name} is not registered for saving")
+            }
+            Self::InvalidEntities => write!(f, "the saved entities overlap or leave out slots"),
+            Self::Deserialize(err) => write!(f, "failed to deserialize snapshot: {err}"),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Deserialize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl World {
+    /// Takes a snapshot of all entities and their registered components.
+    ///
+    /// Entities that are reserved but not spawned yet are saved as free.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            entities: self
+                .entities
+                .iter()
+                .map(|(entity, _)| self.save_entity(entity))
+                .collect(),
+            free: self.entities.free_entities(),
+        }
+    }
+
+    /// Serializes the registered components of the entity, in the order of their ids.
+    pub(crate) fn save_entity(&self, entity: Entity) -> SavedEntity {
+        let registry = &self.replay_registry;
+        let mut ids = registry.components.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let components = ids
+            .iter()
+            .filter_map(|id| {
+                let fns = &registry.components[id];
+                let ptr = self.get_by_id(entity, *id)?;
+                Some(SavedComponent {
+                    name: fns.name.to_owned(),
+                    // SAFETY: The pointer is only read as the component it was registered for.
+                    value: unsafe { (fns.serialize)(ptr) },
+                })
+            })
+            .collect();
+        SavedEntity { entity, components }
+    }
+
+    /// Looks up the ids of the saved components of every entity and deserializes them.
+    pub(crate) fn deserialize_saved(
+        &self,
+        entities: &[SavedEntity],
+    ) -> Result<Vec<Vec<(ComponentId, DeserializedComponent)>>, LoadError> {
+        let registry = &self.replay_registry;
+        entities
+            .iter()
+            .map(|saved| {
+                saved
+                    .components
+                    .iter()
+                    .map(|component| {
+                        let id = registry
+                            .names
+                            .get(&component.name)
+                            .ok_or_else(|| LoadError::UnknownComponent(component.name.clone()))?;
+                        let value = (registry.components[id].deserialize)(&component.value)
+                            .map_err(LoadError::Deserialize)?;
+                        Ok((*id, value))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Inserts the components deserialized by [`World::deserialize_saved`] into the entity.
+    pub(crate) fn insert_saved(
+        &mut self,
+        entity: Entity,
+        components: Vec<(ComponentId, DeserializedComponent)>,
+    ) {
+        for (id, value) in components {
+            let insert = self.replay_registry.components[&id].insert;
+            insert(self, entity, value);
+        }
+    }
+
+    /// Writes a snapshot of the world as JSON, see [`World::snapshot`].
+    ///
+    /// ```
+    /// # use quartz::{component::Component, World};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Component, Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.register_replay_component::<Health>();
+    /// let entity = world.spawn(Health(10));
+    ///
+    /// let mut save = Vec::new();
+    /// world.save(&mut save).unwrap();
+    ///
+    /// let mut loaded = World::new();
+    /// loaded.register_replay_component::<Health>();
+    /// loaded.load(save.as_slice()).unwrap();
+    /// assert_eq!(loaded.get::<Health>(entity), Some(&Health(10)));
+    /// ```
+    pub fn save(&self, writer: impl io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(writer, &self.snapshot())
+    }
+
+    /// Reads a snapshot written by [`World::save`] and loads it, see [`World::load_snapshot`].
+    pub fn load(&mut self, reader: impl io::Read) -> Result<(), LoadError> {
+        let snapshot = serde_json::from_reader(reader).map_err(LoadError::Deserialize)?;
+        self.load_snapshot(&snapshot)
+    }
+
+    /// Replaces all entities with the entities of the snapshot, keeping their ids, so the
+    /// entities spawned afterwards get the same ids as in the saved world.
+    ///
+    /// The entities are cleared like [`World::clear_entities`] and the saved ones are spawned and
+    /// have their components inserted, running hooks as usual. Every saved component must be
+    /// registered with [`World::register_replay_component`]. Entities reserved before loading
+    /// can't be spawned anymore.
+    ///
+    /// Every component is deserialized before the world is cleared, so the world is left
+    /// unchanged if the snapshot is invalid.
+    pub fn load_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), LoadError> {
+        let components = self.deserialize_saved(&snapshot.entities)?;
+        let alive = snapshot
+            .entities
+            .iter()
+            .map(|saved| saved.entity)
+            .collect::<Vec<_>>();
+        let entities =
+            Entities::restore(&alive, &snapshot.free).map_err(|()| LoadError::InvalidEntities)?;
+        self.clear_entities();
+        self.entities = entities;
+
+        for (entity, components) in alive.into_iter().zip(components) {
+            self.spawn_in(Some(entity), ());
+            self.insert_saved(entity, components);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{component::Component, entity::Entity, World};
+
+    use super::{LoadError, Snapshot};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+    impl Component for Name {}
+
+    #[derive(Debug, PartialEq)]
+    struct Unsaved;
+    impl Component for Unsaved {}
+
+    fn register(world: &mut World) {
+        world.register_replay_component::<Health>();
+        world.register_replay_component::<Name>();
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut world = World::new();
+        register(&mut world);
+        let e0 = world.spawn((Health(10), Name("a".into())));
+        let e1 = world.spawn((Health(20), Unsaved));
+        let e2 = world.spawn(Name("c".into()));
+        world.despawn(e1);
+        world.despawn(e0);
+
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+
+        let mut loaded = World::new();
+        register(&mut loaded);
+        loaded.spawn(Health(99));
+        loaded.load(save.as_slice()).unwrap();
+
+        assert_eq!(loaded.iter_entities().count(), 1);
+        assert!(!loaded.contains(e0));
+        assert_eq!(loaded.get::<Name>(e2), Some(&Name("c".into())));
+        assert_eq!(loaded.query::<&Health>().count(), 0);
+
+        // The entity allocator continues where the saved world left off
+        assert_eq!(loaded.spawn(Health(1)), world.spawn(Health(1)));
+        assert_eq!(loaded.spawn(Health(2)), world.spawn(Health(2)));
+        assert_eq!(loaded.spawn(Health(3)), world.spawn(Health(3)));
+    }
+
+    #[test]
+    fn invalid_snapshots() {
+        let mut world = World::new();
+        register(&mut world);
+        let entity = world.spawn(Health(10));
+
+        let mut unregistered = World::new();
+        let result = unregistered.load_snapshot(&world.snapshot());
+        assert!(matches!(result, Err(LoadError::UnknownComponent(_))));
+
+        let mut snapshot = world.snapshot();
+        snapshot.free.push(Entity::PLACEHOLDER);
+        let result = world.load_snapshot(&snapshot);
+        assert!(matches!(result, Err(LoadError::InvalidEntities)));
+        assert_eq!(world.get::<Health>(entity), Some(&Health(10)));
+
+        let mut snapshot = world.snapshot();
+        let other = world.spawn(Name("b".into()));
+        snapshot.entities[0].components[0].value = serde_json::json!("corrupt");
+        let result = world.load_snapshot(&snapshot);
+        assert!(matches!(result, Err(LoadError::Deserialize(_))));
+        assert_eq!(world.get::<Health>(entity), Some(&Health(10)));
+        assert_eq!(world.get::<Name>(other), Some(&Name("b".into())));
+
+        let result = world.load("{".as_bytes());
+        assert!(matches!(result, Err(LoadError::Deserialize(_))));
+        world.load_snapshot(&Snapshot::default()).unwrap();
+        assert!(!world.contains(entity));
+    }
+}
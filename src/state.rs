@@ -0,0 +1,326 @@
+use std::{any::type_name, collections::HashMap, fmt, hash::Hash, ops::Deref};
+
+use crate::{
+    resource::Resource,
+    schedule::{IntoSystemConfig, Schedule},
+    World,
+};
+
+pub use quartz_macros::States;
+
+/// The states of a state machine, like the screens of a game, stored in the [`State`] resource
+/// after [`World::init_state`].
+///
+/// A state is changed by setting the [`NextState`], which [`apply_state_transition`] then applies,
+/// running the [`OnExit`] systems of the old state and the [`OnEnter`] systems of the new one.
+/// Systems that should only run in a state are added with [`in_state`] as their condition.
+///
+/// ```
+/// # use quartz::{
+/// #     resource::Resource,
+/// #     schedule::{IntoSystemConfig, Schedule},
+/// #     state::{apply_state_transition, in_state, NextState, OnEnter, States},
+/// #     system::ResMut,
+/// #     World,
+/// # };
+/// #[derive(States, Debug, Clone, PartialEq, Eq, Hash)]
+/// enum Screen {
+///     Menu,
+///     Game,
+/// }
+///
+/// #[derive(Default, Resource)]
+/// struct Score(u32);
+///
+/// fn start(mut next: ResMut<NextState<Screen>>) {
+///     next.set(Screen::Game);
+/// }
+///
+/// fn reset(mut score: ResMut<Score>) {
+///     score.0 = 0;
+/// }
+///
+/// fn play(mut score: ResMut<Score>) {
+///     score.0 += 1;
+/// }
+///
+/// let mut world = World::new();
+/// world.insert_resource(Score(5));
+/// world.init_state(Screen::Menu);
+/// world.add_state_system(OnEnter(Screen::Game), reset);
+///
+/// let mut schedule = Schedule::new();
+/// schedule
+///     .add_system(start.run_if(in_state(Screen::Menu)))
+///     .add_system(play.run_if(in_state(Screen::Game)))
+///     .add_system(apply_state_transition::<Screen>);
+///
+/// schedule.run(&mut world);
+/// assert_eq!(world.get_resource::<Score>().unwrap().0, 0);
+/// schedule.run(&mut world);
+/// assert_eq!(world.get_resource::<Score>().unwrap().0, 1);
+/// ```
+pub trait States: fmt::Debug + Clone + Eq + Hash + Send + Sync + 'static {}
+
+/// The current state of type `S`, stored as a resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State<S: States>(S);
+
+impl<S: States> Resource for State<S> {}
+
+impl<S: States> State<S> {
+    pub fn get(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: States> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// The state of type `S` to change to in the next [`apply_state_transition`], stored as a
+/// resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextState<S: States>(Option<S>);
+
+impl<S: States> Resource for NextState<S> {}
+
+impl<S: States> Default for NextState<S> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<S: States> NextState<S> {
+    /// Changes to the state in the next transition, replacing the state set before.
+    pub fn set(&mut self, state: S) {
+        self.0 = Some(state);
+    }
+
+    pub fn get(&self) -> Option<&S> {
+        self.0.as_ref()
+    }
+}
+
+/// The systems that run when the state is entered, see [`World::add_state_system`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnEnter<S: States>(pub S);
+
+/// The systems that run when the state is exited, see [`World::add_state_system`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnExit<S: States>(pub S);
+
+/// A schedule that runs on a transition of states of type `S`, implemented by [`OnEnter`] and
+/// [`OnExit`].
+pub trait StateSchedule<S: States> {
+    #[doc(hidden)]
+    fn schedule(self, schedules: &mut StateSchedules<S>) -> &mut Schedule;
+}
+
+impl<S: States> StateSchedule<S> for OnEnter<S> {
+    fn schedule(self, schedules: &mut StateSchedules<S>) -> &mut Schedule {
+        schedules.enter.entry(self.0).or_default()
+    }
+}
+
+impl<S: States> StateSchedule<S> for OnExit<S> {
+    fn schedule(self, schedules: &mut StateSchedules<S>) -> &mut Schedule {
+        schedules.exit.entry(self.0).or_default()
+    }
+}
+
+/// The [`OnEnter`] and [`OnExit`] schedules of the states of type `S`, stored as a resource.
+#[derive(Debug)]
+pub struct StateSchedules<S: States> {
+    enter: HashMap<S, Schedule>,
+    exit: HashMap<S, Schedule>,
+    /// Whether the [`OnEnter`] systems of the initial state ran.
+    entered: bool,
+}
+
+impl<S: States> Resource for StateSchedules<S> {}
+
+impl<S: States> Default for StateSchedules<S> {
+    fn default() -> Self {
+        Self {
+            enter: HashMap::new(),
+            exit: HashMap::new(),
+            entered: false,
+        }
+    }
+}
+
+impl World {
+    /// Adds the [`State`], [`NextState`] and [`StateSchedules`] resources of states of type `S`,
+    /// starting in the given state.
+    ///
+    /// The [`OnEnter`] systems of the initial state run in the first [`apply_state_transition`].
+    pub fn init_state<S: States>(&mut self, state: S) {
+        self.insert_resource(State(state));
+        self.insert_resource(NextState::<S>::default());
+        self.insert_resource(StateSchedules::<S>::default());
+    }
+
+    /// Adds a system to run on a transition, e.g. `OnEnter(Screen::Game)`.
+    ///
+    /// Panics: If [`World::init_state`] wasn't called for the states.
+    pub fn add_state_system<S: States, Marker>(
+        &mut self,
+        schedule: impl StateSchedule<S>,
+        system: impl IntoSystemConfig<Marker>,
+    ) -> &mut Self {
+        let schedules = self
+            .get_resource_mut::<StateSchedules<S>>()
+            .unwrap_or_else(|| panic!("{} was not initialized", type_name::<S>()));
+        schedule.schedule(schedules).add_system(system);
+        self
+    }
+}
+
+/// Changes to the [`NextState`] if it was set to a state other than the current one, running the
+/// [`OnExit`] systems of the current state and the [`OnEnter`] systems of the next one, within the
+/// current frame.
+///
+/// Meant to be added to a schedule as an exclusive system, usually after the systems that set the
+/// next state.
+///
+/// Panics: If [`World::init_state`] wasn't called for the states, or if it is called from the
+/// systems of a transition.
+pub fn apply_state_transition<S: States>(world: &mut World) {
+    let mut schedules = world
+        .remove_resource::<StateSchedules<S>>()
+        .unwrap_or_else(|| panic!("{} was not initialized", type_name::<S>()));
+    let current = world
+        .get_resource::<State<S>>()
+        .expect("initialized states have a state")
+        .0
+        .clone();
+
+    if !schedules.entered {
+        schedules.entered = true;
+        if let Some(schedule) = schedules.enter.get_mut(&current) {
+            schedule.run_systems(world);
+        }
+    }
+
+    let next = world
+        .get_resource_mut::<NextState<S>>()
+        .expect("initialized states have a next state")
+        .0
+        .take();
+    if let Some(next) = next.filter(|next| *next != current) {
+        if let Some(schedule) = schedules.exit.get_mut(&current) {
+            schedule.run_systems(world);
+        }
+        world.insert_resource(State(next.clone()));
+        if let Some(schedule) = schedules.enter.get_mut(&next) {
+            schedule.run_systems(world);
+        }
+    }
+
+    world.insert_resource(schedules);
+}
+
+/// A condition for [`IntoSystemConfig::run_if`] that holds while the current state is `state`.
+pub fn in_state<S: States>(state: S) -> impl Fn(&World) -> bool + Send + Sync + 'static {
+    move |world| {
+        world
+            .get_resource::<State<S>>()
+            .is_some_and(|current| current.0 == state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        resource::Resource,
+        schedule::{IntoSystemConfig, Schedule},
+        system::ResMut,
+        World,
+    };
+
+    use super::{apply_state_transition, in_state, NextState, OnEnter, OnExit, State, States};
+
+    #[derive(States, Debug, Clone, PartialEq, Eq, Hash)]
+    enum Screen {
+        Menu,
+        Game,
+        Pause,
+    }
+
+    #[derive(Default, Resource)]
+    struct Log(Vec<String>);
+
+    fn log(message: &'static str) -> impl FnMut(ResMut<Log>) + Send + Sync + 'static {
+        move |mut log: ResMut<Log>| log.0.push(message.to_owned())
+    }
+
+    #[test]
+    fn transitions() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        world.init_state(Screen::Menu);
+        world
+            .add_state_system(OnEnter(Screen::Menu), log("enter menu"))
+            .add_state_system(OnExit(Screen::Menu), log("exit menu"))
+            .add_state_system(OnEnter(Screen::Game), log("enter game"))
+            .add_state_system(OnExit(Screen::Game), log("exit game"))
+            .add_state_system(OnEnter(Screen::Pause), log("enter pause"));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(log("menu").run_if(in_state(Screen::Menu)))
+            .add_system(log("game").run_if(in_state(Screen::Game)))
+            .add_system(apply_state_transition::<Screen>);
+
+        schedule.run(&mut world);
+        world
+            .get_resource_mut::<NextState<Screen>>()
+            .unwrap()
+            .set(Screen::Game);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        // Setting the current state again doesn't transition
+        world
+            .get_resource_mut::<NextState<Screen>>()
+            .unwrap()
+            .set(Screen::Game);
+        schedule.run(&mut world);
+        world
+            .get_resource_mut::<NextState<Screen>>()
+            .unwrap()
+            .set(Screen::Pause);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        assert_eq!(
+            *world.get_resource::<State<Screen>>().unwrap().get(),
+            Screen::Pause
+        );
+        assert_eq!(
+            world.get_resource::<Log>().unwrap().0,
+            [
+                "menu",
+                "enter menu",
+                "menu",
+                "exit menu",
+                "enter game",
+                "game",
+                "game",
+                "game",
+                "exit game",
+                "enter pause",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "was not initialized")]
+    fn uninitialized_state() {
+        apply_state_transition::<Screen>(&mut World::new());
+    }
+}
@@ -2,12 +2,20 @@ use core::{alloc::Layout, ptr::NonNull};
 
 use std::{
     alloc::handle_alloc_error,
-    collections::HashMap,
-    ops::{Add, AddAssign},
+    any::TypeId,
+    cell::UnsafeCell,
+    collections::{self, HashMap},
+    error::Error,
+    fmt,
+    ops::{Add, AddAssign, SubAssign},
+    sync::atomic::{AtomicU32, Ordering},
 };
 
 use crate::{
-    component::{ComponentId, ComponentInfo, Components},
+    builder::WorldHasher,
+    component::{
+        ChangeDetection, Component, ComponentId, ComponentInfo, ComponentTicks, Components, Tick,
+    },
     entity::Entity,
     ptr::{MutPtr, OwningPtr, Ptr},
 };
@@ -15,10 +23,20 @@ use crate::{
 #[derive(Debug, Default)]
 pub(crate) struct Tables {
     tables: Vec<Table>,
-    table_index: HashMap<Box<[ComponentId]>, TableId>,
+    table_index: HashMap<Box<[ComponentId]>, TableId, WorldHasher>,
+    /// The number of rows every new table reserves.
+    capacity_hint: usize,
 }
 
 impl Tables {
+    pub(crate) fn with_hasher(hasher: WorldHasher, capacity_hint: usize) -> Self {
+        Self {
+            tables: Vec::new(),
+            table_index: HashMap::with_hasher(hasher),
+            capacity_hint,
+        }
+    }
+
     pub(crate) fn get_id_or_insert(
         &mut self,
         ids: &[ComponentId],
@@ -26,7 +44,9 @@ impl Tables {
     ) -> TableId {
         *self.table_index.entry(ids.into()).or_insert_with(|| {
             let id = TableId(self.tables.len());
-            self.tables.push(Table::from_components(ids, components));
+            let mut table = Table::from_components(ids, components);
+            table.reserve(self.capacity_hint);
+            self.tables.push(table);
             id
         })
     }
@@ -39,6 +59,29 @@ impl Tables {
         self.tables.get_mut(id.index())
     }
 
+    /// Retrieves two distinct [`Table`]s mutably at the same time.
+    ///
+    /// Panics: If both ids are the same or one of them does not exist inside this world.
+    pub(crate) fn get_2_mut(&mut self, a: TableId, b: TableId) -> (&mut Table, &mut Table) {
+        assert_ne!(a, b, "cannot borrow the same table twice");
+        if a.index() < b.index() {
+            let (left, right) = self.tables.split_at_mut(b.index());
+            (&mut left[a.index()], &mut right[0])
+        } else {
+            let (left, right) = self.tables.split_at_mut(a.index());
+            (&mut right[0], &mut left[b.index()])
+        }
+    }
+
+    /// Retrieves the [`Table`] for the given [`TableId`] without a bounds check.
+    ///
+    /// # Safety
+    /// The table must exist inside this world. This is only checked with debug assertions.
+    pub(crate) unsafe fn get_unchecked_raw(&self, id: TableId) -> &Table {
+        debug_assert!(id.index() < self.tables.len(), "{id:?} does not exist");
+        self.tables.get_unchecked(id.index())
+    }
+
     /// Retrieves the [`Table`] for the given [`TableId`].
     ///
     /// Panics: If the given id does not exist inside this world.
@@ -50,11 +93,197 @@ impl Tables {
     pub(crate) fn len(&self) -> usize {
         self.tables.len()
     }
+
+    /// Drops the rows of all tables, keeping the tables themselves, see [`Table::clear`].
+    pub(crate) fn clear_rows(&mut self) {
+        for table in self.tables.iter_mut() {
+            table.clear();
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TableId(pub(crate) usize);
 
+/// A component that is moved out of a table row to a callback taking ownership of it, instead
+/// of being dropped, see [`Table::move_to_unchecked`].
+pub(crate) type TakenComponent<'a> = (ComponentId, &'a mut dyn FnMut(OwningPtr<'_>));
+
+/// The error returned when storage for new entities or components can't be allocated, see
+/// [`World::try_spawn`](crate::World::try_spawn).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The required capacity exceeds the maximum size of an allocation.
+    CapacityOverflow,
+    /// The allocator failed to allocate a column.
+    AllocError { layout: Layout },
+    /// A collection failed to grow.
+    Collection(collections::TryReserveError),
+}
+
+impl TryReserveError {
+    /// Fails like the infallible allocation APIs of the standard library, by aborting on
+    /// allocation failures and panicking otherwise.
+    pub(crate) fn handle(self) -> ! {
+        match self {
+            Self::AllocError { layout } => handle_alloc_error(layout),
+            err => panic!("{err}"),
+        }
+    }
+}
+
+impl From<collections::TryReserveError> for TryReserveError {
+    fn from(err: collections::TryReserveError) -> Self {
+        Self::Collection(err)
+    }
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => write!(f, "capacity overflow"),
+            Self::AllocError { layout } => {
+                write!(f, "failed to allocate {} bytes", layout.size())
+            }
+            Self::Collection(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for TryReserveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Collection(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A read-only view of the raw memory of a component column in a single table, e.g. to copy it
+/// into a GPU buffer without gathering the components first.
+///
+/// The view borrows the world, so the column can't move or change while it is alive. The column
+/// stores `len` components `stride` bytes apart, starting at `ptr`.
+#[derive(Clone, Copy)]
+pub struct ColumnView<'w> {
+    table: &'w Table,
+    table_id: TableId,
+    component_id: ComponentId,
+    type_id: Option<TypeId>,
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl<'w> ColumnView<'w> {
+    /// Returns a view of the column, if the table has one for the component.
+    pub(crate) fn new(
+        table: &'w Table,
+        table_id: TableId,
+        component_id: ComponentId,
+        components: &Components,
+    ) -> Option<Self> {
+        let column = table.get_column(component_id)?;
+        Some(Self {
+            table,
+            table_id,
+            component_id,
+            type_id: components.get_info(&component_id)?.type_id(),
+            ptr: column.data,
+            layout: column.item_layout,
+        })
+    }
+
+    pub fn table_id(&self) -> TableId {
+        self.table_id
+    }
+
+    pub fn component_id(&self) -> ComponentId {
+        self.component_id
+    }
+
+    /// A pointer to the first component, which is dangling for empty columns.
+    pub fn ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// The number of rows, including tombstoned ones, see [`ColumnView::is_dense`].
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The distance between two components in bytes.
+    pub fn stride(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// The layout of a single component.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Returns `false` if the column has tombstoned rows, whose bytes must not be read.
+    pub fn is_dense(&self) -> bool {
+        self.table.free_rows.is_empty()
+    }
+
+    /// Returns `false` if the row was tombstoned.
+    ///
+    /// Panics: If the row is out of bounds.
+    pub fn is_live(&self, row: usize) -> bool {
+        self.table.entities[row] != Entity::PLACEHOLDER
+    }
+
+    /// The entities of the rows, with [`Entity::PLACEHOLDER`] for tombstoned rows.
+    pub fn entities(&self) -> &'w [Entity] {
+        &self.table.entities
+    }
+
+    /// The components of the column, if it is dense and stores `T` under this view's id.
+    pub fn as_slice<T: Component>(&self) -> Option<&'w [T]> {
+        if self.type_id != Some(TypeId::of::<T>()) || !self.is_dense() {
+            return None;
+        }
+
+        // SAFETY: The column stores `len` initialized values of `T`.
+        Some(unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().cast::<T>(), self.len()) })
+    }
+}
+
+impl fmt::Debug for ColumnView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColumnView")
+            .field("table_id", &self.table_id)
+            .field("component_id", &self.component_id)
+            .field("ptr", &self.ptr)
+            .field("len", &self.len())
+            .field("layout", &self.layout)
+            .finish()
+    }
+}
+
+/// How rows are removed from tables, see
+/// [`World::set_removal_mode`](crate::World::set_removal_mode).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalMode {
+    /// Moves the last row into the removed one, which is cheap but changes the iteration order.
+    #[default]
+    Swap,
+    /// Shifts all following rows down by one, which costs time linear in the size of the table
+    /// but preserves the order in which entities were added to it.
+    Shift,
+    /// Leaves a tombstone in the removed row, which is reused by the next entity added to the
+    /// table. No other entity is moved, so rows stay valid until their entity is removed, at the
+    /// cost of queries skipping the holes. This is what [`Component::STABLE_ROWS`] does for
+    /// single components.
+    ///
+    /// [`Component::STABLE_ROWS`]: crate::component::Component::STABLE_ROWS
+    Tombstone,
+}
+
 impl TableId {
     pub(crate) fn index(&self) -> usize {
         self.0
@@ -85,6 +314,12 @@ impl AddAssign<usize> for TableRow {
     }
 }
 
+impl SubAssign<usize> for TableRow {
+    fn sub_assign(&mut self, rhs: usize) {
+        self.0 -= rhs;
+    }
+}
+
 impl PartialOrd<usize> for TableRow {
     fn partial_cmp(&self, other: &usize) -> Option<std::cmp::Ordering> {
         self.0.partial_cmp(other)
@@ -99,35 +334,91 @@ impl PartialEq<usize> for TableRow {
 
 #[derive(Debug)]
 pub struct Table {
-    columns: HashMap<ComponentId, Column>,
+    /// Sorted ids of the components stored in this table, `columns[i]` stores `component_ids[i]`.
+    component_ids: Box<[ComponentId]>,
+    columns: Vec<Column>,
+    /// The entity of every row, [`Entity::PLACEHOLDER`] for tombstoned rows.
     entities: Vec<Entity>,
+    /// Whether removed rows are tombstoned instead of moving other rows, see
+    /// [`Component::STABLE_ROWS`](crate::component::Component::STABLE_ROWS).
+    stable_rows: bool,
+    /// Tombstoned rows, which are reused by later allocations.
+    free_rows: Vec<TableRow>,
+    /// The number of rows the entities and all columns have room for.
+    capacity: usize,
 }
 
 impl Table {
+    /// Creates a table with a column for each of the components in `ids`, which must be sorted.
     pub(crate) fn from_components(ids: &[ComponentId], components: &Components) -> Self {
-        let mut table = Self {
-            columns: HashMap::new(),
-            entities: Vec::new(),
-        };
+        debug_assert!(ids.is_sorted(), "table component ids must be sorted");
 
-        ids.iter()
-            .map(|id| (id, components.get_info(id).unwrap()))
-            .for_each(|(id, info)| {
-                table.columns.insert(*id, Column::with_capacity(info, 0));
-            });
-
-        table
+        Self {
+            component_ids: ids.into(),
+            columns: ids
+                .iter()
+                .map(|id| Column::with_capacity(components.get_info(id).unwrap(), 0))
+                .collect(),
+            entities: Vec::new(),
+            stable_rows: ids
+                .iter()
+                .any(|id| components.get_info(id).unwrap().stable_rows),
+            free_rows: Vec::new(),
+            capacity: 0,
+        }
     }
 
-    fn capacity(&self) -> usize {
-        self.entities.capacity()
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
     }
 
+    /// The number of rows in this table, including tombstoned ones.
     pub(crate) fn len(&self) -> usize {
         self.entities.len()
     }
 
+    /// Whether rows removed with `mode` are tombstoned instead of moving other rows.
+    ///
+    /// Tables holding tombstones keep tombstoning until all of them are reused, even if `mode`
+    /// changed in between, as moving rows into a tombstone would corrupt the free rows.
+    pub(crate) fn tombstones(&self, mode: RemovalMode) -> bool {
+        self.stable_rows || mode == RemovalMode::Tombstone || !self.free_rows.is_empty()
+    }
+
+    /// Returns `false` if the row was tombstoned and holds no entity.
+    #[inline]
+    pub(crate) fn is_live(&self, row: TableRow) -> bool {
+        self.free_rows.is_empty() || self.entities[row.index()] != Entity::PLACEHOLDER
+    }
+
+    /// The number of rows in `start..end` that are not tombstoned.
+    pub(crate) fn live_rows(&self, start: TableRow, end: TableRow) -> usize {
+        let tombstones = self
+            .free_rows
+            .iter()
+            .filter(|row| (start.index()..end.index()).contains(&row.index()))
+            .count();
+        end.index() - start.index() - tombstones
+    }
+
+    /// Returns the entity stored in the given row.
+    ///
+    /// Panics: If the row is out of bounds.
+    pub(crate) fn entity(&self, row: TableRow) -> Entity {
+        self.entities[row.index()]
+    }
+
+    /// Replaces the entity stored in a live row, e.g. after it was given a new id.
+    pub(crate) fn set_entity(&mut self, row: TableRow, entity: Entity) {
+        self.entities[row.index()] = entity;
+    }
+
     pub(crate) fn allocate(&mut self, entity: Entity) -> TableRow {
+        if let Some(row) = self.free_rows.pop() {
+            self.entities[row.index()] = entity;
+            return row;
+        }
+
         self.reserve(1);
         let table_row = TableRow(self.len());
         self.entities.push(entity);
@@ -136,24 +427,55 @@ impl Table {
     }
 
     pub(crate) fn reserve(&mut self, additional: usize) {
-        if self.capacity() - self.len() < additional {
-            self.entities.reserve(additional);
-            self.realloc_columns(self.capacity() + additional);
+        if let Err(err) = self.try_reserve(additional) {
+            err.handle();
         }
     }
 
-    fn realloc_columns(&mut self, new_capacity: usize) {
-        for col in self.columns.values_mut() {
-            col.realloc(new_capacity);
+    /// Reserves room for at least `additional` more rows.
+    ///
+    /// The capacity is only raised once the entities and all columns have grown, so a failure
+    /// leaves the table in a consistent state.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.capacity() - self.len() >= additional {
+            return Ok(());
+        }
+
+        let required = self
+            .len()
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let new_capacity = required.max(self.capacity().saturating_mul(2));
+        self.entities
+            .try_reserve_exact(new_capacity - self.entities.len())?;
+        for col in self.columns.iter_mut() {
+            if col.capacity() < new_capacity {
+                col.try_realloc(new_capacity)?;
+            }
         }
+        self.capacity = new_capacity;
+
+        Ok(())
     }
 
-    fn get_column(&self, id: ComponentId) -> Option<&Column> {
-        self.columns.get(&id)
+    /// Returns the index of the column storing the component, if this table has one.
+    pub(crate) fn column_index(&self, id: ComponentId) -> Option<usize> {
+        self.component_ids.binary_search(&id).ok()
+    }
+
+    pub(crate) fn get_column(&self, id: ComponentId) -> Option<&Column> {
+        self.column_index(id).map(|index| &self.columns[index])
     }
 
     pub(crate) fn get_column_mut(&mut self, id: ComponentId) -> Option<&mut Column> {
-        self.columns.get_mut(&id)
+        self.column_index(id).map(|index| &mut self.columns[index])
+    }
+
+    /// Retrieves a column by its index, as returned by [`Table::column_index`].
+    ///
+    /// Safety: The index must be in bounds for this table.
+    pub(crate) unsafe fn get_column_unchecked_mut(&mut self, index: usize) -> &mut Column {
+        self.columns.get_unchecked_mut(index)
     }
 
     pub(crate) unsafe fn get_component(&self, id: ComponentId, row: TableRow) -> Option<Ptr<'_>> {
@@ -161,41 +483,312 @@ impl Table {
             .map(|col| col.get_unchecked(row.index()))
     }
 
+    /// Retrieves a mutable pointer to a component through a shared reference to the table and
+    /// marks it as changed at `change_tick`.
+    ///
+    /// Safety: The caller must ensure that no other reference to this component is alive for
+    /// the lifetime of the returned pointer.
+    pub(crate) unsafe fn get_component_mut_ptr(
+        &self,
+        id: ComponentId,
+        row: TableRow,
+        change_tick: Tick,
+    ) -> Option<MutPtr<'_>> {
+        self.get_column(id).map(|col| {
+            col.ticks.set_changed(row.index(), change_tick);
+            col.get_unchecked_mut_ptr(row.index())
+        })
+    }
+
+    /// Retrieves a mutable pointer to a component and marks it as changed at `change_tick`.
     pub(crate) unsafe fn get_component_mut(
         &mut self,
         id: ComponentId,
         row: TableRow,
+        change_tick: Tick,
     ) -> Option<MutPtr<'_>> {
-        self.get_column_mut(id)
-            .map(|col| col.get_unchecked_mut(row.index()))
+        self.get_column_mut(id).map(|col| {
+            col.ticks.set_changed(row.index(), change_tick);
+            col.get_unchecked_mut(row.index())
+        })
+    }
+
+    /// Returns the change ticks of a component, see [`ChangeDetection`].
+    ///
+    /// Panics: If the row is out of bounds for a component tracked per entity.
+    pub(crate) fn get_ticks(&self, id: ComponentId, row: TableRow) -> Option<ComponentTicks> {
+        self.get_column(id).map(|col| col.ticks.get(row.index()))
     }
 
-    pub(crate) fn swap_remove(&mut self, table_row: TableRow) {
+    /// Removes the row and drops its components. Tables with stable rows tombstone it instead
+    /// of applying `mode`, see [`Table::tombstones`].
+    pub(crate) fn remove(&mut self, table_row: TableRow, mode: RemovalMode) {
         let index = table_row.index();
-        if index == self.entities.len() - 1 {
-            for col in self.columns.values_mut() {
-                col.drop_last();
+        if self.tombstones(mode) {
+            for col in self.columns.iter_mut() {
+                col.drop_in_place(index);
             }
-        } else {
-            for col in self.columns.values_mut() {
-                col.swap_remove(index);
+            self.tombstone(table_row);
+            return;
+        }
+
+        for col in self.columns.iter_mut() {
+            col.remove(index, mode);
+        }
+        match mode {
+            RemovalMode::Swap => self.entities.swap_remove(index),
+            RemovalMode::Shift => self.entities.remove(index),
+            RemovalMode::Tombstone => unreachable!("tombstoned rows are never removed"),
+        };
+    }
+
+    /// Moves the entity at `row` into `new_table`, dropping every component `new_table` has no
+    /// column for. Columns of `new_table` that don't exist in this table are left uninitialized
+    /// for the new row and need to be initialized by the caller.
+    ///
+    /// Returns the row in `new_table`. The row is removed from this table according to `mode`, or
+    /// tombstoned, see [`Table::tombstones`].
+    ///
+    /// If `new_table` lacks the `taken` component, it is passed to its callback, which takes
+    /// ownership of it, instead of being dropped.
+    ///
+    /// Safety: `row` must be a valid row of this table.
+    pub(crate) unsafe fn move_to_unchecked(
+        &mut self,
+        row: TableRow,
+        new_table: &mut Table,
+        mode: RemovalMode,
+        taken: Option<TakenComponent<'_>>,
+    ) -> TableRow {
+        let index = row.index();
+        let entity = self.entities[index];
+        let new_row = new_table.allocate(entity);
+        let tombstone = self.tombstones(mode);
+        let (taken, mut take) = taken.unzip();
+
+        for (id, col) in self.component_ids.iter().zip(self.columns.iter_mut()) {
+            if let Some(new_col) = new_table.get_column_mut(*id) {
+                let ticks = col.ticks.get(index);
+                new_col.initialize_unchecked(
+                    new_row.index(),
+                    col.get_unchecked_mut(index).promote(),
+                    ticks,
+                );
+                if !tombstone {
+                    col.remove_and_forget(index, mode);
+                }
+            } else if taken == Some(*id) {
+                if let Some(take) = &mut take {
+                    take(col.get_unchecked_mut(index).promote());
+                }
+                if !tombstone {
+                    col.remove_and_forget(index, mode);
+                }
+            } else if tombstone {
+                col.drop_in_place(index);
+            } else {
+                col.remove(index, mode);
             }
         }
-        self.entities.swap_remove(index);
+
+        if tombstone {
+            self.tombstone(row);
+            return new_row;
+        }
+
+        match mode {
+            RemovalMode::Swap => self.entities.swap_remove(index),
+            RemovalMode::Shift => self.entities.remove(index),
+            RemovalMode::Tombstone => unreachable!("tombstoned rows are never removed"),
+        };
+
+        new_row
+    }
+
+    /// Moves the entities at `rows` into `new_table` like [`Table::move_to_unchecked`], but column
+    /// by column, and returns their rows in `new_table` in the same order.
+    ///
+    /// Safety: `rows` must be distinct, valid rows of this table.
+    pub(crate) unsafe fn move_rows_to_unchecked(
+        &mut self,
+        rows: &[TableRow],
+        new_table: &mut Table,
+        mode: RemovalMode,
+    ) -> Vec<TableRow> {
+        let tombstone = self.tombstones(mode);
+        new_table.reserve(rows.len());
+        let new_rows = rows
+            .iter()
+            .map(|row| new_table.allocate(self.entities[row.index()]))
+            .collect::<Vec<_>>();
+
+        for (id, col) in self.component_ids.iter().zip(self.columns.iter_mut()) {
+            match new_table.get_column_mut(*id) {
+                Some(new_col) => {
+                    for (row, new_row) in rows.iter().zip(&new_rows) {
+                        let ticks = col.ticks.get(row.index());
+                        new_col.initialize_unchecked(
+                            new_row.index(),
+                            col.get_unchecked_mut(row.index()).promote(),
+                            ticks,
+                        );
+                    }
+                }
+                None if tombstone => {
+                    for row in rows {
+                        col.drop_in_place(row.index());
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if tombstone {
+            for row in rows {
+                self.tombstone(*row);
+            }
+            return new_rows;
+        }
+
+        // Back to front, so the rows left to remove don't move
+        let mut removed = rows.to_vec();
+        removed.sort_unstable();
+        for row in removed.into_iter().rev() {
+            let index = row.index();
+            for (id, col) in self.component_ids.iter().zip(self.columns.iter_mut()) {
+                if new_table.has_column(*id) {
+                    col.remove_and_forget(index, mode);
+                } else {
+                    col.remove(index, mode);
+                }
+            }
+            match mode {
+                RemovalMode::Swap => self.entities.swap_remove(index),
+                RemovalMode::Shift => self.entities.remove(index),
+                RemovalMode::Tombstone => unreachable!("tombstoned rows are never removed"),
+            };
+        }
+
+        new_rows
+    }
+
+    pub(crate) fn has_column(&self, id: ComponentId) -> bool {
+        self.column_index(id).is_some()
+    }
+
+    /// Marks the row as free after its components have been dropped or moved out.
+    fn tombstone(&mut self, row: TableRow) {
+        self.entities[row.index()] = Entity::PLACEHOLDER;
+        self.free_rows.push(row);
+    }
+
+    /// Fills the tombstones, so the columns don't drop their components a second time. Going
+    /// from the back guarantees that only live rows are moved into them.
+    fn fill_tombstones(&mut self) {
+        self.free_rows.sort_unstable();
+        for row in self.free_rows.drain(..).rev() {
+            for col in self.columns.iter_mut() {
+                col.swap_remove_and_forget(row.index());
+            }
+        }
+    }
+
+    /// Drops the components of all rows, keeping the columns and their allocations.
+    pub(crate) fn clear(&mut self) {
+        self.fill_tombstones();
+        for col in self.columns.iter_mut() {
+            col.drop_all();
+        }
+        self.entities.clear();
     }
 }
 
 impl Drop for Table {
     fn drop(&mut self) {
+        self.fill_tombstones();
         self.entities.clear();
     }
 }
 
+/// The change ticks of a column, stored at the granularity chosen by the component.
+#[derive(Debug)]
+enum ColumnTicks {
+    PerEntity(Vec<UnsafeCell<ComponentTicks>>),
+    /// The latest ticks of any component in the column. These are atomic, as mutable queries
+    /// may mark different rows of the same column as changed from multiple threads.
+    PerTable {
+        added: AtomicU32,
+        changed: AtomicU32,
+    },
+}
+
+impl ColumnTicks {
+    fn new(change_detection: ChangeDetection) -> Self {
+        match change_detection {
+            ChangeDetection::PerEntity => Self::PerEntity(Vec::new()),
+            ChangeDetection::PerTable => Self::PerTable {
+                added: AtomicU32::new(0),
+                changed: AtomicU32::new(0),
+            },
+        }
+    }
+
+    /// Sets the ticks of a new element at `index`, which is either a tombstone or the end.
+    fn insert(&mut self, index: usize, ticks: ComponentTicks) {
+        match self {
+            Self::PerEntity(rows) if index < rows.len() => rows[index] = UnsafeCell::new(ticks),
+            Self::PerEntity(rows) => rows.push(UnsafeCell::new(ticks)),
+            Self::PerTable { added, changed } => {
+                added.fetch_max(ticks.added.get(), Ordering::Relaxed);
+                changed.fetch_max(ticks.changed.get(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn get(&self, index: usize) -> ComponentTicks {
+        match self {
+            // SAFETY: Ticks are only written through `set_changed`, which requires exclusive
+            // access to the component.
+            Self::PerEntity(rows) => unsafe { *rows[index].get() },
+            Self::PerTable { added, changed } => ComponentTicks {
+                added: Tick::new(added.load(Ordering::Relaxed)),
+                changed: Tick::new(changed.load(Ordering::Relaxed)),
+            },
+        }
+    }
+
+    /// Safety: The caller must have exclusive access to the component at `index`.
+    unsafe fn set_changed(&self, index: usize, tick: Tick) {
+        match self {
+            Self::PerEntity(rows) => (*rows[index].get()).changed = tick,
+            Self::PerTable { changed, .. } => {
+                changed.fetch_max(tick.get(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn remove(&mut self, index: usize, mode: RemovalMode) {
+        if let Self::PerEntity(rows) = self {
+            match mode {
+                RemovalMode::Swap => {
+                    rows.swap_remove(index);
+                }
+                RemovalMode::Shift => {
+                    rows.remove(index);
+                }
+                // The ticks are overwritten when the row is reused
+                RemovalMode::Tombstone => {}
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Column {
     item_layout: Layout,
     data: NonNull<u8>,
     drop: Option<unsafe fn(OwningPtr<'_>)>,
+    ticks: ColumnTicks,
     len: usize,
     capacity: usize,
 }
@@ -211,6 +804,7 @@ impl Column {
             item_layout,
             data,
             drop: component_info.drop,
+            ticks: ColumnTicks::new(component_info.change_detection),
             len: 0,
             capacity: 0,
         }
@@ -254,6 +848,14 @@ impl Column {
     }
 
     pub fn realloc(&mut self, new_capacity: usize) {
+        if let Err(err) = self.try_realloc(new_capacity) {
+            err.handle();
+        }
+    }
+
+    /// Reallocates the column to hold `new_capacity` elements. The column is left unchanged if
+    /// the allocation fails.
+    fn try_realloc(&mut self, new_capacity: usize) -> Result<(), TryReserveError> {
         if !self.is_zst() {
             let (array_layout, _) = self
                 .item_layout
@@ -263,7 +865,7 @@ impl Column {
             let (new_layout, _) = self
                 .item_layout
                 .repeat(new_capacity)
-                .expect("Array layout creation should succeed");
+                .map_err(|_| TryReserveError::CapacityOverflow)?;
 
             let data = if self.capacity() != 0 {
                 unsafe { std::alloc::realloc(self.data.as_ptr(), array_layout, new_layout.size()) }
@@ -271,9 +873,12 @@ impl Column {
                 unsafe { std::alloc::alloc(new_layout) }
             };
 
-            self.data = NonNull::new(data).unwrap_or_else(|| handle_alloc_error(new_layout));
+            self.data =
+                NonNull::new(data).ok_or(TryReserveError::AllocError { layout: new_layout })?;
         }
         self.capacity = new_capacity;
+
+        Ok(())
     }
 
     #[inline]
@@ -286,18 +891,60 @@ impl Column {
         unsafe { MutPtr::new(self.data) }
     }
 
-    pub(crate) unsafe fn initialize_unchecked(&mut self, index: usize, value: OwningPtr) {
+    /// Initializes the element at `index`, which must be a tombstone or the first uninitialized
+    /// one.
+    pub(crate) unsafe fn initialize_unchecked(
+        &mut self,
+        index: usize,
+        value: OwningPtr,
+        ticks: ComponentTicks,
+    ) {
+        debug_assert!(index <= self.len, "columns must be initialized in order");
         let size = self.item_layout.size();
         let dst = self.data.byte_add(index * size);
         //TODO: is this always nonoverlapping?
         std::ptr::copy_nonoverlapping(value.as_ptr(), dst.as_ptr(), size);
-        self.len += 1;
+        self.ticks.insert(index, ticks);
+        if index == self.len {
+            self.len += 1;
+        }
+    }
+
+    /// Drops the element at `index` without removing it, which leaves a tombstone.
+    fn drop_in_place(&mut self, index: usize) {
+        if let Some(drop) = self.drop {
+            unsafe { drop(self.get_unchecked_mut(index).promote()) };
+        }
+    }
+
+    /// Overwrites the initialized element at `index`, dropping the previous value, and marks it as
+    /// changed at `change_tick`.
+    ///
+    /// Safety: `index` must be in bounds and `value` must point to a value of this column's type.
+    pub(crate) unsafe fn replace_unchecked(
+        &mut self,
+        index: usize,
+        value: OwningPtr,
+        change_tick: Tick,
+    ) {
+        let size = self.item_layout.size();
+        if let Some(drop) = self.drop {
+            drop(self.get_unchecked_mut(index).promote());
+        }
+        let dst = self.data.byte_add(index * size);
+        std::ptr::copy_nonoverlapping(value.as_ptr(), dst.as_ptr(), size);
+        self.ticks.set_changed(index, change_tick);
     }
 
     unsafe fn get_unchecked(&self, index: usize) -> Ptr<'_> {
         self.get_ptr().byte_add(self.item_layout.size() * index)
     }
 
+    /// Safety: The caller must ensure that no other reference to the element is alive.
+    unsafe fn get_unchecked_mut_ptr(&self, index: usize) -> MutPtr<'_> {
+        MutPtr::new(self.data).byte_add(self.item_layout.size() * index)
+    }
+
     unsafe fn get_unchecked_mut(&mut self, index: usize) -> MutPtr<'_> {
         let size = self.item_layout.size();
         self.get_ptr_mut().byte_add(size * index)
@@ -319,8 +966,19 @@ impl Column {
         }
     }
 
+    /// Drops all elements, keeping the allocation.
+    fn drop_all(&mut self) {
+        // SAFETY: The first `len` elements are initialized and forgotten right after.
+        unsafe { self.clear() };
+        self.len = 0;
+        if let ColumnTicks::PerEntity(rows) = &mut self.ticks {
+            rows.clear();
+        }
+    }
+
     fn drop_last(&mut self) {
         let size = self.item_layout.size();
+        self.len -= 1;
         let len = self.len;
         if let Some(drop) = self.drop {
             self.drop = None;
@@ -333,23 +991,81 @@ impl Column {
 
             self.drop = Some(drop);
         }
-        self.len -= 1;
     }
 
+    /// Removes the element at `index` by moving the last element into its place and drops it.
     fn swap_remove(&mut self, index: usize) {
-        debug_assert_ne!(index, self.len - 1);
+        if index != self.len - 1 {
+            unsafe {
+                core::ptr::swap_nonoverlapping::<u8>(
+                    self.get_unchecked_mut(index).as_ptr(),
+                    self.get_unchecked_mut(self.len - 1).as_ptr(),
+                    self.item_layout.size(),
+                )
+            };
+        }
+        self.ticks.remove(index, RemovalMode::Swap);
+        self.drop_last();
+    }
+
+    /// Removes the element at `index` by moving the last element into its place without dropping
+    /// it, e.g. because it has already been moved out.
+    fn swap_remove_and_forget(&mut self, index: usize) {
+        if index != self.len - 1 {
+            unsafe {
+                core::ptr::copy_nonoverlapping::<u8>(
+                    self.get_unchecked_mut(self.len - 1).as_ptr(),
+                    self.get_unchecked_mut(index).as_ptr(),
+                    self.item_layout.size(),
+                )
+            };
+        }
+        self.ticks.remove(index, RemovalMode::Swap);
+        self.len -= 1;
+    }
+
+    /// Removes the element at `index` by shifting all following elements down without dropping
+    /// it, e.g. because it has already been moved out.
+    fn shift_remove_and_forget(&mut self, index: usize) {
+        let size = self.item_layout.size();
         unsafe {
-            core::ptr::swap_nonoverlapping::<u8>(
+            core::ptr::copy::<u8>(
+                self.get_unchecked_mut(index + 1).as_ptr(),
                 self.get_unchecked_mut(index).as_ptr(),
-                self.get_unchecked_mut(self.len - 1).as_ptr(),
-                self.item_layout.size(),
+                (self.len - index - 1) * size,
             )
         };
-        // self.drop_last();
+        self.ticks.remove(index, RemovalMode::Shift);
         self.len -= 1;
     }
+
+    /// Removes the element at `index` according to `mode` and drops it. Tombstoning leaves a
+    /// hole, which the table has to keep track of.
+    fn remove(&mut self, index: usize, mode: RemovalMode) {
+        match mode {
+            RemovalMode::Swap => self.swap_remove(index),
+            RemovalMode::Shift => {
+                self.drop_in_place(index);
+                self.shift_remove_and_forget(index);
+            }
+            RemovalMode::Tombstone => self.drop_in_place(index),
+        }
+    }
+
+    /// Removes the element at `index` according to `mode` without dropping it.
+    fn remove_and_forget(&mut self, index: usize, mode: RemovalMode) {
+        match mode {
+            RemovalMode::Swap => self.swap_remove_and_forget(index),
+            RemovalMode::Shift => self.shift_remove_and_forget(index),
+            RemovalMode::Tombstone => {}
+        }
+    }
 }
 
+// SAFETY: Columns only ever store components, which are `Send + Sync`.
+unsafe impl Send for Column {}
+unsafe impl Sync for Column {}
+
 impl Drop for Column {
     fn drop(&mut self) {
         unsafe {
@@ -369,14 +1085,233 @@ impl Drop for Column {
     }
 }
 
+/// The components of a type that is stored in a sparse set instead of the tables, see
+/// [`StorageType::SparseSet`](crate::component::StorageType::SparseSet).
+///
+/// The components are packed densely, so iterating them doesn't skip holes, and looked up through
+/// the index of their entity.
+#[derive(Debug)]
+pub(crate) struct SparseSet {
+    /// The components, in the order of `entities`.
+    dense: Column,
+    /// The entity of every component.
+    entities: Vec<Entity>,
+    /// The index into `dense` for every entity index, `None` if no entity with the index has the
+    /// component.
+    sparse: Vec<Option<usize>>,
+}
+
+impl SparseSet {
+    fn new(component_info: &ComponentInfo) -> Self {
+        Self {
+            dense: Column::new(component_info),
+            entities: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// The entities that have the component, in the order the components are stored.
+    pub(crate) fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let index = (*self.sparse.get(entity.index() as usize)?)?;
+        (self.entities[index] == entity).then_some(index)
+    }
+
+    pub(crate) fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    pub(crate) fn get(&self, entity: Entity) -> Option<Ptr<'_>> {
+        let index = self.dense_index(entity)?;
+        // SAFETY: Every index in `sparse` points to an initialized component.
+        Some(unsafe { self.dense.get_unchecked(index) })
+    }
+
+    /// Retrieves a mutable pointer to the component through a shared reference to the set and
+    /// marks it as changed at `change_tick`.
+    ///
+    /// Safety: The caller must ensure that no other reference to this component is alive for
+    /// the lifetime of the returned pointer.
+    pub(crate) unsafe fn get_mut_ptr(
+        &self,
+        entity: Entity,
+        change_tick: Tick,
+    ) -> Option<MutPtr<'_>> {
+        let index = self.dense_index(entity)?;
+        self.dense.ticks.set_changed(index, change_tick);
+        Some(self.dense.get_unchecked_mut_ptr(index))
+    }
+
+    /// Retrieves a mutable pointer to the component and marks it as changed at `change_tick`.
+    pub(crate) fn get_mut(&mut self, entity: Entity, change_tick: Tick) -> Option<MutPtr<'_>> {
+        let index = self.dense_index(entity)?;
+        // SAFETY: The set is borrowed mutably and the index points to an initialized component.
+        unsafe {
+            self.dense.ticks.set_changed(index, change_tick);
+            Some(self.dense.get_unchecked_mut(index))
+        }
+    }
+
+    pub(crate) fn get_ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.dense_index(entity)
+            .map(|index| self.dense.ticks.get(index))
+    }
+
+    /// Adds the component to the entity, or overwrites and drops the previous one and marks it as
+    /// changed if the entity already has one.
+    ///
+    /// Safety: `value` must point to a value of this set's component type.
+    pub(crate) unsafe fn insert(&mut self, entity: Entity, value: OwningPtr<'_>, tick: Tick) {
+        if let Some(index) = self.dense_index(entity) {
+            self.dense.replace_unchecked(index, value, tick);
+            return;
+        }
+
+        let index = self.entities.len();
+        if self.dense.capacity() == index {
+            self.dense.realloc((index * 2).max(4));
+        }
+        self.dense
+            .initialize_unchecked(index, value, ComponentTicks::new(tick));
+        self.entities.push(entity);
+        if self.sparse.len() <= entity.index() as usize {
+            self.sparse.resize(entity.index() as usize + 1, None);
+        }
+        self.sparse[entity.index() as usize] = Some(index);
+    }
+
+    /// Removes the component of the entity and drops it, or passes it to `taken` instead. Returns
+    /// `false` if the entity doesn't have the component.
+    pub(crate) fn remove(
+        &mut self,
+        entity: Entity,
+        taken: Option<&mut dyn FnMut(OwningPtr<'_>)>,
+    ) -> bool {
+        let Some(index) = self.dense_index(entity) else {
+            return false;
+        };
+
+        match taken {
+            Some(taken) => {
+                // SAFETY: The component is moved out and forgotten by the column right after.
+                unsafe { taken(self.dense.get_unchecked_mut(index).promote()) };
+                self.dense.swap_remove_and_forget(index);
+            }
+            None => self.dense.swap_remove(index),
+        }
+        self.entities.swap_remove(index);
+        self.sparse[entity.index() as usize] = None;
+        if let Some(moved) = self.entities.get(index) {
+            self.sparse[moved.index() as usize] = Some(index);
+        }
+        true
+    }
+
+    /// Drops the components of all entities.
+    fn clear(&mut self) {
+        self.dense.drop_all();
+        self.entities.clear();
+        self.sparse.clear();
+    }
+
+    /// Replaces the entities of all components, e.g. after they were given new ids.
+    pub(crate) fn map_entities(&mut self, mapper: &mut impl FnMut(Entity) -> Entity) {
+        self.sparse.clear();
+        for (index, entity) in self.entities.iter_mut().enumerate() {
+            *entity = mapper(*entity);
+            if self.sparse.len() <= entity.index() as usize {
+                self.sparse.resize(entity.index() as usize + 1, None);
+            }
+            self.sparse[entity.index() as usize] = Some(index);
+        }
+    }
+}
+
+/// The sparse sets of all components stored in them, see [`SparseSet`].
+#[derive(Debug, Default)]
+pub(crate) struct SparseSets {
+    /// Indexed by [`ComponentId`], only components that were added to an entity have a set.
+    sets: Vec<Option<SparseSet>>,
+}
+
+impl SparseSets {
+    pub(crate) fn get(&self, id: ComponentId) -> Option<&SparseSet> {
+        self.sets.get(id.index())?.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, id: ComponentId) -> Option<&mut SparseSet> {
+        self.sets.get_mut(id.index())?.as_mut()
+    }
+
+    /// Returns the set of the component, which is created if it doesn't exist yet.
+    pub(crate) fn get_or_insert(&mut self, component_info: &ComponentInfo) -> &mut SparseSet {
+        let index = component_info.id().index();
+        if self.sets.len() <= index {
+            self.sets.resize_with(index + 1, || None);
+        }
+        self.sets[index].get_or_insert_with(|| SparseSet::new(component_info))
+    }
+
+    /// Creates the sets of the components that don't have one yet.
+    pub(crate) fn init(
+        &mut self,
+        ids: impl IntoIterator<Item = ComponentId>,
+        components: &Components,
+    ) {
+        for id in ids {
+            let info = components
+                .get_info(&id)
+                .expect("sparse set components are registered");
+            self.get_or_insert(info);
+        }
+    }
+
+    /// The components of all sets the entity has a component in.
+    pub(crate) fn components_of(&self, entity: Entity) -> impl Iterator<Item = ComponentId> + '_ {
+        self.sets
+            .iter()
+            .enumerate()
+            .filter(move |(_, set)| set.as_ref().is_some_and(|set| set.contains(entity)))
+            .map(|(index, _)| ComponentId::new(index))
+    }
+
+    /// Removes and drops all components of the entity, e.g. when it is despawned.
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        for set in self.sets.iter_mut().flatten() {
+            set.remove(entity, None);
+        }
+    }
+
+    pub(crate) fn map_entities(&mut self, mapper: &mut impl FnMut(Entity) -> Entity) {
+        for set in self.sets.iter_mut().flatten() {
+            set.map_entities(mapper);
+        }
+    }
+
+    /// Drops the components of all sets, keeping the sets themselves.
+    pub(crate) fn clear(&mut self) {
+        for set in self.sets.iter_mut().flatten() {
+            set.clear();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        component::{Component, Components},
+        component::{ChangeDetection, Component, ComponentTicks, Components, Tick},
+        entity::Entity,
         ptr::OwningPtr,
     };
 
-    use super::{Column, Tables};
+    use super::{Column, RemovalMode, SparseSets, Tables, TryReserveError};
 
     struct MyComponent {
         _position: (f32, f32, f32),
@@ -399,7 +1334,9 @@ mod tests {
             _position: (3.0, 2.0, 1.0),
         };
 
-        OwningPtr::make(c1, |ptr| unsafe { column.initialize_unchecked(0, ptr) });
+        OwningPtr::make(c1, |ptr| unsafe {
+            column.initialize_unchecked(0, ptr, ComponentTicks::default())
+        });
 
         let mut ptr: *const f32 = column.data.as_ptr().cast();
         for i in 1..4 {
@@ -408,7 +1345,9 @@ mod tests {
                 ptr = ptr.add(1);
             }
         }
-        OwningPtr::make(c2, |ptr| unsafe { column.initialize_unchecked(1, ptr) });
+        OwningPtr::make(c2, |ptr| unsafe {
+            column.initialize_unchecked(1, ptr, ComponentTicks::default())
+        });
         for i in (1..4).rev() {
             unsafe {
                 assert_eq!(*ptr, i as f32);
@@ -417,6 +1356,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn column_ticks() {
+        struct Coarse(u32);
+        impl Component for Coarse {
+            const CHANGE_DETECTION: ChangeDetection = ChangeDetection::PerTable;
+        }
+
+        let mut components = Components::new();
+        let exact = components.register_component::<u32>();
+        let coarse = components.register_component::<Coarse>();
+
+        let mut exact = Column::with_capacity(components.get_info(&exact).unwrap(), 3);
+        let mut coarse = Column::with_capacity(components.get_info(&coarse).unwrap(), 3);
+        for i in 0..3 {
+            let ticks = ComponentTicks::new(Tick::new(i));
+            OwningPtr::make(i, |ptr| unsafe {
+                exact.initialize_unchecked(i as usize, ptr, ticks)
+            });
+            OwningPtr::make(Coarse(i), |ptr| unsafe {
+                coarse.initialize_unchecked(i as usize, ptr, ticks)
+            });
+        }
+
+        unsafe {
+            exact.ticks.set_changed(0, Tick::new(5));
+            coarse.ticks.set_changed(0, Tick::new(5));
+        }
+        assert_eq!(exact.ticks.get(0).added, Tick::new(0));
+        assert_eq!(exact.ticks.get(0).changed, Tick::new(5));
+        assert_eq!(exact.ticks.get(1).changed, Tick::new(1));
+        // Coarse ticks are shared by all rows
+        assert_eq!(coarse.ticks.get(1).added, Tick::new(2));
+        assert_eq!(coarse.ticks.get(1).changed, Tick::new(5));
+
+        exact.swap_remove(0);
+        assert_eq!(exact.ticks.get(0), ComponentTicks::new(Tick::new(2)));
+    }
+
     #[test]
     fn tables() {
         let mut tables = Tables::default();
@@ -447,7 +1424,7 @@ mod tests {
 
         let mut column = Column::with_capacity(component_info, 1);
         OwningPtr::make(my_comp, |ptr| unsafe {
-            column.initialize_unchecked(0, ptr)
+            column.initialize_unchecked(0, ptr, ComponentTicks::default())
         });
 
         unsafe {
@@ -468,7 +1445,7 @@ mod tests {
 
         for i in 0..COMP_COUNT {
             OwningPtr::make(i as u32, |ptr| unsafe {
-                column.initialize_unchecked(i, ptr)
+                column.initialize_unchecked(i, ptr, ComponentTicks::default())
             });
         }
         assert_eq!(column.len, 5);
@@ -496,4 +1473,101 @@ mod tests {
             assert_eq!(ptr.deref::<u32>(), &1);
         }
     }
+
+    #[test]
+    fn shift_remove() {
+        let mut components = Components::new();
+        let component_id = components.register_component::<u32>();
+        let component_info = components.get_info(&component_id).unwrap();
+
+        let mut column = Column::with_capacity(component_info, 5);
+        for i in 0..5 {
+            OwningPtr::make(i as u32, |ptr| unsafe {
+                column.initialize_unchecked(i, ptr, ComponentTicks::new(Tick::new(i as u32)))
+            });
+        }
+
+        column.remove(1, RemovalMode::Shift);
+        column.remove(3, RemovalMode::Shift);
+        assert_eq!(column.len, 3);
+        for (index, value) in [0, 2, 3].into_iter().enumerate() {
+            unsafe {
+                assert_eq!(column.get_unchecked(index).deref::<u32>(), &value);
+            }
+            assert_eq!(column.ticks.get(index).added, Tick::new(value));
+        }
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut components = Components::new();
+        let component_id = components.register_component::<u32>();
+        let component_info = components.get_info(&component_id).unwrap();
+
+        let mut column = Column::with_capacity(component_info, 2);
+        assert_eq!(
+            column.try_realloc(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(column.capacity(), 2);
+
+        let mut tables = Tables::default();
+        let table_id = tables.get_id_or_insert(&[component_id], &components);
+        let table = tables.get_mut_unchecked(table_id);
+        assert!(table.try_reserve(usize::MAX).is_err());
+        assert_eq!(table.capacity(), 0);
+
+        table.try_reserve(3).unwrap();
+        assert_eq!(table.capacity(), 3);
+        assert!(table.columns.iter().all(|column| column.capacity() >= 3));
+    }
+
+    #[test]
+    fn sparse_set() {
+        #[derive(Debug, PartialEq)]
+        struct Marker(u32);
+        impl Component for Marker {}
+
+        let mut components = Components::new();
+        let id = components.register_component::<Marker>();
+        let mut sets = SparseSets::default();
+        sets.init([id], &components);
+        let set = sets.get_mut(id).unwrap();
+
+        let [a, b, c] = [Entity::from(1, 7), Entity::from(1, 2), Entity::from(1, 4)];
+        for (entity, value) in [(a, 0), (b, 1), (c, 2)] {
+            OwningPtr::make(Marker(value), |ptr| unsafe {
+                set.insert(entity, ptr, Tick::new(1))
+            });
+        }
+        OwningPtr::make(Marker(10), |ptr| unsafe {
+            set.insert(b, ptr, Tick::new(2))
+        });
+        assert_eq!(set.len(), 3);
+        assert_eq!(
+            unsafe { set.get(b).unwrap().deref::<Marker>() },
+            &Marker(10)
+        );
+        assert_eq!(set.get_ticks(b).unwrap().changed, Tick::new(2));
+        assert!(!set.contains(Entity::from(2, 7)));
+
+        let mut taken = None;
+        assert!(set.remove(
+            a,
+            Some(&mut |ptr| taken = Some(unsafe { ptr.read::<Marker>() }))
+        ));
+        assert_eq!(taken, Some(Marker(0)));
+        assert!(!set.remove(a, None));
+        assert_eq!(set.entities(), [c, b]);
+        assert_eq!(unsafe { set.get(c).unwrap().deref::<Marker>() }, &Marker(2));
+
+        sets.map_entities(&mut |entity| Entity::from(1, entity.index() + 10));
+        assert_eq!(
+            sets.components_of(Entity::from(1, 14)).collect::<Vec<_>>(),
+            [id]
+        );
+        assert_eq!(sets.components_of(c).count(), 0);
+        sets.remove_entity(Entity::from(1, 12));
+        assert_eq!(sets.get(id).unwrap().entities(), [Entity::from(1, 14)]);
+    }
 }
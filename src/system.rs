@@ -0,0 +1,809 @@
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    command::{CommandQueue, Commands},
+    component::{ComponentId, Tick},
+    query::{Access, Query, QueryFilter, QueryState, Queryable},
+    resource::Resource,
+    schedule::{SetLabel, SystemTypeSet},
+    World,
+};
+
+pub use quartz_macros::SystemParam;
+
+/// Logic that runs against a [`World`], usually a function whose parameters implement
+/// [`SystemParam`], see [`IntoSystem`].
+///
+/// A system is bound to the world it was initialized for, running it on another world panics.
+pub trait System: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    /// Prepares the system to run on the world, called before its first run.
+    fn initialize(&mut self, world: &mut World);
+
+    fn run(&mut self, world: &mut World);
+
+    /// The set only this system is in, which lets other systems be ordered relative to it, see
+    /// [`IntoSystemSet`](crate::schedule::IntoSystemSet).
+    fn type_set(&self) -> Option<SetLabel> {
+        None
+    }
+}
+
+/// Conversion into a [`System`], implemented for systems, for functions of up to 16
+/// [`SystemParam`]s and for functions taking `&mut World`, see [`ExclusiveFunctionSystem`].
+///
+/// ```
+/// # use quartz::{
+/// #     component::Component,
+/// #     query::Query,
+/// #     resource::Resource,
+/// #     system::{IntoSystem, Res, System},
+/// #     World,
+/// # };
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Resource)]
+/// struct Wind(f32);
+///
+/// fn blow(positions: Query<&mut Position>, wind: Res<Wind>) {
+///     for position in positions {
+///         position.0 += wind.0;
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(Position(0.0));
+/// world.insert_resource(Wind(2.0));
+///
+/// let mut system = blow.into_system();
+/// system.run(&mut world);
+/// assert_eq!(world.get::<Position>(entity).unwrap().0, 2.0);
+/// ```
+pub trait IntoSystem<Marker> {
+    type System: System;
+
+    fn into_system(self) -> Self::System;
+}
+
+impl<S: System> IntoSystem<()> for S {
+    type System = S;
+
+    fn into_system(self) -> Self::System {
+        self
+    }
+}
+
+/// The components and resources the parameters of a system access, used to reject systems whose
+/// parameters alias, e.g. `(Query<&mut Position>, Query<&Position>)`.
+///
+/// Query filters are not taken into account, two queries accessing the same component conflict
+/// even if their filters make them disjoint.
+#[derive(Debug, Default)]
+pub struct SystemAccess {
+    component_reads: Vec<ComponentId>,
+    component_writes: Vec<ComponentId>,
+    resource_reads: Vec<TypeId>,
+    resource_writes: Vec<TypeId>,
+}
+
+impl SystemAccess {
+    /// Registers the component access of a query parameter.
+    ///
+    /// Panics: If the query conflicts with a previous parameter.
+    pub fn add_query(&mut self, access: &Access, name: &str) {
+        for id in access.reads() {
+            assert!(
+                !self.component_writes.contains(id),
+                "{name} conflicts with a previous parameter of this system"
+            );
+        }
+        for id in access.writes() {
+            assert!(
+                !self.component_reads.contains(id) && !self.component_writes.contains(id),
+                "{name} conflicts with a previous parameter of this system"
+            );
+        }
+        self.component_reads.extend(access.reads());
+        self.component_writes.extend(access.writes());
+    }
+
+    /// Panics: If a previous parameter writes the resource.
+    pub fn add_resource_read<R: 'static>(&mut self) {
+        let id = TypeId::of::<R>();
+        assert!(
+            !self.resource_writes.contains(&id),
+            "Res<{}> conflicts with a previous parameter of this system",
+            type_name::<R>()
+        );
+        self.resource_reads.push(id);
+    }
+
+    /// Panics: If a previous parameter reads or writes the resource.
+    pub fn add_resource_write<R: 'static>(&mut self) {
+        let id = TypeId::of::<R>();
+        assert!(
+            !self.resource_reads.contains(&id) && !self.resource_writes.contains(&id),
+            "ResMut<{}> conflicts with a previous parameter of this system",
+            type_name::<R>()
+        );
+        self.resource_writes.push(id);
+    }
+}
+
+/// A parameter of a function system, fetched from the world every time the system runs.
+///
+/// Parameters that are often used together can be grouped into a struct with
+/// `#[derive(SystemParam)]`, whose fields are fetched like separate parameters:
+///
+/// ```
+/// # use quartz::{
+/// #     component::Component,
+/// #     query::Query,
+/// #     resource::Resource,
+/// #     system::{IntoSystem, Res, ResMut, System, SystemParam},
+/// #     World,
+/// # };
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Resource)]
+/// struct Gravity(f32);
+/// #[derive(Default, Resource)]
+/// struct Steps(u32);
+///
+/// #[derive(SystemParam)]
+/// struct Physics<'w> {
+///     bodies: Query<'w, &'static mut Position>,
+///     gravity: Res<'w, Gravity>,
+///     steps: ResMut<'w, Steps>,
+/// }
+///
+/// fn fall(mut physics: Physics) {
+///     for position in physics.bodies {
+///         position.0 -= physics.gravity.0;
+///     }
+///     physics.steps.0 += 1;
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(Position(10.0));
+/// world.insert_resource(Gravity(1.0));
+/// world.insert_resource(Steps::default());
+///
+/// fall.into_system().run(&mut world);
+/// assert_eq!(world.get::<Position>(entity).unwrap().0, 9.0);
+/// ```
+///
+/// # Safety
+/// [`SystemParam::init_state`] has to register every access [`SystemParam::get_param`] makes in
+/// the [`SystemAccess`].
+pub unsafe trait SystemParam {
+    type State: Send + Sync + 'static;
+    type Item<'w>: SystemParam<State = Self::State>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State;
+
+    /// Fetches the parameter, `since` is the tick from which on changes are reported, which is
+    /// the tick after the previous run of the system.
+    ///
+    /// # Safety
+    /// The world may only be accessed as registered in [`SystemParam::init_state`], and nothing
+    /// else may access the same components or resources while the item is alive.
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w>;
+
+    /// Applies the deferred changes of the parameter after the system ran.
+    fn apply(state: &mut Self::State, world: &mut World) {}
+}
+
+/// The type of a [`SystemParam`] fetched from the world.
+pub type SystemParamItem<'w, P> = <P as SystemParam>::Item<'w>;
+
+// SAFETY: The access of the query is registered.
+unsafe impl<T, F> SystemParam for Query<'_, T, F>
+where
+    T: Queryable + 'static,
+    F: QueryFilter + 'static,
+    T::State: Send + Sync,
+    F::State: Send + Sync,
+{
+    type State = QueryState<T, F>;
+    type Item<'w> = Query<'w, T, F>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        // Systems may run before the components they query are spawned
+        T::register_components(&mut world.components);
+        F::register_components(&mut world.components);
+        let state = QueryState::new(world);
+        access.add_query(&state.access(world), type_name::<Self>());
+        state
+    }
+
+    /// Reports the changes made after the previous run of the system to [`Changed`] and [`Ref`].
+    ///
+    /// [`Changed`]: crate::query::Changed
+    /// [`Ref`]: crate::query::Ref
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        state.query_since(world, since)
+    }
+}
+
+/// Shared access to the resource `R` in a system.
+///
+/// Panics when the system runs if the resource doesn't exist.
+pub struct Res<'w, R: Resource> {
+    value: &'w R,
+}
+
+impl<R: Resource> Deref for Res<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+// SAFETY: The read of the resource is registered.
+unsafe impl<R: Resource> SystemParam for Res<'_, R> {
+    type State = ();
+    type Item<'w> = Res<'w, R>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        access.add_resource_read::<R>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        Res {
+            value: world
+                .resources
+                .get()
+                .unwrap_or_else(|| panic!("Resource {} does not exist", type_name::<R>())),
+        }
+    }
+}
+
+/// Exclusive access to the resource `R` in a system.
+///
+/// Panics when the system runs if the resource doesn't exist.
+pub struct ResMut<'w, R: Resource> {
+    value: &'w mut R,
+}
+
+impl<R: Resource> Deref for ResMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<R: Resource> DerefMut for ResMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+// SAFETY: The write of the resource is registered.
+unsafe impl<R: Resource> SystemParam for ResMut<'_, R> {
+    type State = ();
+    type Item<'w> = ResMut<'w, R>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        access.add_resource_write::<R>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        ResMut {
+            // SAFETY: The access check guarantees that no other parameter of the system accesses
+            // the resource.
+            value: world
+                .resources
+                .get_mut_unchecked()
+                .unwrap_or_else(|| panic!("Resource {} does not exist", type_name::<R>())),
+        }
+    }
+}
+
+// SAFETY: Commands only reserve entities, which doesn't conflict with other accesses.
+unsafe impl SystemParam for Commands<'_> {
+    type State = CommandQueue;
+    type Item<'w> = Commands<'w>;
+
+    fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+        CommandQueue::default()
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: &'w World,
+        since: Tick,
+    ) -> Self::Item<'w> {
+        Commands::new(state, world)
+    }
+
+    /// Applies the recorded commands in the order they were recorded.
+    fn apply(state: &mut Self::State, world: &mut World) {
+        state.apply(world);
+    }
+}
+
+/// Implements [`SystemParam`] for a tuple of the given parameters, binding their states to the
+/// given names.
+macro_rules! impl_system_param_tuple {
+    ($(($P:ident, $state:ident)),*) => {
+        // SAFETY: The access of every parameter is registered.
+        #[allow(clippy::unused_unit)]
+        unsafe impl<$($P: SystemParam),*> SystemParam for ($($P,)*) {
+            type State = ($($P::State,)*);
+            type Item<'w> = ($($P::Item<'w>,)*);
+
+            fn init_state(world: &mut World, access: &mut SystemAccess) -> Self::State {
+                ($($P::init_state(world, access),)*)
+            }
+
+            unsafe fn get_param<'w>(
+                state: &'w mut Self::State,
+                world: &'w World,
+                since: Tick,
+            ) -> Self::Item<'w> {
+                let ($($state,)*) = state;
+                ($($P::get_param($state, world, since),)*)
+            }
+
+            fn apply(state: &mut Self::State, world: &mut World) {
+                let ($($state,)*) = state;
+                $($P::apply($state, world);)*
+            }
+        }
+    };
+}
+
+/// A function that can be turned into a system, implemented for functions of up to 16
+/// [`SystemParam`]s.
+pub trait SystemParamFunction<Marker>: Send + Sync + 'static {
+    type Param: SystemParam;
+
+    fn run(&mut self, param: SystemParamItem<'_, Self::Param>);
+}
+
+/// Implements [`SystemParamFunction`] for functions taking the given parameters.
+macro_rules! impl_system_param_function {
+    ($(($P:ident, $param:ident)),*) => {
+        impl<Func, $($P: SystemParam),*> SystemParamFunction<fn($($P,)*)> for Func
+        where
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut($($P),*) + FnMut($(SystemParamItem<'_, $P>),*),
+        {
+            type Param = ($($P,)*);
+
+            fn run(&mut self, param: SystemParamItem<'_, Self::Param>) {
+                // Calling through a generic function makes the compiler pick the `FnMut`
+                // implementation taking the fetched items.
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($P),*>(mut func: impl FnMut($($P),*), $($param: $P),*) {
+                    func($($param),*)
+                }
+
+                let ($($param,)*) = param;
+                call(self, $($param),*)
+            }
+        }
+    };
+}
+
+/// Calls the given macro for every suffix of the given parameters, including the empty one.
+macro_rules! impl_for_params {
+    ($macro:ident) => {
+        $macro!();
+    };
+    ($macro:ident, $head:tt $(, $tail:tt)*) => {
+        $macro!($head $(, $tail)*);
+        impl_for_params!($macro $(, $tail)*);
+    };
+}
+
+impl_for_params!(
+    impl_system_param_tuple,
+    (P0, p0),
+    (P1, p1),
+    (P2, p2),
+    (P3, p3),
+    (P4, p4),
+    (P5, p5),
+    (P6, p6),
+    (P7, p7),
+    (P8, p8),
+    (P9, p9),
+    (P10, p10),
+    (P11, p11),
+    (P12, p12),
+    (P13, p13),
+    (P14, p14),
+    (P15, p15)
+);
+
+impl_for_params!(
+    impl_system_param_function,
+    (P0, p0),
+    (P1, p1),
+    (P2, p2),
+    (P3, p3),
+    (P4, p4),
+    (P5, p5),
+    (P6, p6),
+    (P7, p7),
+    (P8, p8),
+    (P9, p9),
+    (P10, p10),
+    (P11, p11),
+    (P12, p12),
+    (P13, p13),
+    (P14, p14),
+    (P15, p15)
+);
+
+/// A system running a function, created by [`IntoSystem`].
+pub struct FunctionSystem<Marker, F: SystemParamFunction<Marker>> {
+    func: F,
+    state: Option<<F::Param as SystemParam>::State>,
+    /// The world tick of the previous run.
+    last_run: Option<Tick>,
+    marker: PhantomData<fn() -> Marker>,
+}
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> System for FunctionSystem<Marker, F> {
+    fn name(&self) -> &str {
+        type_name::<F>()
+    }
+
+    fn type_set(&self) -> Option<SetLabel> {
+        Some(SetLabel::new(SystemTypeSet::<F>::new()))
+    }
+
+    fn initialize(&mut self, world: &mut World) {
+        if self.state.is_none() {
+            let mut access = SystemAccess::default();
+            self.state = Some(F::Param::init_state(world, &mut access));
+        }
+    }
+
+    /// Runs the system at the current world tick and advances the tick afterwards, so the next
+    /// run reports the changes made after this one, but not the changes made by it.
+    fn run(&mut self, world: &mut World) {
+        self.initialize(world);
+        let state = self
+            .state
+            .as_mut()
+            .expect("initialized systems have a state");
+        let since = self.last_run.map_or(Tick::default(), |tick| {
+            Tick::new(tick.get().wrapping_add(1))
+        });
+
+        // SAFETY: The world is borrowed mutably and `init_state` rejected aliasing parameters.
+        let param = unsafe { F::Param::get_param(state, world, since) };
+        self.func.run(param);
+        F::Param::apply(state, world);
+        self.last_run = Some(world.tick());
+        world.increment_change_tick();
+    }
+}
+
+/// Marks the [`IntoSystem`] implementation of functions.
+#[doc(hidden)]
+pub struct IsFunctionSystem;
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> IntoSystem<(IsFunctionSystem, Marker)> for F {
+    type System = FunctionSystem<Marker, F>;
+
+    fn into_system(self) -> Self::System {
+        FunctionSystem {
+            func: self,
+            state: None,
+            last_run: None,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A system running a function that takes the world exclusively, created by [`IntoSystem`] for
+/// functions of type `fn(&mut World)`.
+///
+/// Exclusive systems can make structural changes right away, e.g. spawning in batches or saving
+/// the world. A [`Schedule`] runs all systems one at a time and applies the deferred changes of
+/// every system, like [`Commands`], right after it ran, so an exclusive system sees the changes of
+/// all systems before it. As there are no parallel batches, it is not a sync point beyond that.
+///
+/// ```
+/// # use quartz::{
+/// #     component::Component,
+/// #     query::Query,
+/// #     schedule::{IntoSystemConfig, Schedule},
+/// #     World,
+/// # };
+/// #[derive(Component)]
+/// struct Enemy(u32);
+///
+/// fn spawn_wave(world: &mut World) {
+///     world.spawn_batch((0..10).map(|_| Enemy(0)));
+/// }
+///
+/// fn advance(enemies: Query<&mut Enemy>) {
+///     for enemy in enemies {
+///         enemy.0 += 1;
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let mut schedule = Schedule::new();
+/// schedule
+///     .add_system(advance.after(spawn_wave))
+///     .add_system(spawn_wave);
+/// schedule.run(&mut world);
+/// assert_eq!(world.query::<&Enemy>().map(|enemy| enemy.0).sum::<u32>(), 10);
+/// ```
+///
+/// [`Schedule`]: crate::schedule::Schedule
+pub struct ExclusiveFunctionSystem<F> {
+    func: F,
+}
+
+impl<F: FnMut(&mut World) + Send + Sync + 'static> System for ExclusiveFunctionSystem<F> {
+    fn name(&self) -> &str {
+        type_name::<F>()
+    }
+
+    fn initialize(&mut self, world: &mut World) {}
+
+    /// Runs the function and advances the tick afterwards, like a [`FunctionSystem`].
+    fn run(&mut self, world: &mut World) {
+        (self.func)(world);
+        world.increment_change_tick();
+    }
+
+    fn type_set(&self) -> Option<SetLabel> {
+        Some(SetLabel::new(SystemTypeSet::<F>::new()))
+    }
+}
+
+/// Marks the [`IntoSystem`] implementation of functions taking `&mut World`.
+#[doc(hidden)]
+pub struct IsExclusiveFunctionSystem;
+
+impl<F: FnMut(&mut World) + Send + Sync + 'static> IntoSystem<IsExclusiveFunctionSystem> for F {
+    type System = ExclusiveFunctionSystem<F>;
+
+    fn into_system(self) -> Self::System {
+        ExclusiveFunctionSystem { func: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::Commands,
+        component::Component,
+        entity::Entity,
+        query::{Changed, Query},
+        resource::Resource,
+        time::Time,
+        World,
+    };
+
+    use super::{IntoSystem, Res, ResMut, System, SystemParam};
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Position(f32);
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Velocity(f32);
+
+    #[derive(Debug, Default, PartialEq, Resource)]
+    struct Moved(usize);
+
+    fn movement(query: Query<(&mut Position, &Velocity)>, time: Res<Time>) {
+        for (position, velocity) in query {
+            position.0 += velocity.0 * time.delta_secs();
+        }
+    }
+
+    fn count_moved(query: Query<&Position, Changed<Position>>, mut moved: ResMut<Moved>) {
+        moved.0 = query.count();
+    }
+
+    fn stop(query: Query<(Entity, &Velocity)>, mut commands: Commands) {
+        for (entity, velocity) in query {
+            commands.remove::<Velocity>(entity);
+            commands.spawn(Position(velocity.0));
+        }
+    }
+
+    #[test]
+    fn commands_system() {
+        let mut world = World::new();
+        world.spawn((Position(0.0), Velocity(1.0)));
+        world.spawn((Position(0.0), Velocity(2.0)));
+
+        let mut stop = stop.into_system();
+        stop.run(&mut world);
+        assert_eq!(world.query::<&Velocity>().count(), 0);
+        assert_eq!(world.query::<&Position>().count(), 4);
+
+        stop.run(&mut world);
+        assert_eq!(world.query::<&Position>().count(), 4);
+    }
+
+    #[test]
+    fn exclusive_systems() {
+        fn despawn_stopped(world: &mut World) {
+            let stopped = world
+                .query::<(Entity, &Velocity)>()
+                .filter(|(_, velocity)| velocity.0 == 0.0)
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>();
+            for entity in stopped {
+                world.despawn(entity);
+            }
+        }
+
+        let mut world = World::new();
+        world.spawn((Position(0.0), Velocity(0.0)));
+        let moving = world.spawn((Position(0.0), Velocity(1.0)));
+        world.insert_resource(Moved::default());
+
+        let mut despawn_stopped = despawn_stopped.into_system();
+        let mut count_moved = count_moved.into_system();
+        assert!(despawn_stopped.name().ends_with("despawn_stopped"));
+        count_moved.run(&mut world);
+        despawn_stopped.run(&mut world);
+        assert_eq!(world.query::<&Position>().count(), 1);
+        assert!(world.contains(moving));
+
+        // The changes of exclusive systems are reported to the systems running after them
+        (move |world: &mut World| world.get_mut::<Position>(moving).unwrap().0 = 1.0)
+            .into_system()
+            .run(&mut world);
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(1)));
+    }
+
+    #[derive(SystemParam)]
+    struct Movers<'w> {
+        query: Query<'w, (Entity, &'static Velocity)>,
+        moved: ResMut<'w, Moved>,
+        commands: Commands<'w>,
+    }
+
+    #[derive(SystemParam)]
+    struct Positions<'w>(Query<'w, &'static mut Position>);
+
+    #[test]
+    fn derived_params() {
+        fn stop_movers(mut movers: Movers, Positions(positions): Positions) {
+            for (entity, _) in movers.query {
+                movers.commands.remove::<Velocity>(entity);
+                movers.moved.0 += 1;
+            }
+            for position in positions {
+                position.0 = 0.0;
+            }
+        }
+
+        let mut world = World::new();
+        let entity = world.spawn((Position(1.0), Velocity(1.0)));
+        world.insert_resource(Moved::default());
+
+        stop_movers.into_system().run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(1)));
+        assert_eq!(world.get::<Position>(entity), Some(&Position(0.0)));
+        // The commands of the fields are applied
+        assert_eq!(world.get::<Velocity>(entity), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with a previous parameter")]
+    fn conflicting_derived_params() {
+        fn conflicting(_: Movers, _: Query<&mut Velocity>) {}
+
+        let mut world = World::new();
+        world.spawn((Position(0.0), Velocity(0.0)));
+        world.insert_resource(Moved::default());
+        conflicting.into_system().run(&mut world);
+    }
+
+    #[test]
+    fn function_systems() {
+        let mut world = World::new();
+        let e0 = world.spawn((Position(0.0), Velocity(1.0)));
+        let e1 = world.spawn(Position(0.0));
+        world.insert_resource(Moved::default());
+        world.increment_tick_with(std::time::Duration::from_secs(2));
+
+        let mut movement = movement.into_system();
+        let mut count_moved = count_moved.into_system();
+        assert!(movement.name().ends_with("movement"));
+
+        movement.run(&mut world);
+        assert_eq!(world.get::<Position>(e0), Some(&Position(2.0)));
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(2)));
+
+        world.increment_tick();
+        movement.run(&mut world);
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(1)));
+
+        // Only the changes made after the previous run are reported
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(0)));
+        world.get_mut::<Position>(e1).unwrap().0 = 1.0;
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(1)));
+
+        let mut no_params = (|| {}).into_system();
+        no_params.run(&mut world);
+    }
+
+    #[test]
+    fn systems_before_spawning() {
+        let mut world = World::new();
+        world.insert_resource(Moved::default());
+        let mut stop = stop.into_system();
+        let mut count_moved = count_moved.into_system();
+        stop.run(&mut world);
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(0)));
+
+        world.spawn(Velocity(1.0));
+        world.increment_tick();
+        stop.run(&mut world);
+        count_moved.run(&mut world);
+        assert_eq!(world.get_resource::<Moved>(), Some(&Moved(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with a previous parameter")]
+    fn conflicting_queries() {
+        fn conflicting(_: Query<&mut Position>, _: Query<(&Velocity, &Position)>) {}
+
+        let mut world = World::new();
+        world.spawn((Position(0.0), Velocity(0.0)));
+        conflicting.into_system().run(&mut world);
+    }
+
+    #[test]
+    #[should_panic(expected = "ResMut<quartz::system::tests::Moved> conflicts")]
+    fn conflicting_resources() {
+        fn conflicting(_: Res<Moved>, _: ResMut<Moved>) {}
+
+        let mut world = World::new();
+        world.insert_resource(Moved::default());
+        conflicting.into_system().run(&mut world);
+    }
+
+    #[test]
+    #[should_panic(expected = "Resource quartz::system::tests::Moved does not exist")]
+    fn missing_resource() {
+        fn missing(_: Res<Moved>) {}
+
+        missing.into_system().run(&mut World::new());
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use crate::{component::Tick, resource::Resource};
+
+/// The frame context of a [`World`](crate::World): the current tick and how much time passed
+/// since the previous one.
+///
+/// Advanced together with the world tick, see
+/// [`World::increment_tick`](crate::World::increment_tick). Every world holds it as a resource.
+#[derive(Debug, Clone, Default)]
+pub struct Time {
+    tick: Tick,
+    delta: Duration,
+    elapsed: Duration,
+    last_update: Option<Instant>,
+}
+
+impl Resource for Time {}
+
+impl Time {
+    /// The tick of the current frame.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// The time between the previous and the current frame.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// The sum of all deltas up to and including the current frame.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Advances to the next frame, measuring the delta as the wall clock time since the previous
+    /// call. The delta of the first frame is zero.
+    pub(crate) fn update(&mut self, tick: Tick) {
+        let now = Instant::now();
+        let delta = self
+            .last_update
+            .map_or(Duration::ZERO, |last| now.duration_since(last));
+        self.update_with(tick, delta);
+        self.last_update = Some(now);
+    }
+
+    /// Advances to the next frame with a fixed delta.
+    pub(crate) fn update_with(&mut self, tick: Tick, delta: Duration) {
+        self.tick = tick;
+        self.delta = delta;
+        self.elapsed += delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::component::Tick;
+
+    use super::Time;
+
+    #[test]
+    fn update() {
+        let mut time = Time::default();
+        time.update(Tick::new(1));
+        assert_eq!(time.delta(), Duration::ZERO);
+
+        time.update_with(Tick::new(2), Duration::from_millis(16));
+        time.update_with(Tick::new(3), Duration::from_millis(16));
+        assert_eq!(time.tick(), Tick::new(3));
+        assert_eq!(time.delta(), Duration::from_millis(16));
+        assert_eq!(time.elapsed(), Duration::from_millis(32));
+    }
+}
@@ -0,0 +1,516 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt, slice,
+};
+
+use crate::{
+    component::{Component, ComponentId, StorageType},
+    ptr::{MutPtr, Ptr},
+    query::Access,
+    storage::{Table, TableRow},
+    World,
+};
+
+/// Makes `&dyn Trait` and `&mut dyn Trait` queryable, yielding every component of an entity
+/// that implements the trait.
+///
+/// The implementors have to be registered with [`World::register_trait_impl`]. A query matches
+/// every entity with at least one of them and yields a [`DynRefs`] or [`DynMuts`] iterator over
+/// all of its implementors, in registration order.
+///
+/// ```
+/// # use quartz::{component::Component, World};
+/// trait Drawable {
+///     fn draw(&self) -> String;
+/// }
+/// quartz::queryable_trait!(Drawable);
+///
+/// struct Sprite(&'static str);
+/// impl Component for Sprite {}
+/// impl Drawable for Sprite {
+///     fn draw(&self) -> String {
+///         format!("sprite {}", self.0)
+///     }
+/// }
+///
+/// struct Label(&'static str);
+/// impl Component for Label {}
+/// impl Drawable for Label {
+///     fn draw(&self) -> String {
+///         format!("label {}", self.0)
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.register_trait_impl::<dyn Drawable, Sprite>();
+/// world.register_trait_impl::<dyn Drawable, Label>();
+/// world.spawn(Sprite("player"));
+/// world.spawn((Sprite("door"), Label("exit")));
+///
+/// let mut drawn = world
+///     .query::<&dyn Drawable>()
+///     .flatten()
+///     .map(|drawable| drawable.draw())
+///     .collect::<Vec<_>>();
+/// drawn.sort();
+/// assert_eq!(drawn, ["label exit", "sprite door", "sprite player"]);
+/// ```
+#[macro_export]
+macro_rules! queryable_trait {
+    ($trait:path) => {
+        // SAFETY: The unsizing coercion keeps the address of the component.
+        unsafe impl<C: $trait + 'static> $crate::trait_query::ImplementedBy<C> for dyn $trait {
+            fn cast(ptr: *mut C) -> *mut Self {
+                ptr
+            }
+        }
+
+        impl $crate::query::Queryable for &'static dyn $trait {
+            type Item<'w> = $crate::trait_query::DynRefs<'w, dyn $trait>;
+            type State = $crate::trait_query::DynState;
+
+            const IS_DENSE: bool = false;
+
+            fn init_state(world: &$crate::World) -> Self::State {
+                $crate::trait_query::DynState::new::<dyn $trait>(world)
+            }
+
+            fn get_state(world: &$crate::World) -> Option<Self::State> {
+                Some($crate::trait_query::DynState::new::<dyn $trait>(world))
+            }
+
+            fn update_access(state: &Self::State, access: &mut $crate::query::Access) {
+                state.update_access::<dyn $trait>(access, false);
+            }
+
+            fn matches_row(
+                world: &$crate::World,
+                state: &Self::State,
+                table: &$crate::storage::Table,
+                row: $crate::storage::TableRow,
+            ) -> bool {
+                state.matches_row(world, table, row)
+            }
+
+            fn fetch<'w>(
+                world: &'w $crate::World,
+                state: &Self::State,
+                table: &'w $crate::storage::Table,
+                row: $crate::storage::TableRow,
+                since: $crate::component::Tick,
+            ) -> Self::Item<'w> {
+                $crate::trait_query::DynRefs::new(world, state, table, row)
+            }
+        }
+
+        // SAFETY: `DynRefs` only hands out shared references.
+        unsafe impl $crate::query::ReadOnlyQueryable for &'static dyn $trait {}
+
+        impl $crate::query::Queryable for &'static mut dyn $trait {
+            type Item<'w> = $crate::trait_query::DynMuts<'w, dyn $trait>;
+            type State = $crate::trait_query::DynState;
+
+            const IS_DENSE: bool = false;
+
+            fn init_state(world: &$crate::World) -> Self::State {
+                $crate::trait_query::DynState::new::<dyn $trait>(world)
+            }
+
+            fn get_state(world: &$crate::World) -> Option<Self::State> {
+                Some($crate::trait_query::DynState::new::<dyn $trait>(world))
+            }
+
+            fn update_access(state: &Self::State, access: &mut $crate::query::Access) {
+                state.update_access::<dyn $trait>(access, true);
+            }
+
+            fn matches_row(
+                world: &$crate::World,
+                state: &Self::State,
+                table: &$crate::storage::Table,
+                row: $crate::storage::TableRow,
+            ) -> bool {
+                state.matches_row(world, table, row)
+            }
+
+            fn fetch<'w>(
+                world: &'w $crate::World,
+                state: &Self::State,
+                table: &'w $crate::storage::Table,
+                row: $crate::storage::TableRow,
+                since: $crate::component::Tick,
+            ) -> Self::Item<'w> {
+                $crate::trait_query::DynMuts::new(world, state, table, row)
+            }
+        }
+    };
+}
+
+/// Converts a pointer to the component `C` into a trait object pointer, implemented by
+/// [`queryable_trait!`] for every `dyn Trait` and every `C` implementing the trait.
+///
+/// # Safety
+/// The returned pointer has to point to the same component as `ptr`.
+pub unsafe trait ImplementedBy<C>: 'static {
+    fn cast(ptr: *mut C) -> *mut Self;
+}
+
+/// A component implementing the trait object `Tr`.
+struct TraitImpl<Tr: ?Sized> {
+    id: ComponentId,
+    /// Whether the component is stored in a sparse set instead of the tables.
+    sparse: bool,
+    cast: fn(*mut u8) -> *mut Tr,
+}
+
+impl<Tr: ?Sized> TraitImpl<Tr> {
+    /// Returns the component of the row from its table or sparse set.
+    ///
+    /// Safety: The row must be in bounds for the table.
+    unsafe fn get<'w>(&self, world: &'w World, table: &'w Table, row: TableRow) -> Option<Ptr<'w>> {
+        if self.sparse {
+            world.sparse_sets.get(self.id)?.get(table.entity(row))
+        } else {
+            table.get_component(self.id, row)
+        }
+    }
+
+    /// Retrieves a mutable pointer to the component of the row from its table or sparse set and
+    /// marks it as changed.
+    ///
+    /// Safety: The row must be in bounds for the table and no other reference to the component may
+    /// be alive for the lifetime of the pointer.
+    unsafe fn get_mut_ptr<'w>(
+        &self,
+        world: &'w World,
+        table: &'w Table,
+        row: TableRow,
+    ) -> Option<MutPtr<'w>> {
+        if self.sparse {
+            world
+                .sparse_sets
+                .get(self.id)?
+                .get_mut_ptr(table.entity(row), world.tick())
+        } else {
+            table.get_component_mut_ptr(self.id, row, world.tick())
+        }
+    }
+}
+
+fn cast<Tr: ?Sized + ImplementedBy<C>, C>(ptr: *mut u8) -> *mut Tr {
+    Tr::cast(ptr.cast())
+}
+
+/// The components registered with [`World::register_trait_impl`], grouped by trait.
+#[derive(Default)]
+pub(crate) struct TraitImpls {
+    // Every value is a `Vec<TraitImpl<Tr>>` for the trait object `Tr` of the key
+    impls: Vec<Box<dyn Any + Send + Sync>>,
+    slots: HashMap<TypeId, usize>,
+}
+
+impl TraitImpls {
+    fn get<Tr: ?Sized + 'static>(&self, slot: usize) -> &[TraitImpl<Tr>] {
+        self.impls[slot]
+            .downcast_ref::<Vec<TraitImpl<Tr>>>()
+            .expect("trait impls must be stored in the slot of their trait")
+    }
+}
+
+impl fmt::Debug for TraitImpls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraitImpls")
+            .field("traits", &self.slots.len())
+            .finish()
+    }
+}
+
+impl World {
+    /// Registers the component `C` as an implementor of the trait object `Tr`, so queries for
+    /// `&dyn Trait` yield it, see [`queryable_trait!`].
+    ///
+    /// Queries only see the implementors registered before they were created, which includes
+    /// cached [`QueryState`](crate::query::QueryState)s.
+    pub fn register_trait_impl<Tr: ?Sized + ImplementedBy<C>, C: Component>(&mut self) {
+        let id = self.components.register_component::<C>();
+        let impls = &mut self.trait_impls;
+        let slot = *impls.slots.entry(TypeId::of::<Tr>()).or_insert_with(|| {
+            impls.impls.push(Box::new(Vec::<TraitImpl<Tr>>::new()));
+            impls.impls.len() - 1
+        });
+        let impls = impls.impls[slot]
+            .downcast_mut::<Vec<TraitImpl<Tr>>>()
+            .expect("trait impls must be stored in the slot of their trait");
+
+        if !impls.iter().any(|registered| registered.id == id) {
+            impls.push(TraitImpl {
+                id,
+                sparse: C::STORAGE_TYPE == StorageType::SparseSet,
+                cast: cast::<Tr, C>,
+            });
+        }
+    }
+}
+
+/// The state of a trait object query: the implementors that were registered when it was
+/// created.
+#[derive(Debug, Clone)]
+pub struct DynState {
+    slot: Option<usize>,
+    ids: Vec<ComponentId>,
+    /// Whether every implementor is stored in the tables, so the archetypes tell which rows match.
+    dense: bool,
+}
+
+impl DynState {
+    pub fn new<Tr: ?Sized + 'static>(world: &World) -> Self {
+        let slot = world.trait_impls.slots.get(&TypeId::of::<Tr>()).copied();
+        let impls = slot.map_or(&[][..], |slot| world.trait_impls.get::<Tr>(slot));
+        let ids = impls.iter().map(|registered| registered.id).collect();
+        let dense = impls.iter().all(|registered| !registered.sparse);
+
+        Self { slot, ids, dense }
+    }
+
+    /// Reads or writes every implementor and requires at least one of them, which only narrows
+    /// down the tables if none of them is stored in a sparse set.
+    pub fn update_access<Tr: ?Sized + 'static>(&self, access: &mut Access, write: bool) {
+        for id in &self.ids {
+            if write {
+                access.add_write(*id, type_name::<Tr>());
+            } else {
+                access.add_read(*id, type_name::<Tr>());
+            }
+        }
+        if self.dense {
+            access.add_any_of(&self.ids);
+        }
+    }
+
+    /// Returns `true` if the entity of the row has at least one implementor.
+    pub fn matches_row(&self, world: &World, table: &Table, row: TableRow) -> bool {
+        let entity = table.entity(row);
+        self.ids
+            .iter()
+            .any(|id| table.has_column(*id) || world.has_sparse(entity, *id))
+    }
+
+    fn impls<'w, Tr: ?Sized + 'static>(&self, world: &'w World) -> slice::Iter<'w, TraitImpl<Tr>> {
+        self.slot.map_or([].iter(), |slot| {
+            world.trait_impls.get::<Tr>(slot)[..self.ids.len()].iter()
+        })
+    }
+}
+
+/// The components of an entity implementing the trait object `Tr`, yielded by queries for
+/// `&dyn Trait`, see [`queryable_trait!`].
+pub struct DynRefs<'w, Tr: ?Sized> {
+    world: &'w World,
+    table: &'w Table,
+    row: TableRow,
+    impls: slice::Iter<'w, TraitImpl<Tr>>,
+}
+
+impl<'w, Tr: ?Sized + 'static> DynRefs<'w, Tr> {
+    pub fn new(world: &'w World, state: &DynState, table: &'w Table, row: TableRow) -> Self {
+        Self {
+            world,
+            table,
+            row,
+            impls: state.impls(world),
+        }
+    }
+}
+
+impl<'w, Tr: ?Sized + 'static> Iterator for DynRefs<'w, Tr> {
+    type Item = &'w Tr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.impls.find_map(|registered| {
+            // SAFETY: The cast keeps pointing to the component, which lives as long as the world
+            // is borrowed.
+            unsafe {
+                let ptr = registered.get(self.world, self.table, self.row)?;
+                Some(&*(registered.cast)(ptr.as_ptr()))
+            }
+        })
+    }
+}
+
+/// The components of an entity implementing the trait object `Tr`, yielded by queries for
+/// `&mut dyn Trait`, see [`queryable_trait!`].
+pub struct DynMuts<'w, Tr: ?Sized> {
+    world: &'w World,
+    table: &'w Table,
+    row: TableRow,
+    impls: slice::Iter<'w, TraitImpl<Tr>>,
+}
+
+impl<'w, Tr: ?Sized + 'static> DynMuts<'w, Tr> {
+    pub fn new(world: &'w World, state: &DynState, table: &'w Table, row: TableRow) -> Self {
+        Self {
+            world,
+            table,
+            row,
+            impls: state.impls(world),
+        }
+    }
+}
+
+impl<'w, Tr: ?Sized + 'static> Iterator for DynMuts<'w, Tr> {
+    type Item = &'w mut Tr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.impls.find_map(|registered| {
+            // SAFETY: The access check guarantees this is the only term touching the columns of
+            // the implementors, the query yields every row at most once and every implementor is
+            // yielded at most once per row.
+            unsafe {
+                let ptr = registered.get_mut_ptr(self.world, self.table, self.row)?;
+                Some(&mut *(registered.cast)(ptr.as_ptr()))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{Component, StorageType},
+        query::With,
+        World,
+    };
+
+    trait Health {
+        fn health(&self) -> u32;
+        fn damage(&mut self, amount: u32);
+    }
+    crate::queryable_trait!(Health);
+
+    struct Player(u32);
+    impl Component for Player {}
+    impl Health for Player {
+        fn health(&self) -> u32 {
+            self.0
+        }
+        fn damage(&mut self, amount: u32) {
+            self.0 -= amount;
+        }
+    }
+
+    struct Shield(u32);
+    impl Component for Shield {}
+    impl Health for Shield {
+        fn health(&self) -> u32 {
+            self.0
+        }
+        fn damage(&mut self, amount: u32) {
+            self.0 = self.0.saturating_sub(amount);
+        }
+    }
+
+    struct Armor(u32);
+    impl Component for Armor {
+        const STORAGE_TYPE: StorageType = StorageType::SparseSet;
+    }
+    impl Health for Armor {
+        fn health(&self) -> u32 {
+            self.0
+        }
+        fn damage(&mut self, amount: u32) {
+            self.0 = self.0.saturating_sub(amount);
+        }
+    }
+
+    struct Marker;
+    impl Component for Marker {}
+
+    #[test]
+    fn query_trait_objects() {
+        let mut world = World::new();
+        assert_eq!(world.query::<&dyn Health>().count(), 0);
+
+        world.register_trait_impl::<dyn Health, Player>();
+        world.register_trait_impl::<dyn Health, Shield>();
+        world.register_trait_impl::<dyn Health, Shield>();
+        let a = world.spawn(Player(10));
+        let b = world.spawn((Player(20), Shield(5)));
+        world.insert(b, Marker);
+        world.spawn(Marker);
+
+        let health = |world: &World, entity| {
+            world
+                .get_components::<&dyn Health>(entity)
+                .map(|all| all.map(|health| health.health()).collect::<Vec<_>>())
+        };
+        assert_eq!(health(&world, a), Some(vec![10]));
+        assert_eq!(health(&world, b), Some(vec![20, 5]));
+        assert_eq!(world.query::<&dyn Health>().count(), 2);
+        assert_eq!(
+            world
+                .query_filtered::<&dyn Health, With<Marker>>()
+                .flatten()
+                .count(),
+            2
+        );
+
+        for all in world.query_mut::<&mut dyn Health>() {
+            for health in all {
+                health.damage(8);
+            }
+        }
+        assert_eq!(health(&world, a), Some(vec![2]));
+        assert_eq!(health(&world, b), Some(vec![12, 0]));
+
+        let explanation = world.query::<&dyn Health>().explain();
+        assert_eq!(explanation.rejected().count(), 1);
+    }
+
+    #[test]
+    fn query_sparse_trait_objects() {
+        let mut world = World::new();
+        world.register_trait_impl::<dyn Health, Player>();
+        world.register_trait_impl::<dyn Health, Armor>();
+        let a = world.spawn(Armor(3));
+        let b = world.spawn((Player(20), Armor(4)));
+        let c = world.spawn(Marker);
+        world.spawn((Marker, Armor(5)));
+        world.remove::<Armor>(c);
+
+        let health = |world: &World, entity| {
+            world
+                .get_components::<&dyn Health>(entity)
+                .map(|all| all.map(|health| health.health()).collect::<Vec<_>>())
+        };
+        assert_eq!(health(&world, a), Some(vec![3]));
+        assert_eq!(health(&world, b), Some(vec![20, 4]));
+        assert_eq!(health(&world, c), None);
+        assert_eq!(world.query::<&dyn Health>().count(), 3);
+
+        for all in world.query_mut::<&mut dyn Health>() {
+            for health in all {
+                health.damage(4);
+            }
+        }
+        assert_eq!(health(&world, a), Some(vec![0]));
+        assert_eq!(health(&world, b), Some(vec![16, 0]));
+        assert_eq!(
+            world
+                .query_filtered::<&dyn Health, With<Marker>>()
+                .flatten()
+                .map(|health| health.health())
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts")]
+    fn trait_object_aliasing() {
+        let mut world = World::new();
+        world.register_trait_impl::<dyn Health, Player>();
+        world.spawn(Player(1));
+        let _ = world.query_mut::<(&mut dyn Health, &Player)>();
+    }
+}
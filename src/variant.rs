@@ -0,0 +1,159 @@
+use std::{marker::PhantomData, ptr::NonNull};
+
+use crate::{
+    component::{Component, ComponentHooks, ComponentId, Components},
+    entity::Entity,
+    ptr::OwningPtr,
+    small_vec::IdList,
+    DeferredWorld, World,
+};
+
+pub use quartz_macros::EnumComponent;
+
+/// An enum component whose current variant can be filtered for in queries.
+///
+/// Every entity holding the component is tagged with the [`Variant`] marker of its current
+/// variant, so a query filter like `With<state::Attacking>` only matches the archetypes of
+/// attacking entities without checking any component values. Deriving the trait also implements
+/// [`Component`] and generates a module named after the enum in snake case, with an alias of the
+/// marker of every variant. The enum therefore has to be declared at module level:
+///
+/// ```
+/// # use quartz::{query::{With, Without}, variant::EnumComponent, World};
+/// #[derive(EnumComponent)]
+/// enum State {
+///     Idle,
+///     Attacking { target: u32 },
+///     Fleeing(f32),
+/// }
+///
+/// fn main() {
+///     let mut world = World::new();
+///     let entity = world.spawn(State::Idle);
+///     world.spawn(State::Attacking { target: 1 });
+///     assert_eq!(world.query_filtered::<&State, With<state::Attacking>>().count(), 1);
+///
+///     world.insert(entity, State::Fleeing(2.0));
+///     assert_eq!(world.query_filtered::<&State, Without<state::Idle>>().count(), 2);
+/// }
+/// ```
+///
+/// The markers are updated through the `on_insert` and `on_remove` hooks of the component, which
+/// means a variant change moves the entity to another archetype. Changes made in place through
+/// [`World::get_mut`](crate::World::get_mut) or mutable queries are not tracked, use
+/// [`World::modify_component`](crate::World::modify_component) or insert the component again to
+/// change the variant.
+pub trait EnumComponent: Component {
+    /// Registers the [`Variant`] marker of every variant, in variant order.
+    const VARIANTS: &'static [fn(&mut Components) -> ComponentId];
+
+    /// The position of the current variant in the declaration of the enum.
+    fn variant_index(&self) -> usize;
+}
+
+/// Marks the entities whose enum component `C` is currently its `INDEX`th variant, see
+/// [`EnumComponent`].
+pub struct Variant<C, const INDEX: usize>(PhantomData<C>);
+
+impl<C: EnumComponent, const INDEX: usize> Component for Variant<C, INDEX> {}
+
+/// Installs the hooks keeping the [`Variant`] markers of `C` up to date, called by the derived
+/// [`Component`] implementation.
+pub fn register_hooks<C: EnumComponent>(hooks: &mut ComponentHooks) {
+    hooks.on_insert(sync_hook::<C>).on_remove(sync_hook::<C>);
+}
+
+fn sync_hook<C: EnumComponent>(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+    world.queue(move |world: &mut World| world.sync_variant::<C>(entity));
+}
+
+impl World {
+    /// Tags the entity with the marker of the current variant of its `C` and removes all other
+    /// markers of `C`.
+    fn sync_variant<C: EnumComponent>(&mut self, entity: Entity) {
+        let Some(location) = self.entities.get(entity).copied() else {
+            return;
+        };
+
+        let markers = C::VARIANTS
+            .iter()
+            .map(|register| register(&mut self.components))
+            .collect::<IdList>();
+        let current = self
+            .get::<C>(entity)
+            .map(|component| markers[component.variant_index()]);
+
+        let archetype = self.archetypes.get_unchecked(location.archetype_id);
+        let stale = markers
+            .iter()
+            .copied()
+            .filter(|id| Some(*id) != current && archetype.contains(*id))
+            .collect::<IdList>();
+        let missing = current.filter(|id| !archetype.contains(*id));
+
+        if !stale.is_empty() {
+            self.remove_by_ids(entity, &stale);
+        }
+        if let Some(id) = missing {
+            let location = *self
+                .entities
+                .get(entity)
+                .expect("removing markers doesn't despawn the entity");
+            self.insert_by_ids(entity, location, &[id], |write| {
+                // SAFETY: Markers are zero sized, so any aligned pointer is a valid value.
+                write(unsafe { OwningPtr::new(NonNull::<Variant<C, 0>>::dangling().cast()) })
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query::{With, Without},
+        World,
+    };
+
+    use super::{EnumComponent, Variant};
+
+    #[derive(Debug, PartialEq, EnumComponent)]
+    enum Stance {
+        Idle,
+        Attacking { target: u32 },
+        Fleeing(f32),
+    }
+
+    fn count<F: crate::query::QueryFilter>(world: &World) -> usize {
+        world.query_filtered::<&Stance, F>().count()
+    }
+
+    #[test]
+    fn filter_variants() {
+        let mut world = World::new();
+        let a = world.spawn(Stance::Idle);
+        let b = world.spawn(Stance::Attacking { target: 3 });
+        world.spawn(Stance::Fleeing(1.0));
+
+        assert_eq!(Stance::Fleeing(0.0).variant_index(), 2);
+        assert_eq!(count::<With<stance::Idle>>(&world), 1);
+        assert_eq!(count::<With<stance::Attacking>>(&world), 1);
+        assert_eq!(count::<Without<stance::Fleeing>>(&world), 2);
+
+        world.insert(a, Stance::Attacking { target: 4 });
+        assert_eq!(count::<With<stance::Idle>>(&world), 0);
+        assert_eq!(count::<With<Variant<Stance, 1>>>(&world), 2);
+
+        world.modify_component::<Stance, _>(b, |stance| *stance = Stance::Idle);
+        assert_eq!(
+            world
+                .query_filtered::<&Stance, With<stance::Idle>>()
+                .collect::<Vec<_>>(),
+            [&Stance::Idle]
+        );
+
+        assert!(world.remove::<Stance>(a).is_some());
+        assert!(world.get::<stance::Attacking>(a).is_none());
+        world.despawn(b);
+        assert_eq!(count::<()>(&world), 1);
+    }
+}